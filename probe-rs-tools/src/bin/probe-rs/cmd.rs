@@ -1,3 +1,4 @@
+pub mod analyze_dump;
 pub mod attach;
 pub mod benchmark;
 pub mod cargo_embed;
@@ -6,6 +7,7 @@ pub mod chip;
 pub mod complete;
 pub mod dap_server;
 pub mod debug;
+pub mod doctor;
 pub mod download;
 pub mod erase;
 pub mod gdb;
@@ -13,6 +15,8 @@ pub mod info;
 pub mod itm;
 pub mod list;
 pub mod mi;
+pub mod power;
+pub mod probe_reset;
 pub mod profile;
 pub mod read;
 pub mod reset;
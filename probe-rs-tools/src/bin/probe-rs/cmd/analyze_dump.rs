@@ -0,0 +1,128 @@
+//! Analyzing a core dump offline, with the ELF file of the firmware which was running.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use probe_rs::debug::{stack_frame::StackFrameInfo, DebugInfo};
+use probe_rs::{exception_handler_for_core, CoreDump};
+
+use crate::cmd::run::print_stack_frame;
+
+/// How deep the members of the local variables are read from the dump.
+const VARIABLE_RECURSION_LIMIT: usize = 5;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// The core dump, e.g. written by `run --dump-on-fault`.
+    dump: PathBuf,
+
+    /// The ELF file of the firmware which was running when the dump was taken.
+    #[clap(long)]
+    elf: PathBuf,
+
+    /// Also print the local variables of every frame.
+    #[clap(long)]
+    variables: bool,
+}
+
+impl Cmd {
+    pub fn run(self) -> Result<()> {
+        self.analyze(std::io::stdout().lock())
+    }
+
+    /// Analyzes the dump, and writes the result to `output`.
+    fn analyze(self, mut output: impl Write) -> Result<()> {
+        let mut dump = CoreDump::load(&self.dump)
+            .with_context(|| format!("Failed to load the core dump {}", self.dump.display()))?;
+        let debug_info = DebugInfo::from_file(&self.elf)
+            .with_context(|| format!("Failed to read the debug info of {}", self.elf.display()))?;
+
+        writeln!(
+            output,
+            "Core: {:?} ({:?})",
+            dump.core_type(),
+            dump.instruction_set()
+        )?;
+        writeln!(output, "Memory in the dump:")?;
+        for (range, _) in &dump.data {
+            writeln!(output, "  {:#010x}..{:#010x}", range.start, range.end)?;
+        }
+
+        let registers = dump.debug_registers();
+        writeln!(output, "Registers:")?;
+        for register in &registers.0 {
+            if let Some(value) = register.value {
+                writeln!(output, "  {:>8}: {value}", register.core_register.name())?;
+            }
+        }
+
+        let exception_handler = exception_handler_for_core(dump.core_type());
+        let instruction_set = dump.instruction_set();
+        let mut frames = debug_info
+            .unwind(
+                &mut dump,
+                registers,
+                exception_handler.as_ref(),
+                Some(instruction_set),
+            )
+            .context("Failed to unwind the stack of the dump")?;
+
+        writeln!(output, "Backtrace:")?;
+        for (index, frame) in frames.iter_mut().enumerate() {
+            print_stack_frame(index, frame, &mut output)?;
+
+            if !self.variables {
+                continue;
+            }
+            let Some(variables) = &mut frame.local_variables else {
+                continue;
+            };
+
+            variables.recurse_deferred_variables(
+                &debug_info,
+                &mut dump,
+                VARIABLE_RECURSION_LIMIT,
+                StackFrameInfo {
+                    registers: &frame.registers,
+                    frame_base: frame.frame_base,
+                    canonical_frame_address: frame.canonical_frame_address,
+                },
+            );
+            let root = variables.root_variable().variable_key();
+            for variable in variables.get_children(root) {
+                writeln!(
+                    output,
+                    "       {} = {}",
+                    variable.name,
+                    variable.to_string(variables)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn dump_is_analyzed_offline() {
+        let tests =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../probe-rs/tests/debug-unwind-tests");
+
+        let cmd = Cmd {
+            dump: tests.join("RP2040_full_unwind.coredump"),
+            elf: tests.join("RP2040_full_unwind.elf"),
+            variables: true,
+        };
+        let mut output = vec![];
+        cmd.analyze(&mut output).unwrap();
+
+        insta::assert_snapshot!(String::from_utf8(output).unwrap());
+    }
+}
@@ -1,3 +1,6 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use probe_rs::probe::list::Lister;
 use time::UtcOffset;
 
@@ -6,10 +9,59 @@ use time::UtcOffset;
 pub struct Cmd {
     #[clap(flatten)]
     pub(crate) run: crate::cmd::run::Cmd,
+
+    /// Discard the output which is already buffered in the RTT up channels when attaching, so
+    /// only the output from the time of attaching onward is printed.
+    #[clap(long)]
+    pub(crate) clear_rtt_on_start: bool,
+
+    /// Attach to the target again if the connection to the probe or the target is lost, or if a
+    /// Cortex-M target resets itself, and continue printing the RTT output.
+    ///
+    /// Faults, timeouts and failures reported by the firmware still end the run.
+    #[clap(long)]
+    pub(crate) reconnect: bool,
+
+    /// The maximum number of attempts to reconnect each time the connection is lost. By default,
+    /// probe-rs keeps trying until it is stopped.
+    #[clap(long, value_name = "N", requires = "reconnect")]
+    pub(crate) max_reconnects: Option<u32>,
+
+    /// Another image which may run on the target, e.g. the second slot of an A/B update.
+    ///
+    /// The symbols for RTT and defmt are taken from the image which runs on the target, selected
+    /// by the vector table in use on Cortex-M cores, or by the build ID. The image is selected
+    /// again each time probe-rs attaches to the target.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) alternate_elf: Vec<PathBuf>,
+
+    /// Catch resets of the target, and select the image which runs after the reset again.
+    ///
+    /// This keeps the output going across a firmware update which switches to the other image.
+    #[clap(long, requires = "alternate_elf")]
+    pub(crate) follow_reset_vector: bool,
+
+    /// Print the rate of the RTT output, and the number of bytes dropped because the output
+    /// could not keep up, to stderr periodically.
+    ///
+    /// The report is printed between two lines of the output.
+    #[clap(long)]
+    pub(crate) stats: bool,
+
+    /// The interval of the `--stats` report, in seconds.
+    #[clap(long, value_name = "SECONDS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..), requires = "stats")]
+    pub(crate) stats_interval: u64,
 }
 
 impl Cmd {
-    pub fn run(self, lister: &Lister, timestamp_offset: UtcOffset) -> anyhow::Result<()> {
+    pub fn run(mut self, lister: &Lister, timestamp_offset: UtcOffset) -> anyhow::Result<()> {
+        self.run.shared_options.clear_rtt_on_start = self.clear_rtt_on_start;
+        self.run.shared_options.reconnect = self.reconnect;
+        self.run.shared_options.max_reconnects = self.max_reconnects;
+        self.run.shared_options.alternate_elfs = self.alternate_elf;
+        self.run.shared_options.follow_reset_vector = self.follow_reset_vector;
+        self.run.shared_options.stats_interval =
+            self.stats.then(|| Duration::from_secs(self.stats_interval));
         self.run.run(lister, false, timestamp_offset)?;
 
         Ok(())
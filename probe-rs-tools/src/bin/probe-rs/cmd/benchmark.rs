@@ -1,18 +1,25 @@
 use std::{
     num::ParseIntError,
+    path::PathBuf,
     time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use probe_rs::{probe::list::Lister, MemoryInterface};
+use probe_rs::{
+    probe::{list::Lister, DebugProbeInfo},
+    MemoryInterface,
+};
+use serde::Serialize;
 
 use crate::util::common_options::LoadedProbeOptions;
 use crate::util::common_options::ProbeOptions;
+use crate::util::halt::{self, DEFAULT_HALT_TIMEOUT};
 
 const PROBE_SPEEDS: [u32; 10] = [320, 640, 960, 3200, 6400, 9600, 32000, 64000, 96000, 320000];
 const TEST_SIZES: [usize; 5] = [1, 8, 32, 512, 8192];
 
 #[derive(clap::Parser)]
+#[clap(group(clap::ArgGroup::new("json_results").args(["probe_all", "pattern"]).multiple(true)))]
 pub struct Cmd {
     #[clap(flatten)]
     common: ProbeOptions,
@@ -52,6 +59,45 @@ pub struct Cmd {
     /// both reduce the amount of jitter, and also quantify it (via standard deviation calcs)
     #[clap(long = "iterations", value_parser= parse_usize, default_value="5")]
     iterations: usize,
+
+    /// Run the benchmark against every connected probe, one after the other, and print a ranked
+    /// comparison of the probes.
+    ///
+    /// All probes have to be connected to the same kind of target.
+    #[clap(long = "probe-all", conflicts_with_all = ["probe", "probe_index"])]
+    probe_all: bool,
+
+    /// Measure the throughput and latency of these access patterns, instead of block transfers
+    /// of increasing size.
+    ///
+    /// Can be given multiple times, or as a comma separated list.
+    #[clap(
+        long = "pattern",
+        value_enum,
+        value_delimiter = ',',
+        conflicts_with = "probe_all"
+    )]
+    pattern: Vec<AccessPattern>,
+
+    /// Also write the comparison of `--probe-all`, or the results of `--pattern`, as JSON to this
+    /// file.
+    #[clap(long = "json", value_name = "PATH", requires = "json_results")]
+    json: Option<PathBuf>,
+}
+
+/// The shape of the memory accesses of a `--pattern` benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+enum AccessPattern {
+    /// One transfer of the whole test region, as a flash loader does.
+    Sequential,
+    /// Single words at random addresses in the test region.
+    Random,
+    /// Short blocks of a few words at random addresses in the test region, as a debugger reading
+    /// variables does.
+    Scattered,
+    /// The same word read over and over, as when polling a peripheral register.
+    RegisterPoll,
 }
 
 fn parse_usize(src: &str) -> Result<usize, ParseIntError> {
@@ -82,10 +128,52 @@ struct TestData {
     pub data_type: DataType,
 }
 
+/// The mean throughput of a benchmark, in bytes per second.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct Throughput {
+    read: f64,
+    write: f64,
+}
+
+impl Throughput {
+    fn combined(&self) -> f64 {
+        self.read + self.write
+    }
+}
+
+/// The fastest benchmark of a probe.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct BestResult {
+    speed_khz: u32,
+    data_length_words: usize,
+    read_bytes_per_second: f64,
+    write_bytes_per_second: f64,
+}
+
+/// A row of the `--probe-all` comparison.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ProbeResult {
+    rank: usize,
+    probe_type: String,
+    identifier: String,
+    serial_number: Option<String>,
+    /// `None` if no benchmark succeeded on the probe.
+    best: Option<BestResult>,
+    error: Option<String>,
+}
+
+/// The settings shared by all benchmarks of a run.
+struct Benchmark {
+    speeds: Vec<u32>,
+    address: u64,
+    word_size: u32,
+    iterations: usize,
+}
+
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
         let speed = self.common.speed;
-        let common_options = self.common.load()?;
+        let mut common_options = self.common.load()?;
         let mut max_speed = self.max_speed;
         let mut speeds = vec![];
         // if no max-speed specified, assume the user just wants to use a single speed (as per other cli cmds)
@@ -95,33 +183,61 @@ impl Cmd {
         } else {
             speeds.extend_from_slice(&PROBE_SPEEDS);
         };
-        // if we can't print basic info, we're probably not going to succeed with testing so bubble up the error
-        Cmd::print_info(&common_options, lister)?;
+        speeds.retain(|speed| (self.min_speed..=max_speed).contains(speed));
+        let benchmark = Benchmark {
+            speeds,
+            address: self.address,
+            word_size: self.word_size,
+            iterations: self.iterations,
+        };
 
-        for speed in speeds
-            .iter()
-            .filter(|speed| (self.min_speed..=max_speed).contains(*speed))
-        {
-            for size in TEST_SIZES {
-                let res = Cmd::benchmark(
-                    &common_options,
-                    lister,
-                    *speed,
-                    size,
-                    self.address,
-                    self.word_size,
-                    self.iterations,
-                );
-                match res {
-                    core::result::Result::Ok(_) => {}
-                    core::result::Result::Err(e) => {
-                        println!(
-                            "Test failed for speed {} size {} word_size {}bit - {}",
-                            speed, size, self.word_size, e
-                        )
-                    }
-                }
+        if !self.pattern.is_empty() {
+            let results = benchmark.run_patterns(&common_options, lister, &self.pattern)?;
+
+            if let Some(path) = self.json {
+                let json = serde_json::to_string_pretty(&results)?;
+                std::fs::write(&path, json).with_context(|| {
+                    format!("Failed to write the results to {}", path.display())
+                })?;
             }
+
+            return Ok(());
+        }
+
+        if !self.probe_all {
+            benchmark.run_probe(&common_options, lister)?;
+            return Ok(());
+        }
+
+        let probes = lister.list_all();
+        if probes.is_empty() {
+            anyhow::bail!("No debug probes were found.");
+        }
+
+        let mut results = Vec::with_capacity(probes.len());
+        for (index, info) in probes.iter().enumerate() {
+            println!(
+                "Benchmarking probe {}/{}: {info}\n",
+                index + 1,
+                probes.len()
+            );
+            // The index refers to the same list, so identical probes without a serial number
+            // are benchmarked separately.
+            common_options.set_probe_index(index);
+            let result = benchmark.run_probe(&common_options, lister);
+            if let Err(error) = &result {
+                println!("Benchmarking {info} failed: {error:#}\n");
+            }
+            results.push((info, result));
+        }
+
+        let ranking = rank(results);
+        print_ranking(&ranking);
+
+        if let Some(path) = self.json {
+            let json = serde_json::to_string_pretty(&ranking)?;
+            std::fs::write(&path, json)
+                .with_context(|| format!("Failed to write the comparison to {}", path.display()))?;
         }
 
         Ok(())
@@ -155,7 +271,7 @@ impl Cmd {
         address: u64,
         word_size: u32,
         iterations: usize,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<Option<Throughput>, anyhow::Error> {
         let mut probe = common_options.attach_probe(lister)?;
         let target = common_options.get_target_selector()?;
         if probe.set_speed(speed).is_ok() {
@@ -169,7 +285,7 @@ impl Cmd {
                 iterations
             );
             let mut core = session.core(0).context("Failed to attach to core")?;
-            core.halt(Duration::from_millis(100))
+            halt::halt(&mut core, common_options.halt_timeout(DEFAULT_HALT_TIMEOUT))
                 .context("Halting failed")?;
 
             let mut read_results = Vec::<f64>::with_capacity(iterations);
@@ -186,11 +302,15 @@ impl Cmd {
                     break 'inner;
                 }
             }
+            let throughput = Throughput {
+                read: mean(&read_results).expect("invalid mean"),
+                write: mean(&write_results).expect("invalid mean"),
+            };
             println!(
                 "Results: Read: {:.2} bytes/s Std Dev {:.2}, Write: {:.2} bytes/s Std Dev {:.2}",
-                mean(&read_results).expect("invalid mean"),
+                throughput.read,
                 std_deviation(&read_results).expect("invalid std deviation"),
-                mean(&write_results).expect("invalid mean"),
+                throughput.write,
                 std_deviation(&write_results).expect("invalid std deviation")
             );
             if read_results.len() != iterations || write_results.len() != iterations {
@@ -203,10 +323,210 @@ impl Cmd {
             }
             // Insert another blank line to visually seperate results
             println!();
+            Ok(Some(throughput))
         } else {
             println!("failed to set speed {}", speed);
+            Ok(None)
+        }
+    }
+}
+
+impl Benchmark {
+    /// Runs all benchmarks on the selected probe, and returns the fastest one.
+    fn run_probe(
+        &self,
+        common_options: &LoadedProbeOptions,
+        lister: &Lister,
+    ) -> anyhow::Result<Option<BestResult>> {
+        // if we can't print basic info, we're probably not going to succeed with testing so bubble up the error
+        Cmd::print_info(common_options, lister)?;
+
+        let mut best: Option<BestResult> = None;
+        for speed in &self.speeds {
+            for size in TEST_SIZES {
+                let res = Cmd::benchmark(
+                    common_options,
+                    lister,
+                    *speed,
+                    size,
+                    self.address,
+                    self.word_size,
+                    self.iterations,
+                );
+                match res {
+                    Ok(Some(throughput)) => {
+                        let faster = best.map_or(true, |best| {
+                            throughput.combined()
+                                > best.read_bytes_per_second + best.write_bytes_per_second
+                        });
+                        if faster {
+                            best = Some(BestResult {
+                                speed_khz: *speed,
+                                data_length_words: size,
+                                read_bytes_per_second: throughput.read,
+                                write_bytes_per_second: throughput.write,
+                            });
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        println!(
+                            "Test failed for speed {} size {} word_size {}bit - {}",
+                            speed, size, self.word_size, e
+                        )
+                    }
+                }
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// Runs the benchmarks of the access `patterns` at each speed on the selected probe.
+    fn run_patterns(
+        &self,
+        common_options: &LoadedProbeOptions,
+        lister: &Lister,
+        patterns: &[AccessPattern],
+    ) -> anyhow::Result<Vec<PatternResult>> {
+        if ![8, 32, 64].contains(&self.word_size) {
+            anyhow::bail!("Invalid word size {}, use 8, 32 or 64.", self.word_size);
+        }
+
+        Cmd::print_info(common_options, lister)?;
+
+        let mut results = vec![];
+        for speed in &self.speeds {
+            let mut probe = common_options.attach_probe(lister)?;
+            let target = common_options.get_target_selector()?;
+            if probe.set_speed(*speed).is_err() {
+                println!("failed to set speed {}", speed);
+                continue;
+            }
+            let mut session = common_options.attach_session(probe, target)?;
+            let mut core = session.core(0).context("Failed to attach to core")?;
+            halt::halt(&mut core, common_options.halt_timeout(DEFAULT_HALT_TIMEOUT))
+                .context("Halting failed")?;
+
+            for pattern in patterns {
+                match self.run_pattern(&mut core, *pattern) {
+                    Ok(result) => {
+                        let result = PatternResult {
+                            pattern: *pattern,
+                            speed_khz: *speed,
+                            word_size: self.word_size,
+                            read: result.0,
+                            write: result.1,
+                        };
+                        result.print();
+                        results.push(result);
+                    }
+                    Err(e) => println!(
+                        "Test failed for pattern {:?} speed {} word_size {}bit - {:#}",
+                        pattern, speed, self.word_size, e
+                    ),
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Runs the iterations of a pattern benchmark, and returns the mean read and write results.
+    fn run_pattern(
+        &self,
+        core: &mut probe_rs::Core,
+        pattern: AccessPattern,
+    ) -> anyhow::Result<(Option<AccessResult>, Option<AccessResult>)> {
+        let mut rng = fastrand::Rng::new();
+        let mut reads = vec![];
+        let mut writes = vec![];
+
+        for _ in 0..self.iterations {
+            let accesses = pattern.accesses(&mut rng, PATTERN_REGION_WORDS);
+            let mut test = PatternTest::new(self.address, self.word_size, &mut rng);
+
+            if pattern == AccessPattern::RegisterPoll {
+                // Only the reads are measured, of the data written here.
+                test.initialize(core)?;
+            } else {
+                writes.push(test.write(core, &accesses, &mut rng)?);
+            }
+            reads.push(test.read(core, &accesses)?);
+        }
+
+        Ok((AccessResult::mean(&reads), AccessResult::mean(&writes)))
+    }
+}
+
+/// Orders the probes by the combined read and write throughput of their fastest benchmark.
+///
+/// Probes without a successful benchmark are ranked last.
+fn rank(results: Vec<(&DebugProbeInfo, anyhow::Result<Option<BestResult>>)>) -> Vec<ProbeResult> {
+    let mut ranking = results
+        .into_iter()
+        .map(|(info, result)| {
+            let (best, error) = match result {
+                Ok(Some(best)) => (Some(best), None),
+                Ok(None) => (None, Some("no benchmark succeeded".to_string())),
+                Err(error) => (None, Some(format!("{error:#}"))),
+            };
+            ProbeResult {
+                rank: 0,
+                probe_type: info.probe_type(),
+                identifier: info.identifier.clone(),
+                serial_number: info.serial_number.clone(),
+                best,
+                error,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let score = |result: &ProbeResult| {
+        result
+            .best
+            .map(|best| best.read_bytes_per_second + best.write_bytes_per_second)
+    };
+    // `None` sorts before any throughput, so it ends up last.
+    ranking.sort_by(|a, b| {
+        score(b)
+            .partial_cmp(&score(a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    for (index, result) in ranking.iter_mut().enumerate() {
+        result.rank = index + 1;
+    }
+
+    ranking
+}
+
+fn print_ranking(ranking: &[ProbeResult]) {
+    println!(
+        "{:<4}  {:<24}  {:<24}  {:>10}  {:>14}  {:>14}",
+        "Rank", "Probe", "Serial", "Speed kHz", "Read B/s", "Write B/s"
+    );
+    for result in ranking {
+        let probe = format!("{} ({})", result.identifier, result.probe_type);
+        let serial = result.serial_number.as_deref().unwrap_or("-");
+        match (&result.best, &result.error) {
+            (Some(best), _) => println!(
+                "{:<4}  {:<24}  {:<24}  {:>10}  {:>14.2}  {:>14.2}",
+                result.rank,
+                probe,
+                serial,
+                best.speed_khz,
+                best.read_bytes_per_second,
+                best.write_bytes_per_second
+            ),
+            (None, error) => println!(
+                "{:<4}  {:<24}  {:<24}  failed: {}",
+                result.rank,
+                probe,
+                serial,
+                error.as_deref().unwrap_or("unknown error")
+            ),
         }
-        Ok(())
     }
 }
 
@@ -351,6 +671,256 @@ impl TestData {
     }
 }
 
+/// The number of words of the test region of the `--pattern` benchmarks.
+const PATTERN_REGION_WORDS: usize = 1024;
+/// The number of accesses of one iteration of the random and register poll benchmarks.
+const PATTERN_ACCESSES: usize = 256;
+/// The number of words of each access of the scattered benchmark.
+const SCATTERED_ACCESS_WORDS: usize = 8;
+
+impl AccessPattern {
+    /// Returns the offset and the number of words of each access of one iteration, in a test
+    /// region of `region_words` words.
+    fn accesses(self, rng: &mut fastrand::Rng, region_words: usize) -> Vec<(usize, usize)> {
+        match self {
+            AccessPattern::Sequential => vec![(0, region_words)],
+            AccessPattern::Random => (0..PATTERN_ACCESSES)
+                .map(|_| (rng.usize(..region_words), 1))
+                .collect(),
+            AccessPattern::Scattered => (0..PATTERN_ACCESSES / SCATTERED_ACCESS_WORDS)
+                .map(|_| {
+                    let offset = rng.usize(..=region_words - SCATTERED_ACCESS_WORDS);
+                    (offset, SCATTERED_ACCESS_WORDS)
+                })
+                .collect(),
+            AccessPattern::RegisterPoll => vec![(0, 1); PATTERN_ACCESSES],
+        }
+    }
+}
+
+/// The throughput and latency of the reads or writes of a pattern benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct AccessResult {
+    bytes_per_second: f64,
+    /// The mean duration of a single access.
+    latency_us: f64,
+}
+
+impl AccessResult {
+    fn new(bytes: usize, accesses: usize, duration: Duration) -> Self {
+        AccessResult {
+            bytes_per_second: bytes as f64 / duration.as_secs_f64(),
+            latency_us: duration.as_secs_f64() * 1e6 / accesses as f64,
+        }
+    }
+
+    fn mean(results: &[AccessResult]) -> Option<AccessResult> {
+        let throughput = results
+            .iter()
+            .map(|r| r.bytes_per_second)
+            .collect::<Vec<_>>();
+        let latency = results.iter().map(|r| r.latency_us).collect::<Vec<_>>();
+
+        Some(AccessResult {
+            bytes_per_second: mean(&throughput)?,
+            latency_us: mean(&latency)?,
+        })
+    }
+}
+
+/// The results of a pattern benchmark at one speed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct PatternResult {
+    pattern: AccessPattern,
+    speed_khz: u32,
+    word_size: u32,
+    read: Option<AccessResult>,
+    /// `None` for patterns which only read.
+    write: Option<AccessResult>,
+}
+
+impl PatternResult {
+    fn print(&self) {
+        let format = |result: Option<AccessResult>| match result {
+            Some(result) => format!(
+                "{:.2} bytes/s, {:.2} us per access",
+                result.bytes_per_second, result.latency_us
+            ),
+            None => "-".to_string(),
+        };
+        println!(
+            "Pattern {:?}: Speed {}, Word size {}bit, Read: {}, Write: {}",
+            self.pattern,
+            self.speed_khz,
+            self.word_size,
+            format(self.read),
+            format(self.write)
+        );
+    }
+}
+
+/// The test region of a pattern benchmark, and the data which was written to it.
+struct PatternTest {
+    address: u64,
+    word_size: u32,
+    /// The expected content of the test region.
+    expected: Vec<u64>,
+}
+
+impl PatternTest {
+    fn new(address: u64, word_size: u32, rng: &mut fastrand::Rng) -> Self {
+        PatternTest {
+            address,
+            word_size,
+            expected: (0..PATTERN_REGION_WORDS)
+                .map(|_| random_word(rng, word_size))
+                .collect(),
+        }
+    }
+
+    fn address_of(&self, offset: usize) -> u64 {
+        self.address + (offset * self.word_size as usize / 8) as u64
+    }
+
+    /// Writes the expected content to the whole test region, without measuring it.
+    fn initialize(&self, core: &mut probe_rs::Core) -> anyhow::Result<()> {
+        write_words(core, self.address, self.word_size, &self.expected)?;
+
+        Ok(())
+    }
+
+    /// Writes new random data with the `accesses`, and measures the writes.
+    fn write(
+        &mut self,
+        core: &mut probe_rs::Core,
+        accesses: &[(usize, usize)],
+        rng: &mut fastrand::Rng,
+    ) -> anyhow::Result<AccessResult> {
+        // Initialize the whole region first, so that reads of words which are not written by the
+        // pattern can be verified.
+        self.initialize(core)?;
+
+        let mut duration = Duration::ZERO;
+        let mut bytes = 0;
+        for &(offset, words) in accesses {
+            let data = (0..words)
+                .map(|_| random_word(rng, self.word_size))
+                .collect::<Vec<_>>();
+            let address = self.address_of(offset);
+            duration += write_words(core, address, self.word_size, &data)?;
+            bytes += words * self.word_size as usize / 8;
+            self.expected[offset..offset + words].copy_from_slice(&data);
+        }
+
+        Ok(AccessResult::new(bytes, accesses.len(), duration))
+    }
+
+    /// Reads with the `accesses`, verifies the data and measures the reads.
+    fn read(
+        &self,
+        core: &mut probe_rs::Core,
+        accesses: &[(usize, usize)],
+    ) -> anyhow::Result<AccessResult> {
+        let mut duration = Duration::ZERO;
+        let mut bytes = 0;
+        for &(offset, words) in accesses {
+            let address = self.address_of(offset);
+            let (data, read_duration) = read_words(core, address, self.word_size, words)?;
+            duration += read_duration;
+            bytes += words * self.word_size as usize / 8;
+
+            for (index, (read, expected)) in data
+                .iter()
+                .zip(&self.expected[offset..offset + words])
+                .enumerate()
+            {
+                if read != expected {
+                    anyhow::bail!(
+                        "Readback data differs at address {:08x}: expected word {:08x}, got word {:08x}",
+                        self.address_of(offset + index),
+                        expected,
+                        read
+                    );
+                }
+            }
+        }
+
+        Ok(AccessResult::new(bytes, accesses.len(), duration))
+    }
+}
+
+fn random_word(rng: &mut fastrand::Rng, word_size: u32) -> u64 {
+    match word_size {
+        8 => rng.u8(..).into(),
+        32 => rng.u32(..).into(),
+        _ => rng.u64(..),
+    }
+}
+
+/// Writes `data` with the given word size, and returns the duration of the transfer.
+fn write_words(
+    core: &mut probe_rs::Core,
+    address: u64,
+    word_size: u32,
+    data: &[u64],
+) -> anyhow::Result<Duration> {
+    let start;
+    match word_size {
+        8 => {
+            let data = data.iter().map(|&word| word as u8).collect::<Vec<_>>();
+            start = Instant::now();
+            core.write_8(address, &data)
+        }
+        32 => {
+            let data = data.iter().map(|&word| word as u32).collect::<Vec<_>>();
+            start = Instant::now();
+            core.write_32(address, &data)
+        }
+        _ => {
+            start = Instant::now();
+            core.write_64(address, data)
+        }
+    }
+    .context("Writing the sample data failed")?;
+
+    Ok(start.elapsed())
+}
+
+/// Reads `words` words with the given word size, and returns them with the duration of the
+/// transfer.
+fn read_words(
+    core: &mut probe_rs::Core,
+    address: u64,
+    word_size: u32,
+    words: usize,
+) -> anyhow::Result<(Vec<u64>, Duration)> {
+    let start = Instant::now();
+    let (data, duration) = match word_size {
+        8 => {
+            let mut data = vec![0u8; words];
+            core.read_8(address, &mut data)
+                .context("Reading the sample data failed")?;
+            let duration = start.elapsed();
+            (data.into_iter().map(u64::from).collect(), duration)
+        }
+        32 => {
+            let mut data = vec![0u32; words];
+            core.read_32(address, &mut data)
+                .context("Reading the sample data failed")?;
+            let duration = start.elapsed();
+            (data.into_iter().map(u64::from).collect(), duration)
+        }
+        _ => {
+            let mut data = vec![0u64; words];
+            core.read_64(address, &mut data)
+                .context("Reading the sample data failed")?;
+            (data, start.elapsed())
+        }
+    };
+
+    Ok((data, duration))
+}
+
 /// Calculate arithmetic mean for data
 fn mean(data: &[f64]) -> Option<f64> {
     let sum = data.iter().sum::<f64>();
@@ -381,3 +951,59 @@ fn std_deviation(data: &[f64]) -> Option<f64> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probes_are_ranked_by_throughput() {
+        let probe = |serial: &str| {
+            DebugProbeInfo::new(
+                "J-Link",
+                0x1366,
+                0x0101,
+                Some(serial.to_string()),
+                &probe_rs::probe::jlink::JLinkFactory,
+                None,
+            )
+        };
+        let best = |read: f64, write: f64| BestResult {
+            speed_khz: 4000,
+            data_length_words: 8192,
+            read_bytes_per_second: read,
+            write_bytes_per_second: write,
+        };
+        let (failed, slow, fast) = (probe("1"), probe("2"), probe("3"));
+
+        let ranking = rank(vec![
+            (&failed, Err(anyhow::anyhow!("no target"))),
+            (&slow, Ok(Some(best(100.0, 50.0)))),
+            (&fast, Ok(Some(best(90.0, 90.0)))),
+        ]);
+
+        let serials = ranking
+            .iter()
+            .map(|result| (result.rank, result.serial_number.as_deref().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(serials, [(1, "3"), (2, "2"), (3, "1")]);
+        assert_eq!(ranking[2].error.as_deref(), Some("no target"));
+        assert_eq!(ranking[0].probe_type, "J-Link");
+    }
+
+    #[test]
+    fn pattern_accesses_stay_in_the_region() {
+        let mut rng = fastrand::Rng::with_seed(1);
+
+        assert_eq!(AccessPattern::Sequential.accesses(&mut rng, 64), [(0, 64)]);
+        assert_eq!(
+            AccessPattern::RegisterPoll.accesses(&mut rng, 64),
+            vec![(0, 1); PATTERN_ACCESSES]
+        );
+        for pattern in [AccessPattern::Random, AccessPattern::Scattered] {
+            let accesses = pattern.accesses(&mut rng, 64);
+            assert!(!accesses.is_empty());
+            assert!(accesses.iter().all(|(offset, words)| offset + words <= 64));
+        }
+    }
+}
@@ -26,7 +26,7 @@ use time::{OffsetDateTime, UtcOffset};
 
 use crate::util::cargo::target_instruction_set;
 use crate::util::common_options::{BinaryDownloadOptions, OperationError, ProbeOptions};
-use crate::util::flash::{build_loader, run_flash_download};
+use crate::util::flash::{build_loader, run_flash_download, EraseMode};
 use crate::util::logging::setup_logging;
 use crate::util::rtt::{
     self, try_attach_to_rtt_shared, DefmtState, RttActiveTarget, RttChannelConfig, RttConfig,
@@ -184,10 +184,22 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
         protocol: Some(config.probe.protocol),
         non_interactive: false,
         probe: selector,
+        probe_index: None,
         speed: config.probe.speed,
         connect_under_reset: config.general.connect_under_reset,
+        attach_under_reset: false,
+        power_target: false,
         dry_run: false,
         allow_erase_all: config.flashing.enabled || config.gdb.enabled,
+        debug_auth_key: None,
+        strict_chip: false,
+        swd_multidrop_target: None,
+        halt_timeout: None,
+        reset_delay: None,
+        post_attach_delay: None,
+        reset_vector_catch_on_connect: false,
+        no_reset: false,
+        cross_halt: false,
     };
 
     let (mut session, probe_options) = match probe_options.simple_attach(&lister) {
@@ -231,8 +243,13 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
             disable_progressbars: opt.disable_progressbars,
             disable_double_buffering: config.flashing.disable_double_buffering,
             restore_unwritten: config.flashing.restore_unwritten_bytes,
+            elf_gap_fill: Default::default(),
             flash_layout_output_path: None,
+            operation_log: None,
             verify: config.flashing.verify,
+            force_erase: false,
+            flash_halt_mode: Default::default(),
+            option_bytes: Default::default(),
         };
         let format_options = FormatOptions::default();
         let loader = build_loader(&mut session, path, format_options, image_instr_set)?;
@@ -242,7 +259,7 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
             &download_options,
             &probe_options,
             loader,
-            config.flashing.do_chip_erase,
+            EraseMode::from_chip_erase_flag(config.flashing.do_chip_erase),
         )?;
     }
 
@@ -264,6 +281,7 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
     if config.gdb.enabled {
         let gdb_connection_string = config.gdb.gdb_connection_string.clone();
         let session = session.clone();
+        let exec_file = std::fs::canonicalize(path).ok();
 
         gdb_thread_handle = Some(std::thread::spawn(move || {
             let gdb_connection_string =
@@ -275,10 +293,16 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
                 gdb_connection_string,
             ));
 
-            let instances = {
+            let instances: Vec<_> = {
                 let session = session.lock();
                 GdbInstanceConfiguration::from_session(&session, Some(gdb_connection_string))
-            };
+            }
+            .into_iter()
+            .map(|instance| match &exec_file {
+                Some(exec_file) => instance.with_exec_file(exec_file),
+                None => instance,
+            })
+            .collect();
 
             if let Err(e) = probe_rs::gdb_server::run(&session, instances.iter()) {
                 logging::eprintln("During the execution of GDB an error was encountered:");
@@ -317,6 +341,7 @@ fn run_rttui_app(
     let mut rtt_config = RttConfig {
         enabled: true,
         channels: vec![],
+        json: false,
     };
 
     // Make sure our defaults are the same as the ones intended in the config struct.
@@ -73,7 +73,13 @@ pub(crate) fn render_diagnostics(error: OperationError) {
         OperationError::MultipleProbesFound { .. } => (
             error.to_string(),
             vec![
-                "You can select a probe with the `--probe` argument. See `--help` for how to use it.".into()
+                "You can select a probe with the `--probe` or `--probe-index` argument. See `--help` for how to use it.".into()
+            ],
+        ),
+        OperationError::ProbeIndexOutOfRange { .. } => (
+            error.to_string(),
+            vec![
+                "You can list all connected probes and their indices by running `probe-rs list`.".into()
             ],
         ),
         OperationError::FlashingFailed { source, target, target_spec, .. } => generate_flash_error_hints(source, target, target_spec),
@@ -81,6 +87,10 @@ pub(crate) fn render_diagnostics(error: OperationError) {
             error.to_string(),
             vec![],
         ),
+        OperationError::DebugAuthKeyNotFound { .. } => (
+            error.to_string(),
+            vec![],
+        ),
         OperationError::FailedChipDescriptionParsing { .. } => (
             error.to_string(),
             vec![],
@@ -188,10 +198,29 @@ pub(crate) fn render_diagnostics(error: OperationError) {
                 )
             },
         },
+        OperationError::ChipMismatch { .. } => (
+            error.to_string(),
+            vec![
+                "Check that the `--chip` argument names the chip on your board.".into(),
+                "You can identify the connected chip by running `probe-rs info --autodetect`.".into(),
+            ],
+        ),
+        OperationError::AccessPortNotAvailable { .. } => (
+            error.to_string(),
+            vec![
+                "Run `probe-rs info` to list the access ports of the target.".into(),
+            ],
+        ),
         OperationError::AttachingToCoreFailed(_e) =>  (
             error.to_string(),
             vec![],
         ),
+        OperationError::FailedToSetTargetPower(_e) => (
+            error.to_string(),
+            vec![
+                "Not all probes can supply power to the target. Remove `--power-target` and power the target externally.".into()
+            ],
+        ),
         OperationError::TargetResetFailed(_e) =>  (
             error.to_string(),
             vec![],
@@ -200,6 +229,34 @@ pub(crate) fn render_diagnostics(error: OperationError) {
             error.to_string(),
             vec![],
         ),
+        OperationError::HaltFailed(_e) => (
+            error.to_string(),
+            vec![],
+        ),
+        OperationError::ResetVectorCatchFailed(_e) => (
+            error.to_string(),
+            vec![
+                "Try `--connect-under-reset`, so the core is held in reset while probe-rs connects.".into(),
+            ],
+        ),
+        OperationError::HaltTimeout { .. } => (
+            error.to_string(),
+            vec![
+                "Use `--halt-timeout <ms>` to wait longer for the core to halt.".into(),
+            ],
+        ),
+        OperationError::CrossHaltFailed(_e) => (
+            error.to_string(),
+            vec![
+                "Cross halting is only supported for Cortex-M cores with a CoreSight CTI.".into(),
+            ],
+        ),
+        OperationError::ResetNotAllowed { .. } => (
+            error.to_string(),
+            vec![
+                "Remove `--no-reset` to allow probe-rs to reset the target.".into(),
+            ],
+        ),
         OperationError::CliArgument(_e) => (
             error.to_string(),
             vec![],
@@ -208,6 +265,27 @@ pub(crate) fn render_diagnostics(error: OperationError) {
             error.to_string(),
             vec![],
         ),
+        OperationError::OptionBytes(_e) => (
+            error.to_string(),
+            vec![],
+        ),
+        OperationError::FailedToWriteOperationLog { .. } => (
+            error.to_string(),
+            vec![],
+        ),
+        OperationError::MultidropTargetForMultipleDebugPorts { .. } => (
+            error.to_string(),
+            vec![
+                "The chip selects its cores with their own TARGETSEL values already, so `--swd-multidrop-target` is not needed.".into(),
+            ],
+        ),
+        OperationError::NoChipSelected => (
+            error.to_string(),
+            vec![
+                "Specify the chip with the `--chip` argument.".into(),
+                "You can list all the available chips by running `probe-rs chip list`.".into(),
+            ],
+        ),
     };
 
     use std::io::Write;
@@ -302,6 +380,9 @@ fn generate_flash_error_hints(
 
                 hints
             },
+            FlashError::NoFlashLoaderAlgorithmForData { .. } => vec![
+                "External flash (e.g. QSPI) needs a flash algorithm for the attached flash device. Make sure your target description contains one whose address range covers the data.".into()
+            ],
             FlashError::EraseFailed { ..} => vec![
                 "Perhaps your chip has write protected sectors that need to be cleared?".into(),
                 "Perhaps you need the --nmagic linker arg. See https://github.com/rust-embedded/cortex-m-quickstart/pull/95 for more information.".into()
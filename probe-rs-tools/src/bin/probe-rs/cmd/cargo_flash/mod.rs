@@ -5,6 +5,7 @@ use colored::Colorize;
 use diagnostics::render_diagnostics;
 use probe_rs::probe::list::Lister;
 use std::ffi::OsString;
+use std::time::Duration;
 use std::{path::PathBuf, process};
 
 use crate::util::cargo::target_instruction_set;
@@ -12,6 +13,7 @@ use crate::util::common_options::{
     BinaryDownloadOptions, CargoOptions, OperationError, ProbeOptions,
 };
 use crate::util::flash;
+use crate::util::halt;
 use crate::util::logging::{setup_logging, LevelFilter};
 use crate::util::{cargo::build_artifact, logging};
 
@@ -137,7 +139,7 @@ fn main_try(args: &[OsString]) -> Result<(), OperationError> {
         &opt.download_options,
         &probe_options,
         loader,
-        false,
+        flash::EraseMode::Sector,
     )?;
 
     // Reset target according to CLI options
@@ -146,8 +148,10 @@ fn main_try(args: &[OsString]) -> Result<(), OperationError> {
             .core(0)
             .map_err(OperationError::AttachingToCoreFailed)?;
         if opt.reset_halt {
-            core.reset_and_halt(std::time::Duration::from_millis(500))
-                .map_err(OperationError::TargetResetHaltFailed)?;
+            halt::reset_and_halt(
+                &mut core,
+                probe_options.halt_timeout(Duration::from_millis(500)),
+            )?;
         } else {
             core.reset().map_err(OperationError::TargetResetFailed)?;
         }
@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
 use bytesize::ByteSize;
 use probe_rs::config::MemoryRegion;
+use probe_rs_target::ChipFamily;
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -19,6 +23,18 @@ enum Subcommand {
         /// The name of the chip to display.
         name: String,
     },
+    /// Exports the description of a chip as YAML, with its memory map, cores and flash algorithms.
+    ///
+    /// The description can be modified and loaded with `--chip-description-path`. It only
+    /// contains the exported chip, and replaces all chips of the family when it is loaded.
+    #[clap(name = "export-target")]
+    ExportTarget {
+        /// The name of the chip to export.
+        name: String,
+        /// Write the description to this file, instead of to stdout.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
 }
 
 impl Cmd {
@@ -28,6 +44,15 @@ impl Cmd {
         match self.subcommand {
             Subcommand::List => print_families(output),
             Subcommand::Info { name } => print_chip_info(output, &name),
+            Subcommand::ExportTarget { name, output: None } => export_target(output, &name),
+            Subcommand::ExportTarget {
+                name,
+                output: Some(path),
+            } => {
+                let file = std::fs::File::create(&path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                export_target(file, &name)
+            }
         }
     }
 }
@@ -78,6 +103,70 @@ pub fn print_chip_info(mut output: impl std::io::Write, name: &str) -> anyhow::R
     Ok(())
 }
 
+/// Writes the description of the chip `name` as YAML, without the other chips of its family.
+pub fn export_target(mut output: impl std::io::Write, name: &str) -> anyhow::Result<()> {
+    let family = chip_description(name)?;
+    output.write_all(serialize_to_yaml(&family)?.as_bytes())?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Serializes the family like `target-gen` does.
+///
+/// `serde_yaml` serializes hex formatted integers as single quoted strings, e.g. '0x1234', which
+/// would be parsed as strings again. The quotes are removed so that the description round-trips.
+fn serialize_to_yaml(family: &ChipFamily) -> anyhow::Result<String> {
+    let yaml = serde_yaml::to_string(family)?
+        .lines()
+        .map(|line| {
+            if (line.contains("'0x") || line.contains("'0X")) && line.ends_with('\'') {
+                line.replace('\'', "")
+            } else {
+                line.to_string()
+            }
+        })
+        .fold(String::new(), |mut yaml, line| {
+            yaml.push_str(&line);
+            yaml.push('\n');
+            yaml
+        });
+
+    Ok(yaml)
+}
+
+/// Returns the family of the chip `name`, reduced to the chip and the flash algorithms it uses.
+fn chip_description(name: &str) -> anyhow::Result<ChipFamily> {
+    let (target, mut family) = probe_rs::config::get_target_and_family_by_name(name)?;
+
+    family.variants.retain(|chip| chip.name == target.name);
+    let algorithms = family
+        .variants
+        .iter()
+        .flat_map(|chip| chip.flash_algorithms.iter())
+        .collect::<Vec<_>>();
+    family
+        .flash_algorithms
+        .retain(|algorithm| algorithms.contains(&&algorithm.name));
+
+    Ok(family)
+}
+
+#[test]
+fn exported_target_parses_identically() {
+    let mut buff = Vec::new();
+    export_target(&mut buff, "nrf52840_xxaa").unwrap();
+
+    let family: ChipFamily = serde_yaml::from_slice(&buff).unwrap();
+    family.validate().unwrap();
+    assert_eq!(family.variants.len(), 1);
+    assert_eq!(family.variants[0].name, "nRF52840_xxAA");
+    assert_eq!(family.flash_algorithms.len(), 1);
+
+    // Serializing the parsed description again results in the same YAML.
+    let reserialized = serialize_to_yaml(&family).unwrap();
+    assert_eq!(reserialized, String::from_utf8(buff).unwrap());
+}
+
 #[test]
 fn single_chip_output() {
     let mut buff = Vec::new();
@@ -17,7 +17,7 @@ use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
     path::Path,
 };
-use time::UtcOffset;
+use time::{error::IndeterminateOffset, UtcOffset};
 
 use crate::util::common_options::OperationError;
 
@@ -89,9 +89,9 @@ pub struct Cmd {
 pub fn run(
     cmd: Cmd,
     lister: &Lister,
-    time_offset: UtcOffset,
+    local_offset: Result<UtcOffset, IndeterminateOffset>,
     log_file: Option<&Path>,
 ) -> Result<()> {
     let addr = SocketAddr::new(cmd.ip, cmd.port);
-    debug(lister, addr, cmd.single_session, log_file, time_offset)
+    debug(lister, addr, cmd.single_session, log_file, local_offset)
 }
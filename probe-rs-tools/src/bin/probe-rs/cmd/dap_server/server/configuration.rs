@@ -161,10 +161,22 @@ impl SessionConfig {
             protocol: self.wire_protocol,
             non_interactive: true,
             probe: self.probe.clone(),
+            probe_index: None,
             speed: self.speed,
             connect_under_reset: self.connect_under_reset,
+            attach_under_reset: false,
+            power_target: false,
             dry_run: false,
             allow_erase_all: self.allow_erase_all,
+            debug_auth_key: None,
+            strict_chip: false,
+            swd_multidrop_target: None,
+            halt_timeout: None,
+            reset_delay: None,
+            post_attach_delay: None,
+            reset_vector_catch_on_connect: false,
+            no_reset: false,
+            cross_halt: false,
         }
     }
 }
@@ -737,6 +737,14 @@ impl Debugger {
                             .update_progress(Some(progress), Some("Erasing Sectors"), id)
                             .ok();
                     }
+                    ProgressEvent::SectorEraseSkipped { size } => {
+                        flash_progress.sector_size_done += size as usize;
+                        let progress = flash_progress.sector_size_done as f64
+                            / flash_progress.total_sector_size as f64;
+                        debug_adapter
+                            .update_progress(Some(progress), Some("Erasing Sectors"), id)
+                            .ok();
+                    }
                     ProgressEvent::FailedErasing => {
                         debug_adapter
                             .update_progress(Some(1.0), Some("Erasing Sectors Failed!"), id)
@@ -772,6 +780,8 @@ impl Debugger {
                             .ok();
                     }
                     ProgressEvent::DiagnosticMessage { .. } => (),
+                    ProgressEvent::Operation { .. } => (),
+                    _ => (),
                 }
             })
         });
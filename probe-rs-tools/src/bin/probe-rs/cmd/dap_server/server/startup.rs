@@ -1,5 +1,6 @@
 use super::debugger::Debugger;
 use crate::cmd::dap_server::debug_adapter::{dap::adapter::*, protocol::DapAdapter};
+use crate::util::logging::warn_on_utc_fallback;
 use anyhow::{Context, Result};
 use probe_rs::probe::list::Lister;
 use serde::Deserialize;
@@ -9,7 +10,7 @@ use std::{
     path::Path,
     time::{Duration, UNIX_EPOCH},
 };
-use time::UtcOffset;
+use time::{error::IndeterminateOffset, UtcOffset};
 
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
 pub(crate) enum TargetSessionType {
@@ -36,9 +37,10 @@ pub fn debug(
     addr: std::net::SocketAddr,
     single_session: bool,
     log_file: Option<&Path>,
-    timestamp_offset: UtcOffset,
+    local_offset: Result<UtcOffset, IndeterminateOffset>,
 ) -> Result<()> {
-    let mut debugger = Debugger::new(timestamp_offset, log_file)?;
+    let mut debugger = Debugger::new(local_offset.unwrap_or(UtcOffset::UTC), log_file)?;
+    warn_on_utc_fallback(local_offset);
 
     let old_hook = std::panic::take_hook();
     let logger = debugger.debug_logger.clone();
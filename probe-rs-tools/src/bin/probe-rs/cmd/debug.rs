@@ -19,7 +19,7 @@ use probe_rs::CoreDumpError;
 use probe_rs::CoreInterface;
 use probe_rs::{
     debug::{debug_info::DebugInfo, registers::DebugRegisters, stack_frame::StackFrame},
-    Core, CoreType, InstructionSet, MemoryInterface, RegisterValue,
+    Core, CoreType, InstructionSet, MemoryInterface, RegisterValue, WatchpointKind,
 };
 use rustyline::{error::ReadlineError, DefaultEditor};
 
@@ -552,6 +552,74 @@ impl DebugCli {
             },
         });
 
+        cli.add_command(Command {
+            name: "watch",
+            help_text: "Set a watchpoint: watch <address> [length, default 4] [write|read|access, default write]",
+
+            function: |cli_data, args| {
+                let address = get_int_argument(args, 0)?;
+                let length = if args.len() > 1 {
+                    get_int_argument(args, 1)?
+                } else {
+                    4
+                };
+                let kind = match args.get(2).copied() {
+                    None | Some("write") => WatchpointKind::Write,
+                    Some("read") => WatchpointKind::Read,
+                    Some("access") => WatchpointKind::ReadWrite,
+                    Some(other) => {
+                        return Err(CliError::ArgumentParseError {
+                            argument_index: 2,
+                            argument: other.to_string(),
+                            source: anyhow!("Expected one of 'write', 'read' or 'access'"),
+                        })
+                    }
+                };
+
+                cli_data.core.set_hw_watchpoint(address, length, kind)?;
+
+                println!("Set new watchpoint for {length} bytes at address {address:#08x}");
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "clear_watch",
+            help_text: "Clear a watchpoint",
+
+            function: |cli_data, args| {
+                let address = get_int_argument(args, 0)?;
+
+                cli_data.core.clear_hw_watchpoint(address)?;
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "list_watch",
+            help_text: "List all set watchpoints",
+            function: |cli_data, _| {
+                let watchpoints = cli_data.core.hw_watchpoints()?;
+                let units = watchpoints.len();
+
+                watchpoints
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(idx, wp)| wp.map(|wp| (idx, wp)))
+                    .for_each(|(idx, wp)| {
+                        println!(
+                            "Watchpoint {idx} - {:#010X} ({} bytes, {:?})",
+                            wp.address, wp.length, wp.kind
+                        )
+                    });
+                println!("The core has {units} watchpoint units.");
+
+                Ok(CliState::Continue)
+            },
+        });
+
         cli.add_command(Command {
             name: "bt",
             help_text: "Show backtrace",
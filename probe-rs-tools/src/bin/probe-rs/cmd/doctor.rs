@@ -0,0 +1,334 @@
+use std::path::Path;
+
+use colored::Colorize;
+use probe_rs::probe::{
+    list::{AllProbesLister, Lister},
+    DebugProbeInfo,
+};
+use time::{macros::datetime, OffsetDateTime, UtcOffset};
+
+/// The directories which udev loads rules from.
+const UDEV_RULES_DIRECTORIES: &[&str] = &[
+    "/etc/udev/rules.d",
+    "/run/udev/rules.d",
+    "/lib/udev/rules.d",
+    "/usr/lib/udev/rules.d",
+];
+
+const PROBE_SETUP_GUIDE: &str =
+    "See https://probe.rs/docs/getting-started/probe-setup for how to set up your probes.";
+
+#[derive(clap::Parser)]
+pub struct Cmd {}
+
+impl Cmd {
+    /// Runs all checks and prints their results.
+    ///
+    /// `local_offset` is the result of determining the local time offset at startup.
+    pub fn run(
+        self,
+        lister: &Lister,
+        local_offset: Result<UtcOffset, time::error::IndeterminateOffset>,
+    ) -> anyhow::Result<()> {
+        let probes = lister.list_all();
+
+        let checks = [
+            check_backend(),
+            check_probes(&probes),
+            check_probe_access(lister, &probes),
+            check_udev_rules(&probes),
+            check_clock(local_offset),
+        ];
+
+        for check in &checks {
+            check.print();
+        }
+
+        let failed = checks
+            .iter()
+            .filter(|check| matches!(check.outcome, Outcome::Failed { .. }))
+            .count();
+
+        if failed > 0 {
+            anyhow::bail!("{failed} critical check(s) failed.");
+        }
+
+        println!();
+        println!("No critical problems were found.");
+
+        Ok(())
+    }
+}
+
+/// The result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Passed(String),
+    /// A problem which may cause trouble, but does not prevent probe-rs from working.
+    Warning {
+        problem: String,
+        remediation: Vec<String>,
+    },
+    /// A problem which prevents probe-rs from working.
+    Failed {
+        problem: String,
+        remediation: Vec<String>,
+    },
+    /// The check does not apply to this system.
+    Skipped(String),
+}
+
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+}
+
+impl Check {
+    fn print(&self) {
+        let (status, message, remediation) = match &self.outcome {
+            Outcome::Passed(message) => (" OK ".green(), message, &[][..]),
+            Outcome::Skipped(message) => ("SKIP".dimmed(), message, &[][..]),
+            Outcome::Warning {
+                problem,
+                remediation,
+            } => ("WARN".yellow(), problem, &remediation[..]),
+            Outcome::Failed {
+                problem,
+                remediation,
+            } => ("FAIL".red(), problem, &remediation[..]),
+        };
+
+        println!("[{status}] {}: {message}", self.name.bold());
+        for hint in remediation {
+            for line in textwrap::wrap(hint, 90) {
+                println!("       {line}");
+            }
+        }
+    }
+}
+
+/// Checks that USB devices can be enumerated.
+fn check_backend() -> Check {
+    let outcome = match nusb::list_devices() {
+        Ok(devices) => Outcome::Passed(format!("{} USB devices found", devices.count())),
+        Err(error) => Outcome::Failed {
+            problem: format!("Listing the USB devices failed: {error}"),
+            remediation: vec![
+                "Make sure that the USB subsystem is available, e.g. that /dev/bus/usb is mounted when running in a container.".into(),
+            ],
+        },
+    };
+
+    Check {
+        name: "USB backend",
+        outcome,
+    }
+}
+
+/// Checks that at least one probe is connected.
+fn check_probes(probes: &[DebugProbeInfo]) -> Check {
+    let outcome = if probes.is_empty() {
+        Outcome::Failed {
+            problem: "No debug probes were found.".into(),
+            remediation: vec![
+                "Check the USB cable, and make sure the probe is powered and shows up in the list of USB devices of your system.".into(),
+                format!(
+                    "The following probe drivers are compiled in: {}.",
+                    AllProbesLister::drivers()
+                        .iter()
+                        .map(|driver| driver.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                "If you are on Windows, make sure to install the correct driver. For J-Link usage you will need the https://zadig.akeo.ie/ driver.".into(),
+                PROBE_SETUP_GUIDE.into(),
+            ],
+        }
+    } else {
+        Outcome::Passed(format!("{} debug probe(s) found", probes.len()))
+    };
+
+    Check {
+        name: "Debug probes",
+        outcome,
+    }
+}
+
+/// Checks that all connected probes can be opened.
+fn check_probe_access(lister: &Lister, probes: &[DebugProbeInfo]) -> Check {
+    if probes.is_empty() {
+        return Check {
+            name: "Probe access",
+            outcome: Outcome::Skipped("No debug probes to open".into()),
+        };
+    }
+
+    let failures = probes
+        .iter()
+        .filter_map(|probe| {
+            lister
+                .open(probe)
+                .err()
+                .map(|error| format!("{probe}: {error}"))
+        })
+        .collect::<Vec<_>>();
+
+    let outcome = if failures.is_empty() {
+        Outcome::Passed("All debug probes can be opened".into())
+    } else {
+        let mut remediation = failures;
+        if cfg!(target_os = "linux") {
+            remediation.push("This is most likely a permission issue. Install the udev rules for your probe, see https://probe.rs/docs/getting-started/probe-setup/#udev-rules.".into());
+        } else {
+            remediation.push("Make sure that no other program is using the probe.".into());
+        }
+        remediation.push(PROBE_SETUP_GUIDE.into());
+
+        Outcome::Failed {
+            problem: "Some debug probes could not be opened.".into(),
+            remediation,
+        }
+    };
+
+    Check {
+        name: "Probe access",
+        outcome,
+    }
+}
+
+/// Checks that udev rules exist for the vendors of the connected probes.
+fn check_udev_rules(probes: &[DebugProbeInfo]) -> Check {
+    let name = "udev rules";
+
+    if !cfg!(target_os = "linux") {
+        return Check {
+            name,
+            outcome: Outcome::Skipped("udev rules are only needed on Linux".into()),
+        };
+    }
+
+    let rules = UDEV_RULES_DIRECTORIES
+        .iter()
+        .filter_map(|directory| read_rules(Path::new(directory)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut vendors = probes
+        .iter()
+        .map(|probe| probe.vendor_id)
+        .filter(|vendor_id| !rules_cover_vendor(&rules, *vendor_id))
+        .map(|vendor_id| format!("{vendor_id:04x}"))
+        .collect::<Vec<_>>();
+    vendors.sort();
+    vendors.dedup();
+
+    let outcome = if !vendors.is_empty() {
+        Outcome::Warning {
+            problem: format!("No udev rules were found for the USB vendor(s) {}.", vendors.join(", ")),
+            remediation: vec![
+                "Without udev rules, the probes can only be used as root. Install the rules from https://probe.rs/docs/getting-started/probe-setup/#udev-rules, and run `udevadm control --reload` and `udevadm trigger` afterwards.".into(),
+            ],
+        }
+    } else if probes.is_empty() && !rules.contains("probe-rs") {
+        Outcome::Warning {
+            problem: "The probe-rs udev rules are not installed.".into(),
+            remediation: vec![
+                "Install the rules from https://probe.rs/docs/getting-started/probe-setup/#udev-rules, and run `udevadm control --reload` and `udevadm trigger` afterwards.".into(),
+            ],
+        }
+    } else {
+        Outcome::Passed("udev rules were found".into())
+    };
+
+    Check { name, outcome }
+}
+
+/// Reads all rules files in a directory, if it exists.
+fn read_rules(directory: &Path) -> Option<String> {
+    let rules = std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "rules" {
+                return None;
+            }
+            // The file name is included, so that renamed copies of the probe-rs rules are found.
+            let content = std::fs::read_to_string(&path).ok()?;
+            Some(format!("# {}\n{content}", path.display()))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(rules)
+}
+
+/// Checks if the udev `rules` match devices of the vendor.
+fn rules_cover_vendor(rules: &str, vendor_id: u16) -> bool {
+    let vendor_id = format!("{vendor_id:04x}");
+
+    rules
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .any(|line| {
+            line.split(',').any(|field| {
+                let Some((key, value)) = field.split_once("==") else {
+                    return false;
+                };
+                let key = key.trim();
+                (key == "ATTRS{idVendor}" || key == "ATTR{idVendor}")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .eq_ignore_ascii_case(&vendor_id)
+            })
+        })
+}
+
+/// Checks that the local time can be determined, which is needed for the timestamps in the logs.
+fn check_clock(local_offset: Result<UtcOffset, time::error::IndeterminateOffset>) -> Check {
+    let now = OffsetDateTime::now_utc();
+
+    let outcome = if let Err(error) = local_offset {
+        Outcome::Warning {
+            problem: format!("The local time offset could not be determined: {error}. Timestamps are shown in UTC."),
+            remediation: vec![
+                "Make sure that the time zone of the system is configured, e.g. that /etc/localtime exists, or set the TZ environment variable.".into(),
+            ],
+        }
+    } else if now < datetime!(2024-01-01 0:00 UTC) {
+        Outcome::Warning {
+            problem: format!("The system clock is set to {now}, which is in the past."),
+            remediation: vec![
+                "Synchronize the system clock, e.g. with NTP. A wrong clock results in misleading timestamps in logs and reports.".into(),
+            ],
+        }
+    } else {
+        Outcome::Passed("The local time can be determined".into())
+    };
+
+    Check {
+        name: "System clock",
+        outcome,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendors_in_udev_rules() {
+        let rules = r#"
+# STMicroelectronics ST-LINK/V2
+ATTRS{idVendor}=="0483", ATTRS{idProduct}=="3748", MODE="660", GROUP="plugdev", TAG+="uaccess"
+# ATTRS{idVendor}=="1366"
+SUBSYSTEM=="usb", ATTR{idVendor}=="C251", MODE="660"
+"#;
+
+        assert!(rules_cover_vendor(rules, 0x0483));
+        assert!(rules_cover_vendor(rules, 0xc251));
+        // Commented out rules are not loaded.
+        assert!(!rules_cover_vendor(rules, 0x1366));
+        assert!(!rules_cover_vendor(rules, 0x0d28));
+    }
+}
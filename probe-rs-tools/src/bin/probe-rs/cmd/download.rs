@@ -1,11 +1,24 @@
-use std::path::Path;
+mod parallel;
 
-use probe_rs::probe::list::Lister;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use anyhow::Context;
+use itertools::Itertools;
+use probe_rs::flashing::{DataOrigin, FlashLayout, FlashLoader, FlashProgress};
+use probe_rs::probe::{list::Lister, DebugProbeSelector};
+use probe_rs::Session;
+use serde::Serialize;
+
+use crate::util::bundle::{self, Bundle};
 use crate::util::common_options::BinaryDownloadOptions;
 use crate::util::common_options::ProbeOptions;
-use crate::util::flash::build_loader;
+use crate::util::elf::ElfIdentity;
 use crate::util::flash::run_flash_download;
+use crate::util::flash::EraseMode;
+use crate::util::flash::{build_loader, GapFill};
+use crate::util::operation_log::read_operation_log;
 use crate::FormatOptions;
 
 #[derive(clap::Parser)]
@@ -13,13 +26,85 @@ pub struct Cmd {
     #[clap(flatten)]
     probe_options: ProbeOptions,
 
-    /// The path to the file to be downloaded to the flash
-    path: String,
+    /// The path to the file to be downloaded to the flash.
+    ///
+    /// A `.zip` file is flashed as a bundle: a `probe-rs.json` manifest in the archive gives the
+    /// chip, and the images with their format and placement.
+    #[clap(required_unless_present = "replay")]
+    path: Option<String>,
+
+    /// Issues the flash operations recorded with `--operation-log` again, instead of downloading
+    /// a file.
+    ///
+    /// The sectors and the chip are erased and the pages are programmed in the recorded order,
+    /// with the recorded flash algorithms. The log must have been recorded for the same chip.
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["path", "chip_erase", "erase", "explain", "operation_log"]
+    )]
+    replay: Option<PathBuf>,
 
-    /// Whether to erase the entire chip before downloading
-    #[clap(long)]
+    /// Whether to erase the entire chip before downloading. Falls back to erasing the programmed
+    /// sectors if the flash algorithm does not support chip erases, unlike `--erase chip`.
+    #[clap(long, conflicts_with = "erase")]
     chip_erase: bool,
 
+    /// How the flash is erased before downloading.
+    ///
+    /// `sector` erases the programmed sectors, `chip` erases the entire chip and fails if the
+    /// flash algorithm does not support it, and `auto` erases the entire chip only if all of
+    /// its flash is programmed anyway.
+    #[clap(long, value_enum, value_name = "MODE")]
+    erase: Option<EraseStrategy>,
+
+    /// Print the sectors which will be erased and the pages which will be programmed before
+    /// downloading, together with the offset in the input file each chunk of data comes from.
+    ///
+    /// Combine this with `--dry-run` to only print the plan.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text"
+    )]
+    explain: Option<ExplainFormat>,
+
+    /// What to do if the image does not start or end on a sector boundary.
+    ///
+    /// The bytes of the erased sectors which are not part of the image are lost, unless they are
+    /// read and programmed again. `warn` prints the affected ranges, `error` refuses to download,
+    /// and `pad` preserves the bytes, like `--restore-unwritten`.
+    #[clap(long, value_enum, value_name = "POLICY", default_value = "warn")]
+    align_sectors: AlignSectors,
+
+    /// Flash the image to the boards of several probes at once, each from its own thread.
+    ///
+    /// The boards of the `--probes` are flashed, or of all connected probes if none are given.
+    /// A failure on one board does not stop the others, and the result of every board is
+    /// printed once all of them are done.
+    #[clap(
+        long,
+        conflicts_with_all = ["replay", "explain", "operation_log", "probe", "probe_index"]
+    )]
+    parallel: bool,
+
+    /// The probes to flash with `--parallel`, as a comma separated list of `VID:PID:Serial`.
+    #[clap(
+        long,
+        value_name = "PROBES",
+        value_delimiter = ',',
+        requires = "parallel"
+    )]
+    probes: Vec<DebugProbeSelector>,
+
+    /// Write the result of every board flashed with `--parallel` to this file as JSON, e.g. for
+    /// CI.
+    #[clap(long, value_name = "PATH", requires = "parallel")]
+    summary: Option<PathBuf>,
+
     #[clap(flatten)]
     download_options: BinaryDownloadOptions,
 
@@ -27,20 +112,449 @@ pub struct Cmd {
     format_options: FormatOptions,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum EraseStrategy {
+    Auto,
+    Chip,
+    Sector,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum AlignSectors {
+    Warn,
+    Error,
+    Pad,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ExplainFormat {
+    Text,
+    Json,
+}
+
 impl Cmd {
-    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
-        let (mut session, probe_options) = self.probe_options.simple_attach(lister)?;
+    pub fn run(mut self, lister: &Lister) -> anyhow::Result<()> {
+        let Some(path) = self.path.clone() else {
+            let replay = self
+                .replay
+                .clone()
+                .context("No file to download was given.")?;
+            return self.replay(lister, &replay);
+        };
+
+        let bundle = if Bundle::is_bundle(Path::new(&path)) {
+            bundle::check_no_format_options(&self.format_options)?;
+            let bundle = Bundle::open(Path::new(&path))?;
+            bundle.select_chip(&mut self.probe_options.chip)?;
+            Some(bundle)
+        } else {
+            None
+        };
+
+        if self.parallel {
+            return self.run_parallel(&path, bundle.as_ref());
+        }
+
+        let (mut session, probe_options) = self.probe_options.clone().simple_attach(lister)?;
+
+        let loader = match &bundle {
+            Some(bundle) => bundle.build_loader(&mut session)?,
+            None => build_loader(&mut session, &path, self.format_options.clone(), None)?,
+        };
+
+        let (erase, restore_unwritten) = self.prepare_download(&session, &loader)?;
+        self.download_options.restore_unwritten = restore_unwritten;
+
+        if let Some(format) = self.explain {
+            explain(
+                &session,
+                &loader,
+                erase != EraseMode::Sector,
+                self.download_options.restores_unwritten(),
+                format,
+            )?;
+        }
 
-        let loader = build_loader(&mut session, &self.path, self.format_options, None)?;
         run_flash_download(
             &mut session,
-            Path::new(&self.path),
+            Path::new(&path),
             &self.download_options,
             &probe_options,
             loader,
-            self.chip_erase,
+            erase,
         )?;
 
+        // Images in other formats than ELF do not have a build ID.
+        if let Ok(ElfIdentity {
+            build_id: Some(build_id),
+            ..
+        }) = ElfIdentity::read(Path::new(&path))
+        {
+            println!("Downloaded the image with the build ID {}", build_id.hex());
+        }
+
         Ok(())
     }
+
+    /// Issues the operations of the log at `path` to the target.
+    fn replay(self, lister: &Lister, path: &Path) -> anyhow::Result<()> {
+        let (mut session, probe_options) = self.probe_options.simple_attach(lister)?;
+        probe_options.ensure_reset_allowed("replaying the flash operations")?;
+
+        let operations = read_operation_log(path, &session.target().name)?;
+        if probe_options.dry_run() {
+            println!(
+                "Would replay {} flash operations from {}",
+                operations.len(),
+                path.display()
+            );
+            return Ok(());
+        }
+
+        let timer = Instant::now();
+        probe_rs::flashing::run_flash_operations(&mut session, FlashProgress::empty(), &operations)
+            .with_context(|| format!("Failed to replay the operations of {}", path.display()))?;
+
+        println!(
+            "Replayed {} flash operations in {:.2}s",
+            operations.len(),
+            timer.elapsed().as_secs_f32()
+        );
+
+        Ok(())
+    }
+}
+
+/// The erase mode of `--erase` or `--chip-erase` for downloading the loader.
+impl Cmd {
+    /// Selects how the flash is erased, and checks that the image covers the sectors it
+    /// programs, in the same way for the sequential and the parallel download.
+    ///
+    /// Returns the erase mode, and whether the unwritten bytes have to be restored.
+    fn prepare_download(
+        &self,
+        session: &Session,
+        loader: &FlashLoader,
+    ) -> anyhow::Result<(EraseMode, bool)> {
+        let mut restore_unwritten = self.download_options.restores_unwritten();
+        let erase = erase_mode(
+            self.erase,
+            self.chip_erase,
+            session,
+            loader,
+            restore_unwritten,
+        )?;
+
+        // The gaps in the programmed sectors are filled with the value of `--elf-gap-fill`
+        // before flashing, so the image covers them.
+        if !matches!(self.download_options.elf_gap_fill, GapFill::Value(_)) {
+            check_sector_alignment(session, loader, self.align_sectors, &mut restore_unwritten)?;
+        }
+
+        Ok((erase, restore_unwritten))
+    }
+}
+
+fn erase_mode(
+    strategy: Option<EraseStrategy>,
+    chip_erase: bool,
+    session: &Session,
+    loader: &FlashLoader,
+    restore_unwritten: bool,
+) -> anyhow::Result<EraseMode> {
+    Ok(match strategy {
+        Some(EraseStrategy::Chip) => EraseMode::Chip,
+        Some(EraseStrategy::Sector) => EraseMode::Sector,
+        Some(EraseStrategy::Auto) => {
+            if programs_all_flash(session, loader, restore_unwritten)? {
+                tracing::info!("All of the flash is programmed, erasing the entire chip.");
+                EraseMode::ChipIfSupported
+            } else {
+                EraseMode::Sector
+            }
+        }
+        None => EraseMode::from_chip_erase_flag(chip_erase),
+    })
+}
+
+/// Checks whether the image covers the sectors it is programmed into, and applies the `policy`
+/// if it does not.
+fn check_sector_alignment(
+    session: &Session,
+    loader: &FlashLoader,
+    policy: AlignSectors,
+    restore_unwritten: &mut bool,
+) -> anyhow::Result<()> {
+    let layouts = loader.flash_layouts(session.target(), true)?;
+    let unaligned = merge_ranges(
+        layouts
+            .iter()
+            .flat_map(|layout| layout.fills())
+            .map(|fill| fill.address()..fill.address() + fill.size())
+            .collect(),
+    );
+    if unaligned.is_empty() {
+        return Ok(());
+    }
+
+    let ranges = unaligned
+        .iter()
+        .map(|range| format_range(range.start, range.end - range.start))
+        .join(", ");
+
+    match policy {
+        AlignSectors::Warn if *restore_unwritten => tracing::warn!(
+            "The image does not cover the erased sectors completely, the bytes in {ranges} are read and programmed again."
+        ),
+        AlignSectors::Warn => tracing::warn!(
+            "The image does not cover the erased sectors completely, the bytes in {ranges} are erased. Use `--align-sectors pad` to preserve them."
+        ),
+        AlignSectors::Error => anyhow::bail!(
+            "The image does not cover the erased sectors completely, the bytes in {ranges} would be erased. Align the image to the sectors, or use `--align-sectors pad` to preserve the bytes."
+        ),
+        AlignSectors::Pad => {
+            tracing::info!("Preserving the bytes in {ranges}, which are not part of the image.");
+            *restore_unwritten = true;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sorts the `ranges` and merges adjacent and overlapping ones.
+fn merge_ranges(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<u64>> = vec![];
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// Checks if the loader erases every sector of the flash of the target, in which case
+/// a chip erase is usually faster.
+fn programs_all_flash(
+    session: &Session,
+    loader: &FlashLoader,
+    restore_unwritten: bool,
+) -> anyhow::Result<bool> {
+    let layouts = loader.flash_layouts(session.target(), restore_unwritten)?;
+    let sectors: Vec<Range<u64>> = layouts
+        .iter()
+        .flat_map(|layout| layout.sectors())
+        .map(|sector| sector.address()..sector.address() + sector.size())
+        .collect();
+
+    let flash: Vec<Range<u64>> = session
+        .target()
+        .memory_map
+        .iter()
+        .filter_map(|region| region.as_nvm_region())
+        .filter(|region| !region.is_alias)
+        .map(|region| region.range.clone())
+        .collect();
+
+    Ok(covers_all(&sectors, &flash))
+}
+
+/// Checks if the non-overlapping `sectors` cover all of the `regions`.
+fn covers_all(sectors: &[Range<u64>], regions: &[Range<u64>]) -> bool {
+    !regions.is_empty()
+        && regions.iter().all(|region| {
+            let covered: u64 = sectors
+                .iter()
+                .map(|sector| {
+                    sector
+                        .end
+                        .min(region.end)
+                        .saturating_sub(sector.start.max(region.start))
+                })
+                .sum();
+
+            covered >= region.end - region.start
+        })
+}
+
+/// Prints the flash plan of the loader.
+fn explain(
+    session: &Session,
+    loader: &FlashLoader,
+    chip_erase: bool,
+    restore_unwritten: bool,
+    format: ExplainFormat,
+) -> anyhow::Result<()> {
+    let layouts = loader.flash_layouts(session.target(), restore_unwritten)?;
+    let plan = FlashPlan::new(&layouts, loader.data_origins(), chip_erase);
+
+    match format {
+        ExplainFormat::Text => plan.print(),
+        ExplainFormat::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+    }
+
+    Ok(())
+}
+
+/// The sectors and pages touched by a download, and where the programmed data comes from.
+#[derive(Debug, Serialize)]
+struct FlashPlan {
+    chip_erase: bool,
+    sectors: Vec<PlanRange>,
+    pages: Vec<PlanPage>,
+    fills: Vec<PlanRange>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct PlanRange {
+    address: u64,
+    size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct PlanPage {
+    address: u64,
+    size: u64,
+    data: Vec<PlanData>,
+}
+
+/// A chunk of input data which is programmed into a page.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct PlanData {
+    address: u64,
+    size: u64,
+    file_offset: Option<u64>,
+    name: Option<String>,
+}
+
+impl FlashPlan {
+    fn new(layouts: &[FlashLayout], origins: &[DataOrigin], chip_erase: bool) -> Self {
+        let mut plan = Self {
+            chip_erase,
+            sectors: vec![],
+            pages: vec![],
+            fills: vec![],
+        };
+
+        for layout in layouts {
+            plan.sectors
+                .extend(layout.sectors().iter().map(|sector| PlanRange {
+                    address: sector.address(),
+                    size: sector.size(),
+                }));
+            plan.pages
+                .extend(layout.pages().iter().map(|page| PlanPage {
+                    address: page.address(),
+                    size: page.size() as u64,
+                    data: data_in_range(origins, page.address(), page.size() as u64),
+                }));
+            plan.fills
+                .extend(layout.fills().iter().map(|fill| PlanRange {
+                    address: fill.address(),
+                    size: fill.size(),
+                }));
+        }
+
+        plan.sectors.sort_by_key(|sector| sector.address);
+        plan.pages.sort_by_key(|page| page.address);
+        plan.fills.sort_by_key(|fill| fill.address);
+
+        plan
+    }
+
+    fn print(&self) {
+        if self.chip_erase {
+            println!("The whole chip will be erased.");
+        } else {
+            println!("Sectors to erase:");
+            for sector in &self.sectors {
+                println!("  {}", format_range(sector.address, sector.size));
+            }
+        }
+
+        println!("Pages to program:");
+        for page in &self.pages {
+            println!("  {}", format_range(page.address, page.size));
+            for data in &page.data {
+                let offset = match data.file_offset {
+                    Some(offset) => format!("file offset {offset:#x}"),
+                    None => "unknown file offset".to_string(),
+                };
+                let name = match &data.name {
+                    Some(name) => format!(" ({name})"),
+                    None => String::new(),
+                };
+                println!(
+                    "    {} <- {offset}{name}",
+                    format_range(data.address, data.size)
+                );
+            }
+        }
+
+        if !self.fills.is_empty() {
+            println!("Unwritten bytes which are filled:");
+            for fill in &self.fills {
+                println!("  {}", format_range(fill.address, fill.size));
+            }
+        }
+    }
+}
+
+/// Returns the parts of the input data which fall into `address..address + size`.
+fn data_in_range(origins: &[DataOrigin], address: u64, size: u64) -> Vec<PlanData> {
+    let end = address + size;
+
+    origins
+        .iter()
+        .filter_map(|origin| {
+            let start = origin.address().max(address);
+            let stop = (origin.address() + origin.size()).min(end);
+            if start >= stop {
+                return None;
+            }
+
+            Some(PlanData {
+                address: start,
+                size: stop - start,
+                file_offset: origin
+                    .file_offset()
+                    .map(|offset| offset + (start - origin.address())),
+                name: origin.name().map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+fn format_range(address: u64, size: u64) -> String {
+    format!("{:#010x}..{:#010x} ({size} bytes)", address, address + size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sectors_cover_all_regions() {
+        let flash = [0x0..0x1000, 0x1000_0000..0x1000_0800];
+        let sectors = [0x0..0x800, 0x800..0x1000, 0x1000_0000..0x1000_0800];
+
+        assert!(covers_all(&sectors, &flash));
+        assert!(!covers_all(&sectors[1..], &flash));
+        assert!(!covers_all(&sectors, &[]));
+    }
+
+    #[test]
+    fn adjacent_ranges_are_merged() {
+        let ranges = vec![
+            0x1100..0x1200,
+            0x1000..0x1100,
+            0x3000..0x3010,
+            0x1180..0x1300,
+        ];
+
+        assert_eq!(merge_ranges(ranges), [0x1000..0x1300, 0x3000..0x3010]);
+    }
 }
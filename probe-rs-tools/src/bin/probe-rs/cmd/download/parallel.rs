@@ -0,0 +1,258 @@
+//! Flashing the same image to the boards of several probes at once with `download --parallel`.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use colored::Colorize;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use probe_rs::flashing::{FlashProgress, ProgressEvent};
+use probe_rs::probe::{list::Lister, DebugProbeSelector};
+use serde::Serialize;
+
+use super::Cmd;
+use crate::util::bundle::Bundle;
+use crate::util::common_options::LoadedProbeOptions;
+use crate::util::flash::{build_loader, fill_gaps, loader_options};
+use crate::util::logging;
+
+/// A board to flash, i.e. the probe it is connected to.
+struct Board {
+    /// The selector of the probe, which identifies the board in the output.
+    probe: DebugProbeSelector,
+    probe_options: LoadedProbeOptions,
+}
+
+/// The results of all boards, as written to the `--summary` file.
+#[derive(Debug, Serialize)]
+struct Summary {
+    passed: usize,
+    failed: usize,
+    boards: Vec<BoardResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct BoardResult {
+    probe: String,
+    passed: bool,
+    error: Option<String>,
+    /// The time flashing the board took, in seconds.
+    duration: f32,
+}
+
+impl Cmd {
+    /// Flashes the image at `path` to the boards of all selected probes, each from its own thread.
+    pub(super) fn run_parallel(self, path: &str, bundle: Option<&Bundle>) -> anyhow::Result<()> {
+        let boards = self.boards()?;
+
+        let multi_progress = MultiProgress::new();
+        logging::set_progress_bar(multi_progress.clone());
+
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let threads: Vec<_> = boards
+                .iter()
+                .map(|board| {
+                    let bar = if self.download_options.disable_progressbars {
+                        ProgressBar::hidden()
+                    } else {
+                        multi_progress.add(board_progress_bar(&board.probe))
+                    };
+                    let cmd = &self;
+
+                    scope.spawn(move || {
+                        let start = Instant::now();
+                        let result = cmd.flash_board(board, path, bundle, bar.clone());
+                        match &result {
+                            Ok(()) => bar.finish_with_message("Finished"),
+                            Err(_) => bar.abandon_with_message("Failed"),
+                        }
+                        (result, start.elapsed())
+                    })
+                })
+                .collect();
+
+            threads
+                .into_iter()
+                .map(|thread| {
+                    thread.join().unwrap_or_else(|_| {
+                        (
+                            Err(anyhow::anyhow!("Flashing the board panicked.")),
+                            Duration::ZERO,
+                        )
+                    })
+                })
+                .collect()
+        });
+
+        // If we don't do this, the progress bars disappear.
+        logging::clear_progress_bar();
+
+        let boards: Vec<_> = boards
+            .iter()
+            .zip(results)
+            .map(|(board, (result, duration))| BoardResult {
+                probe: board.probe.to_string(),
+                passed: result.is_ok(),
+                error: result.err().map(|error| format!("{error:#}")),
+                duration: duration.as_secs_f32(),
+            })
+            .collect();
+        let passed = boards.iter().filter(|board| board.passed).count();
+        let summary = Summary {
+            passed,
+            failed: boards.len() - passed,
+            boards,
+        };
+
+        print_summary(&summary);
+
+        if let Some(path) = &self.summary {
+            let json = serde_json::to_string_pretty(&summary)?;
+            std::fs::write(path, json)
+                .with_context(|| format!("Failed to write the summary to {}", path.display()))?;
+        }
+
+        if summary.failed > 0 {
+            anyhow::bail!(
+                "Flashing failed on {} of {} boards.",
+                summary.failed,
+                summary.boards.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The boards of the `--probes`, or of all connected probes.
+    fn boards(&self) -> anyhow::Result<Vec<Board>> {
+        let probe_options = self.probe_options.clone().load()?;
+        let lister = Lister::new();
+
+        let mut boards = vec![];
+        if self.probes.is_empty() {
+            let probes = lister.list_all();
+            if probes.is_empty() {
+                anyhow::bail!("No debug probes were found.");
+            }
+
+            for (index, info) in probes.iter().enumerate() {
+                // The index refers to the same list, so identical probes without a serial number
+                // are flashed separately.
+                let mut probe_options = probe_options.clone();
+                probe_options.set_probe_index(index);
+                boards.push(Board {
+                    probe: info.into(),
+                    probe_options,
+                });
+            }
+        } else {
+            for selector in &self.probes {
+                let mut probe_options = probe_options.clone();
+                probe_options.set_probe(selector.clone());
+                boards.push(Board {
+                    probe: selector.clone(),
+                    probe_options,
+                });
+            }
+        }
+
+        Ok(boards)
+    }
+
+    /// Attaches to the board and flashes the image to it.
+    fn flash_board(
+        &self,
+        board: &Board,
+        path: &str,
+        bundle: Option<&Bundle>,
+        bar: ProgressBar,
+    ) -> anyhow::Result<()> {
+        let probe_options = &board.probe_options;
+        probe_options.ensure_reset_allowed("flashing")?;
+
+        // The lister of the command can not be shared between the threads.
+        let probe = probe_options.attach_probe(&Lister::new())?;
+        let target = probe_options.get_target_selector()?;
+        let mut session = probe_options.attach_session(probe, target)?;
+
+        let mut loader = match bundle {
+            Some(bundle) => bundle.build_loader(&mut session)?,
+            None => build_loader(&mut session, path, self.format_options.clone(), None)?,
+        };
+        let (erase, restore_unwritten) = self.prepare_download(&session, &loader)?;
+        fill_gaps(
+            &mut loader,
+            session.target(),
+            self.download_options.elf_gap_fill,
+        )?;
+
+        // The options are the same as for the sequential download, including `--verify`.
+        let mut options = loader_options(&self.download_options, probe_options.dry_run(), erase);
+        options.keep_unwritten_bytes = restore_unwritten;
+        options.progress = Some(board_progress(bar));
+
+        let option_bytes = if probe_options.dry_run() {
+            None
+        } else {
+            self.download_options.option_bytes.prepare(&mut session)?
+        };
+
+        loader
+            .commit(&mut session, options)
+            .with_context(|| format!("Failed to flash {}", Path::new(path).display()))?;
+
+        if let Some(option_bytes) = option_bytes {
+            option_bytes.apply(&mut session)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn board_progress_bar(probe: &DebugProbeSelector) -> ProgressBar {
+    let style = ProgressStyle::default_bar()
+        .template("{prefix:.bold} {msg:.green.bold:>11} [{elapsed_precise}] [{wide_bar}] {bytes:>8}/{total_bytes:>8}")
+        .expect("Error in progress bar creation. This is a bug, please report it.")
+        .progress_chars("##-");
+
+    let bar = ProgressBar::new(0).with_style(style);
+    bar.set_prefix(probe.to_string());
+    bar.set_message("Attaching");
+    bar.enable_steady_tick(Duration::from_millis(100));
+
+    bar
+}
+
+/// Shows the phase of flashing a board, and the programmed bytes, on its progress bar.
+fn board_progress(bar: ProgressBar) -> FlashProgress {
+    FlashProgress::new(move |event| match event {
+        ProgressEvent::StartedErasing => bar.set_message("Erasing"),
+        ProgressEvent::StartedFilling => bar.set_message("Reading"),
+        ProgressEvent::StartedProgramming { length } => {
+            bar.set_message("Programming");
+            bar.set_length(length);
+            bar.set_position(0);
+        }
+        ProgressEvent::PageProgrammed { size, .. } => bar.inc(size as u64),
+        _ => {}
+    })
+}
+
+fn print_summary(summary: &Summary) {
+    for board in &summary.boards {
+        match &board.error {
+            None => println!(
+                "{} {} in {:.2}s",
+                "  PASS".green().bold(),
+                board.probe,
+                board.duration
+            ),
+            Some(error) => println!("{} {}: {error}", "  FAIL".red().bold(), board.probe),
+        }
+    }
+    println!(
+        "{} of {} boards were flashed successfully.",
+        summary.passed,
+        summary.boards.len()
+    );
+}
@@ -1,22 +1,187 @@
+use std::ops::Range;
+
 use probe_rs::{
-    flashing::{erase_all, FlashProgress},
+    flashing::{blank_check, erase_all, erase_except, FlashProgress},
     probe::list::Lister,
 };
 
 use crate::util::common_options::ProbeOptions;
+use crate::util::option_bytes::OptionBytesOptions;
+use crate::util::parse_u64;
 
 #[derive(clap::Parser)]
 pub struct Cmd {
     #[clap(flatten)]
     common: ProbeOptions,
+
+    /// Read back the nonvolatile memory after erasing, and verify that it is blank.
+    #[clap(long)]
+    blank_check: bool,
+
+    /// Only blank check the `--size` bytes starting at this address, e.g. a single sector.
+    #[clap(long, value_name = "ADDRESS", value_parser = parse_u64, requires_all = ["blank_check", "size"])]
+    address: Option<u64>,
+
+    /// The number of bytes to blank check at `--address`.
+    #[clap(long, value_name = "BYTES", value_parser = parse_u64, requires = "address")]
+    size: Option<u64>,
+
+    /// Keep the given range, given as `<START>:<LENGTH>`, and erase all other sectors instead of
+    /// the whole chip, e.g. to wipe the application but keep the bootloader.
+    ///
+    /// The range must start and end on sector boundaries. Can be given multiple times.
+    #[clap(long, value_name = "START:LENGTH", value_parser = parse_keep_range)]
+    keep: Vec<Range<u64>>,
+
+    #[clap(flatten)]
+    option_bytes: OptionBytesOptions,
 }
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
-        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+        let check_range = match self.address.zip(self.size) {
+            Some((address, size)) => Some(check_range(address, size)?),
+            None => None,
+        };
+        let (mut session, probe_options) = self.common.simple_attach(lister)?;
+        probe_options.ensure_reset_allowed("erasing")?;
+
+        let option_bytes = self.option_bytes.prepare(&mut session)?;
+
+        if self.keep.is_empty() {
+            erase_all(&mut session, FlashProgress::empty())?;
+        } else {
+            erase_except(&mut session, FlashProgress::empty(), &self.keep)?;
+        }
 
-        erase_all(&mut session, FlashProgress::empty())?;
+        if let Some(option_bytes) = option_bytes {
+            option_bytes.apply(&mut session)?;
+        }
+
+        if self.blank_check {
+            let ranges = checked_ranges(&self.keep, check_range);
+            if ranges.is_empty() {
+                anyhow::bail!("The blank check range is kept, so it was not erased");
+            }
+            for range in ranges {
+                if let Some(address) = blank_check(&mut session, range)? {
+                    anyhow::bail!("Blank check failed: the memory at {address:#010x} is not erased")
+                }
+            }
+            println!("Blank check passed");
+        }
 
         Ok(())
     }
 }
+
+/// Parses a `<START>:<LENGTH>` range for `--keep`.
+fn parse_keep_range(input: &str) -> Result<Range<u64>, String> {
+    let (start, length) = input
+        .split_once(':')
+        .ok_or_else(|| format!("expected <START>:<LENGTH>, got '{input}'"))?;
+    let parse = |number: &str| {
+        parse_u64(number.trim()).map_err(|error| format!("invalid number '{number}': {error}"))
+    };
+
+    let start = parse(start)?;
+    let length = parse(length)?;
+    if length == 0 {
+        return Err("the length must not be zero".to_string());
+    }
+    let end = start
+        .checked_add(length)
+        .ok_or_else(|| format!("the range '{input}' exceeds the address space"))?;
+
+    Ok(start..end)
+}
+
+/// Returns the range of `size` bytes at `address` for `--address` and `--size`.
+fn check_range(address: u64, size: u64) -> anyhow::Result<Range<u64>> {
+    if size == 0 {
+        anyhow::bail!("The blank check size must not be zero");
+    }
+    let end = address.checked_add(size).ok_or_else(|| {
+        anyhow::anyhow!("The blank check range at {address:#010x} exceeds the address space")
+    })?;
+
+    Ok(address..end)
+}
+
+/// Returns the erased ranges to blank check, limited to `range` if given.
+fn checked_ranges(keep: &[Range<u64>], range: Option<Range<u64>>) -> Vec<Range<u64>> {
+    let erased = erased_ranges(keep);
+    let Some(range) = range else {
+        return erased;
+    };
+
+    erased
+        .into_iter()
+        .map(|erased| erased.start.max(range.start)..erased.end.min(range.end))
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+/// Returns the ranges of the address space which are not kept.
+fn erased_ranges(keep: &[Range<u64>]) -> Vec<Range<u64>> {
+    let mut keep = keep.to_vec();
+    keep.sort_by_key(|range| range.start);
+
+    let mut ranges = vec![];
+    let mut start = 0;
+    for range in keep {
+        if range.start > start {
+            ranges.push(start..range.start);
+        }
+        start = start.max(range.end);
+    }
+    if start < u64::MAX {
+        ranges.push(start..u64::MAX);
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_ranges_are_parsed() {
+        assert_eq!(parse_keep_range("0x0:0x4000"), Ok(0x0..0x4000));
+        assert_eq!(
+            parse_keep_range("0x8000_0000:4096"),
+            Ok(0x8000_0000..0x8000_1000)
+        );
+        assert!(parse_keep_range("0x0").is_err());
+        assert!(parse_keep_range("0x0:0").is_err());
+    }
+
+    #[test]
+    fn erased_ranges_exclude_kept_ranges() {
+        assert_eq!(erased_ranges(&[]), vec![0..u64::MAX]);
+        assert_eq!(
+            erased_ranges(&[0x1_0000..0x1_1000, 0x0..0x4000, 0x2000..0x5000]),
+            [0x5000..0x1_0000, 0x1_1000..u64::MAX]
+        );
+    }
+
+    #[test]
+    fn blank_check_is_limited_to_range() {
+        assert_eq!(
+            checked_ranges(&[], Some(0x1000..0x2000)),
+            vec![0x1000..0x2000]
+        );
+        assert_eq!(
+            checked_ranges(&[0x0..0x1800, 0x8000..0x9000], Some(0x1000..0x2000)),
+            vec![0x1800..0x2000]
+        );
+        assert_eq!(
+            checked_ranges(&[0x0..0x1800, 0x1800..0x4000], Some(0x1000..0x2000)),
+            []
+        );
+        assert_eq!(check_range(0x1000, 0x1000).unwrap(), 0x1000..0x2000);
+        assert!(check_range(0x1000, 0).is_err());
+        assert!(check_range(u64::MAX, 2).is_err());
+    }
+}
@@ -1,9 +1,18 @@
-use std::time::Duration;
+mod svd;
+mod swv;
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Context;
 
 use parking_lot::FairMutex;
+use probe_rs::gdb_server::MonitorCommands;
 use probe_rs::probe::list::Lister;
 
 use crate::util::common_options::ProbeOptions;
+use crate::util::halt::{self, DEFAULT_HALT_TIMEOUT};
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -13,12 +22,34 @@ pub struct Cmd {
     )]
     gdb_connection_string: Option<String>,
 
-    #[clap(
-        name = "reset-halt",
-        long = "reset-halt",
-        help = "Use this flag to reset and halt (instead of just a halt) the attached core after attaching to the target."
-    )]
-    reset_halt: bool,
+    /// Reset the target before halting or running the cores, when the GDB server starts.
+    #[clap(long = "reset-on-connect", alias = "reset-halt")]
+    reset: bool,
+
+    /// Halt the cores when the GDB server starts. This is the default, unless `--no-reset` is given.
+    #[clap(long, conflicts_with = "run_on_connect")]
+    halt_on_connect: bool,
+
+    /// Keep the cores running when the GDB server starts, e.g. to attach GDB to a running
+    /// firmware later.
+    ///
+    /// GDB still halts the cores once it connects.
+    #[clap(long)]
+    run_on_connect: bool,
+
+    /// The ELF file of the firmware running on the target.
+    ///
+    /// It is advertised to GDB as the executable, so GDB loads its symbols without a `file` command.
+    #[clap(long, value_name = "PATH")]
+    elf: Option<PathBuf>,
+
+    /// The SVD file of the target, to read and write its peripheral registers by name with
+    /// `monitor reg PERIPHERAL.REGISTER[.FIELD] [VALUE]`.
+    #[clap(long, value_name = "PATH")]
+    svd: Option<PathBuf>,
+
+    #[clap(flatten)]
+    swv: swv::SwvOptions,
 
     #[clap(flatten)]
     common: ProbeOptions,
@@ -26,22 +57,70 @@ pub struct Cmd {
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
-        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+        let exec_file = self.elf.as_deref().map(read_exec_file).transpose()?;
+        let svd = self
+            .svd
+            .as_deref()
+            .map(svd::SvdRegisters::load)
+            .transpose()?
+            .map(|svd| Arc::new(svd) as Arc<dyn MonitorCommands>);
+
+        let (mut session, probe_options) = self.common.simple_attach(lister)?;
 
-        if self.reset_halt {
-            session
+        let halt_timeout = probe_options.halt_timeout(DEFAULT_HALT_TIMEOUT);
+        let run = self.run_on_connect && !self.halt_on_connect;
+        if self.reset {
+            probe_options.ensure_reset_allowed("`--reset-on-connect`")?;
+        }
+        match (self.reset, run) {
+            (true, false) => {
+                halt::reset_and_halt(&mut session.core(0)?, halt_timeout)?;
+            }
+            (true, true) => session
                 .core(0)?
-                .reset_and_halt(Duration::from_millis(100))?;
+                .reset()
+                .context("Resetting the target failed")?,
+            (false, _) => {}
+        }
+
+        // With `--no-reset`, the cores are left as they are, until GDB connects.
+        let cores = if probe_options.no_reset() && !self.halt_on_connect && !run {
+            vec![]
+        } else {
+            session.list_cores()
+        };
+        for (core_id, _) in cores {
+            let mut core = session.core(core_id)?;
+            if run {
+                if core.core_halted()? {
+                    core.run()
+                        .with_context(|| format!("Failed to run core {core_id}"))?;
+                }
+            } else {
+                halt::halt(&mut core, halt_timeout)?;
+            }
         }
 
+        let swv_listener = self.swv.setup(&mut session, 0)?;
+
         let gdb_connection_string = self
             .gdb_connection_string
             .unwrap_or_else(|| "localhost:1337".to_string());
 
-        let instances = probe_rs::gdb_server::GdbInstanceConfiguration::from_session(
+        let instances: Vec<_> = probe_rs::gdb_server::GdbInstanceConfiguration::from_session(
             &session,
             Some(gdb_connection_string),
-        );
+        )
+        .into_iter()
+        .map(|instance| match &exec_file {
+            Some(exec_file) => instance.with_exec_file(exec_file),
+            None => instance,
+        })
+        .map(|instance| match &svd {
+            Some(svd) => instance.with_monitor_commands(svd.clone()),
+            None => instance,
+        })
+        .collect();
 
         for instance in instances.iter() {
             println!(
@@ -51,12 +130,31 @@ impl Cmd {
         }
 
         let session = FairMutex::new(session);
+        let stop_swv = AtomicBool::new(false);
 
-        if let Err(e) = probe_rs::gdb_server::run(&session, instances.iter()) {
-            eprintln!("During the execution of GDB an error was encountered:");
-            eprintln!("{e:?}");
-        }
+        std::thread::scope(|scope| {
+            if let Some(listener) = swv_listener {
+                scope.spawn(|| swv::serve(&session, listener, &stop_swv));
+            }
+
+            if let Err(e) = probe_rs::gdb_server::run(&session, instances.iter()) {
+                eprintln!("During the execution of GDB an error was encountered:");
+                eprintln!("{e:?}");
+            }
+            stop_swv.store(true, Ordering::Relaxed);
+        });
 
         Ok(())
     }
 }
+
+/// Checks that `path` is an ELF file, and returns its absolute path, which is what GDB expects.
+fn read_exec_file(path: &std::path::Path) -> anyhow::Result<PathBuf> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("Failed to read the ELF file {}", path.display()))?;
+    if let Err(e) = goblin::elf::Elf::parse(&data) {
+        anyhow::bail!("{} is not a valid ELF file: {e}", path.display());
+    }
+
+    Ok(std::fs::canonicalize(path)?)
+}
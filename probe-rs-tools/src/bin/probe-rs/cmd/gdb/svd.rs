@@ -0,0 +1,393 @@
+//! Reading and writing the peripheral registers described by an SVD file with `monitor reg`,
+//! for `gdb --svd`.
+
+use std::path::Path;
+
+use anyhow::Context;
+use probe_rs::gdb_server::MonitorCommands;
+use probe_rs::{Core, MemoryInterface};
+use svd_parser::Config;
+
+use crate::util::parse_u64;
+
+const HELP_TEXT: &str = r#"Peripheral registers of the SVD file:
+
+    reg PERIPHERAL.REGISTER - read a register and print its fields
+    reg PERIPHERAL.REGISTER.FIELD - read a field of a register
+    reg PERIPHERAL.REGISTER[.FIELD] VALUE - write a register, or only one of its fields
+"#;
+
+/// The number of near-matches listed for an unknown name.
+const MAX_SUGGESTIONS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Register {
+    /// The name, as `PERIPHERAL.REGISTER`.
+    name: String,
+    address: u64,
+    /// The width of the register in bits.
+    size: u32,
+    /// Whether reading the register has side effects, or it can not be read.
+    restricted_read: bool,
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    name: String,
+    offset: u32,
+    width: u32,
+}
+
+impl Field {
+    fn mask(&self) -> u64 {
+        if self.width == 0 {
+            return 0;
+        }
+
+        (u64::MAX >> (64 - self.width.min(64)))
+            .checked_shl(self.offset)
+            .unwrap_or(0)
+    }
+
+    fn extract(&self, register: u64) -> u64 {
+        (register & self.mask()) >> self.offset
+    }
+}
+
+/// The registers of the peripherals of an SVD file.
+#[derive(Debug)]
+pub(crate) struct SvdRegisters {
+    registers: Vec<Register>,
+}
+
+impl SvdRegisters {
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let xml = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the SVD file {}", path.display()))?;
+        Self::parse(&xml)
+            .with_context(|| format!("Failed to parse the SVD file {}", path.display()))
+    }
+
+    fn parse(xml: &str) -> anyhow::Result<Self> {
+        let device =
+            svd_parser::parse_with_config(xml, &Config::default().expand(true).ignore_enums(true))?;
+
+        let mut registers = vec![];
+        for peripheral in &device.peripherals {
+            for register in peripheral.all_registers() {
+                let restricted_read = register.read_action.is_some()
+                    || register
+                        .properties
+                        .access
+                        .is_some_and(|access| !access.can_read());
+
+                registers.push(Register {
+                    name: format!("{}.{}", peripheral.name, register.name),
+                    address: peripheral.base_address + register.address_offset as u64,
+                    size: register.properties.size.unwrap_or(32),
+                    restricted_read,
+                    fields: register
+                        .fields()
+                        .map(|field| Field {
+                            name: field.name.clone(),
+                            offset: field.bit_offset(),
+                            width: field.bit_width(),
+                        })
+                        .collect(),
+                });
+            }
+        }
+
+        Ok(Self { registers })
+    }
+
+    /// Finds a register, or a field of it, by its case-insensitive name.
+    fn find(&self, name: &str) -> Result<(&Register, Option<&Field>), String> {
+        if let Some(register) = self.register(name) {
+            return Ok((register, None));
+        }
+        if let Some((register_name, field_name)) = name.rsplit_once('.') {
+            if let Some(register) = self.register(register_name) {
+                return match register
+                    .fields
+                    .iter()
+                    .find(|field| field.name.eq_ignore_ascii_case(field_name))
+                {
+                    Some(field) => Ok((register, Some(field))),
+                    None => Err(unknown(
+                        name,
+                        register
+                            .fields
+                            .iter()
+                            .map(|field| format!("{}.{}", register.name, field.name)),
+                    )),
+                };
+            }
+        }
+
+        Err(unknown(
+            name,
+            self.registers.iter().map(|register| register.name.clone()),
+        ))
+    }
+
+    fn register(&self, name: &str) -> Option<&Register> {
+        self.registers
+            .iter()
+            .find(|register| register.name.eq_ignore_ascii_case(name))
+    }
+
+    fn read(&self, core: &mut Core<'_>, name: &str) -> Result<String, String> {
+        let (register, field) = self.find(name)?;
+        if register.restricted_read {
+            return Err(format!(
+                "{} can not be read, or reading it has side effects.",
+                register.name
+            ));
+        }
+
+        let value = read_register(core, register)?;
+        Ok(match field {
+            Some(field) => format!(
+                "{}.{} = {:#x}",
+                register.name,
+                field.name,
+                field.extract(value)
+            ),
+            None => format_register(register, value),
+        })
+    }
+
+    fn write(&self, core: &mut Core<'_>, name: &str, value: &str) -> Result<String, String> {
+        let (register, field) = self.find(name)?;
+        let value = parse_u64(value).map_err(|_| format!("`{value}` is not a number."))?;
+
+        let new_value = match field {
+            Some(field) => {
+                if value >> field.width != 0 {
+                    return Err(format!(
+                        "{value:#x} does not fit into the {} bits of {}.{}.",
+                        field.width, register.name, field.name
+                    ));
+                }
+                if register.restricted_read {
+                    return Err(format!(
+                        "{} can not be read, so only the whole register can be written.",
+                        register.name
+                    ));
+                }
+                let old_value = read_register(core, register)?;
+                (old_value & !field.mask()) | (value << field.offset)
+            }
+            None if register.size < 64 && value >> register.size != 0 => {
+                return Err(format!(
+                    "{value:#x} does not fit into the {} bits of {}.",
+                    register.size, register.name
+                ));
+            }
+            None => value,
+        };
+
+        write_register(core, register, new_value)?;
+        Ok(format!("{} = {new_value:#x}", register.name))
+    }
+}
+
+impl MonitorCommands for SvdRegisters {
+    fn handle(&self, command: &str, core: &mut Core<'_>) -> Option<String> {
+        let mut words = command.split_whitespace();
+        if words.next() != Some("reg") {
+            return None;
+        }
+
+        let result = match (words.next(), words.next(), words.next()) {
+            (Some(name), None, None) => self.read(core, name),
+            (Some(name), Some(value), None) => self.write(core, name, value),
+            _ => Err(HELP_TEXT.to_string()),
+        };
+
+        Some(result.unwrap_or_else(|error| error))
+    }
+
+    fn help(&self) -> &str {
+        HELP_TEXT
+    }
+}
+
+fn read_register(core: &mut Core<'_>, register: &Register) -> Result<u64, String> {
+    let result = match register.size {
+        8 => core.read_word_8(register.address).map(u64::from),
+        16 => core.read_word_16(register.address).map(u64::from),
+        64 => core.read_word_64(register.address),
+        _ => core.read_word_32(register.address).map(u64::from),
+    };
+
+    result.map_err(|error| format!("Failed to read {}: {error}", register.name))
+}
+
+fn write_register(core: &mut Core<'_>, register: &Register, value: u64) -> Result<(), String> {
+    let result = match register.size {
+        8 => core.write_word_8(register.address, value as u8),
+        16 => core.write_word_16(register.address, value as u16),
+        64 => core.write_word_64(register.address, value),
+        _ => core.write_word_32(register.address, value as u32),
+    };
+
+    result.map_err(|error| format!("Failed to write {}: {error}", register.name))
+}
+
+fn format_register(register: &Register, value: u64) -> String {
+    let digits = register.size.div_ceil(4) as usize;
+    let mut output = format!(
+        "{} @ {:#010x} = {value:#0width$x}",
+        register.name,
+        register.address,
+        width = digits + 2
+    );
+    for field in &register.fields {
+        let bits = if field.width == 1 {
+            format!("[{}]", field.offset)
+        } else {
+            format!("[{}:{}]", field.offset + field.width - 1, field.offset)
+        };
+        output.push_str(&format!(
+            "\n  {:<12} {bits:<7} = {:#x}",
+            field.name,
+            field.extract(value)
+        ));
+    }
+
+    output
+}
+
+/// The error for an unknown name, with the near-matches among `names`.
+fn unknown(name: &str, names: impl Iterator<Item = String>) -> String {
+    let wanted = name.to_ascii_uppercase();
+    let mut matches: Vec<(usize, String)> = names
+        .filter_map(|candidate| {
+            let upper = candidate.to_ascii_uppercase();
+            let distance = edit_distance(&wanted, &upper);
+            (upper.contains(&wanted) || distance <= 3).then_some((distance, candidate))
+        })
+        .collect();
+    matches.sort();
+
+    if matches.is_empty() {
+        return format!("Unknown register `{name}`.");
+    }
+
+    let suggestions: Vec<_> = matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect();
+    format!(
+        "Unknown register `{name}`. Did you mean: {}?",
+        suggestions.join(", ")
+    )
+}
+
+/// The number of inserted, removed or replaced characters to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a != *b);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device schemaVersion="1.1">
+  <name>TEST</name>
+  <addressUnitBits>8</addressUnitBits>
+  <width>32</width>
+  <size>32</size>
+  <peripherals>
+    <peripheral>
+      <name>SYSCFG</name>
+      <baseAddress>0x40010000</baseAddress>
+      <registers>
+        <register>
+          <name>CFGR1</name>
+          <addressOffset>0x0</addressOffset>
+          <fields>
+            <field><name>MEM_MODE</name><bitOffset>0</bitOffset><bitWidth>2</bitWidth></field>
+            <field><name>PA11_RMP</name><bitOffset>3</bitOffset><bitWidth>1</bitWidth></field>
+          </fields>
+        </register>
+        <register>
+          <name>CFGR2</name>
+          <addressOffset>0x18</addressOffset>
+        </register>
+      </registers>
+    </peripheral>
+  </peripherals>
+</device>
+"#;
+
+    #[test]
+    fn registers_are_found_by_name() {
+        let svd = SvdRegisters::parse(SVD).unwrap();
+
+        let (register, field) = svd.find("syscfg.cfgr2").unwrap();
+        assert_eq!((register.address, field), (0x4001_0018, None));
+
+        let (register, field) = svd.find("SYSCFG.CFGR1.PA11_RMP").unwrap();
+        assert_eq!(register.name, "SYSCFG.CFGR1");
+        let field = field.unwrap();
+        assert_eq!(field.extract(0b1011), 1);
+        assert_eq!(field.mask(), 0b1000);
+
+        assert_eq!(
+            format_register(register, 0b1001),
+            "SYSCFG.CFGR1 @ 0x40010000 = 0x00000009\n  \
+             MEM_MODE     [1:0]   = 0x1\n  \
+             PA11_RMP     [3]     = 0x1"
+        );
+    }
+
+    #[test]
+    fn masks_cover_wide_fields() {
+        let field = |offset, width| Field {
+            name: "FIELD".to_string(),
+            offset,
+            width,
+        };
+
+        assert_eq!(field(0, 64).mask(), u64::MAX);
+        assert_eq!(field(32, 32).mask(), 0xffff_ffff_0000_0000);
+        assert_eq!(field(4, 0).mask(), 0);
+        assert_eq!(field(0, 64).extract(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn unknown_names_list_near_matches() {
+        let svd = SvdRegisters::parse(SVD).unwrap();
+
+        assert_eq!(
+            svd.find("SYSCFG.CFGR").unwrap_err(),
+            "Unknown register `SYSCFG.CFGR`. Did you mean: SYSCFG.CFGR1, SYSCFG.CFGR2?"
+        );
+        assert_eq!(
+            svd.find("SYSCFG.CFGR1.MEMMODE").unwrap_err(),
+            "Unknown register `SYSCFG.CFGR1.MEMMODE`. Did you mean: SYSCFG.CFGR1.MEM_MODE?"
+        );
+        assert_eq!(
+            svd.find("GPIOA.ODR").unwrap_err(),
+            "Unknown register `GPIOA.ODR`."
+        );
+    }
+}
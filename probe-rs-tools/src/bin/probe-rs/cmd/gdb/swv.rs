@@ -0,0 +1,137 @@
+//! Forwarding the ITM stimulus port 0 output to TCP clients while the GDB server runs.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Context;
+use parking_lot::FairMutex;
+use probe_rs::architecture::arm::{component::TraceSink, swo::SwoConfig};
+use probe_rs::Session;
+
+/// How long to wait before reading the SWO data again, if the probe had none.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(clap::Parser)]
+pub struct SwvOptions {
+    /// Capture the ITM output of the target through SWO, and forward the data written to
+    /// stimulus port 0 to the clients of a TCP port, e.g. `nc localhost 1338`.
+    #[clap(long, requires_all = ["swv_clock", "swv_baud"])]
+    swv: bool,
+
+    /// The speed of the clock feeding the TPIU/SWO module in Hz.
+    #[clap(long, value_name = "HZ", requires = "swv")]
+    swv_clock: Option<u32>,
+
+    /// The desired baud rate of the SWO output.
+    #[clap(long, value_name = "BAUD", requires = "swv")]
+    swv_baud: Option<u32>,
+
+    /// The address to serve the SWV output at (localhost:1338 by default).
+    #[clap(long, requires = "swv")]
+    swv_connection_string: Option<String>,
+}
+
+impl SwvOptions {
+    /// Configures the SWO output of the core, and binds the TCP port for the output.
+    ///
+    /// Returns `None` if `--swv` was not given.
+    pub fn setup(&self, session: &mut Session, core: usize) -> anyhow::Result<Option<TcpListener>> {
+        let (true, Some(clock), Some(baud)) = (self.swv, self.swv_clock, self.swv_baud) else {
+            return Ok(None);
+        };
+
+        session
+            .setup_tracing(core, TraceSink::Swo(SwoConfig::new(clock).set_baud(baud)))
+            .context("Failed to configure the SWO output")?;
+
+        let address = self
+            .swv_connection_string
+            .as_deref()
+            .unwrap_or("localhost:1338");
+        let listener = TcpListener::bind(address)
+            .with_context(|| format!("Failed to bind the SWV port {address}"))?;
+        listener.set_nonblocking(true)?;
+
+        println!("Serving the SWV output at {}", listener.local_addr()?);
+
+        Ok(Some(listener))
+    }
+}
+
+/// Decodes the SWO data of the target, and sends the stimulus port 0 output to the clients of
+/// `listener`, until `stop` is set.
+pub fn serve(session: &FairMutex<Session>, listener: TcpListener, stop: &AtomicBool) {
+    let reader = SwoReader {
+        session,
+        stop,
+        data: vec![],
+        position: 0,
+    };
+    let decoder = itm::Decoder::new(reader, itm::DecoderOptions { ignore_eof: false });
+
+    let mut clients = vec![];
+    for packet in decoder.singles() {
+        match packet {
+            Ok(itm::TracePacket::Instrumentation { port: 0, payload }) => {
+                accept_clients(&listener, &mut clients);
+                clients.retain_mut(|client| client.write_all(&payload).is_ok());
+            }
+            Ok(_) => {}
+            Err(itm::DecoderError::MalformedPacket(error)) => {
+                tracing::debug!("Skipping a malformed ITM packet: {error}")
+            }
+            Err(itm::DecoderError::Io(error)) => {
+                tracing::warn!("Failed to read the SWO data, stopping the SWV output: {error}");
+                break;
+            }
+        }
+    }
+}
+
+fn accept_clients(listener: &TcpListener, clients: &mut Vec<TcpStream>) {
+    while let Ok((client, address)) = listener.accept() {
+        tracing::info!("SWV client connected from {address}");
+        if client.set_nonblocking(false).is_ok() {
+            clients.push(client);
+        }
+    }
+}
+
+/// Reads the SWO data from the session, which is shared with the GDB server.
+///
+/// A read waits until data is available, and returns the end of the stream once `stop` is set.
+struct SwoReader<'a> {
+    session: &'a FairMutex<Session>,
+    stop: &'a AtomicBool,
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl Read for SwoReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.data.len() {
+            if self.stop.load(Ordering::Relaxed) {
+                return Ok(0);
+            }
+
+            self.data = self
+                .session
+                .lock()
+                .read_trace_data()
+                .map_err(std::io::Error::other)?;
+            self.position = 0;
+
+            if self.data.is_empty() {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+
+        let count = buf.len().min(self.data.len() - self.position);
+        buf[..count].copy_from_slice(&self.data[self.position..self.position + count]);
+        self.position += count;
+
+        Ok(count)
+    }
+}
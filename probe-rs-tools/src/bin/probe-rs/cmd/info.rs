@@ -1,13 +1,17 @@
+mod clocks;
+mod security;
+
 use std::fmt::Write;
+use std::path::PathBuf;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use jep106::JEP106Code;
 use probe_rs::{
     architecture::{
         arm::{
             ap::{GenericAp, MemoryAp},
             armv6m::Demcr,
-            component::Scs,
+            component::{Cti, Scs},
             dp::{DebugPortId, DebugPortVersion, MinDpSupport, DLPIDR, DPIDR, TARGETID},
             memory::{
                 romtable::{PeripheralID, RomTable},
@@ -21,12 +25,17 @@ use probe_rs::{
             XtensaCommunicationInterface, XtensaDebugInterfaceState,
         },
     },
+    flashing::read_jedec_id,
     probe::{list::Lister, Probe, WireProtocol},
-    MemoryMappedRegister,
+    CoreType, MemoryMappedRegister, Session,
 };
+use probe_rs_target::ChipFamily;
+use serde::Serialize;
 use termtree::Tree;
 
-use crate::util::common_options::ProbeOptions;
+use crate::cmd::power::POWERED_THRESHOLD;
+use crate::util::common_options::{OperationError, ProbeOptions};
+use crate::util::elf::{check_running_build_id, ElfFile, ElfIdentity};
 
 const JEP_ARM: JEP106Code = JEP106Code::new(4, 0x3b);
 
@@ -40,6 +49,79 @@ pub struct Cmd {
     /// when connecting. This is required for targets using SWD multidrop
     #[arg(long, value_parser = parse_hex)]
     target_sel: Option<u32>,
+    /// Only show the access port with this index
+    #[arg(long, value_name = "INDEX")]
+    ap: Option<u8>,
+    /// Show the complete CoreSight ROM table tree
+    ///
+    /// For every component, the base address and the raw CIDR and PIDR values are shown,
+    /// including components which could not be identified.
+    #[arg(long)]
+    rom_table: bool,
+    /// Read the identification of the target, and suggest matching values for `--chip`
+    #[arg(long, conflicts_with = "rom_table")]
+    autodetect: bool,
+    /// Only read the identification registers of the target and suggest matching values for
+    /// `--chip`, and print them as JSON
+    ///
+    /// This skips walking the ROM tables, which makes it much faster than the full info.
+    #[arg(long, conflicts_with_all = ["rom_table", "autodetect"])]
+    identify_only: bool,
+    /// Show the target voltage and current measured by the probe, instead of the target info
+    #[arg(long, conflicts_with_all = ["rom_table", "autodetect", "identify_only"])]
+    power: bool,
+    /// Read the JEDEC ID of the external SPI flash of the target with the selected `--chip`
+    ///
+    /// This confirms which flash is soldered to the board. Only the Espressif chips are
+    /// supported at the moment.
+    #[arg(long, conflicts_with_all = ["rom_table", "autodetect", "identify_only", "power"])]
+    jedec_id: bool,
+    /// Show the source of the system clock and the frequencies of the system and bus clocks of
+    /// the target with the selected `--chip`, decoded from its clock control registers
+    ///
+    /// Only the STM32F4 and STM32G0 families are supported at the moment.
+    #[arg(long, conflicts_with_all = ["rom_table", "autodetect", "identify_only", "power", "jedec_id"])]
+    clocks: bool,
+    /// The frequency of the external oscillator (HSE) in Hz, to calculate the clocks derived
+    /// from it with `--clocks`
+    #[arg(long, value_name = "HZ", requires = "clocks")]
+    hse_frequency: Option<u64>,
+    /// Show the security states of the target with the selected `--chip`: the debug
+    /// authentication, the TrustZone state and partitioning, and the readout protection
+    ///
+    /// The architectural states are read for Armv8-M cores. The protection settings are only
+    /// known for the nRF52, STM32F4, STM32G0 and STM32L4 families at the moment.
+    #[arg(long, conflicts_with_all = ["rom_table", "autodetect", "identify_only", "power", "jedec_id", "clocks"])]
+    security: bool,
+    /// Show the GNU build ID and the `.comment` strings of this ELF file, instead of the target
+    /// info
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["rom_table", "autodetect", "identify_only", "power", "jedec_id", "clocks", "security"])]
+    elf: Option<PathBuf>,
+    /// Check that the build ID in the memory of the target matches the `--elf` file
+    ///
+    /// The build ID note has to be part of the image, e.g. in a section kept in flash. It is read
+    /// from the address of the note in the ELF file, or from `--build-id-symbol`.
+    #[arg(long, requires = "elf")]
+    verify_build_id: bool,
+    /// The symbol at the start of the build ID note in the memory of the target
+    #[arg(long, value_name = "SYMBOL", requires = "verify_build_id")]
+    build_id_symbol: Option<String>,
+    /// The format of the output of `--power`
+    #[arg(long, value_enum, requires = "power")]
+    format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// The power measurements of the probe, `None` if the probe cannot measure them.
+#[derive(Debug, Serialize)]
+struct PowerReport {
+    target_voltage: Option<f32>,
+    target_current: Option<f32>,
 }
 
 // Clippy doesn't like `from_str_radix` with radix 10, but I prefer the symmetry`
@@ -53,17 +135,105 @@ fn parse_hex(src: &str) -> Result<u32, std::num::ParseIntError> {
     }
 }
 
+/// Finds the entry of the family of the chip `name` in `families`, which are identified by a
+/// prefix of the names of their chips, ignoring the case.
+///
+/// The family specific parts of `info`, like `--clocks` and `--security`, are selected with this.
+fn find_family<'a, T>(families: &'a [(&str, T)], name: &str) -> Option<&'a T> {
+    families.iter().find_map(|(prefix, entry)| {
+        name.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+            .then_some(entry)
+    })
+}
+
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        if let Some(elf) = &self.elf {
+            let identity = ElfIdentity::read(elf)?;
+            identity.print();
+            if !self.verify_build_id {
+                return Ok(());
+            }
+
+            let Some(build_id) = identity.build_id else {
+                anyhow::bail!("The ELF file {} has no build ID to verify.", elf.display());
+            };
+            let address = match &self.build_id_symbol {
+                Some(symbol) => ElfFile::read(elf)?
+                    .symbol_address(symbol)?
+                    .with_context(|| {
+                        format!(
+                            "The ELF file {} has no symbol named '{symbol}'.",
+                            elf.display()
+                        )
+                    })?,
+                None => build_id.address.with_context(|| {
+                    format!("The build ID note of {} is not part of the image. Use `--build-id-symbol` to give its address.", elf.display())
+                })?,
+            };
+
+            let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+            return match check_running_build_id(&mut session.core(0)?, &build_id, address)? {
+                Ok(()) => {
+                    println!("The target runs this image.");
+                    Ok(())
+                }
+                Err(running) => Err(anyhow!(
+                    "The target runs another image, its build ID is {running}."
+                )),
+            };
+        }
+
         let probe_options = self.common.load()?;
         let mut probe = probe_options.attach_probe(lister)?;
 
+        if self.power {
+            return show_power(&mut probe, self.format.unwrap_or(OutputFormat::Text));
+        }
+
+        if self.jedec_id {
+            let target = probe_options.get_target_selector()?;
+            let mut session = probe_options.attach_session(probe, target)?;
+            return show_jedec_id(&mut session);
+        }
+
+        if self.clocks {
+            let target = probe_options.get_target_selector()?;
+            let mut session = probe_options.attach_session(probe, target)?;
+            return clocks::show_clocks(&mut session, self.hse_frequency);
+        }
+
+        if self.security {
+            let target = probe_options.get_target_selector()?;
+            let mut session = probe_options.attach_session(probe, target)?;
+            return security::show_security(&mut session);
+        }
+
         let protocols = if let Some(protocol) = probe_options.protocol() {
             vec![protocol]
         } else {
             vec![WireProtocol::Jtag, WireProtocol::Swd]
         };
 
+        if self.identify_only {
+            return identify_only(
+                probe,
+                protocols,
+                probe_options.connect_under_reset(),
+                self.target_sel,
+            );
+        }
+
+        if self.autodetect {
+            return autodetect(
+                probe,
+                protocols,
+                probe_options.connect_under_reset(),
+                self.target_sel,
+            );
+        }
+
         for protocol in protocols {
             println!("Probing target via {protocol}");
             println!();
@@ -73,6 +243,8 @@ impl Cmd {
                 protocol,
                 probe_options.connect_under_reset(),
                 self.target_sel,
+                self.ap,
+                self.rom_table,
             );
 
             probe = new_probe;
@@ -90,6 +262,70 @@ impl Cmd {
     }
 }
 
+fn show_power(probe: &mut Probe, format: OutputFormat) -> Result<()> {
+    let report = PowerReport {
+        target_voltage: probe.get_target_voltage()?,
+        target_current: probe.get_target_current()?,
+    };
+
+    match format {
+        OutputFormat::Text => {
+            match report.target_voltage {
+                Some(voltage) => println!("Target voltage: {voltage:.2} V"),
+                None => println!("The probe cannot measure the target voltage."),
+            }
+            match report.target_current {
+                Some(current) => println!("Target current: {:.1} mA", current * 1000.0),
+                None => println!("The probe cannot measure the target current."),
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    if report
+        .target_voltage
+        .is_some_and(|voltage| voltage < POWERED_THRESHOLD)
+    {
+        tracing::warn!("The target does not seem to be powered.");
+    }
+
+    Ok(())
+}
+
+fn show_jedec_id(session: &mut Session) -> Result<()> {
+    let Some(id) = read_jedec_id(session)? else {
+        return Err(anyhow!(
+            "Reading the JEDEC ID of the SPI flash is not supported for {}.",
+            session.target().name
+        ));
+    };
+
+    println!("JEDEC ID: {id}");
+    match id.manufacturer_name() {
+        Some(name) => println!("Manufacturer: {name} ({:#04x})", id.manufacturer),
+        None => println!("Manufacturer: unknown ({:#04x})", id.manufacturer),
+    }
+    println!("Memory type: {:#04x}", id.memory_type);
+    match id.size() {
+        Some(size) => println!("Capacity: {} ({:#04x})", format_size(size), id.capacity),
+        None => println!("Capacity: unknown ({:#04x})", id.capacity),
+    }
+
+    if id.manufacturer == 0x00 || id.manufacturer == 0xFF {
+        tracing::warn!("The flash did not respond, check whether it is connected and powered.");
+    }
+
+    Ok(())
+}
+
+fn format_size(size: u64) -> String {
+    if size >= 1024 * 1024 {
+        format!("{} MiB", size / (1024 * 1024))
+    } else {
+        format!("{} KiB", size / 1024)
+    }
+}
+
 const ALTERNATE_DP_ADRESSES: [DpAddress; 2] = [
     DpAddress::Multidrop(0x01002927),
     DpAddress::Multidrop(0x11002927),
@@ -100,6 +336,8 @@ fn try_show_info(
     protocol: WireProtocol,
     connect_under_reset: bool,
     target_sel: Option<u32>,
+    ap: Option<u8>,
+    rom_table: bool,
 ) -> (Probe, Result<()>) {
     if let Err(e) = probe.select_protocol(protocol) {
         return (probe, Err(e.into()));
@@ -129,7 +367,7 @@ fn try_show_info(
             );
             println!();
         };
-        match try_show_arm_dp_info(probe, dp_addr) {
+        match try_show_arm_dp_info(probe, dp_addr, ap, rom_table) {
             (probe_moved, Ok(_)) => probe = probe_moved,
             (probe_moved, Err(e)) => {
                 probe = probe_moved;
@@ -139,7 +377,7 @@ fn try_show_info(
                     println!("Trying alternate multi-drop debug ports");
 
                     for address in ALTERNATE_DP_ADRESSES {
-                        match try_show_arm_dp_info(probe, address) {
+                        match try_show_arm_dp_info(probe, address, ap, rom_table) {
                             (probe_moved, Ok(dp_version)) => {
                                 probe = probe_moved;
                                 if dp_version < DebugPortVersion::DPv2 {
@@ -219,7 +457,12 @@ fn try_show_info(
     (probe, Ok(()))
 }
 
-fn try_show_arm_dp_info(probe: Probe, dp_address: DpAddress) -> (Probe, Result<DebugPortVersion>) {
+fn try_show_arm_dp_info(
+    probe: Probe,
+    dp_address: DpAddress,
+    ap: Option<u8>,
+    rom_table: bool,
+) -> (Probe, Result<DebugPortVersion>) {
     tracing::debug!("Trying to show ARM chip information");
     match probe
         .try_into_arm_interface()
@@ -230,7 +473,7 @@ fn try_show_arm_dp_info(probe: Probe, dp_address: DpAddress) -> (Probe, Result<D
                 .map_err(|(interface, e)| (interface.close(), anyhow!(e)))
         }) {
         Ok(mut interface) => {
-            let res = show_arm_info(&mut *interface, dp_address);
+            let res = show_arm_info(&mut *interface, dp_address, ap, rom_table);
             (interface.close(), res)
         }
         Err((probe, e)) => (probe, Err(e)),
@@ -239,8 +482,16 @@ fn try_show_arm_dp_info(probe: Probe, dp_address: DpAddress) -> (Probe, Result<D
 
 /// Try to show information about the ARM chip, connected to a DP at the given address.
 ///
+/// If `ap` is set, only the access port with this index is shown.
+/// If `rom_table` is set, the base address and raw IDs of all components are shown as well.
+///
 /// Returns the version of the DP.
-fn show_arm_info(interface: &mut dyn ArmProbeInterface, dp: DpAddress) -> Result<DebugPortVersion> {
+fn show_arm_info(
+    interface: &mut dyn ArmProbeInterface,
+    dp: DpAddress,
+    ap: Option<u8>,
+    rom_table: bool,
+) -> Result<DebugPortVersion> {
     let dp_info = interface.read_raw_dp_register(dp, DPIDR::ADDRESS)?;
     let dp_info = DebugPortId::from(DPIDR(dp_info));
 
@@ -293,7 +544,19 @@ fn show_arm_info(interface: &mut dyn ArmProbeInterface, dp: DpAddress) -> Result
 
     let num_access_ports = interface.num_access_ports(dp)?;
 
-    for ap_index in 0..num_access_ports {
+    let access_ports = match ap {
+        Some(ap) if usize::from(ap) >= num_access_ports => {
+            return Err(OperationError::AccessPortNotAvailable {
+                ap,
+                reason: format!("the debug port only has {num_access_ports} access ports"),
+            }
+            .into())
+        }
+        Some(ap) => usize::from(ap)..usize::from(ap) + 1,
+        None => 0..num_access_ports,
+    };
+
+    for ap_index in access_ports {
         let ap = ApAddress {
             ap: ap_index as u8,
             dp,
@@ -312,7 +575,12 @@ fn show_arm_info(interface: &mut dyn ArmProbeInterface, dp: DpAddress) -> Result
                 let mut ap_nodes = Tree::new(format!("{} MemoryAP", address.ap));
 
                 if *device_enabled {
-                    match handle_memory_ap(access_port.into(), *debug_base_address, interface) {
+                    match handle_memory_ap(
+                        access_port.into(),
+                        *debug_base_address,
+                        interface,
+                        rom_table,
+                    ) {
                         Ok(component_tree) => ap_nodes.push(component_tree),
                         Err(e) => ap_nodes.push(format!("Error during access: {e}")),
                     };
@@ -360,6 +628,7 @@ fn handle_memory_ap(
     access_port: MemoryAp,
     base_address: u64,
     interface: &mut dyn ArmProbeInterface,
+    rom_table: bool,
 ) -> Result<Tree<String>, anyhow::Error> {
     let component = {
         let mut memory = interface.memory_interface(access_port)?;
@@ -368,7 +637,7 @@ fn handle_memory_ap(
         memory.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
         Component::try_parse(&mut *memory, base_address)?
     };
-    let component_tree = coresight_component_tree(interface, component, access_port)?;
+    let component_tree = coresight_component_tree(interface, component, access_port, rom_table)?;
 
     Ok(component_tree)
 }
@@ -377,8 +646,9 @@ fn coresight_component_tree(
     interface: &mut dyn ArmProbeInterface,
     component: Component,
     access_port: MemoryAp,
+    rom_table: bool,
 ) -> Result<Tree<String>> {
-    let tree = match &component {
+    let mut tree = match &component {
         Component::GenericVerificationComponent(_) => Tree::new("Generic".to_string()),
         Component::Class1RomTable(id, table) => {
             let peripheral_id = id.peripheral_id();
@@ -398,7 +668,28 @@ fn coresight_component_tree(
             for entry in table.entries() {
                 let component = entry.component().clone();
 
-                tree.push(coresight_component_tree(interface, component, access_port)?);
+                tree.push(coresight_component_tree(
+                    interface,
+                    component,
+                    access_port,
+                    rom_table,
+                )?);
+            }
+
+            for entry in table.unidentified_entries() {
+                if rom_table {
+                    tree.push(format!(
+                        "{:#010x} Unidentified component (CIDR: {:#010x}, PIDR: {:#018x})",
+                        entry.component_address(),
+                        entry.cidr(),
+                        entry.pidr()
+                    ));
+                } else {
+                    tree.push(format!(
+                        "Unidentified component at {:#010x}",
+                        entry.component_address()
+                    ));
+                }
             }
 
             tree
@@ -446,6 +737,17 @@ fn coresight_component_tree(
         }
     };
 
+    if rom_table {
+        let id = component.id();
+        tree.root = format!(
+            "{:#010x} {} (CIDR: {:#010x}, PIDR: {:#018x})",
+            id.component_address(),
+            tree.root,
+            id.cidr(),
+            id.pidr()
+        );
+    }
+
     Ok(tree)
 }
 
@@ -501,9 +803,58 @@ fn process_component_entry(
         tree.push(cpu_tree);
     }
 
+    if part.peripheral_type() == PeripheralType::Cti {
+        let cc = &CoresightComponent::new(component.clone(), access_port);
+        let cti = &mut Cti::new(interface, cc);
+
+        tree.push(cti_info_tree(cti)?);
+    }
+
     Ok(())
 }
 
+/// Shows which channels the triggers of the CTI are connected to.
+fn cti_info_tree(cti: &mut Cti) -> Result<Tree<String>> {
+    let devid = cti.devid()?;
+    let mut tree = Tree::new(format!(
+        "CTI: {} triggers, {} channels, {}",
+        devid.triggers,
+        devid.channels,
+        if cti.is_enabled()? {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    ));
+
+    tree.push(format!("Gate: {}", channel_list(cti.gate()?)));
+    for trigger in 0..devid.triggers {
+        let inputs = cti.trigger_in_channels(trigger)?;
+        let outputs = cti.trigger_out_channels(trigger)?;
+        if inputs != 0 || outputs != 0 {
+            tree.push(format!(
+                "Trigger {trigger}: in -> {}, out <- {}",
+                channel_list(inputs),
+                channel_list(outputs)
+            ));
+        }
+    }
+
+    Ok(tree)
+}
+
+fn channel_list(channels: u32) -> String {
+    if channels == 0 {
+        return "no channels".to_string();
+    }
+
+    let channels = (0..32)
+        .filter(|channel| channels & (1 << channel) != 0)
+        .map(|channel| channel.to_string())
+        .collect::<Vec<_>>();
+    format!("channel {}", channels.join(", "))
+}
+
 fn cpu_info_tree(scs: &mut Scs) -> Result<Tree<String>> {
     let mut tree = Tree::new("CPUID".into());
 
@@ -552,10 +903,434 @@ fn print_idcode_info(architecture: &str, idcode: u32) {
     println!("    Manufacturer: {manufacturer_id} ({jep_id})");
 }
 
+/// Identification information read from the target, used to suggest chips from the registry.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct TargetIdentification {
+    manufacturer: Option<JEP106Code>,
+    /// The part number from the ROM table, only available on ARM targets.
+    part: Option<u16>,
+    /// The CPUID register of the first core, only available on ARM targets.
+    cpuid: Option<u32>,
+    /// The JTAG IDCODE, only available on RISC-V and Xtensa targets.
+    idcode: Option<u32>,
+    core_type: Option<CoreType>,
+}
+
+/// How well a chip from the registry matches the [`TargetIdentification`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Confidence {
+    /// The manufacturer matches.
+    Low,
+    /// The manufacturer and the core type match.
+    Medium,
+    /// The manufacturer and the part number match.
+    High,
+}
+
+/// The maximum number of suggestions printed for a single confidence level.
+const MAX_SUGGESTIONS: usize = 20;
+
+fn autodetect(
+    probe: Probe,
+    protocols: Vec<WireProtocol>,
+    connect_under_reset: bool,
+    target_sel: Option<u32>,
+) -> Result<()> {
+    let (protocol, identification) = identify_target(
+        probe,
+        protocols,
+        connect_under_reset,
+        target_sel,
+        |protocol, error| match error {
+            None => println!("No identification could be read via {protocol}"),
+            Some(e) => println!("Error identifying target using protocol {protocol}: {e}"),
+        },
+    )?;
+    println!("Identified target via {protocol}");
+
+    println!(
+        "  Manufacturer: {}",
+        identification
+            .manufacturer
+            .and_then(|m| m.get())
+            .unwrap_or("<unknown>")
+    );
+    if let Some(part) = identification.part {
+        println!("  Part:         {part:#06x}");
+    }
+    if let Some(core_type) = identification.core_type {
+        println!("  Core type:    {core_type:?}");
+    }
+    println!();
+
+    let candidates = rank_chips(&probe_rs::config::families(), &identification);
+
+    let Some(best) = candidates.first().map(|(confidence, _)| *confidence) else {
+        println!("No matching chips were found in the registry. Use `probe-rs chip list` to see all supported chips.");
+        return Ok(());
+    };
+
+    let best_candidates = candidates
+        .iter()
+        .filter(|(confidence, _)| *confidence == best)
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>();
+
+    if best_candidates.len() == 1 {
+        println!("Suggested chip ({best:?} confidence):");
+    } else {
+        println!(
+            "The chip could not be narrowed down. {} chips match with {best:?} confidence:",
+            best_candidates.len()
+        );
+    }
+
+    for name in best_candidates.iter().take(MAX_SUGGESTIONS) {
+        println!("  --chip {name}");
+    }
+    if best_candidates.len() > MAX_SUGGESTIONS {
+        println!("  ... and {} more", best_candidates.len() - MAX_SUGGESTIONS);
+    }
+
+    Ok(())
+}
+
+/// The identification of the target printed by `--identify-only`.
+#[derive(Debug, Serialize)]
+struct IdentityReport {
+    protocol: String,
+    manufacturer: Option<String>,
+    part: Option<u16>,
+    /// The CPUID register of the first core of ARM targets.
+    cpuid: Option<u32>,
+    /// The JTAG IDCODE of RISC-V and Xtensa targets.
+    idcode: Option<u32>,
+    core_type: Option<CoreType>,
+    /// The chips with the best matching confidence.
+    chips: Vec<String>,
+    confidence: Option<Confidence>,
+}
+
+fn identify_only(
+    probe: Probe,
+    protocols: Vec<WireProtocol>,
+    connect_under_reset: bool,
+    target_sel: Option<u32>,
+) -> Result<()> {
+    let (protocol, identification) = identify_target(
+        probe,
+        protocols,
+        connect_under_reset,
+        target_sel,
+        |protocol, error| match error {
+            None => tracing::info!("No identification could be read via {protocol}"),
+            Some(e) => tracing::info!("Error identifying target using protocol {protocol}: {e}"),
+        },
+    )?;
+
+    let candidates = rank_chips(&probe_rs::config::families(), &identification);
+    let confidence = candidates.first().map(|(confidence, _)| *confidence);
+    let chips = candidates
+        .into_iter()
+        .filter(|(chip_confidence, _)| Some(*chip_confidence) == confidence)
+        .map(|(_, name)| name)
+        .collect();
+
+    let report = IdentityReport {
+        protocol: protocol.to_string(),
+        manufacturer: identification
+            .manufacturer
+            .and_then(|m| m.get())
+            .map(str::to_string),
+        part: identification.part,
+        cpuid: identification.cpuid,
+        idcode: identification.idcode,
+        core_type: identification.core_type,
+        chips,
+        confidence,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    Ok(())
+}
+
+/// Reads the identification of the target with the first of the `protocols` which works.
+///
+/// `on_failure` is called for every protocol which did not work, with the error if there was one.
+fn identify_target(
+    mut probe: Probe,
+    protocols: Vec<WireProtocol>,
+    connect_under_reset: bool,
+    target_sel: Option<u32>,
+    on_failure: impl Fn(WireProtocol, Option<anyhow::Error>),
+) -> Result<(WireProtocol, TargetIdentification)> {
+    for protocol in protocols {
+        let (new_probe, result) =
+            try_identify_target(probe, protocol, connect_under_reset, target_sel);
+        probe = new_probe;
+        // The other protocols can still work, even if the probe did not detach cleanly.
+        if let Err(e) = probe.detach() {
+            tracing::warn!("Failed to detach from the target after trying {protocol}: {e}");
+        }
+
+        match result {
+            Ok(Some(id)) => return Ok((protocol, id)),
+            Ok(None) => on_failure(protocol, None),
+            Err(e) => on_failure(protocol, Some(e)),
+        }
+    }
+
+    anyhow::bail!("The target could not be identified.");
+}
+
+fn try_identify_target(
+    mut probe: Probe,
+    protocol: WireProtocol,
+    connect_under_reset: bool,
+    target_sel: Option<u32>,
+) -> (Probe, Result<Option<TargetIdentification>>) {
+    if let Err(e) = probe.select_protocol(protocol) {
+        return (probe, Err(e.into()));
+    }
+
+    let attach_result = if connect_under_reset {
+        probe.attach_to_unspecified_under_reset()
+    } else {
+        probe.attach_to_unspecified()
+    };
+
+    if let Err(e) = attach_result {
+        return (probe, Err(e.into()));
+    }
+
+    if probe.has_arm_interface() {
+        let dp = target_sel.map_or(DpAddress::Default, DpAddress::Multidrop);
+
+        let result = probe
+            .try_into_arm_interface()
+            .map_err(|(iface, e)| (iface, anyhow!(e)))
+            .and_then(|interface| {
+                interface
+                    .initialize(DefaultArmSequence::create(), dp)
+                    .map_err(|(interface, e)| (interface.close(), anyhow!(e)))
+            });
+
+        match result {
+            Ok(mut interface) => {
+                let id = identify_arm_target(&mut *interface, dp);
+                return (interface.close(), id);
+            }
+            Err((returned_probe, e)) => {
+                tracing::debug!("Unable to identify ARM target: {e}");
+                probe = returned_probe;
+            }
+        }
+    }
+
+    // Only JTAG is supported for RISC-V and Xtensa, see `try_show_info`.
+    if protocol != WireProtocol::Jtag {
+        return (probe, Ok(None));
+    }
+
+    if probe.has_riscv_interface() {
+        let idcode = probe.try_get_riscv_interface_builder().and_then(|factory| {
+            let mut state = factory.create_state();
+            let mut interface = factory.attach(&mut state)?;
+            interface.read_idcode()
+        });
+
+        match idcode {
+            Ok(Some(idcode)) => {
+                return (
+                    probe,
+                    Ok(Some(identification_from_idcode(idcode, CoreType::Riscv))),
+                )
+            }
+            Ok(None) => {}
+            Err(e) => tracing::debug!("Unable to identify RISC-V target: {e}"),
+        }
+    }
+
+    if probe.has_xtensa_interface() {
+        let mut state = XtensaDebugInterfaceState::default();
+        let idcode = probe
+            .try_get_xtensa_interface(&mut state)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut interface| Ok(interface.read_idcode()?));
+
+        match idcode {
+            Ok(idcode) => {
+                return (
+                    probe,
+                    Ok(Some(identification_from_idcode(idcode, CoreType::Xtensa))),
+                )
+            }
+            Err(e) => tracing::debug!("Unable to identify Xtensa target: {e}"),
+        }
+    }
+
+    (probe, Ok(None))
+}
+
+fn identify_arm_target(
+    interface: &mut dyn ArmProbeInterface,
+    dp: DpAddress,
+) -> Result<Option<TargetIdentification>> {
+    let Some(chip_info) = interface.read_chip_info_from_rom_table(dp)? else {
+        return Ok(None);
+    };
+
+    // Read the CPUID of the first core, to narrow down the core type.
+    let ap = MemoryAp::new(ApAddress { dp, ap: 0 });
+    let cpuid = interface
+        .memory_interface(ap)
+        .and_then(|mut memory| memory.read_word_32(CPUID_ADDRESS))
+        .inspect_err(|e| tracing::debug!("Failed to read the CPUID: {e}"))
+        .ok();
+
+    Ok(Some(TargetIdentification {
+        manufacturer: Some(chip_info.manufacturer),
+        part: Some(chip_info.part),
+        cpuid,
+        idcode: None,
+        core_type: cpuid.and_then(core_type_from_cpuid),
+    }))
+}
+
+/// The address of the CPUID register of Cortex-M cores.
+const CPUID_ADDRESS: u64 = 0xE000_ED00;
+
+/// Determines the core type from the CPUID register of ARM cores.
+fn core_type_from_cpuid(cpuid: u32) -> Option<CoreType> {
+    let implementer = (cpuid >> 24) & 0xff;
+    let part_no = (cpuid >> 4) & 0xfff;
+
+    // Only cores designed by ARM are known.
+    if implementer != 0x41 {
+        return None;
+    }
+
+    match part_no {
+        0xC20 | 0xC21 | 0xC60 => Some(CoreType::Armv6m),
+        0xC23 => Some(CoreType::Armv7m),
+        0xC24 | 0xC27 => Some(CoreType::Armv7em),
+        0xD20..=0xD23 => Some(CoreType::Armv8m),
+        _ => None,
+    }
+}
+
+fn identification_from_idcode(idcode: u32, core_type: CoreType) -> TargetIdentification {
+    let manufacturer_id = (idcode >> 1) & 0x7ff;
+    let manufacturer =
+        JEP106Code::new((manufacturer_id >> 7) as u8, (manufacturer_id & 0x7f) as u8);
+
+    TargetIdentification {
+        manufacturer: Some(manufacturer),
+        part: None,
+        cpuid: None,
+        idcode: Some(idcode),
+        core_type: Some(core_type),
+    }
+}
+
+/// Ranks all chips in the given families by how well they match the identification.
+///
+/// Chips which don't match at all are not returned. The result is sorted by descending confidence.
+fn rank_chips(
+    families: &[ChipFamily],
+    identification: &TargetIdentification,
+) -> Vec<(Confidence, String)> {
+    let Some(manufacturer) = identification.manufacturer else {
+        return vec![];
+    };
+
+    let mut candidates = families
+        .iter()
+        .filter(|family| family.manufacturer == Some(manufacturer))
+        .flat_map(|family| family.variants())
+        .map(|chip| {
+            let confidence = if identification.part.is_some() && chip.part == identification.part {
+                Confidence::High
+            } else if identification.core_type.is_some()
+                && chip
+                    .cores
+                    .iter()
+                    .any(|core| Some(core.core_type) == identification.core_type)
+            {
+                Confidence::Medium
+            } else {
+                Confidence::Low
+            };
+
+            (confidence, chip.name.clone())
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    candidates
+}
+
 #[cfg(test)]
 mod tests {
+    use probe_rs::CoreType;
+    use probe_rs_target::{Chip, ChipFamily, TargetDescriptionSource};
+
+    use super::{channel_list, find_family, rank_chips, Confidence, TargetIdentification, JEP_ARM};
+
     #[test]
     fn jep_arm_is_arm() {
         assert_eq!(super::JEP_ARM.get(), Some("ARM Ltd"))
     }
+
+    #[test]
+    fn families_are_found_by_prefix() {
+        let families = [("STM32F4", 1), ("nRF52", 2)];
+
+        assert_eq!(find_family(&families, "stm32f407vgtx"), Some(&1));
+        assert_eq!(find_family(&families, "nRF52840_xxAA"), Some(&2));
+        assert_eq!(find_family(&families, "STM32"), None);
+        assert_eq!(find_family(&families, "RP2040"), None);
+    }
+
+    #[test]
+    fn rank_chips_by_part() {
+        let mut first = Chip::generic_arm("first", CoreType::Armv7em);
+        first.part = Some(0x1234);
+        let second = Chip::generic_arm("second", CoreType::Armv7em);
+        let third = Chip::generic_arm("third", CoreType::Armv6m);
+
+        let family = ChipFamily {
+            name: "test".to_string(),
+            manufacturer: Some(JEP_ARM),
+            generated_from_pack: false,
+            pack_file_release: None,
+            variants: vec![third, second, first],
+            flash_algorithms: vec![],
+            source: TargetDescriptionSource::BuiltIn,
+        };
+
+        let identification = TargetIdentification {
+            manufacturer: Some(JEP_ARM),
+            part: Some(0x1234),
+            core_type: Some(CoreType::Armv7em),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            rank_chips(&[family], &identification),
+            vec![
+                (Confidence::High, "first".to_string()),
+                (Confidence::Medium, "second".to_string()),
+                (Confidence::Low, "third".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn cti_channels_are_listed() {
+        assert_eq!(channel_list(0), "no channels");
+        assert_eq!(channel_list(0b101), "channel 0, 2");
+    }
 }
@@ -0,0 +1,284 @@
+//! Decoding the clock configuration of the target from its clock control registers, for
+//! `info --clocks`.
+//!
+//! Each supported family has a function which reads its registers, which is selected with
+//! [`find_family`]. Add new families to [`FAMILIES`].
+
+use anyhow::{anyhow, Result};
+use probe_rs::{MemoryInterface, Session};
+
+use super::find_family;
+
+/// Reads the clock configuration of a family.
+///
+/// The frequency of the external oscillator is passed if it was given with `--hse-frequency`.
+type ReadClocks = fn(&mut dyn MemoryInterface, Option<u64>) -> Result<ClockReport>;
+
+/// The supported families, by the prefix of the names of their chips.
+const FAMILIES: &[(&str, ReadClocks)] = &[("STM32F4", read_stm32f4), ("STM32G0", read_stm32g0)];
+
+/// The clock configuration of the target.
+#[derive(Debug, PartialEq, Eq)]
+struct ClockReport {
+    /// The oscillator or PLL the system clock is taken from.
+    source: &'static str,
+    oscillators: Vec<Oscillator>,
+    /// The frequencies of the system clock and the bus clocks in Hz, `None` if they are derived
+    /// from an oscillator with an unknown frequency.
+    clocks: Vec<(&'static str, Option<u64>)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Oscillator {
+    name: &'static str,
+    enabled: bool,
+    ready: bool,
+}
+
+impl Oscillator {
+    /// Decodes the enable and ready bits of the oscillator from a clock control register.
+    fn from_bits(name: &'static str, register: u32, enable_bit: u32, ready_bit: u32) -> Self {
+        Self {
+            name,
+            enabled: register & (1 << enable_bit) != 0,
+            ready: register & (1 << ready_bit) != 0,
+        }
+    }
+}
+
+/// Prints the clock configuration of the target.
+pub(super) fn show_clocks(session: &mut Session, hse_frequency: Option<u64>) -> Result<()> {
+    let name = session.target().name.clone();
+    let Some(read) = find_family(FAMILIES, &name) else {
+        return Err(anyhow!("Clock reporting is not implemented for {name}."));
+    };
+
+    let report = read(&mut session.core(0)?, hse_frequency)?;
+
+    println!("System clock source: {}", report.source);
+    println!("Oscillators:");
+    for oscillator in &report.oscillators {
+        let state = match (oscillator.enabled, oscillator.ready) {
+            (false, _) => "off",
+            (true, false) => "on, not ready",
+            (true, true) => "on, ready",
+        };
+        println!("  {}: {state}", oscillator.name);
+    }
+    println!("Clocks:");
+    for (name, frequency) in &report.clocks {
+        match frequency {
+            Some(frequency) => println!("  {name}: {}", format_frequency(*frequency)),
+            None => println!("  {name}: unknown, give the HSE frequency with `--hse-frequency`"),
+        }
+    }
+
+    Ok(())
+}
+
+fn format_frequency(frequency: u64) -> String {
+    if frequency < 1_000_000 {
+        format!("{} kHz", frequency as f64 / 1_000.0)
+    } else {
+        format!("{} MHz", frequency as f64 / 1_000_000.0)
+    }
+}
+
+/// The divider of the AHB prescaler of the STM32 families.
+fn ahb_divider(hpre: u32) -> u64 {
+    match hpre {
+        0b1000 => 2,
+        0b1001 => 4,
+        0b1010 => 8,
+        0b1011 => 16,
+        0b1100 => 64,
+        0b1101 => 128,
+        0b1110 => 256,
+        0b1111 => 512,
+        _ => 1,
+    }
+}
+
+/// The divider of the APB prescalers of the STM32 families.
+fn apb_divider(ppre: u32) -> u64 {
+    match ppre {
+        0b100 => 2,
+        0b101 => 4,
+        0b110 => 8,
+        0b111 => 16,
+        _ => 1,
+    }
+}
+
+fn bits(register: u32, shift: u32, width: u32) -> u32 {
+    (register >> shift) & ((1 << width) - 1)
+}
+
+const STM32F4_RCC: u64 = 0x4002_3800;
+const STM32F4_HSI: u64 = 16_000_000;
+
+fn read_stm32f4(memory: &mut dyn MemoryInterface, hse: Option<u64>) -> Result<ClockReport> {
+    let cr = memory.read_word_32(STM32F4_RCC)?;
+    let pllcfgr = memory.read_word_32(STM32F4_RCC + 0x04)?;
+    let cfgr = memory.read_word_32(STM32F4_RCC + 0x08)?;
+
+    Ok(decode_stm32f4(cr, pllcfgr, cfgr, hse))
+}
+
+fn decode_stm32f4(cr: u32, pllcfgr: u32, cfgr: u32, hse: Option<u64>) -> ClockReport {
+    let pll_input = match bits(pllcfgr, 22, 1) {
+        0 => Some(STM32F4_HSI),
+        _ => hse,
+    };
+    let m = bits(pllcfgr, 0, 6) as u64;
+    let n = bits(pllcfgr, 6, 9) as u64;
+    let vco = pll_input
+        .filter(|_| m != 0)
+        .map(|frequency| frequency / m * n);
+    let p = (bits(pllcfgr, 16, 2) as u64 + 1) * 2;
+    // Only some chips of the family have the R output.
+    let r = bits(pllcfgr, 28, 3) as u64;
+
+    let (source, sysclk) = match bits(cfgr, 2, 2) {
+        0b00 => ("HSI", Some(STM32F4_HSI)),
+        0b01 => ("HSE", hse),
+        0b10 => ("PLL (P output)", vco.map(|vco| vco / p)),
+        _ => ("PLL (R output)", vco.filter(|_| r >= 2).map(|vco| vco / r)),
+    };
+    let hclk = sysclk.map(|sysclk| sysclk / ahb_divider(bits(cfgr, 4, 4)));
+
+    ClockReport {
+        source,
+        oscillators: vec![
+            Oscillator::from_bits("HSI", cr, 0, 1),
+            Oscillator::from_bits("HSE", cr, 16, 17),
+            Oscillator::from_bits("PLL", cr, 24, 25),
+        ],
+        clocks: vec![
+            ("SYSCLK", sysclk),
+            ("HCLK", hclk),
+            (
+                "PCLK1",
+                hclk.map(|hclk| hclk / apb_divider(bits(cfgr, 10, 3))),
+            ),
+            (
+                "PCLK2",
+                hclk.map(|hclk| hclk / apb_divider(bits(cfgr, 13, 3))),
+            ),
+        ],
+    }
+}
+
+const STM32G0_RCC: u64 = 0x4002_1000;
+const STM32G0_HSI: u64 = 16_000_000;
+const STM32G0_LSI: u64 = 32_000;
+const STM32G0_LSE: u64 = 32_768;
+
+fn read_stm32g0(memory: &mut dyn MemoryInterface, hse: Option<u64>) -> Result<ClockReport> {
+    let cr = memory.read_word_32(STM32G0_RCC)?;
+    let cfgr = memory.read_word_32(STM32G0_RCC + 0x08)?;
+    let pllcfgr = memory.read_word_32(STM32G0_RCC + 0x0C)?;
+
+    Ok(decode_stm32g0(cr, cfgr, pllcfgr, hse))
+}
+
+fn decode_stm32g0(cr: u32, cfgr: u32, pllcfgr: u32, hse: Option<u64>) -> ClockReport {
+    let hsisys = STM32G0_HSI >> bits(cr, 11, 3);
+
+    let pll_input = match bits(pllcfgr, 0, 2) {
+        0b10 => Some(STM32G0_HSI),
+        0b11 => hse,
+        _ => None,
+    };
+    let m = bits(pllcfgr, 4, 3) as u64 + 1;
+    let n = bits(pllcfgr, 8, 7) as u64;
+    let r = bits(pllcfgr, 29, 3) as u64 + 1;
+    let pllrclk = pll_input
+        .filter(|_| r >= 2)
+        .map(|frequency| frequency / m * n / r);
+
+    let (source, sysclk) = match bits(cfgr, 3, 3) {
+        0b000 => ("HSISYS", Some(hsisys)),
+        0b001 => ("HSE", hse),
+        0b010 => ("PLL (R output)", pllrclk),
+        0b011 => ("LSI", Some(STM32G0_LSI)),
+        _ => ("LSE", Some(STM32G0_LSE)),
+    };
+    let hclk = sysclk.map(|sysclk| sysclk / ahb_divider(bits(cfgr, 8, 4)));
+
+    ClockReport {
+        source,
+        oscillators: vec![
+            Oscillator::from_bits("HSI16", cr, 8, 10),
+            Oscillator::from_bits("HSE", cr, 16, 17),
+            Oscillator::from_bits("PLL", cr, 24, 25),
+        ],
+        clocks: vec![
+            ("SYSCLK", sysclk),
+            ("HCLK", hclk),
+            (
+                "PCLK",
+                hclk.map(|hclk| hclk / apb_divider(bits(cfgr, 12, 3))),
+            ),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stm32f4_pll_clocks_are_decoded() {
+        // 168 MHz from an 8 MHz HSE: M = 8, N = 336, P = 2, AHB / 1, APB1 / 4, APB2 / 2.
+        let cr = 0x0303_0083;
+        let pllcfgr = (1 << 22) | (7 << 24) | (336 << 6) | 8;
+        let cfgr = (0b100 << 13) | (0b101 << 10) | (0b10 << 2) | 0b10;
+
+        let report = decode_stm32f4(cr, pllcfgr, cfgr, Some(8_000_000));
+        assert_eq!(report.source, "PLL (P output)");
+        assert_eq!(
+            report.clocks,
+            [
+                ("SYSCLK", Some(168_000_000)),
+                ("HCLK", Some(168_000_000)),
+                ("PCLK1", Some(42_000_000)),
+                ("PCLK2", Some(84_000_000)),
+            ]
+        );
+        assert!(report
+            .oscillators
+            .iter()
+            .all(|osc| osc.enabled && osc.ready));
+
+        // The frequency of the HSE is needed.
+        let report = decode_stm32f4(cr, pllcfgr, cfgr, None);
+        assert_eq!(report.clocks[0], ("SYSCLK", None));
+    }
+
+    #[test]
+    fn stm32g0_clocks_are_decoded() {
+        // 64 MHz from HSI16: M = 1, N = 8, R = 2, APB / 2.
+        let cr = (1 << 25) | (1 << 24) | (1 << 10) | (1 << 8);
+        let cfgr = (0b100 << 12) | (0b010 << 3) | 0b010;
+        let pllcfgr = (1 << 29) | (1 << 28) | (8 << 8) | 0b10;
+
+        let report = decode_stm32g0(cr, cfgr, pllcfgr, None);
+        assert_eq!(report.source, "PLL (R output)");
+        assert_eq!(
+            report.clocks,
+            [
+                ("SYSCLK", Some(64_000_000)),
+                ("HCLK", Some(64_000_000)),
+                ("PCLK", Some(32_000_000)),
+            ]
+        );
+
+        // After the reset, the system clock is HSI16 divided by one.
+        let report = decode_stm32g0(0x0000_0500, 0, 0x0000_1000, None);
+        assert_eq!(report.source, "HSISYS");
+        assert_eq!(report.clocks[0], ("SYSCLK", Some(16_000_000)));
+        assert_eq!(format_frequency(STM32G0_LSE), "32.768 kHz");
+        assert_eq!(format_frequency(168_000_000), "168 MHz");
+    }
+}
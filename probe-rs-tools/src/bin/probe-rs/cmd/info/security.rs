@@ -0,0 +1,248 @@
+//! Reporting the security states of the target for `info --security`.
+//!
+//! Two kinds of states are reported: the security state, the debug authentication and the SAU
+//! configuration, which the Armv8-M security extension defines for every core, and the readout
+//! protection, which is specific to the vendor. The latter is only known for the families in
+//! [`FAMILIES`], whose option bytes or UICR are decoded here.
+
+use anyhow::Result;
+use probe_rs::{CoreType, MemoryInterface, Session};
+
+use super::find_family;
+
+/// Reads the protection settings of a family.
+type ReadSecurity = fn(&mut dyn MemoryInterface) -> Result<Vec<SecurityState>>;
+
+/// The supported families, by the prefix of the names of their chips.
+const FAMILIES: &[(&str, ReadSecurity)] = &[
+    ("nRF52", read_nrf52),
+    ("STM32F4", read_stm32f4),
+    ("STM32G0", read_stm32_optr),
+    ("STM32L4", read_stm32_optr),
+];
+
+/// One security-relevant state of the target.
+#[derive(Debug, PartialEq, Eq)]
+struct SecurityState {
+    name: &'static str,
+    state: String,
+}
+
+impl SecurityState {
+    fn new(name: &'static str, state: impl Into<String>) -> Self {
+        Self {
+            name,
+            state: state.into(),
+        }
+    }
+}
+
+/// Prints the security states of the target which can be determined.
+pub(super) fn show_security(session: &mut Session) -> Result<()> {
+    let name = session.target().name.clone();
+    let mut core = session.core(0)?;
+
+    println!("Security state of {name}:");
+
+    let states = match core.core_type() {
+        CoreType::Armv8m => read_armv8m(&mut core)?,
+        core_type if core_type.is_cortex_m() => {
+            vec![SecurityState::new("TrustZone", "not supported by the core")]
+        }
+        core_type => vec![SecurityState::new(
+            "Architecture",
+            format!("no architectural security states are read for {core_type:?} cores"),
+        )],
+    };
+    print_states(&states);
+
+    match find_family(FAMILIES, &name) {
+        Some(read) => print_states(&read(&mut core)?),
+        None => println!("  The protection settings of {name} are not known."),
+    }
+
+    Ok(())
+}
+
+fn print_states(states: &[SecurityState]) {
+    for state in states {
+        println!("  {}: {}", state.name, state.state);
+    }
+}
+
+const DAUTHSTATUS: u64 = 0xE000_EFB8;
+const DSCSR: u64 = 0xE000_EE08;
+const SAU_CTRL: u64 = 0xE000_EDD0;
+const SAU_TYPE: u64 = 0xE000_EDD4;
+
+fn read_armv8m(memory: &mut dyn MemoryInterface) -> Result<Vec<SecurityState>> {
+    let dauthstatus = memory.read_word_32(DAUTHSTATUS)?;
+    let mut states = decode_dauthstatus(dauthstatus);
+
+    if !has_security_extension(dauthstatus) {
+        states.push(SecurityState::new(
+            "TrustZone",
+            "not implemented by the core",
+        ));
+        return Ok(states);
+    }
+
+    let dscsr = memory.read_word_32(DSCSR)?;
+    states.push(SecurityState::new(
+        "Current security state",
+        if dscsr & (1 << 16) != 0 {
+            "secure"
+        } else {
+            "non-secure"
+        },
+    ));
+
+    // The SAU is only visible to the debugger with secure debug access.
+    if secure_debug_enabled(dauthstatus) {
+        let sau_ctrl = memory.read_word_32(SAU_CTRL)?;
+        let sau_type = memory.read_word_32(SAU_TYPE)?;
+        states.push(decode_sau(sau_ctrl, sau_type));
+    } else {
+        states.push(SecurityState::new(
+            "TrustZone partitioning",
+            "unknown, secure debug is disabled",
+        ));
+    }
+
+    Ok(states)
+}
+
+/// Decodes the debug authentication of the secure and the non-secure state.
+fn decode_dauthstatus(dauthstatus: u32) -> Vec<SecurityState> {
+    let field = |shift: u32| match (dauthstatus >> shift) & 0b11 {
+        0b11 => "enabled",
+        0b10 => "disabled",
+        _ => "not implemented",
+    };
+
+    vec![
+        SecurityState::new("Non-secure invasive debug", field(0)),
+        SecurityState::new("Non-secure non-invasive debug", field(2)),
+        SecurityState::new("Secure invasive debug", field(4)),
+        SecurityState::new("Secure non-invasive debug", field(6)),
+    ]
+}
+
+/// Whether the core implements the secure state, which is what TrustZone adds.
+fn has_security_extension(dauthstatus: u32) -> bool {
+    (dauthstatus >> 4) & 0b10 != 0
+}
+
+fn secure_debug_enabled(dauthstatus: u32) -> bool {
+    (dauthstatus >> 4) & 0b11 == 0b11
+}
+
+fn decode_sau(sau_ctrl: u32, sau_type: u32) -> SecurityState {
+    let regions = sau_type & 0xFF;
+    let state = match (sau_ctrl & 0b1 != 0, sau_ctrl & 0b10 != 0) {
+        (true, _) => format!("SAU enabled with {regions} regions"),
+        (false, true) => "SAU disabled, all memory is non-secure".to_string(),
+        (false, false) => "SAU disabled, all memory is secure".to_string(),
+    };
+
+    SecurityState::new("TrustZone partitioning", state)
+}
+
+/// Decodes the readout protection level of the option bytes of the STM32 families.
+fn decode_rdp(rdp: u8) -> &'static str {
+    match rdp {
+        0xAA => "level 0, no protection",
+        // Level 2 disables the debug port for good.
+        0xCC => "level 2, debug permanently disabled",
+        _ => "level 1, flash not readable by the debugger",
+    }
+}
+
+const STM32F4_FLASH_OPTCR: u64 = 0x4002_3C14;
+
+fn read_stm32f4(memory: &mut dyn MemoryInterface) -> Result<Vec<SecurityState>> {
+    let optcr = memory.read_word_32(STM32F4_FLASH_OPTCR)?;
+
+    Ok(vec![SecurityState::new(
+        "Readout protection (RDP)",
+        decode_rdp((optcr >> 8) as u8),
+    )])
+}
+
+/// The `FLASH_OPTR` register of the STM32G0 and STM32L4 families.
+const STM32_FLASH_OPTR: u64 = 0x4002_2020;
+
+fn read_stm32_optr(memory: &mut dyn MemoryInterface) -> Result<Vec<SecurityState>> {
+    let optr = memory.read_word_32(STM32_FLASH_OPTR)?;
+
+    Ok(vec![SecurityState::new(
+        "Readout protection (RDP)",
+        decode_rdp(optr as u8),
+    )])
+}
+
+const NRF52_UICR_APPROTECT: u64 = 0x1000_1208;
+
+fn read_nrf52(memory: &mut dyn MemoryInterface) -> Result<Vec<SecurityState>> {
+    let approtect = memory.read_word_32(NRF52_UICR_APPROTECT)?;
+
+    Ok(vec![SecurityState::new(
+        "Access port protection (APPROTECT)",
+        decode_nrf52_approtect(approtect),
+    )])
+}
+
+fn decode_nrf52_approtect(approtect: u32) -> &'static str {
+    match approtect & 0xFF {
+        0x5A => "disabled in hardware",
+        // Newer revisions enable the protection unless UICR and the firmware disable it.
+        0xFF => "not set in UICR, newer revisions need the firmware to disable it",
+        _ => "enabled, the debugger can only erase the chip",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_authentication_is_decoded() {
+        // Secure debug disabled, non-secure debug enabled.
+        let dauthstatus = 0b1010_1111;
+        let states = decode_dauthstatus(dauthstatus);
+        assert_eq!(
+            states[0],
+            SecurityState::new("Non-secure invasive debug", "enabled")
+        );
+        assert_eq!(
+            states[2],
+            SecurityState::new("Secure invasive debug", "disabled")
+        );
+        assert!(has_security_extension(dauthstatus));
+        assert!(!secure_debug_enabled(dauthstatus));
+
+        // Without the security extension.
+        let states = decode_dauthstatus(0b1111);
+        assert_eq!(states[3].state, "not implemented");
+        assert!(!has_security_extension(0b1111));
+    }
+
+    #[test]
+    fn protection_settings_are_decoded() {
+        assert_eq!(decode_sau(0b1, 8).state, "SAU enabled with 8 regions");
+        assert_eq!(
+            decode_sau(0b10, 8).state,
+            "SAU disabled, all memory is non-secure"
+        );
+
+        assert_eq!(decode_rdp(0xAA), "level 0, no protection");
+        assert_eq!(
+            decode_rdp(0xBB),
+            "level 1, flash not readable by the debugger"
+        );
+        assert_eq!(
+            decode_nrf52_approtect(0xFFFF_FF00),
+            "enabled, the debugger can only erase the chip"
+        );
+    }
+}
@@ -1,20 +1,351 @@
-use probe_rs::probe::list::Lister;
+use std::time::Duration;
+
+use probe_rs::probe::{
+    list::{AllProbesLister, Lister},
+    DebugProbeInfo, SupportedSpeeds, UsbLocation, WireProtocol,
+};
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// How often the list of probes is refreshed in watch mode.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(clap::Parser)]
-pub struct Cmd {}
+pub struct Cmd {
+    /// Keep running and report probes as they are connected or disconnected.
+    #[clap(long)]
+    watch: bool,
+
+    /// List the probe drivers compiled into this binary instead of the connected probes.
+    #[clap(long, conflicts_with = "watch")]
+    drivers: bool,
+
+    /// Open every connected probe and list its supported protocols and speeds, and whether it
+    /// supports SWO and powering the target.
+    #[clap(long, conflicts_with_all = ["watch", "drivers"])]
+    capabilities: bool,
+
+    /// Also show the USB bus, port path and device address of each probe, to tell identical
+    /// probes apart by the port they are connected to.
+    #[clap(long, conflicts_with_all = ["drivers", "capabilities"])]
+    usb_details: bool,
+
+    /// The format of the output.
+    #[clap(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        if self.drivers {
+            return list_drivers(self.format);
+        }
+
         let probes = lister.list_all();
 
-        if !probes.is_empty() {
-            println!("The following debug probes were found:");
-            for (num, link) in probes.iter().enumerate() {
-                println!("[{num}]: {link}");
+        if self.capabilities {
+            return list_capabilities(lister, &probes, self.format);
+        }
+
+        if self.watch {
+            return watch(lister, probes, self.format, self.usb_details);
+        }
+
+        match self.format {
+            OutputFormat::Text => {
+                if !probes.is_empty() {
+                    println!("The following debug probes were found:");
+                    for (num, link) in probes.iter().enumerate() {
+                        println!("[{num}]: {}", describe(link, self.usb_details));
+                    }
+                } else {
+                    println!("No debug probes were found.");
+                }
+            }
+            OutputFormat::Json => {
+                let probes = probes
+                    .iter()
+                    .map(|probe| ProbeEntry::new(probe, self.usb_details))
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&probes)?);
             }
-        } else {
-            println!("No debug probes were found.");
         }
+
         Ok(())
     }
 }
+
+/// Describes the probe, followed by its USB location with `usb_details`.
+fn describe(probe: &DebugProbeInfo, usb_details: bool) -> String {
+    match (&probe.usb_location, usb_details) {
+        (Some(location), true) => format!("{probe} at {location}"),
+        (None, true) => format!("{probe} at an unknown USB location"),
+        (_, false) => probe.to_string(),
+    }
+}
+
+fn list_drivers(format: OutputFormat) -> anyhow::Result<()> {
+    let drivers = AllProbesLister::drivers()
+        .iter()
+        .map(|driver| driver.to_string())
+        .collect::<Vec<_>>();
+
+    match format {
+        OutputFormat::Text => {
+            println!("The following probe drivers are available:");
+            for driver in drivers {
+                println!("  {driver}");
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&drivers)?),
+    }
+
+    Ok(())
+}
+
+fn list_capabilities(
+    lister: &Lister,
+    probes: &[DebugProbeInfo],
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let capabilities = probes
+        .iter()
+        .map(|probe| ProbeCapabilities::query(lister, probe))
+        .collect::<Vec<_>>();
+
+    match format {
+        OutputFormat::Text => {
+            if capabilities.is_empty() {
+                println!("No debug probes were found.");
+            }
+
+            for (num, capabilities) in capabilities.iter().enumerate() {
+                println!("[{num}]: {}", capabilities.probe.identifier);
+                capabilities.print();
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&capabilities)?),
+    }
+
+    Ok(())
+}
+
+/// Polls the connected probes until the process is interrupted and prints every change.
+fn watch(
+    lister: &Lister,
+    mut known: Vec<DebugProbeInfo>,
+    format: OutputFormat,
+    usb_details: bool,
+) -> anyhow::Result<()> {
+    // Report the probes which are already connected, so consumers start from a known state.
+    for probe in &known {
+        print_event(ProbeEventKind::Added, probe, format, usb_details)?;
+    }
+
+    loop {
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+
+        let current = lister.list_all();
+
+        for probe in known.iter().filter(|probe| !current.contains(probe)) {
+            print_event(ProbeEventKind::Removed, probe, format, usb_details)?;
+        }
+        for probe in current.iter().filter(|probe| !known.contains(probe)) {
+            print_event(ProbeEventKind::Added, probe, format, usb_details)?;
+        }
+
+        known = current;
+    }
+}
+
+fn print_event(
+    kind: ProbeEventKind,
+    probe: &DebugProbeInfo,
+    format: OutputFormat,
+    usb_details: bool,
+) -> anyhow::Result<()> {
+    let timestamp = OffsetDateTime::now_utc().format(&Rfc3339)?;
+
+    match format {
+        OutputFormat::Text => {
+            let kind = match kind {
+                ProbeEventKind::Added => "Added",
+                ProbeEventKind::Removed => "Removed",
+            };
+            println!("{timestamp} {kind:<7} {}", describe(probe, usb_details));
+        }
+        OutputFormat::Json => {
+            let event = ProbeEvent {
+                event: kind,
+                timestamp,
+                probe: ProbeEntry::new(probe, usb_details),
+            };
+            println!("{}", serde_json::to_string(&event)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ProbeEventKind {
+    Added,
+    Removed,
+}
+
+#[derive(Serialize)]
+struct ProbeEvent {
+    event: ProbeEventKind,
+    timestamp: String,
+    #[serde(flatten)]
+    probe: ProbeEntry,
+}
+
+/// The serialized form of a [`DebugProbeInfo`].
+#[derive(Serialize)]
+struct ProbeEntry {
+    identifier: String,
+    vendor_id: u16,
+    product_id: u16,
+    serial_number: Option<String>,
+    probe_type: String,
+    /// Only set with `--usb-details`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usb_location: Option<UsbLocation>,
+}
+
+impl ProbeEntry {
+    fn new(probe: &DebugProbeInfo, usb_details: bool) -> Self {
+        Self {
+            usb_location: probe.usb_location.clone().filter(|_| usb_details),
+            ..Self::from(probe)
+        }
+    }
+}
+
+impl From<&DebugProbeInfo> for ProbeEntry {
+    fn from(probe: &DebugProbeInfo) -> Self {
+        Self {
+            identifier: probe.identifier.clone(),
+            vendor_id: probe.vendor_id,
+            product_id: probe.product_id,
+            serial_number: probe.serial_number.clone(),
+            probe_type: probe.probe_type(),
+            usb_location: None,
+        }
+    }
+}
+
+/// The capabilities reported by an opened probe.
+#[derive(Serialize)]
+struct ProbeCapabilities {
+    #[serde(flatten)]
+    probe: ProbeEntry,
+    /// Set if the probe could not be opened.
+    error: Option<String>,
+    protocols: Vec<ProtocolCapabilities>,
+    default_speed_khz: Option<u32>,
+    swo: bool,
+    target_power: bool,
+}
+
+#[derive(Serialize)]
+struct ProtocolCapabilities {
+    protocol: WireProtocol,
+    /// The selectable speeds in kHz, or `None` if the probe can't report them.
+    speeds_khz: Option<SupportedSpeeds>,
+    max_speed_khz: Option<u32>,
+}
+
+impl ProbeCapabilities {
+    fn query(lister: &Lister, info: &DebugProbeInfo) -> Self {
+        let mut capabilities = Self {
+            probe: ProbeEntry::from(info),
+            error: None,
+            protocols: vec![],
+            default_speed_khz: None,
+            swo: false,
+            target_power: false,
+        };
+
+        let mut probe = match lister.open(info) {
+            Ok(probe) => probe,
+            Err(error) => {
+                capabilities.error = Some(format!("{error:#}"));
+                return capabilities;
+            }
+        };
+
+        capabilities.default_speed_khz = Some(probe.speed_khz());
+        capabilities.swo = probe.get_swo_interface().is_some();
+        capabilities.target_power = probe.supports_target_power();
+
+        for protocol in [WireProtocol::Swd, WireProtocol::Jtag] {
+            if probe.select_protocol(protocol).is_err() {
+                continue;
+            }
+
+            let speeds = match probe.supported_speeds() {
+                Ok(speeds) => speeds,
+                Err(error) => {
+                    tracing::warn!("Failed to read the supported {protocol} speeds: {error}");
+                    None
+                }
+            };
+
+            capabilities.protocols.push(ProtocolCapabilities {
+                protocol,
+                max_speed_khz: speeds.as_ref().and_then(SupportedSpeeds::max_khz),
+                speeds_khz: speeds,
+            });
+        }
+
+        capabilities
+    }
+
+    fn print(&self) {
+        if let Some(error) = &self.error {
+            println!("    Failed to open the probe: {error}");
+            return;
+        }
+
+        for protocol in &self.protocols {
+            let speeds = match &protocol.speeds_khz {
+                Some(SupportedSpeeds::UpTo(max)) => format!("up to {max} kHz"),
+                Some(SupportedSpeeds::List(list)) => format!(
+                    "{} kHz",
+                    list.iter()
+                        .map(|speed| speed.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                None => "unknown speeds".to_string(),
+            };
+            println!("    {:<13} {speeds}", format!("{}:", protocol.protocol));
+        }
+        if let Some(speed) = self.default_speed_khz {
+            println!("    {:<13} {speed} kHz", "Default speed:");
+        }
+        println!("    {:<13} {}", "SWO:", supported(self.swo));
+        println!(
+            "    {:<13} {}",
+            "Target power:",
+            supported(self.target_power)
+        );
+    }
+}
+
+fn supported(value: bool) -> &'static str {
+    if value {
+        "supported"
+    } else {
+        "not supported"
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use probe_rs::probe::list::Lister;
+use probe_rs::probe::Probe;
+use signal_hook::consts::signal;
+
+use crate::util::common_options::{ProbeOptions, TARGET_POWER_SETTLE_TIME};
+use crate::util::sampling::SamplePacer;
+
+/// Below this voltage, the target is considered to be unpowered.
+pub(crate) const POWERED_THRESHOLD: f32 = 1.0;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Switch the target power supply of the probe on.
+    On {
+        #[clap(flatten)]
+        common: ProbeOptions,
+    },
+    /// Switch the target power supply of the probe off.
+    Off {
+        #[clap(flatten)]
+        common: ProbeOptions,
+    },
+    /// Sample the target voltage and current until Ctrl+C is pressed, and report the energy
+    /// drawn by the target.
+    ///
+    /// The energy is only reported if the probe measures the target current, like J-Link
+    /// probes which support `GET_HW_INFO`.
+    Monitor {
+        /// The time between two samples, in milliseconds.
+        #[clap(long, value_name = "MS", default_value = "100", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+
+        /// Write the samples to this CSV file, instead of to stdout.
+        #[clap(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Stop after this many seconds.
+        #[clap(long, value_name = "SECONDS")]
+        duration: Option<u64>,
+
+        #[clap(flatten)]
+        common: ProbeOptions,
+    },
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        match self.subcommand {
+            Subcommand::On { common } => switch(lister, common, true),
+            Subcommand::Off { common } => switch(lister, common, false),
+            Subcommand::Monitor {
+                interval,
+                output,
+                duration,
+                common,
+            } => {
+                let mut probe = common.load()?.attach_probe(lister)?;
+                monitor(
+                    &mut probe,
+                    Duration::from_millis(interval),
+                    output,
+                    duration.map(Duration::from_secs),
+                )
+            }
+        }
+    }
+}
+
+fn switch(lister: &Lister, common: ProbeOptions, enable: bool) -> anyhow::Result<()> {
+    let probe_options = common.load()?;
+    let mut probe = probe_options.attach_probe(lister)?;
+    let name = if enable { "on" } else { "off" };
+
+    probe
+        .set_target_power(enable)
+        .with_context(|| format!("Failed to switch target power {name}"))?;
+
+    std::thread::sleep(TARGET_POWER_SETTLE_TIME);
+
+    // Read back the target voltage to confirm the switch, if the probe supports it.
+    match probe.get_target_voltage()? {
+        Some(voltage) => {
+            println!("Target voltage: {voltage:.2} V");
+
+            if enable && voltage < POWERED_THRESHOLD {
+                anyhow::bail!("Target power was switched on, but the target voltage did not come up. Check for a short circuit, or whether the probe can supply enough current.");
+            }
+            if !enable && voltage >= POWERED_THRESHOLD {
+                tracing::warn!("Target power was switched off, but the target is still powered. Is it powered from another supply?");
+            }
+        }
+        None => println!("The probe cannot measure the target voltage."),
+    }
+
+    println!("Target power switched {name}");
+
+    Ok(())
+}
+
+fn monitor(
+    probe: &mut Probe,
+    interval: Duration,
+    output: Option<PathBuf>,
+    duration: Option<Duration>,
+) -> anyhow::Result<()> {
+    let voltage = probe.get_target_voltage()?;
+    let current = probe.get_target_current()?;
+    if voltage.is_none() && current.is_none() {
+        anyhow::bail!("The probe cannot measure the target voltage or current.");
+    }
+    if current.is_none() {
+        tracing::warn!("The probe cannot measure the target current, only the voltage is sampled and no energy is reported.");
+    }
+
+    let mut writer: Box<dyn Write> = match &output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).with_context(|| {
+                format!("Failed to create {}", path.display())
+            })?))
+        }
+        None => Box::new(std::io::stdout()),
+    };
+    writeln!(writer, "time_s,voltage_v,current_a")?;
+
+    let exit = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal::SIGINT, exit.clone())?;
+
+    let mut pacer = SamplePacer::new(Some(1.0 / interval.as_secs_f64()));
+    let mut meter = EnergyMeter::default();
+    while !exit.load(Ordering::Relaxed) && duration.map_or(true, |d| pacer.elapsed() < d) {
+        pacer.wait();
+        let time = pacer.elapsed().as_secs_f64();
+        let voltage = probe.get_target_voltage()?;
+        let current = probe.get_target_current()?;
+
+        writeln!(
+            writer,
+            "{time:.4},{},{}",
+            voltage.map(|v| v.to_string()).unwrap_or_default(),
+            current.map(|c| c.to_string()).unwrap_or_default(),
+        )?;
+        meter.add(time, voltage, current);
+    }
+    writer.flush()?;
+
+    eprintln!("{}", pacer.report());
+    eprintln!("{meter}");
+    if let Some(path) = output {
+        eprintln!("Wrote the samples to {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Accumulates the energy drawn by the target from the sampled voltage and current.
+#[derive(Debug, Default)]
+struct EnergyMeter {
+    /// The time, voltage and current of the previous sample.
+    last: Option<(f64, f32, Option<f32>)>,
+    /// The energy in Joules.
+    energy: f64,
+    /// The time over which the energy was accumulated, in seconds.
+    measured: f64,
+    min_voltage: Option<f32>,
+    max_current: Option<f32>,
+}
+
+impl EnergyMeter {
+    fn add(&mut self, time: f64, voltage: Option<f32>, current: Option<f32>) {
+        let Some(voltage) = voltage else {
+            return;
+        };
+        self.min_voltage = Some(self.min_voltage.map_or(voltage, |min| min.min(voltage)));
+        if let Some(current) = current {
+            self.max_current = Some(self.max_current.map_or(current, |max| max.max(current)));
+        }
+
+        // The power between two samples is the average of the power at both samples.
+        if let Some((last_time, last_voltage, Some(last_current))) = self.last {
+            if let Some(current) = current {
+                let power = (last_voltage * last_current + voltage * current) as f64 / 2.0;
+                let elapsed = time - last_time;
+                self.energy += power * elapsed;
+                self.measured += elapsed;
+            }
+        }
+
+        self.last = Some((time, voltage, current));
+    }
+
+    /// The energy in milliwatt hours.
+    fn energy_mwh(&self) -> f64 {
+        self.energy / 3.6
+    }
+
+    /// The average power in milliwatts.
+    fn average_power_mw(&self) -> f64 {
+        if self.measured == 0.0 {
+            return 0.0;
+        }
+        self.energy / self.measured * 1000.0
+    }
+}
+
+impl std::fmt::Display for EnergyMeter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(min_voltage) = self.min_voltage {
+            write!(f, "Minimum voltage: {min_voltage:.2} V")?;
+            if min_voltage < POWERED_THRESHOLD {
+                write!(f, " (the target was not powered for some time)")?;
+            }
+        }
+
+        if let Some(max_current) = self.max_current {
+            write!(
+                f,
+                ", peak current: {:.1} mA, energy: {:.4} mWh over {:.2} s (average {:.2} mW)",
+                max_current * 1000.0,
+                self.energy_mwh(),
+                self.measured,
+                self.average_power_mw()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn energy_is_integrated_between_samples() {
+        let mut meter = EnergyMeter::default();
+        meter.add(0.0, Some(3.0), Some(0.01));
+        meter.add(1.0, Some(3.0), Some(0.03));
+        meter.add(2.0, Some(3.0), Some(0.03));
+
+        // (30 mW + 90 mW) / 2 for one second, and 90 mW for one second.
+        assert!((meter.energy - 0.15).abs() < 1e-6);
+        assert!((meter.energy_mwh() - 0.15 / 3.6).abs() < 1e-6);
+        assert!((meter.average_power_mw() - 75.0).abs() < 1e-3);
+        assert_eq!(meter.max_current, Some(0.03));
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use probe_rs::probe::list::Lister;
+use probe_rs::probe::DebugProbeInfo;
+
+use crate::util::common_options::ProbeOptions;
+
+/// How long to wait for the probe to enumerate again after the reset.
+const ENUMERATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    common: ProbeOptions,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let probe_options = self.common.load()?;
+        if probe_options.dry_run() {
+            println!("Would reset the USB device of the selected probe");
+            return Ok(());
+        }
+
+        let probe = probe_options.probe_info(lister)?;
+        println!("Resetting the USB device of {probe}");
+        probe
+            .reset_usb_device()
+            .with_context(|| format!("Failed to reset the USB device of {}", probe.identifier))?;
+
+        if wait_for_probe(lister, &probe) {
+            println!("The probe enumerated again");
+        } else {
+            tracing::warn!(
+                "The probe did not enumerate again within {}s, it may have to be reconnected.",
+                ENUMERATION_TIMEOUT.as_secs()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Waits until the probe is listed again after the reset.
+fn wait_for_probe(lister: &Lister, probe: &DebugProbeInfo) -> bool {
+    let start = Instant::now();
+
+    // The probe may still be listed right after the reset, before it disconnected.
+    std::thread::sleep(Duration::from_millis(500));
+    while start.elapsed() < ENUMERATION_TIMEOUT {
+        let listed = lister.list_all().iter().any(|listed| {
+            listed.vendor_id == probe.vendor_id
+                && listed.product_id == probe.product_id
+                && listed.serial_number == probe.serial_number
+        });
+        if listed {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    false
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::time::Instant;
 
@@ -15,7 +16,9 @@ use probe_rs::{
     probe::list::Lister,
 };
 
-use crate::util::flash::{build_loader, run_flash_download};
+use crate::util::flash::{build_loader, run_flash_download, EraseMode};
+use crate::util::run_output::RunOutput;
+use crate::util::sampling::{parse_sample_rate, SamplePacer};
 use tracing::info;
 
 #[derive(clap::Parser)]
@@ -34,9 +37,17 @@ pub struct ProfileCmd {
     /// Which core to profile
     #[clap(long, default_value_t = 0)]
     core: usize,
+    /// The number of samples per second. Only supported by the naive method,
+    /// which samples as fast as possible by default.
+    #[clap(long, value_name = "HZ", value_parser = parse_sample_rate)]
+    sample_rate: Option<f64>,
     /// Limit the number of entries to output
     #[clap(long, default_value_t = 25)]
     limit: usize,
+    /// Write all samples to `samples.csv`, and a `meta.json` describing the run, into a new
+    /// timestamped subdirectory of this directory.
+    #[clap(long, value_name = "DIRECTORY")]
+    output_dir: Option<PathBuf>,
     /// Profile Method
     #[clap(subcommand)]
     method: ProfileMethod,
@@ -67,6 +78,10 @@ impl core::fmt::Display for ProfileMethod {
 
 impl ProfileCmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        if self.sample_rate.is_some() && self.method != ProfileMethod::Naive {
+            anyhow::bail!("`--sample-rate` is only supported by the naive method, the ITM sample rate is configured in the target.");
+        }
+
         let (mut session, probe_options) = self
             .run
             .shared_options
@@ -99,11 +114,18 @@ impl ProfileCmd {
                 &self.run.shared_options.download_options,
                 &probe_options,
                 loader,
-                self.run.shared_options.chip_erase,
+                EraseMode::from_chip_erase_flag(self.run.shared_options.chip_erase),
             )?;
         }
 
+        let run_output = self
+            .output_dir
+            .as_deref()
+            .map(|directory| RunOutput::create(directory, "profile"))
+            .transpose()?;
+
         let start = Instant::now();
+        let mut pacer = SamplePacer::new(self.sample_rate);
         let mut reads = 0;
         let mut samples: HashMap<u32, u64> = HashMap::with_capacity(256 * (self.duration as usize));
         let duration = Duration::from_secs(self.duration);
@@ -113,10 +135,13 @@ impl ProfileCmd {
             ProfileMethod::Naive => {
                 let mut core = session.core(self.core)?;
                 info!("Attached to Core {}", self.core);
-                core.reset()?;
+                if !probe_options.no_reset() {
+                    core.reset()?;
+                }
                 let pc_reg = core.program_counter();
 
                 loop {
+                    pacer.wait();
                     core.halt(std::time::Duration::from_millis(10))?;
                     let pc: u32 = core.read_core_reg(pc_reg)?;
                     *samples.entry(pc).or_insert(1) += 1;
@@ -146,6 +171,7 @@ impl ProfileCmd {
 
                 for packet in iter {
                     if let TracePacket::PCSample { pc: Some(pc) } = packet? {
+                        pacer.wait();
                         *samples.entry(pc).or_insert(1) += 1;
                         reads += 1;
                     }
@@ -161,6 +187,20 @@ impl ProfileCmd {
         v.sort_by(|&(_, a), &(_, b)| b.cmp(&a));
 
         println!("Samples {}", reads);
+        let report = pacer.report();
+        println!("{}", report);
+
+        if let Some(run_output) = &run_output {
+            write_samples(run_output, &symbols, &v, reads)?;
+            run_output.write_metadata(
+                "profile",
+                &session,
+                probe_options.probe_serial(lister),
+                Some(file_location),
+                &report,
+            )?;
+            println!("Wrote the samples to {}", run_output.directory().display());
+        }
 
         for (address, count) in v.into_iter().take(self.limit) {
             let name = symbols
@@ -183,6 +223,45 @@ impl ProfileCmd {
     }
 }
 
+/// Writes all samples, sorted by frequency, to `samples.csv`.
+fn write_samples(
+    run_output: &RunOutput,
+    symbols: &Symbols,
+    samples: &[(u32, u64)],
+    reads: u64,
+) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(run_output.create_file("samples.csv")?);
+    writeln!(file, "address,count,percent,function,file,line")?;
+
+    for &(address, count) in samples {
+        let name = symbols.get_name(address as u64).unwrap_or_default();
+        let (location, line) = symbols
+            .get_location(address as u64)
+            .map_or((String::new(), String::new()), |(file, line)| {
+                (file, line.to_string())
+            });
+        writeln!(
+            file,
+            "{address:#010x},{count},{:.3},{},{},{line}",
+            (count as f64 / reads as f64) * 100.0,
+            csv_field(&name),
+            csv_field(&location),
+        )?;
+    }
+
+    file.flush()?;
+    Ok(())
+}
+
+/// Quotes a CSV field if needed, e.g. for function names with generic parameters.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
 // Wrapper around addr2line that allows to look up function names
 pub(crate) struct Symbols {
     loader: Loader,
@@ -231,3 +310,18 @@ impl Symbols {
         })?
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(csv_field("main"), "main");
+        assert_eq!(
+            csv_field("core::ptr::drop_in_place<(u8, u16)>"),
+            "\"core::ptr::drop_in_place<(u8, u16)>\""
+        );
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+}
@@ -1,5 +1,6 @@
-use probe_rs::{probe::list::Lister, MemoryInterface};
+use probe_rs::probe::list::Lister;
 
+use crate::util::access_port::AccessPortOptions;
 use crate::util::common_options::{ProbeOptions, ReadWriteBitWidth, ReadWriteOptions};
 use crate::CoreOptions;
 
@@ -8,6 +9,9 @@ use crate::CoreOptions;
 /// e.g. probe-rs read b32 0x400E1490 2
 ///      Reads 2 32-bit words from address 0x400E1490
 ///
+/// e.g. probe-rs read b32 0x400E1490 1 --endianness big
+///      Reads a 32-bit word of a big-endian peripheral
+///
 /// Output is a space separated list of hex values padded to the read word width.
 /// e.g. 2 words
 ///     00 00 (8-bit)
@@ -24,6 +28,9 @@ pub struct Cmd {
     #[clap(flatten)]
     probe_options: ProbeOptions,
 
+    #[clap(flatten)]
+    access_port: AccessPortOptions,
+
     #[clap(flatten)]
     read_write_options: ReadWriteOptions,
 
@@ -34,10 +41,12 @@ pub struct Cmd {
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
         let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
-        let mut core = session.core(self.shared.core)?;
+        let mut core = self.access_port.memory(&mut session, self.shared.core)?;
         let words = self.words as usize;
+        let width = self.read_write_options.width;
+        let endianness = self.read_write_options.endianness;
 
-        match self.read_write_options.width {
+        match width {
             ReadWriteBitWidth::B8 => {
                 let mut values = vec![0; words];
                 core.read_8(self.read_write_options.address, &mut values)?;
@@ -50,7 +59,7 @@ impl Cmd {
                 let mut values = vec![0; words];
                 core.read_32(self.read_write_options.address, &mut values)?;
                 for val in values {
-                    print!("{:08x} ", val);
+                    print!("{:08x} ", endianness.convert(val.into(), width));
                 }
                 println!();
             }
@@ -58,7 +67,7 @@ impl Cmd {
                 let mut values = vec![0; words];
                 core.read_64(self.read_write_options.address, &mut values)?;
                 for val in values {
-                    print!("{:016x} ", val);
+                    print!("{:016x} ", endianness.convert(val, width));
                 }
                 println!();
             }
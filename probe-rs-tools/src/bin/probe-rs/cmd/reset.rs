@@ -1,6 +1,18 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use probe_rs::flashing::Format;
 use probe_rs::probe::list::Lister;
+use probe_rs::Session;
 
-use crate::{util::common_options::ProbeOptions, CoreOptions};
+use crate::{
+    cmd::run::image_vector_table,
+    util::common_options::{LoadedProbeOptions, ProbeOptions},
+    util::flash::build_loader,
+    util::halt::{self, DEFAULT_HALT_TIMEOUT},
+    CoreOptions, FormatOptions,
+};
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -9,14 +21,147 @@ pub struct Cmd {
 
     #[clap(flatten)]
     common: ProbeOptions,
+
+    /// Repeat the reset the given number of times, to test if the target reliably comes back.
+    ///
+    /// For every cycle, probe-rs attaches to the target, resets it, and verifies that
+    /// the core can be halted and resumed again.
+    #[clap(long, value_name = "N")]
+    repeat: Option<u32>,
+
+    /// The time to wait after each reset, in milliseconds. Only used with `--repeat`.
+    #[clap(long, value_name = "MS", default_value = "0", requires = "repeat")]
+    delay: u64,
+
+    /// Check that the core starts at the reset vector of this ELF file after the reset.
+    ///
+    /// The core is halted at the reset vector, and its program counter and stack pointer are
+    /// compared with the vector table of the image, before it is resumed. This is only supported
+    /// for Cortex-M cores. Combine it with `--connect-under-reset` to attach to a target which is
+    /// not reachable while it runs.
+    #[clap(long, value_name = "PATH", conflicts_with = "repeat")]
+    elf: Option<PathBuf>,
 }
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
-        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+        if let Some(repeat) = self.repeat {
+            let probe_options = self.common.load()?;
+            probe_options.ensure_reset_allowed("`reset`")?;
+            let delay = Duration::from_millis(self.delay);
+
+            return stress_reset(lister, &probe_options, self.shared.core, repeat, delay);
+        }
+
+        let (mut session, probe_options) = self.common.simple_attach(lister)?;
+        probe_options.ensure_reset_allowed("`reset`")?;
+
+        match self.elf {
+            Some(elf) => {
+                let timeout = probe_options.halt_timeout(DEFAULT_HALT_TIMEOUT);
+                reset_and_verify(&mut session, self.shared.core, &elf, timeout)
+            }
+            None => Ok(session.core(self.shared.core)?.reset()?),
+        }
+    }
+}
+
+/// Resets the core, and checks that it starts at the reset vector of the image in `elf`.
+fn reset_and_verify(
+    session: &mut Session,
+    core: usize,
+    elf: &Path,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    if !session.target().cores[core].core_type.is_cortex_m() {
+        anyhow::bail!("Checking the reset vector is only supported for Cortex-M cores.");
+    }
+
+    let format_options = FormatOptions {
+        binary_format: Some(Format::Elf),
+        ..Default::default()
+    };
+    let loader = build_loader(session, elf, format_options, None)?;
+    let vector_table = image_vector_table(&loader)?
+        .with_context(|| format!("The image {} does not contain any data.", elf.display()))?;
+
+    let mut core = session.core(core)?;
+    core.reset_and_halt(timeout)
+        .context("Failed to halt the core at the reset vector")?;
+
+    let pc: u32 = core.read_core_reg(core.program_counter())?;
+    let sp: u32 = core.read_core_reg(core.stack_pointer())?;
+    let expected_pc = vector_table.reset_handler & !1;
+    let expected_sp = vector_table.stack_pointer;
+
+    if pc == expected_pc && sp == expected_sp {
+        println!("The core started at the reset vector: PC = {pc:#010x}, SP = {sp:#010x}");
+    } else {
+        tracing::warn!(
+            "The core did not start at the reset vector of the image: PC = {pc:#010x} (expected {expected_pc:#010x}), SP = {sp:#010x} (expected {expected_sp:#010x}). Is the image flashed, and does a bootloader run before it?"
+        );
+    }
 
-        session.core(self.shared.core)?.reset()?;
+    core.run()
+        .context("Failed to resume the core after the reset")?;
 
-        Ok(())
+    Ok(())
+}
+
+/// Performs `repeat` reset cycles, and reports every cycle which failed.
+fn stress_reset(
+    lister: &Lister,
+    probe_options: &LoadedProbeOptions,
+    core: usize,
+    repeat: u32,
+    delay: Duration,
+) -> anyhow::Result<()> {
+    let mut failures = 0;
+
+    for iteration in 1..=repeat {
+        match reset_cycle(lister, probe_options, core, delay) {
+            Ok(()) => println!("Reset cycle {iteration}/{repeat} succeeded"),
+            Err(e) => {
+                failures += 1;
+                println!("Reset cycle {iteration}/{repeat} failed: {e:#}");
+            }
+        }
+    }
+
+    if failures > 0 {
+        anyhow::bail!("{failures} of {repeat} reset cycles failed");
+    }
+
+    println!("All {repeat} reset cycles succeeded");
+
+    Ok(())
+}
+
+/// Attaches to the target, resets it, and checks that the core can be halted and resumed.
+fn reset_cycle(
+    lister: &Lister,
+    probe_options: &LoadedProbeOptions,
+    core: usize,
+    delay: Duration,
+) -> anyhow::Result<()> {
+    let target = probe_options.get_target_selector()?;
+    let probe = probe_options.attach_probe(lister)?;
+    let mut session = probe_options.attach_session(probe, target)?;
+
+    let mut core = session.core(core)?;
+
+    core.reset().context("Failed to reset the core")?;
+
+    std::thread::sleep(delay);
+
+    halt::halt(&mut core, probe_options.halt_timeout(DEFAULT_HALT_TIMEOUT))
+        .context("Failed to halt the core after the reset")?;
+    if !core.core_halted()? {
+        anyhow::bail!("The core did not halt after the reset");
     }
+
+    core.run()
+        .context("Failed to resume the core after the reset")?;
+
+    Ok(())
 }
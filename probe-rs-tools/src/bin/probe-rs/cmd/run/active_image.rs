@@ -0,0 +1,172 @@
+//! Selecting which of several images, e.g. the two slots of an A/B update, runs on the target.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use probe_rs::flashing::Format;
+use probe_rs::{Core, MemoryInterface, Session};
+
+use crate::cmd::run::image_vector_table;
+use crate::util::elf::{check_running_build_id, BuildIdNote, ElfIdentity};
+use crate::util::flash::build_loader;
+use crate::FormatOptions;
+
+/// The Vector Table Offset Register of Cortex-M cores.
+const VTOR: u64 = 0xE000_ED08;
+
+/// How long to wait for a bootloader to start one of the images after a reset.
+const SELECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the target is checked while waiting for one of the images to start.
+const SELECT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The target was reset while it ran, as caught by `attach --follow-reset-vector` or detected
+/// by `attach --reconnect`.
+#[derive(Debug, thiserror::Error)]
+#[error("The target was reset.")]
+pub(crate) struct TargetWasReset;
+
+/// An image which may run on the target.
+struct Image {
+    path: PathBuf,
+    /// The address of the vector table of a Cortex-M image.
+    vector_table: Option<u64>,
+    /// The build ID note of the image, if it is part of the image.
+    build_id: Option<(BuildIdNote, u64)>,
+}
+
+/// The images which may run on the target, only one of which is active at a time.
+pub(crate) struct Images {
+    images: Vec<Image>,
+    cortex_m: bool,
+}
+
+impl Images {
+    /// Reads the vector tables and build IDs of the images at `paths`.
+    pub(crate) fn new(session: &mut Session, core_id: usize, paths: &[PathBuf]) -> Result<Self> {
+        let cortex_m = session.target().cores[core_id].core_type.is_cortex_m();
+
+        let images = paths
+            .iter()
+            .map(|path| {
+                let format_options = FormatOptions {
+                    binary_format: Some(Format::Elf),
+                    ..Default::default()
+                };
+                let loader = build_loader(session, path, format_options, None)?;
+                let vector_table = image_vector_table(&loader)?.map(|table| table.address);
+                let build_id = ElfIdentity::read(path)?
+                    .build_id
+                    .and_then(|note| note.address.map(|address| (note, address)));
+
+                Ok(Image {
+                    path: path.clone(),
+                    vector_table,
+                    build_id,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { images, cortex_m })
+    }
+
+    /// Returns the path of the image which runs on the target.
+    ///
+    /// The image is selected by the vector table the core uses, or by the build ID found in the
+    /// memory of the target. Right after a reset, a bootloader may still have to start the image,
+    /// so the target is checked again until one of the images is identified. Falls back to the
+    /// first image if none is.
+    pub(crate) fn select(&self, core: &mut Core) -> Result<&Path> {
+        let start = Instant::now();
+
+        let index = loop {
+            if let Some(index) = self.identify(core)? {
+                break index;
+            }
+            if start.elapsed() > SELECT_TIMEOUT {
+                tracing::warn!(
+                    "Failed to identify the image running on the target, using {}.",
+                    self.images[0].path.display()
+                );
+                break 0;
+            }
+            std::thread::sleep(SELECT_POLL_INTERVAL);
+        };
+
+        let path = &self.images[index].path;
+        tracing::info!("The target runs the image {}", path.display());
+
+        Ok(path)
+    }
+
+    /// Returns the index of the image which runs on the target, if it is unambiguous.
+    fn identify(&self, core: &mut Core) -> Result<Option<usize>> {
+        if self.cortex_m {
+            let vtor = core
+                .read_word_32(VTOR)
+                .context("Failed to read the vector table offset")?;
+            let vector_tables: Vec<_> =
+                self.images.iter().map(|image| image.vector_table).collect();
+
+            if let Some(index) = select_by_vector_table(vtor as u64, &vector_tables) {
+                return Ok(Some(index));
+            }
+        }
+
+        let mut matches = vec![];
+        for (index, image) in self.images.iter().enumerate() {
+            if let Some((note, address)) = &image.build_id {
+                if check_running_build_id(core, note, *address)?.is_ok() {
+                    matches.push(index);
+                }
+            }
+        }
+
+        Ok(match matches[..] {
+            [index] => Some(index),
+            _ => None,
+        })
+    }
+}
+
+/// Returns the index of the only image whose vector table is at `vtor`.
+fn select_by_vector_table(vtor: u64, vector_tables: &[Option<u64>]) -> Option<usize> {
+    let mut matches = vector_tables
+        .iter()
+        .enumerate()
+        .filter(|(_, address)| **address == Some(vtor))
+        .map(|(index, _)| index);
+
+    match (matches.next(), matches.next()) {
+        (Some(index), None) => Some(index),
+        _ => None,
+    }
+}
+
+/// Checks whether the halted core is at the reset handler of its vector table, i.e. it was
+/// halted by the reset vector catch.
+pub(crate) fn halted_at_reset(core: &mut Core) -> Result<bool> {
+    let vtor = core.read_word_32(VTOR)?;
+    let reset_handler = core.read_word_32(vtor as u64 + 4)?;
+    let pc: u32 = core.read_core_reg(core.program_counter())?;
+
+    Ok(pc == reset_handler & !1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_is_selected_by_vector_table() {
+        let tables = [Some(0x1_0000), Some(0x8_0000), None];
+
+        assert_eq!(select_by_vector_table(0x8_0000, &tables), Some(1));
+        assert_eq!(select_by_vector_table(0x0, &tables), None);
+        assert_eq!(
+            select_by_vector_table(0x1_0000, &[Some(0x1_0000), Some(0x1_0000)]),
+            None
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! A bounded buffer between polling RTT and writing the output.
+//!
+//! The output is written on a separate thread, so a slow terminal or pipe does not stall polling,
+//! which would make the target block or drop data, depending on its RTT mode. If the output falls
+//! behind by more than the limit, the oldest output is dropped, or polling waits for the output.
+//!
+//! Status lines, like the `attach --stats` report, are written to stderr by the same thread, between
+//! the lines of the output, so they do not break up a line of the output.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// The default for `--max-rtt-backlog`.
+pub(crate) const DEFAULT_MAX_BACKLOG: usize = 1024 * 1024;
+
+/// What to do once the backlog is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum BacklogPolicy {
+    /// Drop the oldest output, and report how much was dropped.
+    DropOldest,
+    /// Stop polling RTT until the output caught up.
+    Block,
+}
+
+pub(crate) struct Backlog {
+    state: Mutex<State>,
+    changed: Condvar,
+    limit: usize,
+    policy: BacklogPolicy,
+}
+
+#[derive(Default)]
+struct State {
+    data: VecDeque<u8>,
+    /// The number of bytes dropped since the last report.
+    dropped: usize,
+    /// The number of bytes dropped in total.
+    total_dropped: usize,
+    /// A status line to write once the output is at the start of a line.
+    status: Option<String>,
+    /// No more data is pushed.
+    closed: bool,
+    /// Writing the output failed, so no more data is accepted.
+    failed: bool,
+}
+
+impl Backlog {
+    pub(crate) fn new(limit: usize, policy: BacklogPolicy) -> Self {
+        Self {
+            state: Mutex::default(),
+            changed: Condvar::new(),
+            limit: limit.max(1),
+            policy,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state stays consistent even if a thread panicked while holding the lock.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Queues `data` for the output.
+    fn push(&self, data: &[u8]) -> io::Result<()> {
+        let mut state = self.lock();
+
+        if self.policy == BacklogPolicy::Block {
+            // Data larger than the limit is queued once the backlog is empty.
+            while !state.failed
+                && state.data.len() + data.len() > self.limit
+                && !state.data.is_empty()
+            {
+                state = self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        }
+
+        if state.failed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "writing the output failed",
+            ));
+        }
+
+        state.data.extend(data);
+
+        if self.policy == BacklogPolicy::DropOldest {
+            let overflow = state.data.len().saturating_sub(self.limit);
+            if overflow > 0 {
+                state.data.drain(..overflow);
+                state.dropped += overflow;
+                state.total_dropped += overflow;
+            }
+        }
+
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    /// The number of bytes which were dropped because the output could not keep up.
+    pub(crate) fn total_dropped(&self) -> usize {
+        self.lock().total_dropped
+    }
+
+    /// Writes `line` to the status output, once the output is at the start of a line.
+    ///
+    /// A pending status line which was not written yet is replaced.
+    pub(crate) fn set_status(&self, line: String) {
+        self.lock().status = Some(line);
+        self.changed.notify_all();
+    }
+
+    /// Stops accepting data. The output thread returns once the queued data is written.
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.changed.notify_all();
+    }
+
+    /// Writes the queued data to `output`, and the status lines to `status_output`, until the
+    /// backlog is closed. Runs on the output thread.
+    pub(crate) fn drain_into(
+        &self,
+        output: &mut dyn Write,
+        status_output: &mut dyn Write,
+    ) -> io::Result<()> {
+        let result = self.write_all_queued(output, status_output);
+        if result.is_err() {
+            self.lock().failed = true;
+            self.changed.notify_all();
+        }
+        result
+    }
+
+    fn write_all_queued(
+        &self,
+        output: &mut dyn Write,
+        status_output: &mut dyn Write,
+    ) -> io::Result<()> {
+        let mut total_dropped = 0;
+        let mut at_line_start = true;
+
+        loop {
+            let (chunk, dropped, status, closed) = {
+                let mut state = self.lock();
+                while state.data.is_empty()
+                    && !state.closed
+                    && !(at_line_start && state.status.is_some())
+                {
+                    state = self
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                }
+                let chunk = state.data.drain(..).collect::<Vec<_>>();
+                let dropped = std::mem::take(&mut state.dropped);
+                if let Some(&last) = chunk.last() {
+                    at_line_start = last == b'\n';
+                }
+                let status = if at_line_start {
+                    state.status.take()
+                } else {
+                    None
+                };
+                // Pushing may wait for room in the backlog.
+                self.changed.notify_all();
+                (chunk, dropped, status, state.closed)
+            };
+
+            if dropped > 0 {
+                total_dropped += dropped;
+                tracing::warn!(
+                    "Dropped {dropped} bytes of RTT output, because the output could not keep up. Use `--max-rtt-backlog` to keep more output, or `--rtt-backlog-policy block`."
+                );
+            }
+
+            if !chunk.is_empty() {
+                output.write_all(&chunk)?;
+                output.flush()?;
+            }
+
+            if let Some(status) = status {
+                writeln!(status_output, "{status}")?;
+            }
+
+            if closed && chunk.is_empty() {
+                break;
+            }
+        }
+
+        if total_dropped > 0 {
+            tracing::warn!("{total_dropped} bytes of RTT output were dropped in total.");
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes into the backlog.
+pub(crate) struct BacklogWriter<'a>(pub(crate) &'a Backlog);
+
+impl Write for BacklogWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.push(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // The output thread flushes after every write.
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_output_is_dropped() {
+        let backlog = Backlog::new(4, BacklogPolicy::DropOldest);
+        let mut writer = BacklogWriter(&backlog);
+        writer.write_all(b"abc").unwrap();
+        writer.write_all(b"def").unwrap();
+        backlog.close();
+
+        let mut output = Vec::new();
+        backlog.drain_into(&mut output, &mut io::sink()).unwrap();
+
+        assert_eq!(output, b"cdef");
+    }
+
+    #[test]
+    fn blocked_output_is_complete() {
+        let backlog = Backlog::new(2, BacklogPolicy::Block);
+
+        let output = std::thread::scope(|scope| {
+            let output_thread = scope.spawn(|| {
+                let mut output = Vec::new();
+                backlog.drain_into(&mut output, &mut io::sink()).unwrap();
+                output
+            });
+
+            let mut writer = BacklogWriter(&backlog);
+            for chunk in [&b"ab"[..], b"cd", b"efg", b"h"] {
+                writer.write_all(chunk).unwrap();
+            }
+            backlog.close();
+
+            output_thread.join().unwrap()
+        });
+
+        assert_eq!(output, b"abcdefgh");
+    }
+
+    #[test]
+    fn status_is_written_between_lines() {
+        let backlog = Backlog::new(64, BacklogPolicy::DropOldest);
+        let mut writer = BacklogWriter(&backlog);
+        writer.write_all(b"first line\nsecond").unwrap();
+        backlog.set_status("stale".to_string());
+        backlog.set_status("status".to_string());
+        backlog.close();
+
+        let mut output = Vec::new();
+        let mut status = Vec::new();
+        backlog.drain_into(&mut output, &mut status).unwrap();
+
+        // The output ends in the middle of a line.
+        assert!(status.is_empty());
+
+        let backlog = Backlog::new(64, BacklogPolicy::DropOldest);
+        BacklogWriter(&backlog).write_all(b"a line\n").unwrap();
+        backlog.set_status("status".to_string());
+        backlog.close();
+
+        backlog.drain_into(&mut output, &mut status).unwrap();
+        assert_eq!(status, b"status\n");
+    }
+
+    #[test]
+    fn failed_output_is_reported() {
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let backlog = Backlog::new(2, BacklogPolicy::Block);
+        BacklogWriter(&backlog).write_all(b"ab").unwrap();
+
+        assert!(backlog.drain_into(&mut Broken, &mut io::sink()).is_err());
+        assert!(BacklogWriter(&backlog).write_all(b"cd").is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! Measuring the time from starting the firmware after the reset until it reaches a boot marker,
+//! for `run --measure-boot-time`.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+
+/// Reports the time from starting the core until the first boot marker is reached.
+#[derive(Debug, Default)]
+pub(crate) struct BootTimer {
+    /// When the core was started after the reset.
+    start: Cell<Option<Instant>>,
+    reached: Cell<bool>,
+}
+
+impl BootTimer {
+    /// Records that the core was started, once.
+    pub(crate) fn start(&self, time: Instant) {
+        if self.start.get().is_none() {
+            self.start.set(Some(time));
+        }
+    }
+
+    /// Prints the time since the start, the first time one of the markers is reached.
+    ///
+    /// `marker` describes what was reached, e.g. the matching line.
+    pub(crate) fn reached(&self, marker: &str) {
+        let Some(start) = self.start.get() else {
+            return;
+        };
+        if self.reached.replace(true) {
+            return;
+        }
+
+        eprintln!(
+            "{} {} until {marker}",
+            "Boot time:".green().bold(),
+            format_duration(start.elapsed())
+        );
+    }
+
+    /// Whether a marker was reached.
+    pub(crate) fn was_reached(&self) -> bool {
+        self.reached.get()
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_first_marker_is_reported() {
+        let timer = BootTimer::default();
+
+        // The time is only measured once the core was started.
+        timer.reached("too early");
+        assert!(!timer.was_reached());
+
+        timer.start(Instant::now());
+        timer.reached("the marker");
+        assert!(timer.was_reached());
+
+        assert_eq!(format_duration(Duration::from_micros(12_345)), "12.3 ms");
+    }
+}
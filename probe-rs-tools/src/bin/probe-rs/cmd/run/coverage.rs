@@ -0,0 +1,143 @@
+//! Reading coverage data from the target after each test.
+//!
+//! The firmware has to provide the coverage data of a test in a buffer, and export two symbols:
+//!
+//! - `__probe_rs_coverage_data`: the buffer, with its capacity as the size of the symbol, e.g.
+//!   `#[no_mangle] static mut __probe_rs_coverage_data: [u8; 16384] = [0; 16384];`
+//! - `__probe_rs_coverage_len`: a `u32` with the number of bytes of coverage data in the buffer.
+//!
+//! The firmware writes the coverage data before it signals the end of a test via semihosting, e.g.
+//! in the LLVM `.profraw` format by using the `minicov` crate. probe-rs reads the data when the test
+//! ends, writes it to `<test name>.profraw` in the output directory, and sets the length back to
+//! zero. The files of all tests can be merged with `llvm-profdata merge`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use probe_rs::{Core, MemoryInterface};
+
+use crate::util::elf::{find_symbol, ElfFile};
+
+const DATA_SYMBOL: &str = "__probe_rs_coverage_data";
+const LENGTH_SYMBOL: &str = "__probe_rs_coverage_len";
+
+/// The buffer which the firmware writes the coverage data of a test to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CoverageBuffer {
+    data: u64,
+    capacity: u64,
+    length: u64,
+}
+
+impl CoverageBuffer {
+    /// Locates the coverage buffer in the ELF file of the firmware.
+    pub(crate) fn from_elf(path: &Path) -> Result<Self> {
+        let file = ElfFile::read(path)?;
+        let elf = file.parse()?;
+
+        let symbol = |name: &str| {
+            find_symbol(&elf, name).ok_or_else(|| {
+                    anyhow!("The ELF file has no `{name}` symbol, which is needed to collect coverage data.")
+                })
+        };
+
+        let data = symbol(DATA_SYMBOL)?;
+        let length = symbol(LENGTH_SYMBOL)?;
+
+        if data.st_size == 0 {
+            anyhow::bail!("The `{DATA_SYMBOL}` symbol has no size.");
+        }
+
+        Ok(Self {
+            data: data.st_value,
+            capacity: data.st_size,
+            length: length.st_value,
+        })
+    }
+
+    /// Reads the coverage data which the firmware wrote since the last read.
+    ///
+    /// Returns `None` if the firmware did not write any data.
+    pub(crate) fn read(&self, core: &mut Core) -> Result<Option<Vec<u8>>> {
+        let length = u64::from(core.read_word_32(self.length)?);
+        if length == 0 {
+            return Ok(None);
+        }
+        if length > self.capacity {
+            anyhow::bail!(
+                "The firmware reported {length} bytes of coverage data, but the buffer only holds {} bytes.",
+                self.capacity
+            );
+        }
+
+        let mut data = vec![0; length as usize];
+        core.read(self.data, &mut data)?;
+
+        // Data which is still in the buffer after the next test is not attributed to it.
+        core.write_word_32(self.length, 0)?;
+
+        Ok(Some(data))
+    }
+}
+
+/// Collects the coverage data of every test into a directory.
+pub(crate) struct CoverageCollector {
+    buffer: CoverageBuffer,
+    output: PathBuf,
+}
+
+impl CoverageCollector {
+    pub(crate) fn new(elf: &Path, output: &Path) -> Result<Self> {
+        let buffer = CoverageBuffer::from_elf(elf)?;
+        std::fs::create_dir_all(output)
+            .with_context(|| format!("Failed to create {}", output.display()))?;
+
+        Ok(Self {
+            buffer,
+            output: output.to_path_buf(),
+        })
+    }
+
+    /// Reads the coverage data of the test which just ended, and writes it to the output directory.
+    pub(crate) fn collect(&self, core: &mut Core, test: &str) -> Result<()> {
+        let Some(data) = self.buffer.read(core)? else {
+            tracing::warn!("The test {test} did not write any coverage data.");
+            return Ok(());
+        };
+
+        let path = self.output.join(coverage_file_name(test));
+        std::fs::write(&path, data)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// The name of the file with the coverage data of a test.
+fn coverage_file_name(test: &str) -> String {
+    let name: String = test
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("{name}.profraw")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_of_test_path() {
+        assert_eq!(
+            coverage_file_name("tests::it_works"),
+            "tests__it_works.profraw"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Filtering the lines of the RTT output with `--grep` and `--grep-invert`.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+
+/// Keeps the lines of the RTT output which match the `--grep` patterns and none of the
+/// `--grep-invert` patterns.
+///
+/// The patterns are matched against the text of a line without its ANSI escape sequences, so that
+/// colored defmt output can be filtered by what is shown. The printed lines keep their colors and
+/// timestamps.
+pub(crate) struct LineFilter<'a> {
+    include: &'a [Regex],
+    exclude: &'a [Regex],
+    /// The incomplete last line of each channel.
+    partial: HashMap<usize, String>,
+}
+
+impl<'a> LineFilter<'a> {
+    pub(crate) fn new(include: &'a [Regex], exclude: &'a [Regex]) -> Self {
+        Self {
+            include,
+            exclude,
+            partial: HashMap::new(),
+        }
+    }
+
+    /// Returns the complete lines of `data` which pass the filter.
+    ///
+    /// Data after the last newline of a channel is kept until the line is complete. Without any
+    /// patterns, `data` is returned unchanged.
+    pub(crate) fn filter(&mut self, channel: usize, data: String) -> String {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return data;
+        }
+
+        let partial = self.partial.entry(channel).or_default();
+        partial.push_str(&data);
+
+        let Some(end) = partial.rfind('\n') else {
+            return String::new();
+        };
+        let rest = partial.split_off(end + 1);
+        let complete = std::mem::replace(partial, rest);
+
+        complete
+            .split_inclusive('\n')
+            .filter(|line| self.is_match(line))
+            .collect()
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        let text = strip_ansi(line.trim_end_matches(['\r', '\n']));
+
+        (self.include.is_empty() || self.include.iter().any(|pattern| pattern.is_match(&text)))
+            && !self.exclude.iter().any(|pattern| pattern.is_match(&text))
+    }
+}
+
+fn strip_ansi(line: &str) -> String {
+    use ansi_parser::{AnsiParser, Output};
+
+    line.ansi_parse()
+        .filter_map(|output| match output {
+            Output::TextBlock(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_filtered() {
+        let include = [Regex::new("^WARN").unwrap(), Regex::new("^ERROR").unwrap()];
+        let exclude = [Regex::new("ignored").unwrap()];
+        let mut filter = LineFilter::new(&include, &exclude);
+
+        assert_eq!(filter.filter(0, "INFO started\nWA".to_string()), "");
+        assert_eq!(
+            filter.filter(1, "\x1b[31mERROR\x1b[0m x\n".to_string()),
+            "\x1b[31mERROR\x1b[0m x\n"
+        );
+        assert_eq!(
+            filter.filter(0, "RN low\r\nERROR ignored\nWARN".to_string()),
+            "WARN low\r\n"
+        );
+        assert_eq!(filter.filter(0, " high\n".to_string()), "WARN high\n");
+
+        let mut filter = LineFilter::new(&[], &[]);
+        assert_eq!(filter.filter(0, "partial".to_string()), "partial");
+    }
+}
@@ -0,0 +1,229 @@
+//! Forwarding the output of the target to a TCP server.
+//!
+//! The connection is opened lazily and re-established when it drops. While the server is not
+//! reachable, the output is kept in a bounded buffer, so a short outage does not lose any lines.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// The amount of output kept while the server is not reachable.
+const BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// The minimum time between two connection attempts.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A connection attempt blocks the run loop, so it must not take long.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(100);
+
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub(crate) struct LogSink {
+    address: String,
+    stream: Option<TcpStream>,
+    buffer: VecDeque<u8>,
+    last_attempt: Option<Instant>,
+    /// Whether the user was already told that the server is not reachable.
+    warned_unreachable: bool,
+    /// Whether the user was already told that output was discarded.
+    warned_overflow: bool,
+}
+
+impl LogSink {
+    pub(crate) fn new(address: String) -> Self {
+        Self {
+            address,
+            stream: None,
+            buffer: VecDeque::new(),
+            last_attempt: None,
+            warned_unreachable: false,
+            warned_overflow: false,
+        }
+    }
+
+    /// Queues `data` and sends as much of the queued output as possible.
+    pub(crate) fn send(&mut self, data: &[u8]) {
+        self.buffer.extend(data);
+
+        let overflow = self.buffer.len().saturating_sub(BUFFER_CAPACITY);
+        if overflow > 0 {
+            self.buffer.drain(..overflow);
+            if !self.warned_overflow {
+                tracing::warn!(
+                    "The log sink {} is not reachable, discarding the oldest output.",
+                    self.address
+                );
+                self.warned_overflow = true;
+            }
+        }
+
+        self.flush_buffer();
+    }
+
+    fn flush_buffer(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        if !self.connect() {
+            return;
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return;
+        };
+
+        let result = stream.write_all(self.buffer.make_contiguous());
+
+        match result {
+            Ok(()) => {
+                self.buffer.clear();
+                self.warned_overflow = false;
+            }
+            Err(error) => {
+                // Whatever was not acknowledged is sent again after reconnecting, so lines may be
+                // repeated but are not lost.
+                tracing::warn!(
+                    "The connection to the log sink {} dropped: {error}",
+                    self.address
+                );
+                self.stream = None;
+            }
+        }
+    }
+
+    /// Returns whether the connection to the server is open, or could be opened.
+    fn connect(&mut self) -> bool {
+        if self.stream.is_none() {
+            if self
+                .last_attempt
+                .is_some_and(|attempt| attempt.elapsed() < RECONNECT_INTERVAL)
+            {
+                return false;
+            }
+            self.last_attempt = Some(Instant::now());
+
+            match open(&self.address) {
+                Ok(stream) => {
+                    tracing::info!("Connected to the log sink {}", self.address);
+                    self.stream = Some(stream);
+                    self.warned_unreachable = false;
+                }
+                Err(error) => {
+                    if !self.warned_unreachable {
+                        tracing::warn!(
+                            "Failed to connect to the log sink {}: {error}. Buffering the output until it is reachable.",
+                            self.address
+                        );
+                        self.warned_unreachable = true;
+                    }
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl Drop for LogSink {
+    fn drop(&mut self) {
+        // Give the output of the last poll a final chance to be sent.
+        self.last_attempt = None;
+        self.flush_buffer();
+
+        if !self.buffer.is_empty() {
+            tracing::warn!(
+                "{} bytes of output could not be sent to the log sink {}.",
+                self.buffer.len(),
+                self.address
+            );
+        }
+    }
+}
+
+fn open(address: &str) -> io::Result<TcpStream> {
+    let mut last_error = None;
+
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "the address could not be resolved")
+    }))
+}
+
+/// Writes the output to the log sink, and to `output` unless only the sink is used.
+pub(crate) struct TeeWriter<'a> {
+    pub(crate) output: Option<&'a mut (dyn Write + Send)>,
+    pub(crate) sink: &'a mut LogSink,
+}
+
+impl Write for TeeWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(output) = self.output.as_mut() {
+            output.write_all(buf)?;
+        }
+        self.sink.send(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.output.as_mut() {
+            Some(output) => output.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn output_is_sent_to_the_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let mut sink = LogSink::new(address);
+        sink.send(b"first\n");
+        sink.send(b"second\n");
+        drop(sink);
+
+        let (mut connection, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        connection.read_to_string(&mut received).unwrap();
+
+        assert_eq!(received, "first\nsecond\n");
+    }
+
+    #[test]
+    fn output_is_buffered_while_unreachable() {
+        // Nothing listens on the port after the listener is dropped.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        let mut sink = LogSink::new(address);
+        sink.send(&[b'a'; BUFFER_CAPACITY]);
+        sink.send(b"newest");
+
+        assert_eq!(sink.buffer.len(), BUFFER_CAPACITY);
+        assert!(sink.buffer.iter().rev().take(6).eq(b"tsewen".iter()));
+        assert!(sink.warned_unreachable);
+        assert!(sink.warned_overflow);
+    }
+}
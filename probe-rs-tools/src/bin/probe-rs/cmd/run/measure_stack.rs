@@ -0,0 +1,167 @@
+//! Measuring the peak stack usage by painting the stack before the firmware starts.
+
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use goblin::elf::section_header::SHF_ALLOC;
+use probe_rs::{Core, MemoryInterface};
+use probe_rs_target::{MemoryRange, MemoryRegion};
+
+use crate::util::elf::{find_symbol, ElfFile};
+
+/// The value written to every byte of the stack before the firmware starts.
+const STACK_PAINT: u8 = 0xCC;
+
+/// The stack of the firmware, as described by the symbols and sections of its ELF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StackRegion {
+    /// The stack grows downwards from `range.end`.
+    range: Range<u64>,
+}
+
+impl StackRegion {
+    /// Locates the stack of the firmware.
+    ///
+    /// The top of the stack is the `_stack_start` symbol of `cortex-m-rt`. The bottom is the
+    /// `_stack_end` symbol if it exists, or else the end of the last RAM section below the top,
+    /// or the start of the RAM region if there is none (as with `flip-link`).
+    pub(crate) fn from_elf(path: &Path, memory_map: &[MemoryRegion]) -> Result<Self> {
+        let file = ElfFile::read(path)?;
+        let elf = file.parse()?;
+
+        let symbol = |name: &str| find_symbol(&elf, name).map(|sym| sym.st_value);
+
+        let top = symbol("_stack_start").ok_or_else(|| {
+            anyhow!("The ELF file has no `_stack_start` symbol, which marks the top of the stack.")
+        })?;
+
+        let ram = memory_map
+            .iter()
+            .filter_map(MemoryRegion::as_ram_region)
+            .find(|region| region.range.start < top && top <= region.range.end)
+            .ok_or_else(|| {
+                anyhow!("The top of the stack at {top:#010x} is not in a RAM region.")
+            })?;
+
+        let bottom = match symbol("_stack_end") {
+            Some(bottom) if bottom < top => bottom,
+            _ => {
+                let sections = elf
+                    .section_headers
+                    .iter()
+                    .filter(|section| section.sh_flags & u64::from(SHF_ALLOC) != 0)
+                    .map(|section| section.sh_addr..section.sh_addr + section.sh_size);
+
+                stack_bottom(top, &ram.range, sections)
+            }
+        };
+
+        // Only whole words are painted.
+        let range = bottom.next_multiple_of(4)..top & !3;
+        if range.is_empty() {
+            anyhow::bail!("The stack at {bottom:#010x}..{top:#010x} is empty.");
+        }
+
+        Ok(Self { range })
+    }
+
+    /// The size of the stack in bytes.
+    pub(crate) fn size(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    /// Fills the stack with a known pattern. Must be called before the firmware starts.
+    pub(crate) fn paint(&self, core: &mut Core) -> Result<()> {
+        tracing::info!(
+            "Painting the stack at {:#010x}..{:#010x}",
+            self.range.start,
+            self.range.end
+        );
+
+        let paint = vec![STACK_PAINT; self.size() as usize];
+        core.write(self.range.start, &paint)?;
+
+        Ok(())
+    }
+
+    /// Returns the number of stack bytes which were used since the stack was painted.
+    pub(crate) fn measure(&self, core: &mut Core) -> Result<u64> {
+        let mut data = vec![0; self.size() as usize];
+        core.read(self.range.start, &mut data)?;
+
+        Ok(used_bytes(&data))
+    }
+}
+
+/// The bottom of a stack which grows down from `top`: the end of the highest section
+/// which is located in `ram` below the stack.
+fn stack_bottom(top: u64, ram: &Range<u64>, sections: impl Iterator<Item = Range<u64>>) -> u64 {
+    sections
+        .filter(|section| !section.is_empty() && ram.contains_range(section))
+        .map(|section| section.end)
+        .filter(|&end| end <= top)
+        .max()
+        .unwrap_or(ram.start)
+}
+
+/// The number of bytes at the end of the painted `stack` which were overwritten.
+fn used_bytes(stack: &[u8]) -> u64 {
+    let untouched = stack
+        .iter()
+        .position(|&byte| byte != STACK_PAINT)
+        .unwrap_or(stack.len());
+
+    (stack.len() - untouched) as u64
+}
+
+/// Prints the stack usage of the firmware.
+pub(crate) fn print_stack_usage(used: u64, size: u64) {
+    let percentage = used as f64 * 100.0 / size as f64;
+
+    eprintln!("Stack usage: {used} of {size} bytes ({percentage:.1}%)");
+    if used == size {
+        eprintln!("The whole stack was used, the firmware has most likely overflowed its stack.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_bottom_is_end_of_highest_section_below_top() {
+        let ram = 0x2000_0000..0x2001_0000;
+        let sections = [
+            // Flash is ignored
+            0x0800_0000..0x0800_4000,
+            0x2000_0000..0x2000_0100,
+            0x2000_0100..0x2000_0400,
+        ];
+
+        assert_eq!(
+            stack_bottom(0x2001_0000, &ram, sections.into_iter()),
+            0x2000_0400
+        );
+    }
+
+    #[test]
+    fn stack_bottom_is_ram_start_with_flip_link() {
+        // With flip-link, the statics are placed above the stack.
+        let ram = 0x2000_0000..0x2001_0000;
+        let sections = std::iter::once(0x2000_f000..0x2001_0000);
+
+        assert_eq!(stack_bottom(0x2000_f000, &ram, sections), 0x2000_0000);
+    }
+
+    #[test]
+    fn used_bytes_counts_from_first_overwritten_byte() {
+        let mut stack = vec![STACK_PAINT; 16];
+        assert_eq!(used_bytes(&stack), 0);
+
+        stack[10] = 0;
+        // A byte which happens to match the paint is still counted as used.
+        stack[12] = STACK_PAINT;
+        assert_eq!(used_bytes(&stack), 6);
+    }
+}
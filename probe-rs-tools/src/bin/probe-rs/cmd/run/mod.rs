@@ -1,32 +1,60 @@
+mod active_image;
+mod backlog;
+mod boot_time;
+mod coverage;
+mod line_filter;
+mod log_sink;
+mod measure_stack;
 mod normal_run_mode;
+mod poll_interval;
+mod ram_values;
+mod stats;
 use normal_run_mode::*;
 mod test_run_mode;
 use test_run_mode::*;
 
+use std::cell::Cell;
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
-use probe_rs::debug::{DebugInfo, DebugRegisters};
-use probe_rs::flashing::FileDownloadError;
+use colored::Colorize;
+use probe_rs::debug::{DebugInfo, DebugRegisters, StackFrame};
+use probe_rs::flashing::{FileDownloadError, FlashLoader};
 use probe_rs::rtt::ScanRegion;
 use probe_rs::{
-    exception_handler_for_core, probe::list::Lister, Core, CoreInterface, Error, HaltReason,
-    Session, VectorCatchCondition,
+    exception_handler_for_core, probe::list::Lister, probe::DebugProbeError, Core, CoreInterface,
+    Error, HaltReason, MemoryInterface, Session, UnknownCommandDetails, VectorCatchCondition,
 };
-use probe_rs_target::MemoryRegion;
+use probe_rs_target::{MemoryRange, MemoryRegion};
+use regex::Regex;
 use signal_hook::consts::signal;
 use time::UtcOffset;
 
-use crate::util::common_options::{BinaryDownloadOptions, ProbeOptions};
-use crate::util::flash::{build_loader, run_flash_download};
+use self::active_image::{Images, TargetWasReset};
+use self::backlog::{Backlog, BacklogPolicy, BacklogWriter, DEFAULT_MAX_BACKLOG};
+use self::boot_time::BootTimer;
+use self::line_filter::LineFilter;
+use self::log_sink::{LogSink, TeeWriter};
+use self::poll_interval::{PollInterval, DEFAULT_MAX_POLL_INTERVAL, DEFAULT_MIN_POLL_INTERVAL};
+use self::ram_values::{check_ram_values, parse_ram_value, write_ram_values, RamValue};
+use self::stats::RttStats;
+use crate::util::common_options::{
+    BinaryDownloadOptions, LoadedProbeOptions, OperationError, ProbeOptions, ReadWriteBitWidth,
+};
+use crate::util::elf::{check_running_build_id, ElfIdentity};
+use crate::util::exit_code::TargetReportedFailure;
+use crate::util::flash::{build_loader, run_flash_download, EraseMode};
+use crate::util::halt::{self, DEFAULT_HALT_TIMEOUT};
 use crate::util::rtt::{
-    self, try_attach_to_rtt, ChannelDataCallbacks, DefmtState, RttActiveTarget, RttConfig,
+    self, try_attach_to_rtt, ChannelDataCallbacks, DataFormat, DefmtState, RttActiveTarget,
+    RttConfig,
 };
 use crate::FormatOptions;
 
@@ -66,10 +94,57 @@ pub struct SharedOptions {
     #[clap(long)]
     pub(crate) always_print_stacktrace: bool,
 
+    /// Keep the other cores of a multi-core target running when the run ends.
+    ///
+    /// By default, all cores are halted at the end, so that a readout afterwards sees a
+    /// consistent state. They are halted together through their CTI where available.
+    #[clap(long)]
+    pub(crate) leave_cores_running: bool,
+
     /// Whether to erase the entire chip before downloading
     #[clap(long, help_heading = "DOWNLOAD CONFIGURATION")]
     pub(crate) chip_erase: bool,
 
+    /// Load the image into RAM and start it from there, without programming the flash.
+    ///
+    /// All data of the image has to be located in RAM. The initial stack pointer and the
+    /// reset handler are read from the vector table at the lowest address of the image.
+    /// This is only supported for Cortex-M cores.
+    #[clap(
+        long,
+        conflicts_with = "chip_erase",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub(crate) ram: bool,
+
+    /// Write a value to the RAM of the target after flashing, before the firmware starts, given
+    /// as `<ADDRESS>=<VALUE>`. Can be specified multiple times.
+    ///
+    /// This lets the firmware read parameters from a fixed address, which has to be excluded
+    /// from the initialization of the RAM at startup, e.g. in a `.uninit` section.
+    #[clap(
+        long = "set",
+        value_name = "ADDRESS=VALUE",
+        value_parser = parse_ram_value,
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub(crate) set: Vec<RamValue>,
+
+    /// The width of the values written with `--set`.
+    #[clap(
+        long,
+        value_enum,
+        default_value = "b32",
+        requires = "set",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub(crate) width: ReadWriteBitWidth,
+
+    /// Do not check that the initial stack pointer and the reset vector of the image point into
+    /// the memory of the target before running it.
+    #[clap(long)]
+    pub(crate) no_vector_check: bool,
+
     /// Suppress filename and line number information from the rtt log
     #[clap(long)]
     pub(crate) no_location: bool,
@@ -84,6 +159,159 @@ pub struct SharedOptions {
     /// Scan the memory to find the RTT control block
     #[clap(long)]
     pub(crate) rtt_scan_memory: bool,
+
+    /// Only print the RTT up channel with the given number. By default, all up channels are printed.
+    #[clap(long, value_name = "CHANNEL")]
+    pub(crate) up_channel: Option<usize>,
+
+    /// Write the bytes of the RTT up channel to stdout, or the `--rtt-log-file`, verbatim, without
+    /// decoding them.
+    ///
+    /// Use this for channels which carry a binary protocol, e.g. to pipe them into a decoder.
+    /// Only the channel selected with `--up-channel` is printed, channel 0 by default.
+    /// Channels named `defmt` are always decoded.
+    #[clap(long)]
+    pub(crate) raw: bool,
+
+    /// Write the RTT output to this file instead of printing it.
+    ///
+    /// With `--raw`, the file contains the bytes of the channel verbatim. The file is
+    /// overwritten if it exists. Stack traces are still printed.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) rtt_log_file: Option<PathBuf>,
+
+    /// Print each line and each defmt message received over RTT as a JSON object, instead of
+    /// rendered text.
+    ///
+    /// defmt messages contain the `timestamp`, `level`, `module`, `file`, `line` and `message`
+    /// fields, lines of other channels are printed as `{"raw": "..."}`. The data of binary
+    /// channels is printed as `{"data": "..."}`, encoded with base64.
+    #[clap(long, conflicts_with_all = ["raw", "log_format"])]
+    pub(crate) json_logs: bool,
+
+    /// Only print the lines of the RTT output which match this regular expression.
+    ///
+    /// Can be given multiple times, a line is printed if it matches any of the expressions.
+    /// The expressions are matched against the text of the line without colors.
+    #[clap(long, value_name = "REGEX", conflicts_with = "raw")]
+    pub(crate) grep: Vec<Regex>,
+
+    /// Do not print the lines of the RTT output which match this regular expression.
+    ///
+    /// Can be given multiple times, and takes precedence over `--grep`.
+    #[clap(long, value_name = "REGEX", conflicts_with = "raw")]
+    pub(crate) grep_invert: Vec<Regex>,
+
+    /// Forward the output to a TCP server at this address, in addition to printing it.
+    ///
+    /// The connection is re-established if it drops. While the server is not reachable, the
+    /// output is buffered, and the oldest output is discarded once the buffer is full.
+    #[clap(long, value_name = "HOST:PORT")]
+    pub(crate) log_sink: Option<String>,
+
+    /// Only forward the output to the `--log-sink`, instead of also printing it.
+    #[clap(long, requires = "log_sink")]
+    pub(crate) log_sink_only: bool,
+
+    /// Answer semihosting operations which probe-rs does not support with an error code, and
+    /// keep running, instead of aborting.
+    ///
+    /// The target receives -1 as the result of the operation. By default, unsupported operations
+    /// abort the tests in test mode, and are left unanswered in normal mode.
+    #[clap(long)]
+    pub(crate) continue_on_semihosting_error: bool,
+
+    /// Forward the lines read from stdin to the RTT down channel with the given number.
+    #[clap(long, value_name = "CHANNEL")]
+    pub(crate) down_channel: Option<usize>,
+
+    /// What to do with input which does not fit into the buffer of the RTT down channel.
+    #[clap(long, value_enum, default_value = "block", requires = "down_channel")]
+    pub(crate) down_channel_mode: DownChannelMode,
+
+    /// The maximum number of bytes of RTT output which are kept while the output cannot keep up
+    /// with the target, e.g. because of a slow terminal.
+    #[clap(long, value_name = "BYTES", alias = "max-rtt-bytes", default_value_t = DEFAULT_MAX_BACKLOG)]
+    pub(crate) max_rtt_backlog: usize,
+
+    /// What to do once the `--max-rtt-backlog` is full.
+    #[clap(long, value_enum, default_value = "drop-oldest")]
+    pub(crate) rtt_backlog_policy: BacklogPolicy,
+
+    /// Poll RTT at this fixed interval in milliseconds, instead of adapting the interval to the
+    /// output.
+    #[clap(
+        long,
+        value_name = "MS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["rtt_poll_min_interval", "rtt_poll_max_interval"]
+    )]
+    pub(crate) rtt_poll_interval: Option<u64>,
+
+    /// The interval in milliseconds at which RTT is polled while data is received.
+    ///
+    /// While the channels are idle, the interval doubles up to `--rtt-poll-max-interval`, to save
+    /// CPU time.
+    #[clap(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..), default_value_t = DEFAULT_MIN_POLL_INTERVAL)]
+    pub(crate) rtt_poll_min_interval: u64,
+
+    /// The longest interval in milliseconds at which RTT is polled while the channels are idle.
+    ///
+    /// This is the latency of the first output after an idle period.
+    #[clap(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..), default_value_t = DEFAULT_MAX_POLL_INTERVAL)]
+    pub(crate) rtt_poll_max_interval: u64,
+
+    /// Set by `attach --clear-rtt-on-start`.
+    #[clap(skip)]
+    pub(crate) clear_rtt_on_start: bool,
+
+    /// Set by `attach --reconnect`.
+    #[clap(skip)]
+    pub(crate) reconnect: bool,
+
+    /// Set by `attach --max-reconnects`.
+    #[clap(skip)]
+    pub(crate) max_reconnects: Option<u32>,
+
+    /// Set by `attach --alternate-elf`.
+    #[clap(skip)]
+    pub(crate) alternate_elfs: Vec<PathBuf>,
+
+    /// Set by `attach --follow-reset-vector`.
+    #[clap(skip)]
+    pub(crate) follow_reset_vector: bool,
+
+    /// Set by `attach --stats`.
+    #[clap(skip)]
+    pub(crate) stats_interval: Option<Duration>,
+}
+
+/// The time to wait before attaching to the target again, after the connection was lost.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// The core stopped running the firmware.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum CoreStopped {
+    #[error("CPU halted unexpectedly.")]
+    Halted,
+    #[error("CPU halted unexpectedly at {0:#010x}.")]
+    HaltedAt(u64),
+    #[error("The core is locked up.")]
+    LockedUp,
+}
+
+/// The firmware was still running after the timeout of the run, in seconds.
+#[derive(Debug, thiserror::Error)]
+#[error("The target was still running after {0} seconds.")]
+pub(crate) struct RunTimedOut(pub(crate) u64);
+
+/// How input for a full RTT down channel is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum DownChannelMode {
+    /// Keep the input, and send it once the target made room in the buffer.
+    Block,
+    /// Discard the input which did not fit into the buffer.
+    Drop,
 }
 
 impl Cmd {
@@ -93,32 +321,132 @@ impl Cmd {
         run_download: bool,
         timestamp_offset: UtcOffset,
     ) -> Result<()> {
+        if self.run_options.measure_stack && !run_download {
+            anyhow::bail!(
+                "Measuring the stack requires starting the firmware, which `attach` does not do. Use `run` instead."
+            );
+        }
+
+        if self.run_options.halt_at.is_some() && !run_download {
+            anyhow::bail!(
+                "Halting at an address requires running the firmware from reset, which `attach` does not do. Use `run` instead."
+            );
+        }
+
+        if self.run_options.measure_boot_time && !run_download {
+            anyhow::bail!(
+                "Measuring the boot time requires running the firmware from reset, which `attach` does not do. Use `run` instead."
+            );
+        }
+
+        if self.run_options.measure_boot_time
+            && self.run_options.boot_marker.is_none()
+            && self.run_options.boot_symbol.is_none()
+        {
+            anyhow::bail!(
+                "Measuring the boot time requires a `--boot-marker` or a `--boot-symbol` to measure it until."
+            );
+        }
+
+        if !self.shared_options.set.is_empty() && !run_download {
+            anyhow::bail!(
+                "Writing values before the firmware starts requires starting it, which `attach` does not do. Use `run` instead."
+            );
+        }
+
+        let rtt_poll_interval = match self.shared_options.rtt_poll_interval {
+            Some(interval) => {
+                let interval = Duration::from_millis(interval);
+                PollInterval::new(interval, interval)
+            }
+            None if self.shared_options.rtt_poll_min_interval
+                > self.shared_options.rtt_poll_max_interval =>
+            {
+                anyhow::bail!(
+                    "The `--rtt-poll-min-interval` must not be longer than the `--rtt-poll-max-interval`."
+                );
+            }
+            None => PollInterval::new(
+                Duration::from_millis(self.shared_options.rtt_poll_min_interval),
+                Duration::from_millis(self.shared_options.rtt_poll_max_interval),
+            ),
+        };
+
         let run_mode = detect_run_mode(&self)?;
 
         let (mut session, probe_options) =
             self.shared_options.probe_options.simple_attach(lister)?;
         let core_id = rtt::get_target_core_id(&mut session, &self.shared_options.path);
 
-        if run_download {
+        if run_download && self.test_options.no_flash {
+            let mut core = session.core(core_id)?;
+            check_resident_image(&mut core, &self.shared_options.path)?;
+
+            // Start the tests from the reset, as after flashing.
+            halt::reset_and_halt(&mut core, probe_options.halt_timeout(DEFAULT_HALT_TIMEOUT))?;
+        } else if run_download {
+            probe_options.ensure_reset_allowed("flashing")?;
+
             let loader = build_loader(
                 &mut session,
                 &self.shared_options.path,
                 self.shared_options.format_options,
                 None,
             )?;
+
+            // Validate the image before touching the target.
+            if !self.shared_options.no_vector_check
+                && session.target().cores[core_id].core_type.is_cortex_m()
+            {
+                check_vector_table(
+                    &loader,
+                    &session.target().memory_map,
+                    self.shared_options.ram,
+                )?;
+            }
+
+            check_ram_values(
+                &self.shared_options.set,
+                self.shared_options.width,
+                &session.target().memory_map,
+            )?;
+
+            let halt_timeout = probe_options.halt_timeout(DEFAULT_HALT_TIMEOUT);
+            let vector_table = if self.shared_options.ram {
+                if !session.target().cores[core_id].core_type.is_cortex_m() {
+                    anyhow::bail!("Running from RAM is only supported for Cortex-M cores.");
+                }
+                let vector_table = ram_vector_table(&loader, &session.target().memory_map)?;
+
+                // Reset before loading the image, so the reset cannot interfere with it.
+                halt::reset_and_halt(&mut session.core(core_id)?, halt_timeout)?;
+
+                Some(vector_table)
+            } else {
+                None
+            };
+
             run_flash_download(
                 &mut session,
                 &self.shared_options.path,
                 &self.shared_options.download_options,
                 &probe_options,
                 loader,
-                self.shared_options.chip_erase,
+                EraseMode::from_chip_erase_flag(self.shared_options.chip_erase),
             )?;
 
-            // reset the core to leave it in a consistent state after flashing
-            session
-                .core(core_id)?
-                .reset_and_halt(Duration::from_millis(100))?;
+            if let Some(vector_table) = vector_table {
+                prepare_ram_start(&mut session.core(core_id)?, &vector_table)?;
+            } else {
+                // reset the core to leave it in a consistent state after flashing
+                halt::reset_and_halt(&mut session.core(core_id)?, halt_timeout)?;
+            }
+
+            write_ram_values(
+                &mut session.core(core_id)?,
+                &self.shared_options.set,
+                self.shared_options.width,
+            )?;
         }
 
         let memory_map = session.target().memory_map.clone();
@@ -126,25 +454,183 @@ impl Cmd {
             true => session.target().rtt_scan_regions.clone(),
             false => Vec::new(),
         };
+        let log_sink = self
+            .shared_options
+            .log_sink
+            .map(|address| Arc::new(Mutex::new(LogSink::new(address))));
+        // Only one thread reads stdin, so no input is lost between the runs.
+        let input = self.shared_options.down_channel.map(|channel| {
+            Arc::new(Mutex::new(InputForwarder::new(
+                channel,
+                self.shared_options.down_channel_mode,
+            )))
+        });
+        let rtt_log_file = match &self.shared_options.rtt_log_file {
+            Some(path) => Some(Arc::new(Mutex::new(File::create(path).with_context(
+                || format!("Failed to create the RTT log file {}", path.display()),
+            )?))),
+            None => None,
+        };
+        let mut clear_rtt_on_start = self.shared_options.clear_rtt_on_start;
+
+        // With alternate images, the symbols are taken from the image which runs on the target.
+        let images = if self.shared_options.alternate_elfs.is_empty() {
+            None
+        } else {
+            let mut paths = vec![self.shared_options.path.clone()];
+            paths.extend(self.shared_options.alternate_elfs.iter().cloned());
+            Some(Images::new(&mut session, core_id, &paths)?)
+        };
+        let select_image = |session: &mut Session| -> Result<PathBuf> {
+            match &images {
+                Some(images) => Ok(images.select(&mut session.core(core_id)?)?.to_path_buf()),
+                None => Ok(self.shared_options.path.clone()),
+            }
+        };
 
-        run_mode.run(
-            session,
-            RunLoop {
-                core_id,
-                memory_map,
-                rtt_scan_regions,
-                timestamp_offset,
-                path: self.shared_options.path,
-                always_print_stacktrace: self.shared_options.always_print_stacktrace,
-                no_location: self.shared_options.no_location,
-                log_format: self.shared_options.log_format,
+        let mut run_loop = |path: PathBuf| RunLoop {
+            core_id,
+            memory_map: memory_map.clone(),
+            rtt_scan_regions: rtt_scan_regions.clone(),
+            timestamp_offset,
+            halt_timeout: probe_options.halt_timeout(DEFAULT_HALT_TIMEOUT),
+            path,
+            always_print_stacktrace: self.shared_options.always_print_stacktrace,
+            halt_all_cores_on_exit: !self.shared_options.leave_cores_running,
+            no_reset: probe_options.no_reset(),
+            no_location: self.shared_options.no_location,
+            log_format: self.shared_options.log_format.clone(),
+            up_channel: match self.shared_options.raw {
+                true => Some(self.shared_options.up_channel.unwrap_or(0)),
+                false => self.shared_options.up_channel,
             },
-        )?;
+            raw: self.shared_options.raw,
+            json_logs: self.shared_options.json_logs,
+            grep: self.shared_options.grep.clone(),
+            grep_invert: self.shared_options.grep_invert.clone(),
+            rtt_log_file: rtt_log_file.clone(),
+            log_sink: log_sink.clone(),
+            log_sink_only: self.shared_options.log_sink_only,
+            continue_on_semihosting_error: self.shared_options.continue_on_semihosting_error,
+            input: input.clone(),
+            // The output buffered after a reconnection is new, and is not discarded.
+            clear_rtt_on_start: Cell::new(std::mem::take(&mut clear_rtt_on_start)),
+            max_rtt_backlog: self.shared_options.max_rtt_backlog,
+            rtt_backlog_policy: self.shared_options.rtt_backlog_policy,
+            rtt_poll_interval,
+            follow_reset_vector: self.shared_options.follow_reset_vector,
+            detect_resets: self.shared_options.reconnect,
+            stats_interval: self.shared_options.stats_interval,
+            boot_timer: self.run_options.measure_boot_time.then(BootTimer::default),
+        };
 
-        Ok(())
+        loop {
+            let path = select_image(&mut session)?;
+            let error = match run_mode.run(session, run_loop(path)) {
+                Ok(()) => return Ok(()),
+                // The image may have changed, the symbols are selected again after attaching.
+                Err(error) if error.is::<TargetWasReset>() => {
+                    eprintln!("{}", "--- target reset ---".yellow().bold());
+                    session = reattach(lister, &probe_options)?;
+                    resume_after_reset(&mut session.core(core_id)?)?;
+                    continue;
+                }
+                Err(error) if !self.shared_options.reconnect || !is_connection_lost(&error) => {
+                    return Err(error)
+                }
+                Err(error) => error,
+            };
+            tracing::warn!("Lost the connection to the target, reconnecting: {error:#}");
+
+            // The attempts are counted separately each time the connection is lost.
+            let mut attempts = 0;
+            session = loop {
+                if self
+                    .shared_options
+                    .max_reconnects
+                    .is_some_and(|max| attempts >= max)
+                {
+                    return Err(error.context(format!(
+                        "Failed to reconnect to the target after {attempts} attempts"
+                    )));
+                }
+                attempts += 1;
+
+                std::thread::sleep(RECONNECT_DELAY);
+                match reattach(lister, &probe_options) {
+                    Ok(session) => break session,
+                    Err(e) => tracing::warn!("Reconnection attempt {attempts} failed: {e:#}"),
+                }
+            };
+
+            eprintln!("{}", "--- reconnected ---".yellow().bold());
+        }
+    }
+}
+
+/// Returns whether the run failed because the connection to the probe or the target was lost.
+///
+/// The failures reported by the firmware, faults and timeouts are results of the run, even if
+/// they are caused by a probe error, and are not retried.
+fn is_connection_lost(error: &anyhow::Error) -> bool {
+    if error.is::<TargetReportedFailure>() || error.is::<CoreStopped>() || error.is::<RunTimedOut>()
+    {
+        return false;
+    }
+
+    error
+        .chain()
+        .any(|cause| cause.is::<Error>() || cause.is::<DebugProbeError>())
+}
+
+/// Halts all cores of a multi-core target when the run ended with `result`, unless
+/// `--leave-cores-running` or `--no-reset` is given.
+///
+/// The cores keep running if the target was reset, as the run continues after attaching again.
+/// Failing to halt them only fails the run if it was successful otherwise.
+fn halt_cores_on_exit(session: &mut Session, run_loop: &RunLoop, result: Result<()>) -> Result<()> {
+    if !run_loop.halt_all_cores_on_exit
+        || run_loop.no_reset
+        || result
+            .as_ref()
+            .is_err_and(|error| error.is::<TargetWasReset>())
+    {
+        return result;
+    }
+
+    match (halt::halt_all_cores(session, run_loop.halt_timeout), result) {
+        (Err(error), Ok(())) => Err(error.into()),
+        (Err(error), Err(run_error)) => {
+            tracing::warn!("Failed to halt all cores: {error}");
+            Err(run_error)
+        }
+        (Ok(()), result) => result,
     }
 }
 
+/// Resumes the core after the reset vector catch of `attach --follow-reset-vector`, so that a
+/// bootloader can start the image.
+fn resume_after_reset(core: &mut Core) -> Result<()> {
+    core.disable_vector_catch(VectorCatchCondition::CoreReset)
+        .or_else(|error| match error {
+            Error::NotImplemented(_) => Ok(()),
+            error => Err(error),
+        })?;
+    if core.core_halted()? {
+        core.run()?;
+    }
+
+    Ok(())
+}
+
+/// Attaches to the target again, without flashing or resetting it.
+fn reattach(lister: &Lister, probe_options: &LoadedProbeOptions) -> Result<Session> {
+    let target = probe_options.get_target_selector()?;
+    let probe = probe_options.attach_probe(lister)?;
+
+    Ok(probe_options.attach_session(probe, target)?)
+}
+
 trait RunMode {
     fn run(&self, session: Session, run_loop: RunLoop) -> Result<()>;
 }
@@ -173,14 +659,46 @@ fn detect_run_mode(cmd: &Cmd) -> Result<Box<dyn RunMode>, anyhow::Error> {
         }
     };
 
+    if elf_contains_test && cmd.shared_options.reconnect {
+        anyhow::bail!("Reconnecting is not supported for tests.");
+    }
+
     if elf_contains_test {
         // We tolerate the run options, even in test mode so that you can set `probe-rs run --catch-hardfault` as cargo runner (used for both unit tests and normal binaries)
         tracing::info!("Detected embedded-test in ELF file. Running as test");
-        Ok(TestRunMode::new(&cmd.test_options))
+        if cmd.shared_options.probe_options.no_reset {
+            return Err(OperationError::ResetNotAllowed {
+                action: "running tests",
+            }
+            .into());
+        }
+        let isolation = if cmd.test_options.reflash_between_tests {
+            Some(TestIsolation::Reflash(ReflashOptions {
+                format_options: cmd.shared_options.format_options.clone(),
+                restore_unwritten: cmd.shared_options.download_options.restores_unwritten(),
+                disable_double_buffering: cmd
+                    .shared_options
+                    .download_options
+                    .disable_double_buffering,
+                verify: cmd.shared_options.download_options.verify,
+                dry_run: cmd.shared_options.probe_options.dry_run,
+            }))
+        } else if cmd.test_options.reset_between_tests {
+            Some(TestIsolation::Reset)
+        } else {
+            None
+        };
+        Ok(TestRunMode::new(&cmd.test_options, isolation))
     } else {
         let test_args_specified = cmd.test_options.list
             || cmd.test_options.exact
             || cmd.test_options.format.is_some()
+            || cmd.test_options.shuffle
+            || cmd.test_options.shuffle_seed.is_some()
+            || cmd.test_options.reset_between_tests
+            || cmd.test_options.reflash_between_tests
+            || cmd.test_options.no_flash
+            || cmd.test_options.coverage_output.is_some()
             || !cmd.test_options.filter.is_empty();
         if test_args_specified {
             return Err(anyhow!("probe-rs was invoked with arguments exclusive to test mode, but the binary does not contain embedded-test"));
@@ -196,9 +714,47 @@ struct RunLoop {
     rtt_scan_regions: Vec<Range<u64>>,
     path: PathBuf,
     timestamp_offset: UtcOffset,
+    /// The time to wait for the core to halt.
+    halt_timeout: Duration,
     always_print_stacktrace: bool,
+    /// Halt all cores of a multi-core target when the run ends.
+    halt_all_cores_on_exit: bool,
+    /// Leave the cores as they were found with `--no-reset`, without halting or resuming them.
+    no_reset: bool,
     no_location: bool,
     log_format: Option<String>,
+    /// Only print this up channel, if set.
+    up_channel: Option<usize>,
+    raw: bool,
+    json_logs: bool,
+    /// Only print the lines of the output which match any of these patterns, if set.
+    grep: Vec<Regex>,
+    /// Do not print the lines of the output which match any of these patterns.
+    grep_invert: Vec<Regex>,
+    /// Write the RTT output to this file instead of the terminal, if set.
+    rtt_log_file: Option<Arc<Mutex<File>>>,
+    /// Forward the output to this sink, if set.
+    log_sink: Option<Arc<Mutex<LogSink>>>,
+    log_sink_only: bool,
+    /// Answer unsupported semihosting operations with an error, instead of aborting.
+    continue_on_semihosting_error: bool,
+    /// Forwards stdin to a down channel, if set. It is shared by all runs of the command.
+    input: Option<Arc<Mutex<InputForwarder>>>,
+    /// Discard the RTT output which is buffered when RTT is attached, once.
+    clear_rtt_on_start: Cell<bool>,
+    /// The RTT output which is kept while the output cannot keep up.
+    max_rtt_backlog: usize,
+    rtt_backlog_policy: BacklogPolicy,
+    /// How long to wait between two polls of RTT.
+    rtt_poll_interval: PollInterval,
+    /// Catch resets of the target, so that the image is selected again.
+    follow_reset_vector: bool,
+    /// End the run when the target is reset while it runs, so that it is attached again.
+    detect_resets: bool,
+    /// Report the rate of the output at this interval, if set.
+    stats_interval: Option<Duration>,
+    /// Measures the time until the boot marker is reached, if set.
+    boot_timer: Option<BootTimer>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -209,6 +765,104 @@ enum ReturnReason<R> {
     Predicate(R),
     /// Timeout elapsed
     Timeout,
+    /// A line of the output matched [`OutputPatterns::exit_on_match`]
+    OutputMatched,
+}
+
+/// Patterns which end the run loop when a line of the RTT output matches them.
+#[derive(Debug, Clone, Default)]
+struct OutputPatterns {
+    /// Return successfully when a line matches.
+    exit_on_match: Option<Regex>,
+    /// Return with an error when a line matches.
+    fail_on_match: Option<Regex>,
+    /// Report the first line which matches, without ending the run loop.
+    boot_marker: Option<Regex>,
+}
+
+impl OutputPatterns {
+    fn is_empty(&self) -> bool {
+        self.exit_on_match.is_none() && self.fail_on_match.is_none() && self.boot_marker.is_none()
+    }
+}
+
+/// The result of matching a line against the [`OutputPatterns`].
+#[derive(Debug, PartialEq, Eq)]
+enum OutputMatch {
+    Exit(String),
+    Fail(String),
+}
+
+/// Splits the RTT output into lines and matches them against the [`OutputPatterns`].
+struct LineMatcher<'a> {
+    patterns: &'a OutputPatterns,
+    line: String,
+    result: Option<OutputMatch>,
+    /// The first line which matched [`OutputPatterns::boot_marker`], and whether it was taken.
+    boot_marker: Option<String>,
+    boot_marker_taken: bool,
+}
+
+impl<'a> LineMatcher<'a> {
+    fn new(patterns: &'a OutputPatterns) -> Self {
+        Self {
+            patterns,
+            line: String::new(),
+            result: None,
+            boot_marker: None,
+            boot_marker_taken: false,
+        }
+    }
+
+    fn feed(&mut self, data: &str) {
+        if self.patterns.is_empty() || self.result.is_some() {
+            return;
+        }
+
+        self.line.push_str(data);
+
+        while let Some(end) = self.line.find('\n') {
+            let line = self.line[..end].trim_end_matches('\r').to_string();
+            self.line.drain(..=end);
+
+            if let Some(marker) = &self.patterns.boot_marker {
+                if self.boot_marker.is_none() && !self.boot_marker_taken && marker.is_match(&line) {
+                    self.boot_marker = Some(line.clone());
+                }
+            }
+
+            if let Some(result) = self.match_line(line) {
+                self.result = Some(result);
+                return;
+            }
+        }
+    }
+
+    fn match_line(&self, line: String) -> Option<OutputMatch> {
+        // The failure pattern takes precedence, so that a line matching both fails the run.
+        if let Some(pattern) = &self.patterns.fail_on_match {
+            if pattern.is_match(&line) {
+                return Some(OutputMatch::Fail(line));
+            }
+        }
+        if let Some(pattern) = &self.patterns.exit_on_match {
+            if pattern.is_match(&line) {
+                return Some(OutputMatch::Exit(line));
+            }
+        }
+        None
+    }
+
+    fn take_result(&mut self) -> Option<OutputMatch> {
+        self.result.take()
+    }
+
+    /// Returns the line which matched the boot marker, once.
+    fn take_boot_marker(&mut self) -> Option<String> {
+        let line = self.boot_marker.take()?;
+        self.boot_marker_taken = true;
+        Some(line)
+    }
 }
 
 /// The output stream to print RTT and Stack Traces to
@@ -226,6 +880,11 @@ impl RunLoop {
     /// * If the predicate returns `Err(e)` the run loop will return `Err(e)`.
     ///
     /// The function will also return on timeout with `Ok(ReturnReason::Timeout)` or if the user presses CTRL + C with `Ok(ReturnReason::User)`.
+    ///
+    /// If a line of the RTT output matches the exit pattern of `output_patterns`, the run loop returns
+    /// `Ok(ReturnReason::OutputMatched)`. If it matches the failure pattern, the run loop returns an error.
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(name = "run", skip_all)]
     fn run_until<F, R>(
         &self,
         core: &mut Core,
@@ -233,14 +892,23 @@ impl RunLoop {
         catch_reset: bool,
         output_stream: OutputStream,
         timeout: Option<Duration>,
+        output_patterns: &OutputPatterns,
         mut predicate: F,
     ) -> Result<ReturnReason<R>>
     where
         F: FnMut(HaltReason, &mut Core) -> Result<Option<R>>,
     {
-        if catch_hardfault || catch_reset {
+        if self.no_reset {
+            // The core is neither halted to set up the vector catch, nor resumed.
+            if catch_hardfault || catch_reset {
+                tracing::warn!("Vector catch is not enabled with --no-reset.");
+            }
+            if core.core_halted()? {
+                tracing::info!("The core is halted, and is left halted because of --no-reset.");
+            }
+        } else if catch_hardfault || catch_reset {
             if !core.core_halted()? {
-                core.halt(Duration::from_millis(100))?;
+                halt::halt(core, self.halt_timeout)?;
             }
 
             if catch_hardfault {
@@ -257,18 +925,27 @@ impl RunLoop {
             }
         }
 
-        if core.core_halted()? {
+        if !self.no_reset && core.core_halted()? {
             core.run()?;
         }
         let start = Instant::now();
+        if let Some(boot_timer) = &self.boot_timer {
+            boot_timer.start(start);
+        }
 
         let mut rtt_config = rtt::RttConfig::default();
         rtt_config.channels.push(rtt::RttChannelConfig {
-            channel_number: Some(0),
+            channel_number: Some(self.up_channel.unwrap_or(0)),
+            data_format: if self.raw {
+                DataFormat::BinaryLE
+            } else {
+                DataFormat::String
+            },
             show_location: !self.no_location,
             log_format: self.log_format.clone(),
             ..Default::default()
         });
+        rtt_config.json = self.json_logs;
 
         let mut rtta = attach_to_rtt(
             core,
@@ -281,11 +958,21 @@ impl RunLoop {
         )
         .context("Failed to attach to RTT")?;
 
+        // Only the output which was buffered before attaching is discarded, not the output
+        // between tests.
+        if let Some(rtta) = rtta.as_mut().filter(|_| self.clear_rtt_on_start.take()) {
+            let discarded = rtta
+                .clear_up_channels(core)
+                .context("Failed to clear the RTT buffers")?;
+            tracing::info!("Discarded {discarded} bytes of RTT output buffered before attaching.");
+        }
+
         let result = self.do_run_until(
             core,
             &mut rtta,
             output_stream,
             timeout,
+            output_patterns,
             start,
             &mut predicate,
         );
@@ -305,12 +992,14 @@ impl RunLoop {
         result
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn do_run_until<F, R>(
         &self,
         core: &mut Core,
         rtta: &mut Option<rtt::RttActiveTarget>,
         output_stream: OutputStream,
         timeout: Option<Duration>,
+        output_patterns: &OutputPatterns,
         start: Instant,
         predicate: &mut F,
     ) -> Result<ReturnReason<R>>
@@ -322,7 +1011,7 @@ impl RunLoop {
 
         let mut stdout;
         let mut stderr;
-        let output_stream: &mut dyn Write = match output_stream {
+        let terminal: &mut (dyn Write + Send) = match output_stream {
             OutputStream::Stdout => {
                 stdout = std::io::stdout();
                 &mut stdout
@@ -333,7 +1022,104 @@ impl RunLoop {
             }
         };
 
-        let return_reason = loop {
+        // The terminal is only kept for the stack trace if the RTT output goes to the log file.
+        let mut terminal = Some(terminal);
+        let mut rtt_log_file = self.rtt_log_file.as_ref().map(|file| file.lock().unwrap());
+        let output_stream: &mut (dyn Write + Send) = match rtt_log_file.as_deref_mut() {
+            Some(file) => file,
+            None => terminal.take().unwrap(),
+        };
+
+        let mut log_sink = self.log_sink.as_ref().map(|sink| sink.lock().unwrap());
+        let mut tee;
+        let output_stream: &mut (dyn Write + Send) = match log_sink.as_deref_mut() {
+            Some(sink) => {
+                tee = TeeWriter {
+                    output: (!self.log_sink_only).then_some(output_stream),
+                    sink,
+                };
+                &mut tee
+            }
+            None => output_stream,
+        };
+
+        let backlog = Backlog::new(self.max_rtt_backlog, self.rtt_backlog_policy);
+        let (return_reason, output_result, output_stream) = std::thread::scope(|scope| {
+            let output_thread = scope.spawn(|| {
+                let result = backlog.drain_into(output_stream, &mut std::io::stderr());
+                (result, output_stream)
+            });
+
+            let return_reason = self.poll_until(
+                core,
+                rtta,
+                &backlog,
+                timeout,
+                output_patterns,
+                start,
+                predicate,
+                &exit,
+            );
+
+            backlog.close();
+            let (output_result, output_stream) =
+                output_thread.join().expect("the output thread panicked");
+            (return_reason, output_result, output_stream)
+        });
+        output_result.context("Failed to write the output")?;
+        let return_reason = return_reason?;
+
+        if self.always_print_stacktrace
+            || return_reason.is_err()
+            || matches!(return_reason, Ok(ReturnReason::Timeout))
+        {
+            if !core.core_halted()? {
+                core.halt(Duration::from_secs(1))?;
+            }
+            let output_stream = terminal.unwrap_or(output_stream);
+            print_stacktrace(core, Path::new(&self.path), output_stream)?;
+        }
+
+        signal_hook::low_level::unregister(sig_id);
+        signal_hook::flag::register_conditional_default(signal::SIGINT, exit)?;
+
+        return_reason
+    }
+
+    /// Polls the core and RTT until the predicate, the output or the user ends the run.
+    ///
+    /// The outer error aborts the run, the inner result is the reason for ending it.
+    #[allow(clippy::too_many_arguments)]
+    fn poll_until<F, R>(
+        &self,
+        core: &mut Core,
+        rtta: &mut Option<rtt::RttActiveTarget>,
+        backlog: &Backlog,
+        timeout: Option<Duration>,
+        output_patterns: &OutputPatterns,
+        start: Instant,
+        predicate: &mut F,
+        exit: &AtomicBool,
+    ) -> Result<Result<ReturnReason<R>>>
+    where
+        F: FnMut(HaltReason, &mut Core) -> Result<Option<R>>,
+    {
+        let mut line_matcher = LineMatcher::new(output_patterns);
+        let mut line_filter = LineFilter::new(&self.grep, &self.grep_invert);
+        let mut input = self.input.as_ref().map(|input| input.lock().unwrap());
+        let mut poll_interval = self.rtt_poll_interval;
+        let mut stats = self
+            .stats_interval
+            .map(|interval| RttStats::new(interval, Instant::now()));
+        // The resets before the run are cleared, only the cores which report them are checked.
+        let detect_resets = self.detect_resets
+            && match core.was_reset() {
+                Ok(_) => true,
+                Err(Error::NotImplemented(_)) => false,
+                Err(error) => return Err(error.into()),
+            };
+
+        loop {
             // check for halt first, poll rtt after.
             // this is important so we do one last poll after halt, so we flush all messages
             // the core printed before halting, such as a panic message.
@@ -347,15 +1133,56 @@ impl RunLoop {
                 probe_rs::CoreStatus::Running
                 | probe_rs::CoreStatus::Sleeping
                 | probe_rs::CoreStatus::Unknown => {
-                    // Carry on
+                    if detect_resets && core.was_reset()? {
+                        return_reason = Some(Err(TargetWasReset.into()));
+                    }
                 }
 
                 probe_rs::CoreStatus::LockedUp => {
-                    return Err(anyhow!("The core is locked up."));
+                    return Err(CoreStopped::LockedUp.into());
                 }
             }
 
-            let had_rtt_data = poll_rtt(rtta, core, output_stream)?;
+            let activity = poll_rtt(
+                rtta,
+                core,
+                &mut BacklogWriter(backlog),
+                self.up_channel,
+                &mut line_matcher,
+                &mut line_filter,
+            )?;
+
+            if let Some(stats) = stats.as_mut() {
+                stats.record(activity.bytes, activity.lines);
+                if let Some(report) = stats.report(Instant::now(), backlog.total_dropped()) {
+                    backlog.set_status(report);
+                }
+            }
+
+            if let (Some(input), Some(rtta)) = (input.as_deref_mut(), rtta.as_mut()) {
+                input.forward(rtta, core)?;
+            }
+
+            if let (Some(line), Some(boot_timer)) =
+                (line_matcher.take_boot_marker(), &self.boot_timer)
+            {
+                boot_timer.reached(&format!("the line `{line}`"));
+            }
+
+            if return_reason.is_none() {
+                match line_matcher.take_result() {
+                    Some(OutputMatch::Exit(_)) => {
+                        return_reason = Some(Ok(ReturnReason::OutputMatched))
+                    }
+                    Some(OutputMatch::Fail(line)) => {
+                        return_reason = Some(Err(TargetReportedFailure(format!(
+                            "The output matched the failure pattern: {line}"
+                        ))
+                        .into()))
+                    }
+                    None => {}
+                }
+            }
 
             if return_reason.is_none() {
                 if exit.load(Ordering::Relaxed) {
@@ -370,35 +1197,282 @@ impl RunLoop {
             }
 
             if let Some(reason) = return_reason {
-                break reason;
+                return Ok(reason);
             }
 
-            // Poll RTT with a frequency of 10 Hz if we do not receive any new data.
-            // Once we receive new data, we bump the frequency to 1kHz.
-            //
             // If the polling frequency is too high, the USB connection to the probe
             // can become unstable. Hence we only pull as little as necessary.
-            if had_rtt_data {
-                std::thread::sleep(Duration::from_millis(1));
-            } else {
-                std::thread::sleep(Duration::from_millis(100));
-            }
-        };
+            std::thread::sleep(poll_interval.next(activity.bytes > 0));
+        }
+    }
+}
 
-        if self.always_print_stacktrace
-            || return_reason.is_err()
-            || matches!(return_reason, Ok(ReturnReason::Timeout))
-        {
-            if !core.core_halted()? {
-                core.halt(Duration::from_secs(1))?;
+/// The start of the vector table of a Cortex-M image.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct VectorTable {
+    pub(crate) address: u64,
+    pub(crate) stack_pointer: u32,
+    pub(crate) reset_handler: u32,
+}
+
+/// Checks that all data of the loader is located in RAM, and reads the vector table at the
+/// lowest address of the image.
+fn ram_vector_table(loader: &FlashLoader, memory_map: &[MemoryRegion]) -> Result<VectorTable> {
+    for (address, data) in loader.data() {
+        let range = address..address + data.len() as u64;
+        let in_ram = memory_map
+            .iter()
+            .filter_map(MemoryRegion::as_ram_region)
+            .any(|region| region.range.contains_range(&range));
+
+        if !in_ram {
+            anyhow::bail!(
+                "The image data at {:#010x}..{:#010x} is not located in RAM.",
+                range.start,
+                range.end
+            );
+        }
+    }
+
+    // Data chunks are returned in ascending address order.
+    let Some((address, _)) = loader.data().next() else {
+        anyhow::bail!("The image does not contain any data.");
+    };
+
+    vector_table_at(loader, address).ok_or_else(|| {
+        anyhow!("The image at {address:#010x} is too small to contain a vector table.")
+    })
+}
+
+/// Reads the initial stack pointer and the reset handler from the vector table at `address`.
+fn vector_table_at(loader: &FlashLoader, address: u64) -> Option<VectorTable> {
+    let (start, data) = loader
+        .data()
+        .find(|(start, data)| *start <= address && address + 8 <= start + data.len() as u64)?;
+
+    let offset = (address - start) as usize;
+    let word = |index: usize| {
+        let bytes = &data[offset + index * 4..offset + index * 4 + 4];
+        u32::from_le_bytes(bytes.try_into().unwrap())
+    };
+
+    Some(VectorTable {
+        address,
+        stack_pointer: word(0),
+        reset_handler: word(1),
+    })
+}
+
+/// Checks that the target runs the image at `path`, by the build ID of the image.
+fn check_resident_image(core: &mut Core, path: &Path) -> Result<()> {
+    let Some(build_id) = ElfIdentity::read(path)?.build_id else {
+        anyhow::bail!(
+            "The ELF file {} has no build ID to verify the image on the target with. Link it with `--build-id`, or flash it.",
+            path.display()
+        );
+    };
+    let address = build_id.address.with_context(|| {
+        format!(
+            "The build ID note of {} is not part of the image.",
+            path.display()
+        )
+    })?;
+
+    if let Err(running) = check_running_build_id(core, &build_id, address)? {
+        anyhow::bail!(
+            "The target runs another image than {}, its build ID is {running}. Flash the image, or run without `--no-flash`.",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks that the vector table of the image points into the memory of the target, so that
+/// misbuilt images fail before they are run instead of with a hard fault.
+fn check_vector_table(loader: &FlashLoader, memory_map: &[MemoryRegion], ram: bool) -> Result<()> {
+    match image_vector_table(loader)? {
+        Some(vector_table) => validate_vector_table(&vector_table, memory_map, ram),
+        None => Ok(()),
+    }
+}
+
+/// Reads the vector table of the image, or `None` if the image does not contain any data.
+pub(crate) fn image_vector_table(loader: &FlashLoader) -> Result<Option<VectorTable>> {
+    // `cortex-m-rt` places the vector table in its own section, other images start with it.
+    let address = loader
+        .data_origins()
+        .iter()
+        .find(|origin| {
+            origin
+                .name()
+                .is_some_and(|name| name.split(", ").any(|section| section == ".vector_table"))
+        })
+        .map(|origin| origin.address())
+        .or_else(|| loader.data().next().map(|(address, _)| address));
+
+    let Some(address) = address else {
+        return Ok(None);
+    };
+    let Some(vector_table) = vector_table_at(loader, address) else {
+        anyhow::bail!("The image at {address:#010x} is too small to contain a vector table.");
+    };
+
+    Ok(Some(vector_table))
+}
+
+/// Checks the initial stack pointer and the reset vector, and warns if the vector table is not
+/// located where the core looks for it after a reset.
+fn validate_vector_table(
+    vector_table: &VectorTable,
+    memory_map: &[MemoryRegion],
+    ram: bool,
+) -> Result<()> {
+    let hint = "Check the memory layout in the linker script, or use `--no-vector-check` to skip this check.";
+
+    let stack_pointer = u64::from(vector_table.stack_pointer);
+    let stack_in_ram = memory_map
+        .iter()
+        .filter_map(MemoryRegion::as_ram_region)
+        .any(|region| region.range.start < stack_pointer && stack_pointer <= region.range.end);
+    if !stack_in_ram {
+        anyhow::bail!(
+            "The initial stack pointer {stack_pointer:#010x} in the vector table at {:#010x} does not point into RAM. {hint}",
+            vector_table.address
+        );
+    }
+
+    if vector_table.reset_handler & 1 == 0 {
+        anyhow::bail!(
+            "The reset vector {:#010x} in the vector table at {:#010x} does not have the Thumb bit set. {hint}",
+            vector_table.reset_handler,
+            vector_table.address
+        );
+    }
+
+    let reset_handler = u64::from(vector_table.reset_handler & !1);
+    let reset_in_memory = memory_map.iter().any(|region| match region {
+        MemoryRegion::Ram(region) => region.range.contains(&reset_handler),
+        MemoryRegion::Nvm(region) => !ram && region.range.contains(&reset_handler),
+        MemoryRegion::Generic(_) => false,
+    });
+    if !reset_in_memory {
+        anyhow::bail!(
+            "The reset vector {reset_handler:#010x} in the vector table at {:#010x} does not point into {}. {hint}",
+            vector_table.address,
+            if ram { "RAM" } else { "RAM or flash" }
+        );
+    }
+
+    // An image run from RAM is started from its vector table directly.
+    let boot_memory = memory_map
+        .iter()
+        .filter_map(MemoryRegion::as_nvm_region)
+        .filter(|region| region.is_boot_memory)
+        .map(|region| region.range.start)
+        .collect::<Vec<_>>();
+    if !ram && !boot_memory.is_empty() && !boot_memory.contains(&vector_table.address) {
+        tracing::warn!(
+            "The vector table at {:#010x} is not located at the start of the boot memory at {:#010x}. The core does not use it after a reset, unless a bootloader or the firmware sets VTOR.",
+            vector_table.address,
+            boot_memory[0]
+        );
+    }
+
+    Ok(())
+}
+
+/// Points the halted core to the vector table of an image loaded into RAM, so that it starts
+/// the image once it is resumed.
+fn prepare_ram_start(core: &mut Core, vector_table: &VectorTable) -> Result<()> {
+    /// The Vector Table Offset Register of Cortex-M cores.
+    const VTOR: u64 = 0xE000_ED08;
+
+    tracing::info!(
+        "Starting from RAM: vector table at {:#010x}, SP = {:#010x}, PC = {:#010x}",
+        vector_table.address,
+        vector_table.stack_pointer,
+        vector_table.reset_handler
+    );
+
+    // Not every Cortex-M core implements a writable VTOR, the image can still run without it.
+    if let Err(error) = core.write_word_32(VTOR, vector_table.address as u32) {
+        tracing::warn!("Failed to set the vector table offset: {error}");
+    }
+    core.write_core_reg(core.stack_pointer(), vector_table.stack_pointer)?;
+    // Clear the Thumb bit, the PC register always holds a halfword aligned address.
+    core.write_core_reg(core.program_counter(), vector_table.reset_handler & !1)?;
+
+    Ok(())
+}
+
+/// Reads lines from stdin in the background, and writes them to an RTT down channel.
+struct InputForwarder {
+    channel: usize,
+    mode: DownChannelMode,
+    receiver: Receiver<Vec<u8>>,
+    /// Input which was not written to the channel yet.
+    pending: Vec<u8>,
+}
+
+impl InputForwarder {
+    fn new(channel: usize, mode: DownChannelMode) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        // The thread is blocked in `read_line` most of the time, and is not joined. It ends
+        // with the process, or once stdin is closed.
+        std::thread::spawn(move || {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match std::io::stdin().read_line(&mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if sender.send(line.as_bytes().to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
             }
-            print_stacktrace(core, Path::new(&self.path), output_stream)?;
+        });
+
+        Self {
+            channel,
+            mode,
+            receiver,
+            pending: Vec::new(),
         }
+    }
 
-        signal_hook::low_level::unregister(sig_id);
-        signal_hook::flag::register_conditional_default(signal::SIGINT, exit)?;
+    /// Writes the input received so far to the down channel.
+    fn forward(&mut self, rtta: &mut RttActiveTarget, core: &mut Core) -> Result<()> {
+        while let Ok(data) = self.receiver.try_recv() {
+            self.pending.extend(data);
+        }
 
-        return_reason
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let Some(channel) = rtta.active_down_channels.get_mut(&self.channel) else {
+            return Err(anyhow!(
+                "The target has no RTT down channel with number {}.",
+                self.channel
+            ));
+        };
+
+        let written = channel.down_channel.write(core, &self.pending)?;
+        self.pending.drain(..written);
+
+        if self.mode == DownChannelMode::Drop && !self.pending.is_empty() {
+            tracing::warn!(
+                "The RTT down channel is full, dropping {} bytes of input.",
+                self.pending.len()
+            );
+            self.pending.clear();
+        }
+
+        Ok(())
     }
 }
 
@@ -424,89 +1498,148 @@ fn print_stacktrace<S: Write + ?Sized>(
         )
         .unwrap();
     for (i, frame) in stack_frames.iter().enumerate() {
-        write!(
-            output_stream,
-            "Frame {}: {} @ {}",
-            i, frame.function_name, frame.pc
-        )?;
+        print_stack_frame(i, frame, output_stream)?;
+    }
+    Ok(())
+}
 
-        if frame.is_inlined {
-            write!(output_stream, " inline")?;
-        }
-        writeln!(output_stream)?;
+/// Prints the function and the source location of a frame of a stack trace.
+pub(crate) fn print_stack_frame<S: Write + ?Sized>(
+    index: usize,
+    frame: &StackFrame,
+    output_stream: &mut S,
+) -> Result<(), anyhow::Error> {
+    write!(
+        output_stream,
+        "Frame {}: {} @ {}",
+        index, frame.function_name, frame.pc
+    )?;
+
+    if frame.is_inlined {
+        write!(output_stream, " inline")?;
+    }
+    writeln!(output_stream)?;
 
-        if let Some(location) = &frame.source_location {
-            if location.directory.is_some() || location.file.is_some() {
-                write!(output_stream, "       ")?;
+    if let Some(location) = &frame.source_location {
+        if location.directory.is_some() || location.file.is_some() {
+            write!(output_stream, "       ")?;
 
-                if let Some(dir) = &location.directory {
-                    write!(output_stream, "{}", dir.to_path().display())?;
-                }
+            if let Some(dir) = &location.directory {
+                write!(output_stream, "{}", dir.to_path().display())?;
+            }
 
-                if let Some(file) = &location.file {
-                    write!(output_stream, "/{file}")?;
+            if let Some(file) = &location.file {
+                write!(output_stream, "/{file}")?;
 
-                    if let Some(line) = location.line {
-                        write!(output_stream, ":{line}")?;
+                if let Some(line) = location.line {
+                    write!(output_stream, ":{line}")?;
 
-                        if let Some(col) = location.column {
-                            match col {
-                                probe_rs::debug::ColumnType::LeftEdge => {
-                                    write!(output_stream, ":1")?
-                                }
-                                probe_rs::debug::ColumnType::Column(c) => {
-                                    write!(output_stream, ":{c}")?
-                                }
+                    if let Some(col) = location.column {
+                        match col {
+                            probe_rs::debug::ColumnType::LeftEdge => write!(output_stream, ":1")?,
+                            probe_rs::debug::ColumnType::Column(c) => {
+                                write!(output_stream, ":{c}")?
                             }
                         }
                     }
                 }
-
-                writeln!(output_stream)?;
             }
+
+            writeln!(output_stream)?;
         }
     }
+
+    Ok(())
+}
+
+/// Answers a semihosting operation which probe-rs does not support with -1, so the target
+/// can continue.
+fn reject_semihosting_operation(core: &mut Core, details: UnknownCommandDetails) -> Result<()> {
+    tracing::warn!(
+        "The target requested the unsupported semihosting operation {:#x} with parameter {:#x}, returning -1.",
+        details.operation,
+        details.parameter
+    );
+    details.write_status(core, u32::MAX)?;
+
     Ok(())
 }
 
+/// The output received by one poll of RTT.
+#[derive(Debug, Default, Clone, Copy)]
+struct RttActivity {
+    bytes: usize,
+    lines: usize,
+}
+
 /// Poll RTT and print the received buffer.
 fn poll_rtt<S: Write + ?Sized>(
     rtta: &mut Option<rtt::RttActiveTarget>,
     core: &mut Core<'_>,
     out_stream: &mut S,
-) -> Result<bool, anyhow::Error> {
-    let mut had_data = false;
+    up_channel: Option<usize>,
+    line_matcher: &mut LineMatcher,
+    line_filter: &mut LineFilter,
+) -> Result<RttActivity, anyhow::Error> {
+    let mut activity = RttActivity::default();
     if let Some(rtta) = rtta {
-        struct OutCollector<'a, O: Write + ?Sized> {
+        struct OutCollector<'a, 'p, 'f, O: Write + ?Sized> {
             out_stream: &'a mut O,
-            had_data: bool,
+            up_channel: Option<usize>,
+            line_matcher: &'a mut LineMatcher<'p>,
+            line_filter: &'a mut LineFilter<'f>,
+            activity: RttActivity,
+        }
+
+        impl<O: Write + ?Sized> OutCollector<'_, '_, '_, O> {
+            fn is_selected(&self, channel: usize) -> bool {
+                self.up_channel.map_or(true, |selected| selected == channel)
+            }
         }
 
-        impl<O: Write + ?Sized> ChannelDataCallbacks for OutCollector<'_, O> {
+        impl<O: Write + ?Sized> ChannelDataCallbacks for OutCollector<'_, '_, '_, O> {
             fn on_string_data(
                 &mut self,
-                _channel: usize,
+                channel: usize,
                 data: String,
             ) -> Result<(), anyhow::Error> {
-                if data.is_empty() {
+                if data.is_empty() || !self.is_selected(channel) {
                     return Ok(());
                 }
-                self.had_data = true;
+                self.activity.bytes += data.len();
+                self.activity.lines += data.matches('\n').count();
+                // The exit and failure patterns also see the lines which are not printed.
+                self.line_matcher.feed(&data);
+                let data = self.line_filter.filter(channel, data);
                 self.out_stream.write_all(data.as_bytes())?;
                 Ok(())
             }
+
+            /// Binary data is only received in raw mode, and written without any processing.
+            fn on_binary_data(&mut self, channel: usize, data: &[u8]) -> Result<(), anyhow::Error> {
+                if data.is_empty() || !self.is_selected(channel) {
+                    return Ok(());
+                }
+                self.activity.bytes += data.len();
+                self.out_stream.write_all(data)?;
+                self.out_stream.flush()?;
+                Ok(())
+            }
         }
 
         let mut out = OutCollector {
             out_stream,
-            had_data: false,
+            up_channel,
+            line_matcher,
+            line_filter,
+            activity,
         };
 
         rtta.poll_rtt_fallible(core, &mut out)?;
-        had_data = out.had_data;
+        activity = out.activity;
     }
 
-    Ok(had_data)
+    Ok(activity)
 }
 
 fn attach_to_rtt(
@@ -537,3 +1670,148 @@ fn attach_to_rtt(
     let defmt_state = DefmtState::try_from_bytes(&elf)?;
     RttActiveTarget::new(core, rtt, defmt_state, rtt_config, timestamp_offset).map(Some)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_matcher_matches_complete_lines() {
+        let patterns = OutputPatterns {
+            exit_on_match: Some(Regex::new("^ready$").unwrap()),
+            fail_on_match: Some(Regex::new("panicked").unwrap()),
+            ..Default::default()
+        };
+        let mut matcher = LineMatcher::new(&patterns);
+
+        matcher.feed("booting\r\nrea");
+        assert_eq!(matcher.take_result(), None);
+
+        matcher.feed("dy\npanicked at main.rs\n");
+        assert_eq!(
+            matcher.take_result(),
+            Some(OutputMatch::Exit("ready".to_string()))
+        );
+    }
+
+    #[test]
+    fn line_matcher_prefers_failure() {
+        let patterns = OutputPatterns {
+            exit_on_match: Some(Regex::new("done").unwrap()),
+            fail_on_match: Some(Regex::new("error").unwrap()),
+            ..Default::default()
+        };
+        let mut matcher = LineMatcher::new(&patterns);
+
+        matcher.feed("done with error\n");
+        assert_eq!(
+            matcher.take_result(),
+            Some(OutputMatch::Fail("done with error".to_string()))
+        );
+    }
+
+    #[test]
+    fn line_matcher_reports_the_first_boot_marker() {
+        let patterns = OutputPatterns {
+            exit_on_match: Some(Regex::new("^done$").unwrap()),
+            boot_marker: Some(Regex::new("^init").unwrap()),
+            ..Default::default()
+        };
+        let mut matcher = LineMatcher::new(&patterns);
+
+        matcher.feed("booting\ninit clocks\n");
+        assert_eq!(matcher.take_boot_marker(), Some("init clocks".to_string()));
+        assert_eq!(matcher.take_result(), None);
+
+        matcher.feed("init radio\ndone\n");
+        assert_eq!(matcher.take_boot_marker(), None);
+        assert_eq!(
+            matcher.take_result(),
+            Some(OutputMatch::Exit("done".to_string()))
+        );
+    }
+
+    fn loader_for(target: &probe_rs::Target, address: u64, data: &[u8]) -> FlashLoader {
+        let mut loader = FlashLoader::new(target.memory_map.clone(), target.source().clone());
+        loader.add_data(address, data).unwrap();
+        loader
+    }
+
+    #[test]
+    fn ram_vector_table_is_read_from_image() {
+        let target = probe_rs::config::get_target_by_name("STM32F103C8").unwrap();
+        let image = [0x00, 0x50, 0x00, 0x20, 0x41, 0x01, 0x00, 0x20, 0xFF, 0xFF];
+        let loader = loader_for(&target, 0x2000_0000, &image);
+
+        assert_eq!(
+            ram_vector_table(&loader, &target.memory_map).unwrap(),
+            VectorTable {
+                address: 0x2000_0000,
+                stack_pointer: 0x2000_5000,
+                reset_handler: 0x2000_0141,
+            }
+        );
+    }
+
+    #[test]
+    fn vector_table_must_point_into_memory() {
+        let target = probe_rs::config::get_target_by_name("STM32F103C8").unwrap();
+        let vector_table = |stack_pointer, reset_handler| VectorTable {
+            address: 0x0800_0000,
+            stack_pointer,
+            reset_handler,
+        };
+
+        assert!(validate_vector_table(
+            &vector_table(0x2000_5000, 0x0800_0101),
+            &target.memory_map,
+            false
+        )
+        .is_ok());
+        // The stack pointer points into flash.
+        assert!(validate_vector_table(
+            &vector_table(0x0800_5000, 0x0800_0101),
+            &target.memory_map,
+            false
+        )
+        .is_err());
+        // The Thumb bit is not set.
+        assert!(validate_vector_table(
+            &vector_table(0x2000_5000, 0x0800_0100),
+            &target.memory_map,
+            false
+        )
+        .is_err());
+        // The reset handler is in flash, while the image runs from RAM.
+        assert!(validate_vector_table(
+            &vector_table(0x2000_5000, 0x0800_0101),
+            &target.memory_map,
+            true
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn ram_vector_table_rejects_flash_data() {
+        let target = probe_rs::config::get_target_by_name("STM32F103C8").unwrap();
+        let loader = loader_for(&target, 0x0800_0000, &[0; 8]);
+
+        assert!(ram_vector_table(&loader, &target.memory_map).is_err());
+    }
+
+    #[test]
+    fn only_probe_errors_are_lost_connections() {
+        let probe_error = anyhow::Error::from(Error::Probe(DebugProbeError::Timeout));
+        assert!(is_connection_lost(
+            &probe_error.context("Failed to poll RTT")
+        ));
+        assert!(is_connection_lost(&DebugProbeError::Timeout.into()));
+
+        assert!(!is_connection_lost(&CoreStopped::LockedUp.into()));
+        assert!(!is_connection_lost(&RunTimedOut(10).into()));
+        assert!(!is_connection_lost(
+            &TargetReportedFailure("The output matched the failure pattern".to_string()).into()
+        ));
+        assert!(!is_connection_lost(&anyhow!("Failed to open the log file")));
+    }
+}
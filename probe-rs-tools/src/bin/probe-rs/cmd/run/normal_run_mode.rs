@@ -1,6 +1,18 @@
-use crate::cmd::run::{OutputStream, RunLoop, RunMode};
-use anyhow::anyhow;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cmd::run::active_image::{halted_at_reset, TargetWasReset};
+use crate::cmd::run::measure_stack::{print_stack_usage, StackRegion};
+use crate::cmd::run::{
+    halt_cores_on_exit, print_stacktrace, reject_semihosting_operation, CoreStopped,
+    OutputPatterns, OutputStream, ReturnReason, RunLoop, RunMode, RunTimedOut,
+};
+use crate::util::exit_code::TargetReportedFailure;
+use crate::util::resolve_address;
 use probe_rs::{BreakpointCause, Core, HaltReason, SemihostingCommand, Session};
+use probe_rs_target::MemoryRegion;
+use regex::Regex;
 
 /// Options only used in normal run mode
 #[derive(Debug, clap::Parser, Clone)]
@@ -11,6 +23,62 @@ pub struct NormalRunOptions {
     /// Enable hardfault vector catch if its supported on the target.
     #[clap(long, help_heading = "RUN OPTIONS")]
     pub catch_hardfault: bool,
+    /// Write a core dump to the given file when the target faults, and exit with an error.
+    ///
+    /// The dump contains all core registers and the RAM regions of the core, in the core dump
+    /// format of probe-rs (MessagePack), which can be loaded with the ELF file to print the
+    /// backtrace. This enables the hardfault vector catch.
+    #[clap(long, value_name = "PATH", help_heading = "RUN OPTIONS")]
+    pub dump_on_fault: Option<PathBuf>,
+    /// Exit successfully once a line of the RTT output matches the given regular expression.
+    #[clap(long, value_name = "REGEX", help_heading = "RUN OPTIONS")]
+    pub exit_on_match: Option<Regex>,
+    /// Exit with an error once a line of the RTT output matches the given regular expression.
+    #[clap(long, value_name = "REGEX", help_heading = "RUN OPTIONS")]
+    pub fail_on_match: Option<Regex>,
+    /// Exit with an error if the target is still running after the given number of seconds.
+    #[clap(long, value_name = "SECONDS", help_heading = "RUN OPTIONS")]
+    pub timeout: Option<u64>,
+    /// Paint the stack before starting the firmware, and report the peak stack usage on exit.
+    ///
+    /// The stack is located with the `_stack_start` symbol of the ELF file.
+    #[clap(long, help_heading = "RUN OPTIONS")]
+    pub measure_stack: bool,
+    /// Halt the core once the program reaches this address or symbol of the ELF file, print the
+    /// location, and exit with the core halted.
+    ///
+    /// Uses a hardware breakpoint.
+    #[clap(long, value_name = "SYMBOL|ADDRESS", help_heading = "RUN OPTIONS")]
+    pub halt_at: Option<String>,
+    /// Keep running after reaching `--halt-at`, instead of exiting.
+    #[clap(long, requires = "halt_at", help_heading = "RUN OPTIONS")]
+    pub continue_after: bool,
+    /// Measure the time from starting the firmware after the reset until it reaches the
+    /// `--boot-marker` or the `--boot-symbol`, and print it.
+    ///
+    /// The time is measured on the host, so it includes the latency of the probe and is only
+    /// a rough estimate. It is useful to compare the boot time of different builds.
+    #[clap(long, help_heading = "RUN OPTIONS")]
+    pub measure_boot_time: bool,
+    /// The boot is complete once a line of the RTT output matches this regular expression.
+    #[clap(
+        long,
+        value_name = "REGEX",
+        requires = "measure_boot_time",
+        help_heading = "RUN OPTIONS"
+    )]
+    pub boot_marker: Option<Regex>,
+    /// The boot is complete once the program reaches this address or symbol of the ELF file.
+    ///
+    /// Uses a hardware breakpoint, the program keeps running after reaching it.
+    #[clap(
+        long,
+        value_name = "SYMBOL|ADDRESS",
+        requires = "measure_boot_time",
+        conflicts_with = "halt_at",
+        help_heading = "RUN OPTIONS"
+    )]
+    pub boot_symbol: Option<String>,
 }
 
 /// Normal run mode (non-test)
@@ -25,16 +93,87 @@ impl NormalRunMode {
 }
 impl RunMode for NormalRunMode {
     fn run(&self, mut session: Session, run_loop: RunLoop) -> anyhow::Result<()> {
+        let result = self.run_core(&mut session, &run_loop);
+        halt_cores_on_exit(&mut session, &run_loop, result)
+    }
+}
+
+impl NormalRunMode {
+    fn run_core(&self, session: &mut Session, run_loop: &RunLoop) -> anyhow::Result<()> {
+        let dump_ranges = ram_ranges(
+            &run_loop.memory_map,
+            &session.target().cores[run_loop.core_id].name,
+        );
         let mut core = session.core(run_loop.core_id)?;
 
-        let halt_handler = |halt_reason: HaltReason, _core: &mut Core| match halt_reason {
+        let halt_at = self
+            .run_options
+            .halt_at
+            .as_deref()
+            .map(|halt_at| RunToBreakpoint::set(&mut core, halt_at, &run_loop.path))
+            .transpose()?;
+        let mut reached_halt_at = false;
+
+        let boot_symbol = self
+            .run_options
+            .boot_symbol
+            .as_deref()
+            .map(|symbol| RunToBreakpoint::set(&mut core, symbol, &run_loop.path))
+            .transpose()?;
+
+        let mut halt_handler = |halt_reason: HaltReason, core: &mut Core| match halt_reason {
+            HaltReason::Breakpoint(BreakpointCause::Hardware | BreakpointCause::Unknown)
+                if halt_at.is_some() || boot_symbol.is_some() =>
+            {
+                let pc: u64 = core.read_core_reg(core.program_counter())?;
+
+                if let Some(boot_symbol) = RunToBreakpoint::reached(&boot_symbol, core, pc)? {
+                    if let Some(boot_timer) = &run_loop.boot_timer {
+                        boot_timer.reached(&boot_symbol.to_string());
+                    }
+
+                    Ok(None) // Continue running
+                } else if let Some(halt_at) = RunToBreakpoint::reached(&halt_at, core, pc)? {
+                    reached_halt_at = true;
+                    println!("Reached {halt_at}");
+                    print_stacktrace(core, &run_loop.path, &mut std::io::stdout())?;
+
+                    if self.run_options.continue_after {
+                        Ok(None) // Continue running
+                    } else {
+                        Ok(Some(())) // Exit the run loop, and leave the core halted
+                    }
+                } else {
+                    Err(CoreStopped::HaltedAt(pc).into())
+                }
+            }
+            HaltReason::Exception if run_loop.follow_reset_vector && halted_at_reset(core)? => {
+                Err(TargetWasReset.into())
+            }
+            HaltReason::Exception => {
+                if let Some(path) = self.run_options.dump_on_fault.as_deref() {
+                    match write_core_dump(core, dump_ranges.clone(), path) {
+                        Ok(()) => println!("Wrote the core dump to {}", path.display()),
+                        Err(e) => tracing::error!("Failed to write the core dump: {e:#}"),
+                    }
+                }
+                Err(CoreStopped::Halted.into())
+            }
             HaltReason::Breakpoint(BreakpointCause::Semihosting(cmd)) => {
                 match cmd {
                     SemihostingCommand::ExitSuccess => {
                         Ok(Some(())) // Exit the run loop
                     }
-                    SemihostingCommand::ExitError(details) => {
-                        Err(anyhow!("Semihosting indicates exit with {}", details))
+                    SemihostingCommand::ExitError(details) => Err(TargetReportedFailure(format!(
+                        "Semihosting indicates exit with {}",
+                        details
+                    ))
+                    .into()),
+                    SemihostingCommand::Unknown(details)
+                        if run_loop.continue_on_semihosting_error =>
+                    {
+                        reject_semihosting_operation(core, details)?;
+                        Ok(None) // Continue running
                     }
                     SemihostingCommand::Unknown(details) => {
                         tracing::warn!("Target wanted to run semihosting operation {:#x} with parameter {:#x},\
@@ -47,16 +186,114 @@ impl RunMode for NormalRunMode {
                     }
                 }
             }
-            _ => Err(anyhow!("CPU halted unexpectedly.")),
+            _ => Err(CoreStopped::Halted.into()),
+        };
+        let timeout = self.run_options.timeout.map(Duration::from_secs);
+        let output_patterns = OutputPatterns {
+            exit_on_match: self.run_options.exit_on_match.clone(),
+            fail_on_match: self.run_options.fail_on_match.clone(),
+            boot_marker: self.run_options.boot_marker.clone(),
+        };
+
+        let stack = if self.run_options.measure_stack {
+            let stack = StackRegion::from_elf(&run_loop.path, &run_loop.memory_map)?;
+            stack.paint(&mut core)?;
+            Some(stack)
+        } else {
+            None
         };
-        run_loop.run_until(
+
+        let result = run_loop.run_until(
             &mut core,
-            self.run_options.catch_hardfault,
-            self.run_options.catch_reset,
+            self.run_options.catch_hardfault || self.run_options.dump_on_fault.is_some(),
+            self.run_options.catch_reset || run_loop.follow_reset_vector,
             OutputStream::Stdout,
-            None,
-            halt_handler,
-        )?;
+            timeout,
+            &output_patterns,
+            &mut halt_handler,
+        );
+
+        // The stack usage is also reported if the firmware crashed, which may be due to an overflow.
+        if let Some(stack) = stack {
+            match stack.measure(&mut core) {
+                Ok(used) => print_stack_usage(used, stack.size()),
+                Err(e) => tracing::warn!("Failed to measure the stack usage: {e}"),
+            }
+        }
+
+        let return_reason = result?;
+
+        if halt_at.is_some() && !reached_halt_at && return_reason != ReturnReason::User {
+            tracing::warn!("The program did not reach the `--halt-at` address.");
+        }
+
+        let boot_timer = run_loop.boot_timer.as_ref();
+        if boot_timer.is_some_and(|timer| !timer.was_reached())
+            && return_reason != ReturnReason::User
+        {
+            tracing::warn!("The program did not reach the boot marker.");
+        }
+
+        if return_reason == ReturnReason::Timeout {
+            return Err(RunTimedOut(self.run_options.timeout.unwrap_or_default()).into());
+        }
+
         Ok(())
     }
 }
+
+/// A hardware breakpoint at an address or a symbol of the ELF file, which the core runs to.
+struct RunToBreakpoint<'a> {
+    /// The address or symbol, as it was given.
+    name: &'a str,
+    address: u64,
+}
+
+impl<'a> RunToBreakpoint<'a> {
+    /// Resolves `name` in the ELF file, and sets a hardware breakpoint at its address.
+    fn set(core: &mut Core, name: &'a str, elf: &Path) -> anyhow::Result<Self> {
+        let address = resolve_address(name, Some(elf))?;
+        core.set_hw_breakpoint(address)?;
+
+        Ok(Self { name, address })
+    }
+
+    /// Checks whether the core halted at the breakpoint, with the program counter at `pc`, and
+    /// clears the breakpoint if it did.
+    fn reached<'b>(
+        breakpoint: &'b Option<Self>,
+        core: &mut Core,
+        pc: u64,
+    ) -> anyhow::Result<Option<&'b Self>> {
+        match breakpoint {
+            Some(breakpoint) if breakpoint.address == pc => {
+                core.clear_hw_breakpoint(breakpoint.address)?;
+                Ok(Some(breakpoint))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl std::fmt::Display for RunToBreakpoint<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {:#010x}", self.name, self.address)
+    }
+}
+
+/// The RAM regions of the memory map which the core with the name `core` can access.
+fn ram_ranges(memory_map: &[MemoryRegion], core: &str) -> Vec<Range<u64>> {
+    memory_map
+        .iter()
+        .filter_map(MemoryRegion::as_ram_region)
+        .filter(|region| region.cores.is_empty() || region.cores.iter().any(|name| name == core))
+        .map(|region| region.range.clone())
+        .collect()
+}
+
+/// Writes the registers of the halted core and the memory in `ranges` to a core dump file.
+fn write_core_dump(core: &mut Core, ranges: Vec<Range<u64>>, path: &Path) -> anyhow::Result<()> {
+    core.dump(ranges)?.store(path)?;
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Choosing how long to wait between two polls of RTT.
+//!
+//! Polling often keeps the latency of the output low, but keeps the host busy, and a too high
+//! polling frequency can make the USB connection to the probe unstable. So RTT is polled at the
+//! minimum interval while data is received, and the interval doubles while the channels are idle,
+//! up to the maximum.
+
+use std::time::Duration;
+
+/// The default for `--rtt-poll-min-interval`, in milliseconds.
+pub(crate) const DEFAULT_MIN_POLL_INTERVAL: u64 = 1;
+
+/// The default for `--rtt-poll-max-interval`, in milliseconds.
+pub(crate) const DEFAULT_MAX_POLL_INTERVAL: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PollInterval {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl PollInterval {
+    /// An interval which adapts between `min` and `max`.
+    pub(crate) fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max,
+            current: min,
+        }
+    }
+
+    /// Returns how long to wait before the next poll, depending on whether the last poll read
+    /// any data.
+    pub(crate) fn next(&mut self, had_data: bool) -> Duration {
+        self.current = if had_data {
+            self.min
+        } else {
+            (self.current * 2).clamp(self.min, self.max)
+        };
+
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_backs_off_while_idle() {
+        let mut interval = PollInterval::new(Duration::from_millis(1), Duration::from_millis(10));
+
+        let idle: Vec<_> = (0..5).map(|_| interval.next(false).as_millis()).collect();
+        assert_eq!(idle, [2, 4, 8, 10, 10]);
+
+        // Data resets the interval to the minimum.
+        assert_eq!(interval.next(true), Duration::from_millis(1));
+        assert_eq!(interval.next(false), Duration::from_millis(2));
+
+        // A fixed interval does not change.
+        let fixed = Duration::from_millis(20);
+        let mut interval = PollInterval::new(fixed, fixed);
+        assert_eq!(interval.next(false), fixed);
+        assert_eq!(interval.next(true), fixed);
+    }
+}
@@ -0,0 +1,126 @@
+//! Writing values given on the command line into the RAM of the target before it starts.
+
+use anyhow::Context;
+use probe_rs::{Core, MemoryInterface};
+use probe_rs_target::MemoryRegion;
+
+use crate::util::common_options::ReadWriteBitWidth;
+use crate::util::parse_u64;
+
+/// A value to write to the RAM of the target, given as `<address>=<value>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RamValue {
+    pub address: u64,
+    pub value: u64,
+}
+
+/// Parses a `<address>=<value>` pair for `--set`.
+pub(crate) fn parse_ram_value(input: &str) -> Result<RamValue, String> {
+    let (address, value) = input
+        .split_once('=')
+        .ok_or_else(|| format!("expected <ADDRESS>=<VALUE>, got '{input}'"))?;
+    let parse = |number: &str| {
+        parse_u64(number.trim()).map_err(|error| format!("invalid number '{number}': {error}"))
+    };
+
+    Ok(RamValue {
+        address: parse(address)?,
+        value: parse(value)?,
+    })
+}
+
+/// Checks that all `values` fit into `width`, and are located in the RAM of the target.
+pub(crate) fn check_ram_values(
+    values: &[RamValue],
+    width: ReadWriteBitWidth,
+    memory_map: &[MemoryRegion],
+) -> anyhow::Result<()> {
+    let bits = width as u32;
+    let size = bits as u64 / 8;
+
+    for value in values {
+        if bits < 64 && value.value >> bits != 0 {
+            anyhow::bail!(
+                "The value {:#x} for the address {:#010x} does not fit into {bits} bits.",
+                value.value,
+                value.address
+            );
+        }
+        if value.address % size != 0 {
+            anyhow::bail!(
+                "The address {:#010x} is not aligned to {size} bytes.",
+                value.address
+            );
+        }
+
+        let in_ram = memory_map
+            .iter()
+            .filter_map(MemoryRegion::as_ram_region)
+            .any(|ram| ram.range.start <= value.address && value.address + size <= ram.range.end);
+        if !in_ram {
+            anyhow::bail!(
+                "The address {:#010x} is not located in the RAM of the target.",
+                value.address
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the `values` with the given `width` to the memory of the halted `core`.
+pub(crate) fn write_ram_values(
+    core: &mut Core,
+    values: &[RamValue],
+    width: ReadWriteBitWidth,
+) -> anyhow::Result<()> {
+    for value in values {
+        tracing::info!("Writing {:#x} to {:#010x}", value.value, value.address);
+
+        match width {
+            ReadWriteBitWidth::B8 => core.write_word_8(value.address, value.value as u8),
+            ReadWriteBitWidth::B32 => core.write_word_32(value.address, value.value as u32),
+            ReadWriteBitWidth::B64 => core.write_word_64(value.address, value.value),
+        }
+        .with_context(|| format!("Failed to write to {:#010x}", value.address))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use probe_rs_target::RamRegion;
+
+    use super::*;
+
+    #[test]
+    fn values_are_parsed_and_checked() {
+        let value = parse_ram_value("0x2000_0000=42").unwrap();
+        assert_eq!(
+            value,
+            RamValue {
+                address: 0x2000_0000,
+                value: 42
+            }
+        );
+        assert!(parse_ram_value("0x2000_0000").is_err());
+        assert!(parse_ram_value("0x2000_0000=x").is_err());
+
+        let memory_map = [MemoryRegion::Ram(RamRegion {
+            name: None,
+            range: 0x2000_0000..0x2000_1000,
+            is_boot_memory: false,
+            cores: vec![],
+        })];
+        let check = |address, value, width| {
+            check_ram_values(&[RamValue { address, value }], width, &memory_map)
+        };
+
+        assert!(check(0x2000_0ffc, 0xFFFF_FFFF, ReadWriteBitWidth::B32).is_ok());
+        assert!(check(0x2000_0000, 0x1_0000_0000, ReadWriteBitWidth::B32).is_err());
+        assert!(check(0x2000_0002, 1, ReadWriteBitWidth::B32).is_err());
+        assert!(check(0x2000_1000, 1, ReadWriteBitWidth::B8).is_err());
+        assert!(check(0x1000, 1, ReadWriteBitWidth::B8).is_err());
+    }
+}
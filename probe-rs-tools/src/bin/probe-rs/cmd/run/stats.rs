@@ -0,0 +1,84 @@
+//! Reporting the rate of the RTT output periodically, for `attach --stats`.
+
+use std::time::{Duration, Instant};
+
+/// Counts the RTT output, and reports its rate once per interval.
+#[derive(Debug)]
+pub(crate) struct RttStats {
+    interval: Duration,
+    /// The start of the current interval.
+    since: Instant,
+    bytes: usize,
+    lines: usize,
+    /// The number of dropped bytes at the start of the current interval.
+    dropped: usize,
+}
+
+impl RttStats {
+    pub(crate) fn new(interval: Duration, now: Instant) -> Self {
+        Self {
+            interval,
+            since: now,
+            bytes: 0,
+            lines: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Counts the output received from the target.
+    pub(crate) fn record(&mut self, bytes: usize, lines: usize) {
+        self.bytes += bytes;
+        self.lines += lines;
+    }
+
+    /// Returns the report of the current interval once it elapsed, and starts the next one.
+    ///
+    /// `total_dropped` is the number of bytes dropped by the output so far.
+    pub(crate) fn report(&mut self, now: Instant, total_dropped: usize) -> Option<String> {
+        let elapsed = now.duration_since(self.since);
+        if elapsed < self.interval {
+            return None;
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        let report = format!(
+            "RTT: {:.0} bytes/s, {:.1} lines/s, {} bytes dropped",
+            self.bytes as f64 / seconds,
+            self.lines as f64 / seconds,
+            total_dropped - self.dropped
+        );
+
+        *self = Self {
+            dropped: total_dropped,
+            ..Self::new(self.interval, now)
+        };
+
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_is_reported_per_interval() {
+        let start = Instant::now();
+        let mut stats = RttStats::new(Duration::from_secs(2), start);
+
+        stats.record(1000, 10);
+        assert_eq!(stats.report(start + Duration::from_secs(1), 0), None);
+
+        stats.record(1000, 5);
+        assert_eq!(
+            stats.report(start + Duration::from_secs(2), 100).as_deref(),
+            Some("RTT: 1000 bytes/s, 7.5 lines/s, 100 bytes dropped")
+        );
+
+        // The next interval starts from zero.
+        assert_eq!(
+            stats.report(start + Duration::from_secs(4), 100).as_deref(),
+            Some("RTT: 0 bytes/s, 0.0 lines/s, 0 bytes dropped")
+        );
+    }
+}
@@ -1,10 +1,20 @@
-use crate::cmd::run::{print_stacktrace, OutputStream, ReturnReason, RunLoop, RunMode};
+use crate::cmd::run::coverage::CoverageCollector;
+use crate::cmd::run::{
+    halt_cores_on_exit, print_stacktrace, reject_semihosting_operation, OutputPatterns,
+    OutputStream, ReturnReason, RunLoop, RunMode,
+};
+use crate::util::exit_code::TargetReportedFailure;
+use crate::util::flash::build_loader;
+use crate::util::halt;
+use crate::FormatOptions;
 use anyhow::{anyhow, Result};
 use libtest_mimic::{Arguments, Failed, FormatSetting, Trial};
+use probe_rs::flashing::DownloadOptions;
 use probe_rs::{BreakpointCause, Core, HaltReason, SemihostingCommand, Session};
 use serde::Deserialize;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Options only used when in test run mode
 #[derive(Debug, clap::Parser)]
@@ -49,6 +59,51 @@ pub struct TestOptions {
     )]
     pub skip_test: Vec<String>,
 
+    /// Run the tests in a random order.
+    ///
+    /// The seed used for shuffling is printed, so that the order can be reproduced with `--shuffle-seed`.
+    #[clap(long, help_heading = "TEST OPTIONS")]
+    pub shuffle: bool,
+
+    /// Run the tests in a random order, determined by the given seed. Implies `--shuffle`.
+    #[clap(long, value_name = "SEED", help_heading = "TEST OPTIONS")]
+    pub shuffle_seed: Option<u64>,
+
+    /// Reset all cores of the target before each test.
+    ///
+    /// By default, only the core running the tests is reset before each test.
+    #[clap(long, help_heading = "TEST OPTIONS")]
+    pub reset_between_tests: bool,
+
+    /// Flash the binary again before each test. Implies `--reset-between-tests`.
+    #[clap(long, help_heading = "TEST OPTIONS")]
+    pub reflash_between_tests: bool,
+
+    /// Run the tests against the image which is already on the target, instead of flashing it.
+    ///
+    /// The ELF file is only used for its symbols. The build ID of the image on the target has to
+    /// match the build ID of the ELF file, so link it with `--build-id`.
+    #[clap(
+        long,
+        help_heading = "TEST OPTIONS",
+        conflicts_with_all = ["reflash_between_tests", "ram"]
+    )]
+    pub no_flash: bool,
+
+    /// Collect the coverage data of each test into this directory, as `<test name>.profraw`.
+    ///
+    /// The firmware has to write the coverage data of a test to the `__probe_rs_coverage_data`
+    /// buffer before the test ends, and set the `u32` `__probe_rs_coverage_len` to the number of
+    /// bytes written, e.g. with the `minicov` crate. The files can be merged with
+    /// `llvm-profdata merge`.
+    #[clap(long, value_name = "DIRECTORY", help_heading = "TEST OPTIONS")]
+    pub coverage_output: Option<PathBuf>,
+
+    /// Stop a test after this many seconds, and count it as timed out, unless the test sets its
+    /// own timeout with `#[timeout]`. The remaining tests are still run.
+    #[clap(long, value_name = "SECONDS", help_heading = "TEST OPTIONS")]
+    pub timeout_per_test: Option<u64>,
+
     /// Options which are ignored, but exist for compatibility with libtest.
     /// E.g. so that vscode and intellij can invoke the test runner with the args they are used to
     #[clap(flatten)]
@@ -71,13 +126,41 @@ struct NoOpTestOptions {
     unstable_flags: Option<String>,
 }
 
+/// How the target is prepared before each test, in addition to resetting the core running the tests.
+#[derive(Debug, Clone)]
+pub enum TestIsolation {
+    /// Reset all cores of the target.
+    Reset,
+    /// Flash the binary again, and reset all cores of the target.
+    Reflash(ReflashOptions),
+}
+
+/// The options used to flash the binary again before each test.
+#[derive(Debug, Clone)]
+pub struct ReflashOptions {
+    pub format_options: FormatOptions,
+    pub restore_unwritten: bool,
+    pub disable_double_buffering: bool,
+    pub verify: bool,
+    pub dry_run: bool,
+}
+
 /// Test run mode
 pub struct TestRunMode {
     libtest_args: Arguments,
+    /// The seed used to shuffle the tests, if they should be run in a random order.
+    shuffle_seed: Option<u64>,
+    isolation: Option<TestIsolation>,
+    coverage_output: Option<PathBuf>,
+    /// The timeout of tests which do not set their own.
+    default_timeout: Duration,
 }
 
+/// The timeout of tests, if neither the test nor `--timeout-per-test` set one.
+const DEFAULT_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+
 impl TestRunMode {
-    pub fn new(test_options: &TestOptions) -> Box<Self> {
+    pub fn new(test_options: &TestOptions, isolation: Option<TestIsolation>) -> Box<Self> {
         Box::new(Self {
             libtest_args: Arguments {
                 test_threads: Some(1), // Avoid parallel execution
@@ -93,6 +176,16 @@ impl TestRunMode {
                 },
                 ..Arguments::default()
             },
+            shuffle_seed: match test_options.shuffle_seed {
+                Some(seed) => Some(seed),
+                None if test_options.shuffle => Some(fastrand::u64(..)),
+                None => None,
+            },
+            isolation,
+            coverage_output: test_options.coverage_output.clone(),
+            default_timeout: test_options
+                .timeout_per_test
+                .map_or(DEFAULT_TEST_TIMEOUT, Duration::from_secs),
         })
     }
 
@@ -154,6 +247,12 @@ impl TestRunMode {
                             Ok(Some(list))
                         }
                     }
+                    SemihostingCommand::Unknown(details)
+                        if session_and_runloop.run_loop.continue_on_semihosting_error =>
+                    {
+                        reject_semihosting_operation(core, details)?;
+                        Ok(None) // Continue running
+                    }
                     other => Err(anyhow!(
                         "Unexpected semihosting command {:?} cmdline_requested: {:?}",
                         other,
@@ -170,6 +269,7 @@ impl TestRunMode {
             true,
             OutputStream::Stderr,
             Some(Duration::from_secs(5)),
+            &OutputPatterns::default(),
             halt_handler,
         )? {
             ReturnReason::User => Err(anyhow!(
@@ -179,6 +279,7 @@ impl TestRunMode {
             ReturnReason::Timeout => Err(anyhow!(
                 "The target did not respond with test list until timeout."
             )),
+            ReturnReason::OutputMatched => unreachable!("No output patterns are used in test mode"),
         }
     }
 
@@ -187,12 +288,18 @@ impl TestRunMode {
         test: Test,
         session_and_runloop: &mut SessionAndRunLoop,
     ) -> std::result::Result<(), Failed> {
+        if let Some(isolation) = session_and_runloop.isolation.clone() {
+            let start = Instant::now();
+            Self::isolate_test(&isolation, session_and_runloop)?;
+            session_and_runloop.isolation_time += start.elapsed();
+        }
+
         let core = &mut session_and_runloop.session.core(0)?;
         tracing::info!("Running test {}", test.name);
-        core.reset_and_halt(Duration::from_millis(100))?;
+        halt::reset_and_halt(core, session_and_runloop.run_loop.halt_timeout)?;
 
         let timeout = test.timeout.map(|t| Duration::from_secs(t as u64));
-        let timeout = timeout.unwrap_or(Duration::from_secs(60)); // TODO: make global timeout configurable: https://github.com/probe-rs/embedded-test/issues/3
+        let timeout = timeout.unwrap_or(session_and_runloop.default_timeout);
         let mut cmdline_requested = false;
 
         // When the target first invokes SYS_GET_CMDLINE (0x15), we answer "run <test_name>
@@ -215,6 +322,12 @@ impl TestRunMode {
                         Ok(Some(TestOutcome::Panic))
                     }
 
+                    SemihostingCommand::Unknown(details)
+                        if session_and_runloop.run_loop.continue_on_semihosting_error =>
+                    {
+                        reject_semihosting_operation(core, details)?;
+                        Ok(None) // Continue running
+                    }
                     other => {
                         // Invalid sequence of semihosting calls => Abort testing altogether
                         Err(anyhow!(
@@ -237,9 +350,18 @@ impl TestRunMode {
             true,
             OutputStream::Stderr,
             Some(timeout),
+            &OutputPatterns::default(),
             halt_handler,
         ) {
             Ok(ReturnReason::Timeout) => {
+                // The core was halted when the timeout expired, and is reset before the next test.
+                if session_and_runloop.json {
+                    println!(
+                        r#"{{ "type": "test", "event": "timeout", "name": {} }}"#,
+                        serde_json::Value::String(test.name.clone())
+                    );
+                }
+                session_and_runloop.timed_out.push(test.name.clone());
                 Err(Failed::from(format!("Test timed out after {:?}", timeout)))
             }
             Ok(ReturnReason::User) => {
@@ -248,6 +370,15 @@ impl TestRunMode {
                 std::process::exit(1);
             }
             Ok(ReturnReason::Predicate(outcome)) => {
+                if let Some(coverage) = &session_and_runloop.coverage {
+                    if let Err(e) = coverage.collect(core, &test.name) {
+                        tracing::warn!(
+                            "Failed to collect the coverage data of {}: {e:#}",
+                            test.name
+                        );
+                    }
+                }
+
                 if outcome == test.expected_outcome {
                     Ok(())
                 } else {
@@ -264,6 +395,9 @@ impl TestRunMode {
                     )))
                 }
             }
+            Ok(ReturnReason::OutputMatched) => {
+                unreachable!("No output patterns are used in test mode")
+            }
             Err(e) => {
                 // Probe-rs error: We do not mark the test as failed and instead exit the process
                 eprintln!("Error: {:?}", e);
@@ -271,6 +405,47 @@ impl TestRunMode {
             }
         }
     }
+
+    /// Prepares the target for the next test, according to the requested isolation.
+    ///
+    /// The core running the tests is always reset afterwards by [`Self::run_test`].
+    fn isolate_test(
+        isolation: &TestIsolation,
+        session_and_runloop: &mut SessionAndRunLoop,
+    ) -> Result<()> {
+        let session = &mut session_and_runloop.session;
+
+        if let TestIsolation::Reflash(options) = isolation {
+            tracing::info!("Flashing the binary again before running the next test");
+
+            let loader = build_loader(
+                session,
+                &session_and_runloop.run_loop.path,
+                options.format_options.clone(),
+                None,
+            )?;
+
+            let mut download_options = DownloadOptions::default();
+            download_options.keep_unwritten_bytes = options.restore_unwritten;
+            download_options.disable_double_buffering = options.disable_double_buffering;
+            download_options.verify = options.verify;
+            download_options.dry_run = options.dry_run;
+
+            loader.commit(session, download_options)?;
+        }
+
+        for (core_id, _) in session.list_cores() {
+            // The core running the tests is reset right before the test is started.
+            if core_id != 0 {
+                halt::reset_and_halt(
+                    &mut session.core(core_id)?,
+                    session_and_runloop.run_loop.halt_timeout,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -313,19 +488,72 @@ impl RunMode for TestRunMode {
     fn run(&self, session: Session, run_loop: RunLoop) -> Result<()> {
         tracing::info!("libtest args {:?}", self.libtest_args);
 
+        let coverage = self
+            .coverage_output
+            .as_deref()
+            .map(|output| CoverageCollector::new(&run_loop.path, output))
+            .transpose()?;
+
         // Unfortunately libtest-mimic wants test functions to live for 'static, so we need to use a mutex to share the session and runloop
-        let session_and_runloop = Arc::new(Mutex::new(SessionAndRunLoop { session, run_loop }));
+        let session_and_runloop = Arc::new(Mutex::new(SessionAndRunLoop {
+            session,
+            run_loop,
+            isolation: self.isolation.clone(),
+            isolation_time: Duration::ZERO,
+            coverage,
+            default_timeout: self.default_timeout,
+            json: matches!(self.libtest_args.format, Some(FormatSetting::Json)),
+            timed_out: Vec::new(),
+        }));
+
+        let mut tests = Self::create_tests(session_and_runloop.clone())?;
+
+        // libtest-mimic runs the tests in the order they are passed in, as we only use a single thread.
+        if let Some(seed) = self.shuffle_seed {
+            eprintln!("Shuffling tests with seed {seed}. Use `--shuffle-seed {seed}` to run them in the same order again.");
+            fastrand::Rng::with_seed(seed).shuffle(&mut tests);
+        }
+
+        let conclusion = libtest_mimic::run(&self.libtest_args, tests);
 
-        let tests = Self::create_tests(session_and_runloop)?;
-        if libtest_mimic::run(&self.libtest_args, tests).has_failed() {
-            Err(anyhow!("Some tests failed"))
+        if self.isolation.is_some() {
+            let isolation_time = session_and_runloop.lock().unwrap().isolation_time;
+            eprintln!("Spent {isolation_time:.2?} preparing the target between tests");
+        }
+
+        let session_and_runloop = &mut *session_and_runloop.lock().unwrap();
+        let timed_out = &session_and_runloop.timed_out;
+        if !timed_out.is_empty() {
+            eprintln!("{} test(s) timed out:", timed_out.len());
+            for name in timed_out {
+                eprintln!("    {name}");
+            }
+        }
+
+        let result = if conclusion.has_failed() {
+            Err(TargetReportedFailure("Some tests failed".to_string()).into())
         } else {
             Ok(())
-        }
+        };
+
+        halt_cores_on_exit(
+            &mut session_and_runloop.session,
+            &session_and_runloop.run_loop,
+            result,
+        )
     }
 }
 
 struct SessionAndRunLoop {
     session: Session,
     run_loop: RunLoop,
+    isolation: Option<TestIsolation>,
+    /// The total time spent preparing the target between tests.
+    isolation_time: Duration,
+    coverage: Option<CoverageCollector>,
+    default_timeout: Duration,
+    /// Whether the test report is printed as JSON.
+    json: bool,
+    /// The names of the tests which timed out.
+    timed_out: Vec<String>,
 }
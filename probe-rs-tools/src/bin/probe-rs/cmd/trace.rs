@@ -1,15 +1,42 @@
 use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
-use std::time::Instant;
 
+use anyhow::anyhow;
 use probe_rs::probe::list::Lister;
-use probe_rs::MemoryInterface;
+use probe_rs::{Core, CoreStatus, MemoryInterface};
 use scroll::{Pwrite, LE};
+use signal_hook::consts::signal;
 
-use crate::util::{common_options::ProbeOptions, parse_u64};
+use crate::util::run_output::RunOutput;
+use crate::util::sampling::{parse_sample_rate, SamplePacer};
+use crate::util::{common_options::ProbeOptions, parse_u64, resolve_address};
 use crate::CoreOptions;
 
+/// The output format of `probe-rs trace`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TraceFormat {
+    /// The timestamp and the value as little endian integers, as read by `plot.py`.
+    /// Only supports a single value.
+    Binary,
+    /// One row with the timestamp and all values per sample.
+    Csv,
+    /// One JSON object with the timestamp and all values per sample.
+    Json,
+}
+
+/// A 32-bit value which is sampled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TracedValue {
+    /// The address or symbol, as given on the command line.
+    label: String,
+    address: u64,
+}
+
 #[derive(clap::Parser)]
 pub struct Cmd {
     #[clap(flatten)]
@@ -20,46 +47,275 @@ pub struct Cmd {
 
     /// The address of the memory to dump from the target.
     #[clap(value_parser = parse_u64)]
-    loc: u64,
+    loc: Option<u64>,
+
+    /// Additional addresses of 32-bit values to sample. Can be repeated.
+    #[clap(long = "address", value_parser = parse_u64, value_name = "ADDRESS")]
+    addresses: Vec<u64>,
+
+    /// Symbols of 32-bit values to sample, resolved with `--elf`. Can be repeated.
+    #[clap(long = "symbol", value_name = "SYMBOL")]
+    symbols: Vec<String>,
+
+    /// The format of the samples written to stdout.
+    #[clap(long, value_enum, default_value = "binary")]
+    format: TraceFormat,
+
+    /// Start sampling once the program reaches this address or symbol.
+    #[clap(long, value_name = "SYMBOL|ADDRESS")]
+    start_trigger: Option<String>,
+
+    /// Stop sampling once the program reaches this address or symbol.
+    #[clap(long, value_name = "SYMBOL|ADDRESS")]
+    stop_trigger: Option<String>,
+
+    /// The number of samples per second.
+    #[clap(long, value_name = "HZ", value_parser = parse_sample_rate, default_value = "20")]
+    sample_rate: f64,
+
+    /// The ELF file used to resolve the symbols of the values and the triggers.
+    #[clap(long, value_name = "PATH")]
+    elf: Option<PathBuf>,
+
+    /// Write the samples to a file, instead of stdout, and a `meta.json` describing the run,
+    /// into a new timestamped subdirectory of this directory.
+    #[clap(long, value_name = "DIRECTORY")]
+    output_dir: Option<PathBuf>,
+}
+
+impl TraceFormat {
+    /// The name of the file the samples are written to with `--output-dir`.
+    fn file_name(&self) -> &'static str {
+        match self {
+            TraceFormat::Binary => "samples.bin",
+            TraceFormat::Csv => "samples.csv",
+            TraceFormat::Json => "samples.json",
+        }
+    }
 }
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
-        let mut xs = vec![];
-        let mut ys = vec![];
+        let start_trigger = self
+            .start_trigger
+            .as_deref()
+            .map(|trigger| resolve_address(trigger, self.elf.as_deref()))
+            .transpose()?;
+        let stop_trigger = self
+            .stop_trigger
+            .as_deref()
+            .map(|trigger| resolve_address(trigger, self.elf.as_deref()))
+            .transpose()?;
+
+        let values = self.traced_values()?;
+        if self.format == TraceFormat::Binary && values.len() > 1 {
+            anyhow::bail!("The binary format supports a single value, use `--format csv` or `--format json` to trace multiple values.");
+        }
 
-        let start = Instant::now();
+        let run_output = self
+            .output_dir
+            .as_deref()
+            .map(|directory| RunOutput::create(directory, "trace"))
+            .transpose()?;
+        let mut output: Box<dyn Write> = match &run_output {
+            Some(run_output) => Box::new(BufWriter::new(
+                run_output.create_file(self.format.file_name())?,
+            )),
+            None => Box::new(std::io::stdout()),
+        };
 
-        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+        let (mut session, probe_options) = self.common.simple_attach(lister)?;
 
         let mut core = session.core(self.shared.core)?;
 
-        loop {
-            // Prepare read.
-            let elapsed = start.elapsed();
-            let instant = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        // The triggers are hardware breakpoints, the core halts briefly when it reaches them.
+        if let Some(address) = start_trigger {
+            core.set_hw_breakpoint(address)?;
+            if core.core_halted()? {
+                core.run()?;
+            }
+            wait_for_trigger(&mut core, address)?;
+            core.clear_hw_breakpoint(address)?;
+            core.run()?;
+        }
+
+        if let Some(address) = stop_trigger {
+            core.set_hw_breakpoint(address)?;
+        }
+
+        if self.format == TraceFormat::Csv {
+            let labels = values.iter().map(|value| value.label.as_str());
+            writeln!(
+                output,
+                "{}",
+                std::iter::once("time_ms")
+                    .chain(labels)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )?;
+        }
+
+        // Stop sampling on CTRL + C, so that the sample rate can be reported.
+        let exit = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal::SIGINT, exit.clone())?;
+
+        let mut pacer = SamplePacer::new(Some(self.sample_rate));
+
+        while !exit.load(Ordering::Relaxed) {
+            pacer.wait();
+            let instant = pacer.elapsed().as_millis() as u64;
+
+            // Read data. The values are read one after another, so they are not sampled at the exact same time.
+            let samples = values
+                .iter()
+                .map(|value| core.read_word_32(value.address))
+                .collect::<Result<Vec<u32>, _>>()?;
+
+            match self.format {
+                TraceFormat::Binary => {
+                    // Send value to plot.py.
+                    let mut buf = [0_u8; 8];
+                    // Unwrap is safe!
+                    buf.pwrite_with(instant, 0, LE).unwrap();
+                    buf.pwrite_with(samples[0], 4, LE).unwrap();
+                    output.write_all(&buf)?;
+                }
+                TraceFormat::Csv => writeln!(output, "{}", csv_row(instant, &samples))?,
+                TraceFormat::Json => writeln!(output, "{}", json_row(instant, &values, &samples))?,
+            }
+
+            output.flush()?;
 
-            // Read data.
-            let value: u32 = core.read_word_32(self.loc)?;
+            if let Some(address) = stop_trigger {
+                if triggered(&mut core, address)? {
+                    core.clear_hw_breakpoint(address)?;
+                    core.run()?;
+                    break;
+                }
+            }
+        }
+
+        let report = pacer.report();
+        eprintln!("{}", report);
+
+        if let Some(run_output) = &run_output {
+            drop(core);
+            run_output.write_metadata(
+                "trace",
+                &session,
+                probe_options.probe_serial(lister),
+                self.elf.as_deref(),
+                &report,
+            )?;
+            eprintln!("Wrote the samples to {}", run_output.directory().display());
+        }
 
-            xs.push(instant);
-            ys.push(value);
+        Ok(())
+    }
+}
 
-            // Send value to plot.py.
-            let mut buf = [0_u8; 8];
-            // Unwrap is safe!
-            buf.pwrite_with(instant, 0, LE).unwrap();
-            buf.pwrite_with(value, 4, LE).unwrap();
-            std::io::stdout().write_all(&buf)?;
+impl Cmd {
+    /// Collects the values given as positional argument, with `--address` and with `--symbol`.
+    fn traced_values(&self) -> anyhow::Result<Vec<TracedValue>> {
+        let mut values: Vec<TracedValue> = self
+            .loc
+            .iter()
+            .chain(&self.addresses)
+            .map(|&address| TracedValue {
+                label: format!("{address:#010x}"),
+                address,
+            })
+            .collect();
 
-            std::io::stdout().flush()?;
+        for symbol in &self.symbols {
+            let Some(elf) = self.elf.as_deref() else {
+                anyhow::bail!("Symbols can only be resolved with `--elf`.");
+            };
+            values.push(TracedValue {
+                label: symbol.clone(),
+                address: resolve_address(symbol, Some(elf))?,
+            });
+        }
 
-            // Schedule next read.
-            let elapsed = start.elapsed();
-            let instant = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
-            let poll_every_ms = 50;
-            let time_to_wait = poll_every_ms - instant % poll_every_ms;
-            sleep(Duration::from_millis(time_to_wait));
+        if values.is_empty() {
+            anyhow::bail!(
+                "No value to trace was given. Pass an address, or use `--address` or `--symbol`."
+            );
         }
+
+        Ok(values)
+    }
+}
+
+fn csv_row(instant: u64, samples: &[u32]) -> String {
+    std::iter::once(instant.to_string())
+        .chain(samples.iter().map(|sample| sample.to_string()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn json_row(instant: u64, values: &[TracedValue], samples: &[u32]) -> serde_json::Value {
+    let samples: serde_json::Map<String, serde_json::Value> = values
+        .iter()
+        .zip(samples)
+        .map(|(value, sample)| (value.label.clone(), (*sample).into()))
+        .collect();
+
+    serde_json::json!({ "time_ms": instant, "values": samples })
+}
+
+/// Checks if the core is halted at the trigger.
+fn triggered(core: &mut Core, address: u64) -> anyhow::Result<bool> {
+    match core.status()? {
+        CoreStatus::Halted(_) => {
+            let pc: u64 = core.read_core_reg(core.program_counter())?;
+            if pc == address {
+                Ok(true)
+            } else {
+                Err(anyhow!(
+                    "The core halted at {pc:#010x} instead of the trigger."
+                ))
+            }
+        }
+        _ => Ok(false),
+    }
+}
+
+fn wait_for_trigger(core: &mut Core, address: u64) -> anyhow::Result<()> {
+    while !triggered(core, address)? {
+        sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_contain_all_samples() {
+        let values = [
+            TracedValue {
+                label: "0x20000000".to_string(),
+                address: 0x2000_0000,
+            },
+            TracedValue {
+                label: "SENSOR".to_string(),
+                address: 0x2000_0004,
+            },
+        ];
+
+        assert_eq!(csv_row(50, &[1, 2]), "50,1,2");
+        assert_eq!(
+            json_row(50, &values, &[1, 2]),
+            serde_json::json!({ "time_ms": 50, "values": { "0x20000000": 1, "SENSOR": 2 } })
+        );
+    }
+
+    #[test]
+    fn trigger_addresses_need_no_elf() {
+        assert_eq!(resolve_address("0x0800_0100", None).unwrap(), 0x0800_0100);
+        assert!(resolve_address("main", None).is_err());
     }
 }
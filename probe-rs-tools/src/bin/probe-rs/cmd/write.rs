@@ -1,6 +1,11 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use indicatif::{ProgressBar, ProgressStyle};
 use probe_rs::{probe::list::Lister, MemoryInterface};
 
-use crate::util::common_options::{ProbeOptions, ReadWriteBitWidth, ReadWriteOptions};
+use crate::util::access_port::AccessPortOptions;
+use crate::util::common_options::{Endianness, ProbeOptions, ReadWriteBitWidth};
 use crate::util::parse_u64;
 use crate::CoreOptions;
 
@@ -9,9 +14,26 @@ use crate::CoreOptions;
 /// e.g. probe-rs write b32 0x400E1490 0xDEADBEEF 0xCAFEF00D
 ///      Writes 0xDEADBEEF to address 0x400E1490 and 0xCAFEF00D to address 0x400E1494
 ///
+/// e.g. probe-rs write --file image.bin --address 0x20000000
+///      Writes the contents of image.bin to the memory starting at 0x20000000
+///
+/// e.g. probe-rs write --fill 0xDEADBEEF --address 0x20000000 --length 1024
+///      Fills 1024 bytes starting at 0x20000000 with the repeated word 0xDEADBEEF
+///
+/// e.g. probe-rs write --hex-string DEADBEEF00 --address 0x20000000
+///      Writes the bytes 0xDE, 0xAD, 0xBE, 0xEF and 0x00 to the memory starting at 0x20000000
+///
+/// e.g. probe-rs write --address 0x40020014 --set-bits 0x20 --clear-bits 0x1
+///      Reads the word at 0x40020014, sets bit 5, clears bit 0, and writes it back
+///
 /// NOTE: Only supports RAM addresses
 #[derive(clap::Parser)]
 #[clap(verbatim_doc_comment)]
+#[clap(group(
+    clap::ArgGroup::new("region")
+        .args(["file", "hex_string", "fill", "set_bits", "clear_bits", "toggle_bits"])
+        .multiple(true)
+))]
 pub struct Cmd {
     #[clap(flatten)]
     shared: CoreOptions,
@@ -20,20 +42,178 @@ pub struct Cmd {
     probe_options: ProbeOptions,
 
     #[clap(flatten)]
-    read_write_options: ReadWriteOptions,
+    access_port: AccessPortOptions,
+
+    /// Width of the data to write.
+    #[clap(value_enum, ignore_case = true, required_unless_present = "region")]
+    width: Option<ReadWriteBitWidth>,
+
+    /// The address to start from.
+    /// Takes an integer as an argument, and can be specified in decimal (16), hexadecimal (0x10) or octal (0o20) format.
+    #[clap(value_parser = parse_u64, required_unless_present = "region")]
+    address: Option<u64>,
 
     /// Values to write to the target.
     /// Takes a list of integer values and can be specified in decimal (16), hexadecimal (0x10) or octal (0o20) format.
     #[clap(value_parser = parse_u64)]
     values: Vec<u64>,
+
+    /// Write the raw contents of a file to the memory starting at `--address`, instead of values.
+    /// This does not use the flash algorithms, so the memory has to be writable, like RAM.
+    #[clap(
+        long,
+        value_name = "PATH",
+        requires = "region_address",
+        conflicts_with_all = ["width", "address", "values"]
+    )]
+    file: Option<PathBuf>,
+
+    /// Write the bytes of a hex string like `DEADBEEF00` to the memory starting at `--address`, in
+    /// this order. The bytes are written with 8-bit accesses, or with the width given with `--width`.
+    #[clap(
+        long,
+        value_name = "HEX",
+        value_parser = parse_hex_string,
+        requires = "region_address",
+        conflicts_with_all = ["width", "address", "values", "file"]
+    )]
+    hex_string: Option<HexBytes>,
+
+    /// Fill `--length` bytes of memory starting at `--address` with the repeated value, instead of
+    /// writing values. A pattern of several values can be given as a comma separated list.
+    ///
+    /// The values are 32-bit words, or have the size given with `--width`.
+    #[clap(
+        long,
+        value_name = "VALUE",
+        value_parser = parse_u64,
+        value_delimiter = ',',
+        requires_all = ["region_address", "length"],
+        conflicts_with_all = ["width", "address", "values", "file", "hex_string"]
+    )]
+    fill: Vec<u64>,
+
+    /// The number of bytes to fill.
+    #[clap(long, value_name = "BYTES", value_parser = parse_u64, requires = "fill")]
+    length: Option<u64>,
+
+    /// Read the word at `--address`, set the bits of this mask, and write it back.
+    ///
+    /// Can be combined with `--clear-bits` and `--toggle-bits`, the bits are cleared first, then
+    /// set, then toggled. The word is 32 bits wide, or has the size given with `--width`.
+    #[clap(
+        long,
+        value_name = "MASK",
+        value_parser = parse_u64,
+        requires = "region_address",
+        conflicts_with_all = ["width", "address", "values", "file", "hex_string", "fill"]
+    )]
+    set_bits: Option<u64>,
+
+    /// Read the word at `--address`, clear the bits of this mask, and write it back.
+    #[clap(
+        long,
+        value_name = "MASK",
+        value_parser = parse_u64,
+        requires = "region_address",
+        conflicts_with_all = ["width", "address", "values", "file", "hex_string", "fill"]
+    )]
+    clear_bits: Option<u64>,
+
+    /// Read the word at `--address`, toggle the bits of this mask, and write it back.
+    #[clap(
+        long,
+        value_name = "MASK",
+        value_parser = parse_u64,
+        requires = "region_address",
+        conflicts_with_all = ["width", "address", "values", "file", "hex_string", "fill"]
+    )]
+    toggle_bits: Option<u64>,
+
+    /// The address to write the file to, to start filling at, or of the word to modify.
+    #[clap(long = "address", value_name = "ADDRESS", value_parser = parse_u64, requires = "region")]
+    region_address: Option<u64>,
+
+    /// Width of the memory accesses used to write the file or the fill pattern. The address and the
+    /// size of the data have to be aligned to it. By default, 32-bit accesses are used where possible.
+    #[clap(long = "width", value_enum, ignore_case = true, requires = "region")]
+    region_width: Option<ReadWriteBitWidth>,
+
+    /// The byte order of the values, the fill values and the bit masks. The bytes of a file or a
+    /// hex string are always written in their order.
+    #[clap(long, value_enum, default_value_t)]
+    endianness: Endianness,
+
+    /// Read the memory back after writing it, and fail if it does not contain the written data.
+    #[clap(long)]
+    verify: bool,
+
+    /// Do not check the word at this address with `--verify`, e.g. for a register which reads
+    /// back differently than it was written. Can be given multiple times.
+    #[clap(long, value_name = "ADDRESS", value_parser = parse_u64, requires = "verify")]
+    no_verify_register: Vec<u64>,
 }
 
+/// The number of bytes written at once, between updates of the progress bar.
+const FILE_CHUNK_SIZE: usize = 16 * 1024;
+
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
         let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
-        let mut core = session.core(self.shared.core)?;
+        let mut core = self.access_port.memory(&mut session, self.shared.core)?;
+
+        if let Some(address) = self.region_address {
+            if self.set_bits.is_some() || self.clear_bits.is_some() || self.toggle_bits.is_some() {
+                let width = self.region_width.unwrap_or(ReadWriteBitWidth::B32);
+                let bits = BitOperations {
+                    set: self.set_bits.unwrap_or_default(),
+                    clear: self.clear_bits.unwrap_or_default(),
+                    toggle: self.toggle_bits.unwrap_or_default(),
+                };
+                return modify_bits(
+                    &mut *core,
+                    address,
+                    width,
+                    bits,
+                    self.endianness,
+                    self.verify,
+                );
+            }
+
+            // Hex strings are written byte by byte, unless a width is given.
+            let width = match &self.hex_string {
+                Some(_) => Some(self.region_width.unwrap_or(ReadWriteBitWidth::B8)),
+                None => self.region_width,
+            };
+
+            let data = if let Some(path) = &self.file {
+                std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?
+            } else if let Some(bytes) = &self.hex_string {
+                bytes.clone()
+            } else {
+                // Clap requires the length for fills.
+                let length = self.length.unwrap_or_default();
+                let width = self.region_width.unwrap_or(ReadWriteBitWidth::B32);
+                fill_pattern(&self.fill, width, length, self.endianness)?
+            };
+
+            write_data(&mut *core, &data, address, width)?;
+
+            if self.verify {
+                let read = read_data(&mut *core, address, data.len(), width)?;
+                let width = width.unwrap_or(ReadWriteBitWidth::B32);
+                check_read_back(address, &data, &read, width, &self.no_verify_register)?;
+            }
+
+            return Ok(());
+        }
+
+        // Clap requires these unless a file is written.
+        let (Some(width), Some(address)) = (self.width, self.address) else {
+            anyhow::bail!("Pass a width, an address and the values to write.");
+        };
 
-        match self.read_write_options.width {
+        let written: Vec<u8> = match width {
             ReadWriteBitWidth::B8 => {
                 let mut bvalues = Vec::new();
                 for val in &self.values {
@@ -46,7 +226,8 @@ impl Cmd {
                     }
                     bvalues.push(*val as u8);
                 }
-                core.write_8(self.read_write_options.address, &bvalues)?;
+                core.write_8(address, &bvalues)?;
+                bvalues
             }
             ReadWriteBitWidth::B32 => {
                 let mut bvalues = Vec::new();
@@ -58,15 +239,431 @@ impl Cmd {
                             self.values,
                         ));
                     }
-                    bvalues.push(*val as u32);
+                    bvalues.push(self.endianness.convert(*val, width) as u32);
                 }
-                core.write_32(self.read_write_options.address, &bvalues)?;
+                core.write_32(address, &bvalues)?;
+                bvalues
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect()
             }
             ReadWriteBitWidth::B64 => {
-                core.write_64(self.read_write_options.address, &self.values)?;
+                let values: Vec<u64> = self
+                    .values
+                    .iter()
+                    .map(|value| self.endianness.convert(*value, width))
+                    .collect();
+                core.write_64(address, &values)?;
+                values
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect()
             }
+        };
+
+        if self.verify {
+            let read = read_data(&mut *core, address, written.len(), Some(width))?;
+            check_read_back(address, &written, &read, width, &self.no_verify_register)?;
         }
 
         Ok(())
     }
 }
+
+/// Writes `data` to the memory at `address`, using memory accesses of the given `width`.
+fn write_data(
+    core: &mut dyn MemoryInterface,
+    data: &[u8],
+    address: u64,
+    width: Option<ReadWriteBitWidth>,
+) -> anyhow::Result<()> {
+    let (head, body, tail) = match width {
+        Some(width) => {
+            let alignment = width as usize / 8;
+            if address % alignment as u64 != 0 || data.len() % alignment != 0 {
+                anyhow::bail!(
+                    "{}-bit writes need an address and a file size which are multiples of {alignment} bytes.",
+                    width as usize
+                );
+            }
+            (&[][..], data, &[][..])
+        }
+        None => split_aligned(address, data, 4),
+    };
+    let width = width.unwrap_or(ReadWriteBitWidth::B32);
+
+    let progress = ProgressBar::new(data.len() as u64).with_style(
+        ProgressStyle::with_template("{wide_bar} {bytes}/{total_bytes} ({bytes_per_sec})")
+            .expect("Error in progress bar creation. This is a bug, please report it."),
+    );
+
+    let mut chunk_address = address;
+    let mut write = |data: &[u8], width: ReadWriteBitWidth| -> anyhow::Result<()> {
+        for chunk in data.chunks(FILE_CHUNK_SIZE) {
+            match width {
+                ReadWriteBitWidth::B8 => core.write_8(chunk_address, chunk)?,
+                ReadWriteBitWidth::B32 => core.write_mem_32bit(chunk_address, chunk)?,
+                ReadWriteBitWidth::B64 => core.write_mem_64bit(chunk_address, chunk)?,
+            }
+            chunk_address += chunk.len() as u64;
+            progress.inc(chunk.len() as u64);
+        }
+        Ok(())
+    };
+
+    write(head, ReadWriteBitWidth::B8)?;
+    write(body, width)?;
+    write(tail, ReadWriteBitWidth::B8)?;
+
+    progress.finish_and_clear();
+    println!(
+        "Wrote {} bytes to {:#010x}..{:#010x}",
+        data.len(),
+        address,
+        address + data.len() as u64
+    );
+
+    Ok(())
+}
+
+/// The masks of the bits changed by `--set-bits`, `--clear-bits` and `--toggle-bits`.
+#[derive(Debug, Clone, Copy, Default)]
+struct BitOperations {
+    set: u64,
+    clear: u64,
+    toggle: u64,
+}
+
+impl BitOperations {
+    fn apply(&self, value: u64) -> u64 {
+        ((value & !self.clear) | self.set) ^ self.toggle
+    }
+}
+
+/// Reads the word of the given `width` at `address`, applies the `bits` operations to it in the
+/// given byte order, and writes it back right away.
+fn modify_bits(
+    core: &mut dyn MemoryInterface,
+    address: u64,
+    width: ReadWriteBitWidth,
+    bits: BitOperations,
+    endianness: Endianness,
+    verify: bool,
+) -> anyhow::Result<()> {
+    let size = width as u64 / 8;
+    if address % size != 0 {
+        anyhow::bail!("The address {address:#010x} is not aligned to {size} bytes.");
+    }
+    for mask in [bits.set, bits.clear, bits.toggle] {
+        if size < 8 && mask >> (size * 8) != 0 {
+            anyhow::bail!("{mask:#x} is too large for a {} bit word.", width as usize);
+        }
+    }
+
+    let read = |core: &mut dyn MemoryInterface| -> anyhow::Result<u64> {
+        Ok(match width {
+            ReadWriteBitWidth::B8 => core.read_word_8(address)?.into(),
+            ReadWriteBitWidth::B32 => core.read_word_32(address)?.into(),
+            ReadWriteBitWidth::B64 => core.read_word_64(address)?,
+        })
+    };
+
+    let old = endianness.convert(read(core)?, width);
+    let new = bits.apply(old);
+    let written = endianness.convert(new, width);
+    match width {
+        ReadWriteBitWidth::B8 => core.write_word_8(address, written as u8)?,
+        ReadWriteBitWidth::B32 => core.write_word_32(address, written as u32)?,
+        ReadWriteBitWidth::B64 => core.write_word_64(address, written)?,
+    }
+    println!("{address:#010x}: {old:#x} -> {new:#x}");
+
+    if verify {
+        let read = endianness.convert(read(core)?, width);
+        if read != new {
+            anyhow::bail!(
+                "{address:#010x} did not read back as it was written: wrote {new:#x}, read {read:#x}"
+            );
+        }
+        println!("Verified {size} bytes");
+    }
+
+    Ok(())
+}
+
+/// Reads `len` bytes of memory at `address`, with the same memory accesses as [`write_data`].
+fn read_data(
+    core: &mut dyn MemoryInterface,
+    address: u64,
+    len: usize,
+    width: Option<ReadWriteBitWidth>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut data = vec![0; len];
+
+    let (head_len, body_len) = match width {
+        Some(_) => (0, len),
+        None => {
+            let (head, body, _) = split_aligned(address, &data, 4);
+            (head.len(), body.len())
+        }
+    };
+    let (head, rest) = data.split_at_mut(head_len);
+    let (body, tail) = rest.split_at_mut(body_len);
+    let body_address = address + head_len as u64;
+
+    core.read_8(address, head)?;
+    match width.unwrap_or(ReadWriteBitWidth::B32) {
+        ReadWriteBitWidth::B8 => core.read_8(body_address, body)?,
+        ReadWriteBitWidth::B32 => core.read_mem_32bit(body_address, body)?,
+        ReadWriteBitWidth::B64 => core.read_mem_64bit(body_address, body)?,
+    }
+    core.read_8(body_address + body_len as u64, tail)?;
+
+    Ok(data)
+}
+
+/// A word which did not read back as it was written.
+#[derive(Debug, PartialEq, Eq)]
+struct Mismatch {
+    address: u64,
+    written: u64,
+    read: u64,
+}
+
+/// The maximum number of mismatching words which are listed.
+const MAX_LISTED_MISMATCHES: usize = 8;
+
+/// Compares the `read` memory with the `written` data at `address`, and fails if any word of the
+/// given `width` differs, except the words at the `ignored` addresses.
+fn check_read_back(
+    address: u64,
+    written: &[u8],
+    read: &[u8],
+    width: ReadWriteBitWidth,
+    ignored: &[u64],
+) -> anyhow::Result<()> {
+    let mismatches = find_mismatches(address, written, read, width as usize / 8, ignored);
+    if mismatches.is_empty() {
+        println!("Verified {} bytes", written.len());
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "{} words did not read back as they were written:",
+        mismatches.len()
+    );
+    for mismatch in mismatches.iter().take(MAX_LISTED_MISMATCHES) {
+        message.push_str(&format!(
+            "\n    {:#010x}: wrote {:#x}, read {:#x}",
+            mismatch.address, mismatch.written, mismatch.read
+        ));
+    }
+    if mismatches.len() > MAX_LISTED_MISMATCHES {
+        message.push_str(&format!(
+            "\n    and {} more",
+            mismatches.len() - MAX_LISTED_MISMATCHES
+        ));
+    }
+    message.push_str("\nUse `--no-verify-register` to skip registers which read back differently.");
+
+    anyhow::bail!(message)
+}
+
+/// Finds the words of `word_size` bytes which differ between `written` and `read`. Partial words
+/// at the start and the end of the data are compared as far as they were written.
+fn find_mismatches(
+    address: u64,
+    written: &[u8],
+    read: &[u8],
+    word_size: usize,
+    ignored: &[u64],
+) -> Vec<Mismatch> {
+    let end = address + written.len() as u64;
+    let mut word = address - address % word_size as u64;
+    let mut mismatches = vec![];
+
+    while word < end {
+        let start = word.max(address);
+        let range =
+            (start - address) as usize..((word + word_size as u64).min(end) - address) as usize;
+        let ignore = ignored.contains(&word) || ignored.contains(&start);
+
+        if !ignore && written[range.clone()] != read[range.clone()] {
+            let value = |bytes: &[u8]| {
+                bytes
+                    .iter()
+                    .rev()
+                    .fold(0u64, |value, &byte| value << 8 | u64::from(byte))
+            };
+            mismatches.push(Mismatch {
+                address: start,
+                written: value(&written[range.clone()]),
+                read: value(&read[range]),
+            });
+        }
+
+        word += word_size as u64;
+    }
+
+    mismatches
+}
+
+/// The bytes of `--hex-string`.
+type HexBytes = Vec<u8>;
+
+/// Parses a hex string with two digits per byte, like `DEADBEEF00`.
+fn parse_hex_string(input: &str) -> Result<HexBytes, String> {
+    if input.is_empty() {
+        return Err("the hex string is empty".to_string());
+    }
+    if let Some(invalid) = input.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("`{invalid}` is not a hex digit"));
+    }
+    if input.len() % 2 != 0 {
+        return Err(format!(
+            "the hex string has {} digits, but every byte needs two digits",
+            input.len()
+        ));
+    }
+
+    Ok((0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&input[i..i + 2], 16).expect("the digits were checked"))
+        .collect())
+}
+
+/// Repeats the `values` of the given `width` and byte order for `length` bytes.
+fn fill_pattern(
+    values: &[u64],
+    width: ReadWriteBitWidth,
+    length: u64,
+    endianness: Endianness,
+) -> anyhow::Result<Vec<u8>> {
+    let size = width as usize / 8;
+    if length % size as u64 != 0 {
+        anyhow::bail!("The length has to be a multiple of the {size} bytes of the fill values.");
+    }
+
+    let mut pattern = Vec::with_capacity(values.len() * size);
+    for &value in values {
+        if size < 8 && value >> (size * 8) != 0 {
+            anyhow::bail!(
+                "{value:#x} is too large for a {} bit fill value.",
+                width as usize
+            );
+        }
+        pattern.extend_from_slice(&endianness.convert(value, width).to_le_bytes()[..size]);
+    }
+
+    Ok(pattern.into_iter().cycle().take(length as usize).collect())
+}
+
+/// Splits `data` written to `address` into the bytes before the first `alignment` boundary, the
+/// aligned bytes, and the remaining bytes after the last boundary.
+fn split_aligned(address: u64, data: &[u8], alignment: usize) -> (&[u8], &[u8], &[u8]) {
+    let misalignment = address as usize % alignment;
+    let head_len = if misalignment == 0 {
+        0
+    } else {
+        (alignment - misalignment).min(data.len())
+    };
+
+    let (head, rest) = data.split_at(head_len);
+    let (body, tail) = rest.split_at(rest.len() - rest.len() % alignment);
+
+    (head, body, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_unaligned_data() {
+        let data = [0u8; 10];
+
+        let (head, body, tail) = split_aligned(0x2000_0001, &data, 4);
+        assert_eq!((head.len(), body.len(), tail.len()), (3, 4, 3));
+
+        let (head, body, tail) = split_aligned(0x2000_0000, &data, 4);
+        assert_eq!((head.len(), body.len(), tail.len()), (0, 8, 2));
+
+        let (head, body, tail) = split_aligned(0x2000_0003, &data[..1], 4);
+        assert_eq!((head.len(), body.len(), tail.len()), (1, 0, 0));
+    }
+
+    #[test]
+    fn mismatching_words_are_found() {
+        let written = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA];
+        let mut read = written;
+        read[1] = 0;
+        read[9] = 0;
+
+        // The data starts in the middle of a word, which is compared as far as it was written.
+        assert_eq!(
+            find_mismatches(0x2000_0002, &written, &read, 4, &[]),
+            [
+                Mismatch {
+                    address: 0x2000_0002,
+                    written: 0x2211,
+                    read: 0x0011
+                },
+                Mismatch {
+                    address: 0x2000_0008,
+                    written: 0xAA99_8877,
+                    read: 0x0099_8877
+                },
+            ]
+        );
+        assert_eq!(
+            find_mismatches(0x2000_0002, &written, &read, 4, &[0x2000_0000, 0x2000_0008]),
+            []
+        );
+    }
+
+    #[test]
+    fn fill_repeats_pattern() {
+        assert_eq!(
+            fill_pattern(&[0xDEADBEEF], ReadWriteBitWidth::B32, 8, Endianness::Target).unwrap(),
+            [0xEF, 0xBE, 0xAD, 0xDE, 0xEF, 0xBE, 0xAD, 0xDE]
+        );
+        assert_eq!(
+            fill_pattern(&[0x01, 0x02], ReadWriteBitWidth::B8, 3, Endianness::Big).unwrap(),
+            [0x01, 0x02, 0x01]
+        );
+        assert_eq!(
+            fill_pattern(&[0xDEADBEEF], ReadWriteBitWidth::B32, 4, Endianness::Big).unwrap(),
+            [0xDE, 0xAD, 0xBE, 0xEF]
+        );
+        assert!(fill_pattern(&[0x100], ReadWriteBitWidth::B8, 4, Endianness::Target).is_err());
+        assert!(fill_pattern(&[0], ReadWriteBitWidth::B32, 6, Endianness::Target).is_err());
+    }
+
+    #[test]
+    fn hex_string_is_parsed_into_bytes() {
+        assert_eq!(
+            parse_hex_string("DEADbeef00").unwrap(),
+            [0xDE, 0xAD, 0xBE, 0xEF, 0x00]
+        );
+        assert_eq!(parse_hex_string("").unwrap_err(), "the hex string is empty");
+        assert_eq!(
+            parse_hex_string("DEADBEE").unwrap_err(),
+            "the hex string has 7 digits, but every byte needs two digits"
+        );
+        assert_eq!(
+            parse_hex_string("0xDEAD").unwrap_err(),
+            "`x` is not a hex digit"
+        );
+    }
+
+    #[test]
+    fn bits_are_cleared_set_and_toggled() {
+        let bits = BitOperations {
+            set: 0b0110,
+            clear: 0b0011,
+            toggle: 0b1100,
+        };
+        assert_eq!(bits.apply(0b1_0001), 0b1_1010);
+        assert_eq!(BitOperations::default().apply(0xDEAD_BEEF), 0xDEAD_BEEF);
+    }
+}
@@ -7,13 +7,17 @@ use std::ffi::OsStr;
 use std::fs;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::SystemTime;
 use std::{ffi::OsString, path::PathBuf};
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use colored::Colorize;
+use flate2::{write::GzEncoder, Compression};
 use itertools::Itertools;
 use probe_rs::flashing::{BinOptions, Format, IdfOptions};
+use probe_rs::probe::list::{AllProbesLister, DriverSubsetLister};
+use probe_rs::probe::ProbeFactory;
 use probe_rs::{probe::list::Lister, Target};
 use report::Report;
 use serde::Serialize;
@@ -21,28 +25,66 @@ use serde::{de::Error, Deserialize, Deserializer};
 use serde_json::Value;
 use time::{OffsetDateTime, UtcOffset};
 
-use crate::util::logging::setup_logging;
+use crate::util::elf::{find_symbol, section_address, symbol_address, ElfFile};
+use crate::util::exit_code::{ExitCode, EXIT_CODE_HELP};
+use crate::util::logging::{setup_logging, warn_on_utc_fallback};
 use crate::util::parse_u32;
 use crate::util::parse_u64;
+use crate::util::target_description::load_target_description_dirs;
 
-const MAX_LOG_FILES: usize = 20;
+/// The number of log files kept in the log folder by default, see `--log-retain`.
+const DEFAULT_LOG_RETAIN: usize = 20;
 
 #[derive(clap::Parser)]
 #[clap(
     name = "probe-rs",
     about = "The probe-rs CLI",
     version = env!("PROBE_RS_VERSION"),
-    long_version = env!("PROBE_RS_LONG_VERSION")
+    long_version = env!("PROBE_RS_LONG_VERSION"),
+    after_long_help = EXIT_CODE_HELP
 )]
 struct Cli {
     /// Location for log file
     ///
+    /// If the location is an existing folder, a new log file is created in it, and old log files
+    /// are deleted according to `--log-retain`. Otherwise, the log is appended to the file.
+    ///
     /// If no location is specified, the behaviour depends on `--log-to-folder`.
     #[clap(long, global = true, help_heading = "LOG CONFIGURATION")]
     log_file: Option<PathBuf>,
-    /// Enable logging to the default folder. This option is ignored if `--log-file` is specified.
+    /// Enable logging to a new file in the given folder, or in the default folder if no folder
+    /// is given. This option is ignored if `--log-file` is specified.
+    #[clap(
+        long,
+        global = true,
+        value_name = "FOLDER",
+        require_equals = true,
+        num_args = 0..=1,
+        help_heading = "LOG CONFIGURATION"
+    )]
+    log_to_folder: Option<Option<PathBuf>>,
+    /// The number of log files to keep in the log folder, including the new one. Older log files
+    /// are deleted.
+    #[clap(
+        long,
+        global = true,
+        value_name = "COUNT",
+        default_value_t = DEFAULT_LOG_RETAIN,
+        help_heading = "LOG CONFIGURATION"
+    )]
+    log_retain: usize,
+    /// Compress the old log files in the log folder with gzip. The most recent log file is kept
+    /// uncompressed.
+    #[clap(long, global = true, help_heading = "LOG CONFIGURATION")]
+    log_compress: bool,
+    /// Only keep the log file if the command fails.
+    ///
+    /// The log is written to a temporary file first, which is moved to the log location if the
+    /// command exits with an error, and deleted otherwise. This is not supported by `dap-server`.
+    ///
+    /// Requires `--log-file`, `--log-to-folder` or `--report`.
     #[clap(long, global = true, help_heading = "LOG CONFIGURATION")]
-    log_to_folder: bool,
+    log_only_on_error: bool,
     #[clap(
         long,
         short,
@@ -54,6 +96,37 @@ struct Cli {
         default_missing_value = "./report.zip"
     )]
     report: Option<PathBuf>,
+    /// Only use the given probe driver. Can be specified multiple times.
+    ///
+    /// Use `probe-rs list --drivers` to show the available drivers.
+    #[clap(
+        long,
+        global = true,
+        value_name = "DRIVER",
+        conflicts_with = "disable_driver",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    driver: Vec<String>,
+    /// Do not use the given probe driver. Can be specified multiple times.
+    #[clap(
+        long,
+        global = true,
+        value_name = "DRIVER",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    disable_driver: Vec<String>,
+    /// Load the target descriptions of all YAML files in the given directory and its
+    /// subdirectories. Can be specified multiple times.
+    ///
+    /// Chips of later directories override chips of the same name of earlier ones, and
+    /// `--chip-description-path` overrides all of them.
+    #[clap(
+        long,
+        global = true,
+        value_name = "DIR",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    target_description_dir: Vec<PathBuf>,
     #[clap(subcommand)]
     subcommand: Subcommand,
 }
@@ -68,6 +141,11 @@ enum Subcommand {
     Info(cmd::info::Cmd),
     /// Resets the target attached to the selected debug probe
     Reset(cmd::reset::Cmd),
+    /// Switches the target power supply of the selected debug probe
+    Power(cmd::power::Cmd),
+    /// Resets the USB device of the selected debug probe, to recover a probe which stopped
+    /// responding
+    ProbeReset(cmd::probe_reset::Cmd),
     /// Run a GDB server
     Gdb(cmd::gdb::Cmd),
     /// Basic command line debugger
@@ -97,6 +175,11 @@ enum Subcommand {
     Write(cmd::write::Cmd),
     Complete(cmd::complete::Cmd),
     Mi(cmd::mi::Cmd),
+    /// Check the system for common problems with probe drivers, permissions and the clock
+    Doctor(cmd::doctor::Cmd),
+    /// Print the registers, the backtrace and optionally the variables of a core dump, without
+    /// a target
+    AnalyzeDump(cmd::analyze_dump::Cmd),
 }
 
 /// Shared options for core selection, shared between commands
@@ -135,6 +218,15 @@ pub struct FormatOptions {
     /// The address in memory where the binary will be put at. This is only considered when `bin` is selected as the format.
     #[clap(long, value_parser = parse_u64, help_heading = "DOWNLOAD CONFIGURATION")]
     pub base_address: Option<u64>,
+    /// Put the binary at the address of a symbol or section in an ELF file, given as `<elf>:<symbol>`,
+    /// instead of using `--base-address`. This is only considered when `bin` is selected as the format.
+    #[clap(
+        long,
+        value_name = "ELF:SYMBOL",
+        conflicts_with = "base_address",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub base_address_from_elf: Option<String>,
     /// The number of bytes to skip at the start of the binary file. This is only considered when `bin` is selected as the format.
     #[clap(long, value_parser = parse_u32, default_value = "0", help_heading = "DOWNLOAD CONFIGURATION")]
     pub skip: u32,
@@ -159,7 +251,10 @@ impl FormatOptions {
             });
         Ok(match format {
             Format::Bin(_) => Format::Bin(BinOptions {
-                base_address: self.base_address,
+                base_address: match &self.base_address_from_elf {
+                    Some(location) => Some(address_from_elf(location)?),
+                    None => self.base_address,
+                },
                 skip: self.skip,
             }),
             Format::Hex => Format::Hex,
@@ -173,18 +268,38 @@ impl FormatOptions {
     }
 }
 
-/// Determine the default location for the logfile
-///
-/// This has to be called as early as possible, and while the program
-/// is single-threaded. Otherwise, determining the local time might fail.
-fn default_logfile_location() -> Result<PathBuf> {
+/// Resolves a `<elf>:<symbol>` location to the address of the symbol, or of the section with
+/// that name.
+fn address_from_elf(location: &str) -> Result<u64> {
+    let (path, name) = location
+        .rsplit_once(':')
+        .filter(|(path, name)| !path.is_empty() && !name.is_empty())
+        .with_context(|| format!("'{location}' is not of the form `<elf>:<symbol>`."))?;
+
+    let file = ElfFile::read(Path::new(path))?;
+    let elf = file.parse()?;
+
+    find_symbol(&elf, name)
+        .map(|sym| symbol_address(&sym))
+        .or_else(|| section_address(&elf, name))
+        .with_context(|| format!("The ELF file {path} has no symbol or section named '{name}'."))
+}
+
+/// Determine the default folder for log files
+fn default_log_folder() -> Result<PathBuf> {
     let project_dirs = directories::ProjectDirs::from("rs", "probe-rs", "probe-rs")
         .context("the application storage directory could not be determined")?;
-    let directory = project_dirs.data_dir();
+
+    Ok(project_dirs.data_dir().to_path_buf())
+}
+
+/// Determine the location for a new logfile in `directory`
+fn new_logfile_location(directory: &Path) -> Result<PathBuf> {
+    // The name is a Unix timestamp, so it does not depend on the local time offset.
     let logname = sanitize_filename::sanitize_with_options(
         format!(
             "{}.log",
-            OffsetDateTime::now_local()?.unix_timestamp_nanos() / 1_000_000
+            OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000
         ),
         sanitize_filename::Options {
             replacement: "_",
@@ -198,37 +313,110 @@ fn default_logfile_location() -> Result<PathBuf> {
     Ok(log_path)
 }
 
-/// Prune all old log files in the `directory`.
-fn prune_logs(directory: &Path) -> Result<(), anyhow::Error> {
-    // Get the path and elapsed creation time of all files in the log directory that have the '.log'
-    // suffix.
+/// Prune old log files in the `directory`, so that `retain` log files are left
+/// once the new log file is created.
+///
+/// If `compress` is set, the log files which are kept are compressed with gzip,
+/// except for the most recent one.
+fn prune_logs(directory: &Path, retain: usize, compress: bool) -> Result<(), anyhow::Error> {
+    // Get the path and last modification time of all files in the log directory that have the
+    // '.log' or '.log.gz' suffix.
     let mut log_files = fs::read_dir(directory)?
         .filter_map(|entry| {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.extension() == Some(OsStr::new("log")) {
-                    let metadata = fs::metadata(&path).ok()?;
-                    let last_modified = metadata.created().ok()?;
-                    Some((path, last_modified))
-                } else {
-                    None
-                }
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            if name.ends_with(".log") || name.ends_with(".log.gz") {
+                let last_modified = fs::metadata(&path).ok()?.modified().ok()?;
+                Some((path, last_modified))
             } else {
                 None
             }
         })
         .collect_vec();
 
-    // Order all files by the elapsed creation time with smallest first.
+    // Order all files by the last modification time with the most recent first.
     log_files.sort_unstable_by_key(|(_, b)| Reverse(*b));
 
-    // Iterate all files except for the first `MAX_LOG_FILES` and delete them.
-    for (path, _) in log_files.iter().skip(MAX_LOG_FILES) {
+    let kept = retain.saturating_sub(1).min(log_files.len());
+
+    // Iterate all files except for the newest ones and delete them, leaving room for the new file.
+    for (path, _) in &log_files[kept..] {
         fs::remove_file(path)?;
     }
+
+    if compress {
+        // The most recent log is kept uncompressed, so it can still be read while it is written.
+        for (path, last_modified) in log_files[..kept].iter().skip(1) {
+            if path.extension() == Some(OsStr::new("log")) {
+                compress_log(path, *last_modified)
+                    .with_context(|| format!("Failed to compress {}", path.display()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compresses the log file at `path` into a `.log.gz` file next to it, and deletes the original.
+fn compress_log(path: &Path, last_modified: SystemTime) -> Result<()> {
+    let mut compressed_path = path.as_os_str().to_owned();
+    compressed_path.push(".gz");
+
+    let compressed = fs::File::create(&compressed_path)?;
+    let mut encoder = GzEncoder::new(compressed, Compression::default());
+    std::io::copy(&mut fs::File::open(path)?, &mut encoder)?;
+
+    // The modification time determines the order of the log files when pruning.
+    encoder.finish()?.set_modified(last_modified)?;
+    fs::remove_file(path)?;
+
     Ok(())
 }
 
+/// Normalizes a driver name for comparison, so that e.g. `cmsis-dap`, `CMSIS-DAP` and `cmsisdap`
+/// all refer to the same driver.
+fn normalize_driver_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Selects the probe drivers to use, based on the `--driver` and `--disable-driver` arguments.
+fn select_drivers(
+    enabled: &[String],
+    disabled: &[String],
+) -> Result<Vec<&'static dyn ProbeFactory>> {
+    let available = AllProbesLister::drivers();
+
+    let find = |name: &String| {
+        available
+            .iter()
+            .copied()
+            .find(|driver| {
+                normalize_driver_name(&driver.to_string()) == normalize_driver_name(name)
+            })
+            .with_context(|| {
+                format!(
+                    "Unknown probe driver '{name}'. Available drivers: {}",
+                    available.iter().join(", ")
+                )
+            })
+    };
+
+    if !enabled.is_empty() {
+        return enabled.iter().map(find).collect();
+    }
+
+    let disabled = disabled.iter().map(find).collect::<Result<Vec<_>>>()?;
+
+    Ok(available
+        .iter()
+        .copied()
+        .filter(|driver| !disabled.contains(driver))
+        .collect())
+}
+
 /// Returns the cleaned arguments for the handler of the respective end binary
 /// (cli, cargo-flash, cargo-embed, etc.)
 fn multicall_check<'list>(args: &'list [OsString], want: &str) -> Option<&'list [OsString]> {
@@ -248,12 +436,23 @@ fn multicall_check<'list>(args: &'list [OsString], want: &str) -> Option<&'list
     None
 }
 
-fn main() -> Result<()> {
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error:?}");
+            ExitCode::from_error(&error).into()
+        }
+    }
+}
+
+fn run() -> Result<()> {
     // Determine the local offset as early as possible to avoid potential
     // issues with multiple threads and getting the offset.
-    // FIXME: we should probably let the user know if we can't determine the offset. However,
-    //        at this point we don't have a logger yet.
-    let utc_offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    // If it can't be determined, e.g. in containers without time zone data, we fall back to UTC
+    // and warn about it once logging is set up.
+    let local_offset = UtcOffset::current_local_offset();
+    let utc_offset = local_offset.unwrap_or(UtcOffset::UTC);
 
     let args: Vec<_> = std::env::args_os().collect();
     if let Some(args) = multicall_check(&args, "cargo-flash") {
@@ -276,42 +475,90 @@ fn main() -> Result<()> {
     }
 
     // Parse the commandline options.
-    let matches = Cli::parse_from(args);
+    let arg_matches = Cli::command().get_matches_from(args);
+    let matches = Cli::from_arg_matches(&arg_matches).unwrap_or_else(|error| error.exit());
+
+    // Checked here, as clap does not support groups of global arguments.
+    if matches.log_only_on_error
+        && matches.log_file.is_none()
+        && matches.log_to_folder.is_none()
+        && matches.report.is_none()
+    {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--log-only-on-error requires --log-file, --log-to-folder or --report",
+            )
+            .exit();
+    }
 
-    // Setup the probe lister, list all probes normally
-    let lister = Lister::new();
+    // Setup the probe lister, list all probes normally unless the drivers are restricted
+    let lister = if matches.driver.is_empty() && matches.disable_driver.is_empty() {
+        Lister::new()
+    } else {
+        let drivers = select_drivers(&matches.driver, &matches.disable_driver)?;
+        Lister::with_lister(Box::new(DriverSubsetLister::new(drivers)))
+    };
 
-    let log_path = if let Some(location) = matches.log_file {
-        Some(location)
-    } else if matches.log_to_folder || matches.report.is_some() {
+    let default_folder =
+        || default_log_folder().context("Unable to determine default log file location.");
+    let log_folder = match (&matches.log_file, &matches.log_to_folder) {
+        (Some(location), _) if location.is_dir() => Some(location.clone()),
+        (Some(_), _) => None,
+        (None, Some(Some(folder))) => Some(folder.clone()),
+        (None, Some(None)) => Some(default_folder()?),
         // We always log if we create a report.
-        let location =
-            default_logfile_location().context("Unable to determine default log file location.")?;
-        prune_logs(
-            location
-                .parent()
-                .expect("A file parent directory. Please report this as a bug."),
-        )?;
+        (None, None) if matches.report.is_some() => Some(default_folder()?),
+        (None, None) => None,
+    };
+
+    let log_path = if let Some(folder) = &log_folder {
+        let location = new_logfile_location(folder)
+            .context("Unable to determine the location of the log file.")?;
+        // Without a new log file, there is no need to make room for it.
+        if !matches.log_only_on_error {
+            prune_logs(folder, matches.log_retain, matches.log_compress)?;
+        }
         Some(location)
     } else {
-        None
+        matches.log_file
     };
 
     // the DAP server has special logging requirements. Run it before initializing logging,
     // so it can do its own special init.
     if let Subcommand::DapServer(cmd) = matches.subcommand {
-        return cmd::dap_server::run(cmd, &lister, utc_offset, log_path.as_deref());
+        load_target_description_dirs(&matches.target_description_dir)?;
+        return cmd::dap_server::run(cmd, &lister, local_offset, log_path.as_deref());
     }
 
-    let _logger_guard = setup_logging(log_path.as_deref(), None);
+    let staging_log = log_path
+        .as_ref()
+        .filter(|_| matches.log_only_on_error)
+        .map(|_| StagingLog::new());
+
+    let logger_guard = setup_logging(
+        staging_log
+            .as_ref()
+            .map(StagingLog::path)
+            .or(log_path.as_deref()),
+        None,
+    );
+    warn_on_utc_fallback(local_offset);
+    load_target_description_dirs(&matches.target_description_dir)?;
+
+    // The span of the command contains the spans of its phases, like attaching and flashing, so the
+    // timing of each phase can be read from the log.
+    let command_span = tracing::info_span!("command", subcommand = arg_matches.subcommand_name());
 
     let mut elf = None;
-    let result = match matches.subcommand {
+    let result = command_span.in_scope(|| match matches.subcommand {
         Subcommand::DapServer { .. } => unreachable!(), // handled above.
         Subcommand::List(cmd) => cmd.run(&lister),
         Subcommand::Info(cmd) => cmd.run(&lister),
         Subcommand::Gdb(cmd) => cmd.run(&lister),
         Subcommand::Reset(cmd) => cmd.run(&lister),
+        Subcommand::Power(cmd) => cmd.run(&lister),
+        Subcommand::ProbeReset(cmd) => cmd.run(&lister),
         Subcommand::Debug(cmd) => cmd.run(&lister),
         Subcommand::Download(cmd) => cmd.run(&lister),
         Subcommand::Run(cmd) => {
@@ -332,11 +579,86 @@ fn main() -> Result<()> {
         Subcommand::Write(cmd) => cmd.run(&lister),
         Subcommand::Complete(cmd) => cmd.run(&lister),
         Subcommand::Mi(cmd) => cmd.run(),
-    };
+        Subcommand::Doctor(cmd) => cmd.run(&lister, local_offset),
+        Subcommand::AnalyzeDump(cmd) => cmd.run(),
+    });
+    drop(command_span);
+
+    if let (Some(staging_log), Some(log_path)) = (&staging_log, &log_path) {
+        let staging_path = staging_log.path();
+        // Dropping the guard flushes the buffered log lines to the file.
+        drop(logger_guard);
+
+        let retained = if result.is_err() {
+            log_folder
+                .as_deref()
+                .map_or(Ok(()), |folder| {
+                    prune_logs(folder, matches.log_retain, matches.log_compress)
+                })
+                .and_then(|_| retain_log(staging_path, log_path, log_folder.is_none()))
+                .map(|_| eprintln!("Wrote log to {}", log_path.display()))
+        } else {
+            fs::remove_file(staging_path).map_err(anyhow::Error::from)
+        };
+        // The logger is already gone, so this is printed directly.
+        if let Err(error) = retained {
+            eprintln!("Failed to clean up the log file: {error:?}");
+        }
+    }
 
     compile_report(result, matches.report, elf, log_path.clone())
 }
 
+/// The temporary log file for `--log-only-on-error`.
+///
+/// The file is removed when this is dropped, so it does not stay behind if probe-rs exits early.
+struct StagingLog {
+    path: PathBuf,
+}
+
+impl StagingLog {
+    fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("probe-rs-{}.log", std::process::id()));
+
+        // The log is appended to the file, so remove a leftover of a process with the same ID.
+        let _ = fs::remove_file(&path);
+
+        Self { path }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for StagingLog {
+    fn drop(&mut self) {
+        // The file was already moved or removed if the command ran to the end.
+        if self.path.exists() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Moves the log file at `staging_path` to `log_path`, or appends it to `log_path` if `append` is
+/// set.
+fn retain_log(staging_path: &Path, log_path: &Path, append: bool) -> Result<()> {
+    if append {
+        let mut log_file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)?;
+        std::io::copy(&mut fs::File::open(staging_path)?, &mut log_file)?;
+        fs::remove_file(staging_path)?;
+    } else if fs::rename(staging_path, log_path).is_err() {
+        // Renaming fails if the temporary directory is on another file system.
+        fs::copy(staging_path, log_path)?;
+        fs::remove_file(staging_path)?;
+    }
+
+    Ok(())
+}
+
 fn compile_report(
     result: Result<()>,
     path: Option<PathBuf>,
@@ -379,7 +701,9 @@ fn compile_report(
 
 #[cfg(test)]
 mod test {
-    use crate::multicall_check;
+    use std::time::{Duration, SystemTime};
+
+    use crate::{address_from_elf, multicall_check, prune_logs, retain_log, select_drivers};
 
     #[test]
     fn argument_preprocessing() {
@@ -403,4 +727,90 @@ mod test {
             os_strs(&["cargo-flash", "--chip", "esp32c2"])
         );
     }
+
+    #[test]
+    fn driver_selection() {
+        let drivers = select_drivers(&["cmsisdap".to_string(), "J-LINK".to_string()], &[]).unwrap();
+        let names = drivers.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        assert_eq!(names, ["CMSIS-DAP", "J-Link"]);
+
+        let drivers = select_drivers(&[], &["st-link".to_string()]).unwrap();
+        assert!(drivers.iter().all(|d| d.to_string() != "ST-LINK"));
+        assert!(drivers.iter().any(|d| d.to_string() == "FTDI"));
+
+        assert!(select_drivers(&["not-a-driver".to_string()], &[]).is_err());
+    }
+
+    #[test]
+    fn base_address_from_symbol_or_section() {
+        let elf = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../probe-rs/tests/debug-unwind-tests/RP2040_full_unwind.elf"
+        );
+
+        assert_eq!(
+            address_from_elf(&format!("{elf}:__vector_table")).unwrap(),
+            0x1000_0100
+        );
+        // The Thumb bit of functions is not part of the address.
+        assert_eq!(
+            address_from_elf(&format!("{elf}:Reset")).unwrap(),
+            0x1000_01c0
+        );
+        assert_eq!(
+            address_from_elf(&format!("{elf}:.boot2")).unwrap(),
+            0x1000_0000
+        );
+        assert!(address_from_elf(&format!("{elf}:missing")).is_err());
+        assert!(address_from_elf(elf).is_err());
+    }
+
+    #[test]
+    fn log_pruning_compresses_old_logs() {
+        let directory = std::env::temp_dir().join(format!("probe-rs-logs-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let now = SystemTime::now();
+        for (i, name) in ["a.log", "b.log", "c.log.gz", "d.log", "other.txt"]
+            .iter()
+            .enumerate()
+        {
+            let file = std::fs::File::create(directory.join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(60 * i as u64))
+                .unwrap();
+        }
+
+        // Two old logs are kept for a retention of three, including the new one.
+        prune_logs(&directory, 3, true).unwrap();
+
+        let mut files = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(files, ["a.log", "b.log.gz", "other.txt"]);
+    }
+
+    #[test]
+    fn retained_log_is_appended_or_moved() {
+        let directory =
+            std::env::temp_dir().join(format!("probe-rs-retain-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let staging = directory.join("staging.log");
+        let log = directory.join("probe-rs.log");
+
+        std::fs::write(&staging, "first\n").unwrap();
+        retain_log(&staging, &log, false).unwrap();
+        std::fs::write(&staging, "second\n").unwrap();
+        retain_log(&staging, &log, true).unwrap();
+
+        let content = std::fs::read_to_string(&log).unwrap();
+        let staging_exists = staging.exists();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(content, "first\nsecond\n");
+        assert!(!staging_exists);
+    }
 }
@@ -0,0 +1,158 @@
+//! Accessing memory through a specific memory access port (MEM-AP), instead of through a core.
+
+use probe_rs::architecture::arm::{
+    ap::{GenericAp, MemoryAp},
+    memory::ArmProbe,
+    ApAddress, ApInformation,
+};
+use probe_rs::{Error, MemoryInterface, Session};
+
+use super::chip_check::default_dp_address;
+use super::common_options::OperationError;
+
+#[derive(clap::Parser, Debug, Clone, Copy)]
+pub struct AccessPortOptions {
+    /// Access the memory through the memory access port (MEM-AP) with this index, instead of
+    /// through the core. Only supported on ARM targets.
+    #[clap(long, value_name = "INDEX")]
+    pub ap: Option<u8>,
+}
+
+impl AccessPortOptions {
+    /// Opens the memory of the selected access port, or of the `core` if no access port is selected.
+    pub fn memory<'session>(
+        &self,
+        session: &'session mut Session,
+        core: usize,
+    ) -> anyhow::Result<Box<dyn MemoryInterface + 'session>> {
+        let Some(ap) = self.ap else {
+            return Ok(Box::new(session.core(core)?));
+        };
+
+        let access_port = memory_access_port(session, ap)?;
+        let memory = session.get_arm_interface()?.memory_interface(access_port)?;
+
+        Ok(Box::new(AccessPortMemory(memory)))
+    }
+}
+
+/// Checks that the access port `ap` exists on the debug port of the default core, and that it is
+/// a memory access port.
+pub fn memory_access_port(session: &mut Session, ap: u8) -> Result<MemoryAp, OperationError> {
+    let unavailable = |reason: String| OperationError::AccessPortNotAvailable { ap, reason };
+
+    let Some(dp) = default_dp_address(session) else {
+        return Err(unavailable("access ports only exist on ARM targets".into()));
+    };
+
+    let interface = session
+        .get_arm_interface()
+        .map_err(|e| unavailable(e.to_string()))?;
+
+    let count = interface
+        .num_access_ports(dp)
+        .map_err(|e| unavailable(e.to_string()))?;
+    if usize::from(ap) >= count {
+        return Err(unavailable(format!(
+            "the debug port only has {count} access ports"
+        )));
+    }
+
+    let address = ApAddress { dp, ap };
+    match interface.ap_information(GenericAp::new(address)) {
+        Ok(ApInformation::MemoryAp(_)) => Ok(MemoryAp::new(address)),
+        Ok(ApInformation::Other { .. }) => {
+            Err(unavailable("it is not a memory access port".into()))
+        }
+        Err(e) => Err(unavailable(e.to_string())),
+    }
+}
+
+/// The memory behind a memory access port.
+struct AccessPortMemory<'probe>(Box<dyn ArmProbe + 'probe>);
+
+impl MemoryInterface for AccessPortMemory<'_> {
+    fn supports_native_64bit_access(&mut self) -> bool {
+        self.0.supports_native_64bit_access()
+    }
+
+    fn read_word_64(&mut self, address: u64) -> Result<u64, Error> {
+        Ok(self.0.read_word_64(address)?)
+    }
+
+    fn read_word_32(&mut self, address: u64) -> Result<u32, Error> {
+        Ok(self.0.read_word_32(address)?)
+    }
+
+    fn read_word_16(&mut self, address: u64) -> Result<u16, Error> {
+        Ok(self.0.read_word_16(address)?)
+    }
+
+    fn read_word_8(&mut self, address: u64) -> Result<u8, Error> {
+        Ok(self.0.read_word_8(address)?)
+    }
+
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), Error> {
+        Ok(self.0.read_64(address, data)?)
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
+        Ok(self.0.read_32(address, data)?)
+    }
+
+    fn read_16(&mut self, address: u64, data: &mut [u16]) -> Result<(), Error> {
+        Ok(self.0.read_16(address, data)?)
+    }
+
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        Ok(self.0.read_8(address, data)?)
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        Ok(self.0.read(address, data)?)
+    }
+
+    fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), Error> {
+        Ok(self.0.write_word_64(address, data)?)
+    }
+
+    fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), Error> {
+        Ok(self.0.write_word_32(address, data)?)
+    }
+
+    fn write_word_16(&mut self, address: u64, data: u16) -> Result<(), Error> {
+        Ok(self.0.write_word_16(address, data)?)
+    }
+
+    fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), Error> {
+        Ok(self.0.write_word_8(address, data)?)
+    }
+
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), Error> {
+        Ok(self.0.write_64(address, data)?)
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
+        Ok(self.0.write_32(address, data)?)
+    }
+
+    fn write_16(&mut self, address: u64, data: &[u16]) -> Result<(), Error> {
+        Ok(self.0.write_16(address, data)?)
+    }
+
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        Ok(self.0.write_8(address, data)?)
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        Ok(self.0.write(address, data)?)
+    }
+
+    fn supports_8bit_transfers(&self) -> Result<bool, Error> {
+        Ok(self.0.supports_8bit_transfers()?)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.0.flush()?)
+    }
+}
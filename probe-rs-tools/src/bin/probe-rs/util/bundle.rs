@@ -0,0 +1,333 @@
+//! Firmware bundles: ZIP archives with the images of a release, and a `probe-rs.json` manifest
+//! which describes the chip and how each image is flashed.
+//!
+//! The images take the same options as `--binary-format` and the other download flags:
+//!
+//! ```json
+//! {
+//!     "chip": "nRF52840_xxAA",
+//!     "images": [
+//!         { "file": "bootloader.hex", "binary_format": "hex" },
+//!         { "file": "app.bin", "binary_format": "bin", "base_address": 65536 }
+//!     ]
+//! }
+//! ```
+
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context};
+use probe_rs::flashing::{FlashLoader, Format};
+use probe_rs::Session;
+use serde::Deserialize;
+use tempfile::TempDir;
+
+use super::flash::add_image;
+use crate::FormatOptions;
+
+/// The name of the manifest in the archive.
+pub const MANIFEST_NAME: &str = "probe-rs.json";
+
+/// The keys of an image in the manifest, the fields of [`FormatOptions`] and the path.
+const IMAGE_KEYS: &[&str] = &[
+    "file",
+    "binary_format",
+    "format",
+    "base_address",
+    "base_address_from_elf",
+    "skip",
+    "idf_bootloader",
+    "idf_partition_table",
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    /// The chip the images are built for.
+    chip: Option<String>,
+    images: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Image {
+    /// The path of the image in the archive.
+    file: PathBuf,
+    #[serde(flatten)]
+    format: FormatOptions,
+}
+
+/// An extracted bundle, which is deleted when dropped.
+pub struct Bundle {
+    directory: TempDir,
+    chip: Option<String>,
+    images: Vec<Image>,
+}
+
+impl Bundle {
+    /// Checks whether `path` is a bundle, going by its extension.
+    pub fn is_bundle(path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+    }
+
+    /// Extracts the bundle into a temporary directory, and validates its manifest.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open the bundle {}", path.display()))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .with_context(|| format!("{} is not a valid ZIP archive", path.display()))?;
+
+        let directory = tempfile::Builder::new()
+            .prefix("probe-rs-bundle-")
+            .tempdir()
+            .context("Failed to create a temporary directory for the bundle")?;
+
+        let mut bundle = Self {
+            directory,
+            chip: None,
+            images: vec![],
+        };
+        archive
+            .extract(bundle.directory.path())
+            .with_context(|| format!("Failed to extract the bundle {}", path.display()))?;
+
+        let manifest = fs::read_to_string(bundle.directory.path().join(MANIFEST_NAME))
+            .with_context(|| format!("The bundle has no {MANIFEST_NAME} manifest"))?;
+        let manifest: Manifest = serde_json::from_str(&manifest)
+            .with_context(|| format!("The {MANIFEST_NAME} manifest of the bundle is invalid"))?;
+
+        if manifest.images.is_empty() {
+            bail!("The {MANIFEST_NAME} manifest of the bundle does not list any images.");
+        }
+
+        bundle.chip = manifest.chip;
+        for image in manifest.images {
+            let image = bundle.validate(image)?;
+            bundle.images.push(image);
+        }
+
+        Ok(bundle)
+    }
+
+    /// Uses the chip of the manifest, unless `--chip` is given, in which case both have to match.
+    pub fn select_chip(&self, chip: &mut Option<String>) -> anyhow::Result<()> {
+        match (&self.chip, chip.as_deref()) {
+            (Some(bundle_chip), Some(chip)) if !bundle_chip.eq_ignore_ascii_case(chip) => {
+                bail!(
+                    "The bundle is built for the chip {bundle_chip}, but --chip {chip} was given."
+                )
+            }
+            (Some(bundle_chip), None) => *chip = Some(bundle_chip.clone()),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Builds a flash loader with all images of the bundle.
+    pub fn build_loader(&self, session: &mut Session) -> anyhow::Result<FlashLoader> {
+        let mut loader = session.target().flash_loader();
+
+        for image in &self.images {
+            add_image(
+                session,
+                &mut loader,
+                self.directory.path().join(&image.file),
+                image.format.clone(),
+                None,
+            )
+            .with_context(|| format!("Failed to load {} from the bundle", image.file.display()))?;
+        }
+
+        Ok(loader)
+    }
+
+    /// Checks that an image of the manifest is valid, and resolves its paths into the bundle.
+    fn validate(&self, image: serde_json::Value) -> anyhow::Result<Image> {
+        if let Some(keys) = image.as_object() {
+            if let Some(key) = keys.keys().find(|key| !IMAGE_KEYS.contains(&key.as_str())) {
+                bail!(
+                    "Unknown option '{key}' for an image in the {MANIFEST_NAME} manifest, expected one of {}.",
+                    IMAGE_KEYS.join(", ")
+                );
+            }
+        }
+
+        let mut image: Image = serde_json::from_value(image).with_context(|| {
+            format!("An image in the {MANIFEST_NAME} manifest of the bundle is invalid")
+        })?;
+        let file = image.file.display().to_string();
+        let format = &mut image.format;
+
+        let is_bin = matches!(format.binary_format, Some(Format::Bin(_)));
+        if !is_bin
+            && (format.base_address.is_some()
+                || format.base_address_from_elf.is_some()
+                || format.skip != 0)
+        {
+            bail!("{file}: `base_address`, `base_address_from_elf` and `skip` are only valid with `\"binary_format\": \"bin\"`.");
+        }
+        if format.base_address.is_some() && format.base_address_from_elf.is_some() {
+            bail!("{file}: `base_address` and `base_address_from_elf` cannot be used together.");
+        }
+
+        // Without a format, the default format of the target is used, which may be IDF.
+        let may_be_idf = matches!(format.binary_format, None | Some(Format::Idf(_)));
+        if !may_be_idf && (format.idf_bootloader.is_some() || format.idf_partition_table.is_some())
+        {
+            bail!("{file}: `idf_bootloader` and `idf_partition_table` are only valid with `\"binary_format\": \"idf\"`.");
+        }
+
+        self.resolve(&image.file)?;
+        if let Some(location) = &mut format.base_address_from_elf {
+            let Some((path, symbol)) = location.rsplit_once(':') else {
+                bail!("{file}: `base_address_from_elf` is not of the form `<elf>:<symbol>`.");
+            };
+            *location = format!("{}:{symbol}", self.resolve(Path::new(path))?.display());
+        }
+        for path in [&mut format.idf_bootloader, &mut format.idf_partition_table]
+            .into_iter()
+            .flatten()
+        {
+            *path = self.resolve(path)?;
+        }
+
+        Ok(image)
+    }
+
+    /// Returns the extracted path of a file in the bundle.
+    fn resolve(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        if !is_inside_bundle(path) {
+            bail!(
+                "{} is not a relative path inside the bundle.",
+                path.display()
+            );
+        }
+
+        let resolved = self.directory.path().join(path);
+        if !resolved.is_file() {
+            bail!("The bundle does not contain {}.", path.display());
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// Checks that `path` cannot point outside of the bundle.
+fn is_inside_bundle(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Checks that none of the options which are taken from the manifest are given on the command line.
+pub fn check_no_format_options(options: &FormatOptions) -> anyhow::Result<()> {
+    if options.binary_format.is_some()
+        || options.base_address.is_some()
+        || options.base_address_from_elf.is_some()
+        || options.skip != 0
+        || options.idf_bootloader.is_some()
+        || options.idf_partition_table.is_some()
+    {
+        bail!("The format of the images of a bundle is given by its {MANIFEST_NAME} manifest, and cannot be set on the command line.");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::write::FileOptions;
+
+    use super::*;
+
+    fn write_bundle(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "probe-rs-bundle-test-{}-{name}.zip",
+            std::process::id()
+        ));
+        let mut archive = zip::ZipWriter::new(File::create(&path).unwrap());
+        for (name, content) in files {
+            archive
+                .start_file(*name, FileOptions::<()>::default())
+                .unwrap();
+            archive.write_all(content.as_bytes()).unwrap();
+        }
+        archive.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn manifest_is_resolved() {
+        let path = write_bundle(
+            "valid",
+            &[
+                (
+                    MANIFEST_NAME,
+                    r#"{
+                        "chip": "nRF52840_xxAA",
+                        "images": [
+                            { "file": "images/app.bin", "binary_format": "bin", "base_address": 4096 },
+                            { "file": "bootloader.hex", "format": "hex" }
+                        ]
+                    }"#,
+                ),
+                ("images/app.bin", "app"),
+                ("bootloader.hex", ":00000001FF"),
+            ],
+        );
+
+        let bundle = Bundle::open(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(bundle.images.len(), 2);
+        assert_eq!(bundle.images[0].format.base_address, Some(4096));
+        assert!(bundle
+            .directory
+            .path()
+            .join(&bundle.images[1].file)
+            .is_file());
+
+        let mut chip = None;
+        bundle.select_chip(&mut chip).unwrap();
+        assert_eq!(chip.as_deref(), Some("nRF52840_xxAA"));
+        assert!(bundle
+            .select_chip(&mut Some("STM32F429ZITx".into()))
+            .is_err());
+
+        let directory = bundle.directory.path().to_path_buf();
+        drop(bundle);
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn invalid_images_are_rejected() {
+        let cases = [
+            (
+                r#"{ "file": "app.elf", "base_address": 4096 }"#,
+                "only valid",
+            ),
+            (
+                r#"{ "file": "app.elf", "base_adress": 4096 }"#,
+                "Unknown option",
+            ),
+            (r#"{ "file": "../app.elf" }"#, "relative path"),
+            (r#"{ "file": "missing.elf" }"#, "does not contain"),
+        ];
+
+        for (index, (image, error)) in cases.into_iter().enumerate() {
+            let manifest = format!(r#"{{ "images": [{image}] }}"#);
+            let path = write_bundle(
+                &format!("invalid-{index}"),
+                &[(MANIFEST_NAME, &manifest), ("app.elf", "")],
+            );
+
+            let result = Bundle::open(&path).map(|_| ());
+            fs::remove_file(path).unwrap();
+
+            let message = format!("{:#}", result.unwrap_err());
+            assert!(message.contains(error), "{image}: {message}");
+        }
+    }
+}
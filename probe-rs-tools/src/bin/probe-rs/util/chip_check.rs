@@ -0,0 +1,169 @@
+//! Checking that the connected target is the chip selected with `--chip`.
+
+use probe_rs::architecture::arm::{ArmChipInfo, DpAddress};
+use probe_rs::Session;
+use probe_rs_target::{Chip, ChipFamily, CoreAccessOptions};
+
+/// The result of comparing the identification read from the target with the selected chip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChipCheck {
+    /// The identification matches the chip, as far as it can be told.
+    Match,
+    /// The target could not be identified, or the chip has no identification to compare with.
+    Unknown,
+    /// The identification fits other chips of the family better, but the chips of the
+    /// family cannot be told apart reliably.
+    Ambiguous(String),
+    /// The identification contradicts the chip.
+    Mismatch(String),
+}
+
+/// Reads the identification of the target and compares it with the chip `name`.
+///
+/// Only ARM targets can be identified, the check is skipped for all other targets.
+pub fn check_chip(session: &mut Session, name: &str) -> ChipCheck {
+    let Ok((target, family)) = probe_rs::config::get_target_and_family_by_name(name) else {
+        return ChipCheck::Unknown;
+    };
+    let Some(chip) = family
+        .variants()
+        .iter()
+        .find(|chip| chip.name == target.name)
+    else {
+        return ChipCheck::Unknown;
+    };
+
+    let Some(dp) = default_dp_address(session) else {
+        return ChipCheck::Unknown;
+    };
+
+    let info = session
+        .get_arm_interface()
+        .map_err(anyhow::Error::from)
+        .and_then(|interface| Ok(interface.read_chip_info_from_rom_table(dp)?));
+
+    match info {
+        Ok(Some(info)) => compare(&family, chip, &info),
+        Ok(None) => ChipCheck::Unknown,
+        Err(e) => {
+            tracing::debug!("Failed to read the identification of the target: {e}");
+            ChipCheck::Unknown
+        }
+    }
+}
+
+/// Compares the identification read from the ROM table with the `chip` of the `family`.
+/// Returns the address of the debug port of the default core, if it is an ARM core.
+pub fn default_dp_address(session: &Session) -> Option<DpAddress> {
+    match &session.target().default_core().core_access_options {
+        CoreAccessOptions::Arm(options) if options.psel != 0 => {
+            Some(DpAddress::Multidrop(options.psel))
+        }
+        CoreAccessOptions::Arm(_) => Some(DpAddress::Default),
+        _ => None,
+    }
+}
+
+fn compare(family: &ChipFamily, chip: &Chip, found: &ArmChipInfo) -> ChipCheck {
+    let Some(manufacturer) = family.manufacturer else {
+        return ChipCheck::Unknown;
+    };
+
+    if manufacturer != found.manufacturer {
+        return ChipCheck::Mismatch(format!(
+            "the target was made by {}, but {} is made by {manufacturer}",
+            found.manufacturer, chip.name
+        ));
+    }
+
+    match chip.part {
+        Some(part) if part == found.part => ChipCheck::Match,
+        Some(part) => ChipCheck::Mismatch(format!(
+            "the target has the part number {:#06x}, but {} has the part number {part:#06x}",
+            found.part, chip.name
+        )),
+        None => {
+            let others = family
+                .variants()
+                .iter()
+                .filter(|variant| variant.part == Some(found.part))
+                .map(|variant| variant.name.as_str())
+                .collect::<Vec<_>>();
+
+            if others.is_empty() {
+                ChipCheck::Match
+            } else {
+                ChipCheck::Ambiguous(format!(
+                    "the part number {:#06x} of the target belongs to {}",
+                    found.part,
+                    others.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use probe_rs::CoreType;
+    use probe_rs_target::TargetDescriptionSource;
+
+    use super::*;
+
+    const JEP_ST: jep106::JEP106Code = jep106::JEP106Code::new(0, 0x20);
+    const JEP_NORDIC: jep106::JEP106Code = jep106::JEP106Code::new(2, 0x44);
+
+    fn family(variants: Vec<Chip>) -> ChipFamily {
+        ChipFamily {
+            name: "test".to_string(),
+            manufacturer: Some(JEP_ST),
+            generated_from_pack: false,
+            pack_file_release: None,
+            variants,
+            flash_algorithms: vec![],
+            source: TargetDescriptionSource::BuiltIn,
+        }
+    }
+
+    #[test]
+    fn different_manufacturer_is_a_mismatch() {
+        let chip = Chip::generic_arm("first", CoreType::Armv7em);
+        let family = family(vec![chip.clone()]);
+
+        let found = ArmChipInfo {
+            manufacturer: JEP_NORDIC,
+            part: 0x1234,
+        };
+
+        assert!(matches!(
+            compare(&family, &chip, &found),
+            ChipCheck::Mismatch(_)
+        ));
+    }
+
+    #[test]
+    fn part_number_of_other_chip() {
+        let mut first = Chip::generic_arm("first", CoreType::Armv7em);
+        first.part = Some(0x1234);
+        let mut second = Chip::generic_arm("second", CoreType::Armv7em);
+        second.part = Some(0x5678);
+        let third = Chip::generic_arm("third", CoreType::Armv7em);
+        let family = family(vec![first.clone(), second.clone(), third.clone()]);
+
+        let found = ArmChipInfo {
+            manufacturer: JEP_ST,
+            part: 0x5678,
+        };
+
+        assert!(matches!(
+            compare(&family, &first, &found),
+            ChipCheck::Mismatch(_)
+        ));
+        assert_eq!(compare(&family, &second, &found), ChipCheck::Match);
+        // Without a part number, the chip could still be a variant of the second one.
+        assert!(matches!(
+            compare(&family, &third, &found),
+            ChipCheck::Ambiguous(_)
+        ));
+    }
+}
@@ -1,11 +1,16 @@
 use std::{
     fs::File,
-    io::Write,
+    io::{IsTerminal, Write},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use super::cargo::ArtifactError;
-use crate::util::parse_u64;
+use super::chip_check::{check_chip, ChipCheck};
+use super::flash::{FlashHaltMode, GapFill};
+use super::halt::{self, DEFAULT_HALT_TIMEOUT};
+use super::option_bytes::{OptionBytesError, OptionBytesOptions};
+use crate::util::{parse_u32, parse_u64};
 use probe_rs::{
     config::{RegistryError, TargetSelector},
     flashing::{FileDownloadError, FlashError},
@@ -15,8 +20,16 @@ use probe_rs::{
     },
     Permissions, Session, Target,
 };
+use probe_rs_target::CoreAccessOptions;
 use serde::{Deserialize, Serialize};
 
+/// The time to wait after switching the target power supply, before the target voltage is
+/// expected to be stable.
+pub const TARGET_POWER_SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Above this number of matching chips, the user is asked to enter a longer name instead.
+const MAX_CHIP_SUGGESTIONS: usize = 30;
+
 /// Common options when flashing a target device.
 #[derive(Debug, clap::Parser)]
 pub struct BinaryDownloadOptions {
@@ -27,8 +40,28 @@ pub struct BinaryDownloadOptions {
     #[arg(long, help_heading = "DOWNLOAD CONFIGURATION")]
     pub disable_double_buffering: bool,
     /// Enable this flag to restore all bytes erased in the sector erase but not overwritten by any page.
-    #[arg(long, help_heading = "DOWNLOAD CONFIGURATION")]
+    ///
+    /// The affected sectors are read back before erasing, which makes flashing slower.
+    #[arg(
+        long,
+        visible_alias = "keep-unwritten",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
     pub restore_unwritten: bool,
+    /// What the bytes of the programmed sectors which the image does not cover are set to, e.g.
+    /// the gaps between two load segments in the same sector.
+    ///
+    /// `erased` (the default) leaves them erased, `preserve` keeps their previous contents like
+    /// `--restore-unwritten`, and a byte value such as `0xFF` programs them with it.
+    #[arg(
+        long,
+        value_name = "erased|preserve|VALUE",
+        value_parser = GapFill::parse,
+        default_value = "erased",
+        conflicts_with = "restore_unwritten",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub elf_gap_fill: GapFill,
     /// Requests the flash builder to output the layout into the given file in SVG format.
     #[arg(
         value_name = "filename",
@@ -36,9 +69,45 @@ pub struct BinaryDownloadOptions {
         help_heading = "DOWNLOAD CONFIGURATION"
     )]
     pub flash_layout_output_path: Option<String>,
+    /// Records the low-level flash operations into the given file, so that they can be repeated
+    /// with `download --replay`.
+    ///
+    /// The file contains a JSON object per line: a header with the format version and the chip,
+    /// followed by every sector erase, chip erase and programmed page.
+    #[arg(long, value_name = "PATH", help_heading = "DOWNLOAD CONFIGURATION")]
+    pub operation_log: Option<PathBuf>,
     /// After flashing, read back all the flashed data to verify it has been written correctly.
     #[arg(long, help_heading = "DOWNLOAD CONFIGURATION")]
     pub verify: bool,
+    /// Erase all sectors before programming them, even if they are already erased.
+    ///
+    /// By default, each sector is read first, and is not erased again if it is already erased.
+    #[arg(
+        long,
+        visible_alias = "skip-erase-check",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub force_erase: bool,
+    /// How the cores are halted while the flash is programmed.
+    ///
+    /// Only change this if flashing fails intermittently with the default. The possible values
+    /// describe which targets benefit from them.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub flash_halt_mode: FlashHaltMode,
+    #[clap(flatten)]
+    pub option_bytes: OptionBytesOptions,
+}
+
+impl BinaryDownloadOptions {
+    /// Whether the bytes of the programmed sectors which are not overwritten are restored.
+    pub fn restores_unwritten(&self) -> bool {
+        self.restore_unwritten || self.elf_gap_fill == GapFill::Preserve
+    }
 }
 
 /// Supported bit-widths for read/write commands (not every device may support each width).
@@ -52,6 +121,31 @@ pub enum ReadWriteBitWidth {
     B64 = 64,
 }
 
+/// The byte order of the values read or written with read/write commands.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Endianness {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first, e.g. for big-endian peripherals.
+    Big,
+    /// The byte order of the target.
+    #[default]
+    Target,
+}
+
+impl Endianness {
+    /// Converts a value of the given `width` between the byte order of the target and this byte
+    /// order, in both directions.
+    pub fn convert(self, value: u64, width: ReadWriteBitWidth) -> u64 {
+        match (self, width) {
+            // The memory of all supported architectures is accessed in little-endian order.
+            (Self::Little | Self::Target, _) | (Self::Big, ReadWriteBitWidth::B8) => value,
+            (Self::Big, ReadWriteBitWidth::B32) => u64::from((value as u32).swap_bytes()),
+            (Self::Big, ReadWriteBitWidth::B64) => value.swap_bytes(),
+        }
+    }
+}
+
 /// Common options for read/write operations to a target device.
 #[derive(Debug, clap::Parser)]
 pub struct ReadWriteOptions {
@@ -62,10 +156,13 @@ pub struct ReadWriteOptions {
     /// Takes an integer as an argument, and can be specified in decimal (16), hexadecimal (0x10) or octal (0o20) format.
     #[clap(value_parser = parse_u64)]
     pub address: u64,
+    /// The byte order of the values.
+    #[clap(long, value_enum, default_value_t)]
+    pub endianness: Endianness,
 }
 
 /// Common options and logic when interfacing with a [Probe].
-#[derive(clap::Parser, Debug)]
+#[derive(clap::Parser, Debug, Clone)]
 pub struct ProbeOptions {
     #[arg(long, env = "PROBE_RS_CHIP", help_heading = "PROBE CONFIGURATION")]
     pub chip: Option<String>,
@@ -81,9 +178,12 @@ pub struct ProbeOptions {
     #[arg(long, env = "PROBE_RS_PROTOCOL", help_heading = "PROBE CONFIGURATION")]
     pub protocol: Option<WireProtocol>,
 
-    /// Disable interactive probe selection
+    /// Disable interactive probe and chip selection
+    ///
+    /// Probes and chips are only selected interactively if stdin and stdout are terminals.
     #[arg(
         long,
+        visible_alias = "no-interactive",
         env = "PROBE_RS_NON_INTERACTIVE",
         help_heading = "PROBE CONFIGURATION"
     )]
@@ -95,6 +195,17 @@ pub struct ProbeOptions {
     /// probe with the same VID:PID.",
     #[arg(long, env = "PROBE_RS_PROBE", help_heading = "PROBE CONFIGURATION")]
     pub probe: Option<DebugProbeSelector>,
+    /// Use this flag to select the probe with the given index in the list printed by
+    /// `probe-rs list`.
+    ///
+    /// This is useful if multiple identical probes without a serial number are connected.
+    #[arg(
+        long,
+        env = "PROBE_RS_PROBE_INDEX",
+        conflicts_with = "probe",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub probe_index: Option<usize>,
     /// The protocol speed in kHz.
     #[arg(long, env = "PROBE_RS_SPEED", help_heading = "PROBE CONFIGURATION")]
     pub speed: Option<u32>,
@@ -106,6 +217,28 @@ pub struct ProbeOptions {
         help_heading = "PROBE CONFIGURATION"
     )]
     pub connect_under_reset: bool,
+    /// Use this flag to attach while the chip is held in reset, and release the reset into
+    /// a halted state.
+    ///
+    /// Unlike `--connect-under-reset`, this verifies that all cores are halted after the reset
+    /// was released, and resets and halts them again if they are not. This is required for
+    /// chips which gate the debug clock in deep sleep.
+    #[arg(
+        long,
+        env = "PROBE_RS_ATTACH_UNDER_RESET",
+        conflicts_with = "connect_under_reset",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub attach_under_reset: bool,
+    /// Use this flag to enable the target power supply of the probe before attaching.
+    ///
+    /// This is only supported by some probes.
+    #[arg(
+        long,
+        env = "PROBE_RS_POWER_TARGET",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub power_target: bool,
     #[arg(long, env = "PROBE_RS_DRY_RUN", help_heading = "PROBE CONFIGURATION")]
     pub dry_run: bool,
     /// Use this flag to allow all memory, including security keys and 3rd party
@@ -116,6 +249,112 @@ pub struct ProbeOptions {
         help_heading = "PROBE CONFIGURATION"
     )]
     pub allow_erase_all: bool,
+    /// Unlock the debug access of a locked chip with the key in this file, instead of erasing it.
+    ///
+    /// Supported for the nRF5340 and nRF9160, which are unlocked with the 4 byte key the firmware
+    /// wrote to the `APPROTECT.DISABLE` and `SECUREAPPROTECT.DISABLE` registers of the CTRL-AP. The
+    /// file contains the raw key in little-endian byte order. If the key does not unlock the chip,
+    /// it is only erased with `--allow-erase-all`.
+    #[arg(long, value_name = "PATH", help_heading = "PROBE CONFIGURATION")]
+    pub debug_auth_key: Option<PathBuf>,
+    /// Fail instead of warning if the identification read from the target does not match
+    /// the chip selected with `--chip`.
+    #[arg(
+        long,
+        env = "PROBE_RS_STRICT_CHIP",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub strict_chip: bool,
+    /// Select the target with this TARGETSEL value on an SWD multidrop bus, where several
+    /// targets share the SWD lines.
+    ///
+    /// The value is written to the TARGETSEL register of the debug port when connecting, so
+    /// only the matching target responds. It is used instead of the debug port in the chip
+    /// description, so all cores of the chip must share one debug port. This requires SWD and a
+    /// debug port version 2.
+    #[arg(
+        long,
+        value_name = "TARGETSEL",
+        value_parser = parse_u32,
+        requires = "chip",
+        env = "PROBE_RS_SWD_MULTIDROP_TARGET",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub swd_multidrop_target: Option<u32>,
+    /// The time to wait for a core to halt, in milliseconds.
+    ///
+    /// If the core does not halt in time, the state of the core is reported, e.g. whether it
+    /// is sleeping or locked up.
+    #[arg(
+        long,
+        value_name = "MS",
+        env = "PROBE_RS_HALT_TIMEOUT",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub halt_timeout: Option<u64>,
+    /// The time to wait after resetting the target before accessing it again, in milliseconds.
+    ///
+    /// Some targets need time to settle after a reset before the debug port responds reliably.
+    /// The delay is inserted between asserting the reset and the next access to the target, for
+    /// every reset of a Cortex-M core, e.g. by `reset`, `run`, `download` and `gdb`.
+    #[arg(
+        long,
+        value_name = "MS",
+        env = "PROBE_RS_RESET_DELAY",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub reset_delay: Option<u64>,
+    /// The time to wait after attaching to the target before accessing it, in milliseconds.
+    ///
+    /// This gives targets time to settle after `--connect-under-reset` or `--attach-under-reset`
+    /// released the reset, before e.g. flashing them.
+    #[arg(
+        long,
+        value_name = "MS",
+        env = "PROBE_RS_POST_ATTACH_DELAY",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub post_attach_delay: Option<u64>,
+    /// Use this flag to stop all cores at the reset vector when connecting, before the first
+    /// instruction of the firmware.
+    ///
+    /// This rescues targets whose firmware disables the debug port or corrupts the flash
+    /// shortly after booting. With `--connect-under-reset`, the reset vector catch is enabled
+    /// while the target is held in reset, so no code runs at all. Otherwise, all cores are
+    /// halted after attaching, and the target is reset with the catch enabled. The cores stay
+    /// halted until a command resumes them. Cores which do not support catching the reset
+    /// vector are left halted.
+    #[arg(
+        long,
+        env = "PROBE_RS_RESET_VECTOR_CATCH_ON_CONNECT",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub reset_vector_catch_on_connect: bool,
+    /// Use this flag to connect without resetting the target, and without halting or resuming
+    /// its cores, e.g. to inspect a device which is hung.
+    ///
+    /// `attach`, `gdb`, `profile`, `trace`, `read`, `write` and `debug` leave the target running
+    /// as it was found. `download`, `erase`, `reset`, and `run` with flashing or tests fail
+    /// instead of resetting the target. Attaching to RISC-V and Xtensa cores still halts them.
+    #[arg(
+        long,
+        env = "PROBE_RS_NO_RESET",
+        conflicts_with_all = ["connect_under_reset", "attach_under_reset", "reset_vector_catch_on_connect"],
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub no_reset: bool,
+    /// Use this flag to halt all cores whenever one of them halts, e.g. at a breakpoint, so the
+    /// other cores do not keep running while one is inspected.
+    ///
+    /// The Cortex-M cores are connected through their CoreSight cross trigger interfaces (CTI).
+    /// Cores without a CTI keep running. Resuming a core, e.g. when `run` continues after a
+    /// semihosting call, resumes the cores which halted with it as well.
+    #[arg(
+        long,
+        env = "PROBE_RS_CROSS_HALT",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub cross_halt: bool,
 }
 
 impl ProbeOptions {
@@ -129,18 +368,46 @@ impl ProbeOptions {
         self,
         lister: &Lister,
     ) -> Result<(Session, LoadedProbeOptions), OperationError> {
-        let common_options = self.load()?;
+        let mut common_options = self.load()?;
+
+        if common_options.is_interactive()
+            && common_options.0.probe.is_none()
+            && common_options.0.probe_index.is_none()
+            && !common_options.0.dry_run
+        {
+            // Remember the selection, so the same probe is opened again if the chip has to be
+            // selected after a failed attach.
+            let list = lister.list_all();
+            if list.len() > 1 {
+                common_options.0.probe_index =
+                    Some(LoadedProbeOptions::interactive_probe_select(&list)?);
+            }
+        }
 
         let target = common_options.get_target_selector()?;
         let probe = common_options.attach_probe(lister)?;
-        let session = common_options.attach_session(probe, target)?;
+        let session = match common_options.attach_session(probe, target) {
+            Err(OperationError::AttachingFailed {
+                source: probe_rs::Error::ChipNotFound(RegistryError::ChipAutodetectFailed),
+                ..
+            }) if common_options.is_interactive() && common_options.0.chip.is_none() => {
+                println!("The chip could not be detected automatically.");
+                common_options.0.chip = Some(LoadedProbeOptions::interactive_chip_select()?);
+
+                // The failed attach consumed the probe, so it has to be opened again.
+                let target = common_options.get_target_selector()?;
+                let probe = common_options.attach_probe(lister)?;
+                common_options.attach_session(probe, target)?
+            }
+            result => result?,
+        };
 
         Ok((session, common_options))
     }
 }
 
 /// Common options and logic when interfacing with a [Probe] which already did all pre operation preparation.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LoadedProbeOptions(ProbeOptions);
 
 impl LoadedProbeOptions {
@@ -179,13 +446,17 @@ impl LoadedProbeOptions {
     /// Resolves a resultant target selector from passed [ProbeOptions].
     pub fn get_target_selector(&self) -> Result<TargetSelector, OperationError> {
         let target = if let Some(chip_name) = &self.0.chip {
-            let target = probe_rs::config::get_target_by_name(chip_name).map_err(|error| {
+            let mut target = probe_rs::config::get_target_by_name(chip_name).map_err(|error| {
                 OperationError::ChipNotFound {
                     source: error,
                     name: chip_name.clone(),
                 }
             })?;
 
+            if let Some(target_sel) = self.0.swd_multidrop_target {
+                select_multidrop_target(&mut target, target_sel)?;
+            }
+
             TargetSelector::Specified(target)
         } else {
             TargetSelector::Auto
@@ -194,10 +465,13 @@ impl LoadedProbeOptions {
         Ok(target)
     }
 
-    /// Allow for a stdin selection of the given probes
-    fn interactive_probe_select(
-        list: &[DebugProbeInfo],
-    ) -> Result<&DebugProbeInfo, OperationError> {
+    /// Whether probes and chips may be selected interactively.
+    fn is_interactive(&self) -> bool {
+        !self.0.non_interactive && std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+    }
+
+    /// Allow for a stdin selection of the given probes, returns the index of the selected probe.
+    fn interactive_probe_select(list: &[DebugProbeInfo]) -> Result<usize, OperationError> {
         println!("Available Probes:");
         for (i, probe_info) in list.iter().enumerate() {
             println!("{i}: {probe_info}");
@@ -216,22 +490,153 @@ impl LoadedProbeOptions {
             .parse::<usize>()
             .map_err(OperationError::ParseProbeIndex)?;
 
-        list.get(probe_idx).ok_or(OperationError::NoProbesFound)
+        if probe_idx >= list.len() {
+            return Err(OperationError::ProbeIndexOutOfRange {
+                index: probe_idx,
+                count: list.len(),
+            });
+        }
+
+        Ok(probe_idx)
+    }
+
+    /// Allow for a stdin selection of the chip, by searching the chips for the entered name.
+    fn interactive_chip_select() -> Result<String, OperationError> {
+        loop {
+            print!("Enter the beginning of the chip name, e.g. `nRF52840` or `STM32F4`: ");
+            std::io::stdout().flush()?;
+
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            let input = input.trim();
+            if input.is_empty() {
+                return Err(OperationError::NoChipSelected);
+            }
+
+            let mut chips = probe_rs::config::search_chips(input).unwrap_or_default();
+            chips.sort();
+
+            match chips.len() {
+                0 => println!("No chip starts with '{input}'."),
+                1 => {
+                    println!("Selected {}.", chips[0]);
+                    return Ok(chips.swap_remove(0));
+                }
+                count if count > MAX_CHIP_SUGGESTIONS => {
+                    println!("{count} chips start with '{input}', enter a longer name.")
+                }
+                _ => {
+                    println!("Matching chips:");
+                    for (i, chip) in chips.iter().enumerate() {
+                        println!("{i}: {chip}");
+                    }
+
+                    print!("Selection: ");
+                    std::io::stdout().flush()?;
+
+                    let mut input = String::new();
+                    std::io::stdin().read_line(&mut input)?;
+
+                    match input.trim().parse::<usize>() {
+                        Ok(index) if index < chips.len() => return Ok(chips.swap_remove(index)),
+                        _ => println!("Invalid selection."),
+                    }
+                }
+            }
+        }
     }
 
     /// Selects a probe from a list of probes.
     /// If there is only one probe, it will be selected automatically.
     /// If there are multiple probes, the user will be prompted to select one unless
     /// started in non-interactive mode.
-    fn select_probe(lister: &Lister, non_interactive: bool) -> Result<Probe, OperationError> {
+    ///
+    /// If an index is given, the probe at that position in the list is selected.
+    fn select_probe(
+        lister: &Lister,
+        non_interactive: bool,
+        index: Option<usize>,
+    ) -> Result<Probe, OperationError> {
+        let probe_info = Self::select_probe_info(lister, non_interactive, index)?;
+        Ok(lister.open(probe_info)?)
+    }
+
+    /// Selects a probe from a list of probes like [`Self::select_probe`], without opening it.
+    fn select_probe_info(
+        lister: &Lister,
+        non_interactive: bool,
+        index: Option<usize>,
+    ) -> Result<DebugProbeInfo, OperationError> {
+        let mut list = lister.list_all();
+        let index = match (list.len(), index) {
+            (0, _) => return Err(OperationError::NoProbesFound),
+            (count, Some(index)) if index >= count => {
+                return Err(OperationError::ProbeIndexOutOfRange { index, count })
+            }
+            (_, Some(index)) => index,
+            (1, None) => 0,
+            _ if non_interactive => return Err(OperationError::MultipleProbesFound { list }),
+            _ => Self::interactive_probe_select(&list)?,
+        };
+
+        Ok(list.swap_remove(index))
+    }
+
+    /// Returns the info of the selected probe, without opening it, e.g. for probes which do not
+    /// respond anymore.
+    pub fn probe_info(&self, lister: &Lister) -> Result<DebugProbeInfo, OperationError> {
+        match &self.0.probe {
+            Some(selector) => lister
+                .list_all()
+                .into_iter()
+                .find(|probe| {
+                    probe.vendor_id == selector.vendor_id
+                        && probe.product_id == selector.product_id
+                        && selector
+                            .serial_number
+                            .as_ref()
+                            .map_or(true, |serial| probe.serial_number.as_ref() == Some(serial))
+                })
+                .ok_or(OperationError::NoProbesFound),
+            None => Self::select_probe_info(lister, !self.is_interactive(), self.0.probe_index),
+        }
+    }
+
+    /// Returns the serial number of the selected probe, if it has one.
+    ///
+    /// The probes are listed again, so this is a best effort if probes were connected since.
+    pub fn probe_serial(&self, lister: &Lister) -> Option<String> {
+        if self.0.dry_run {
+            return None;
+        }
+        if let Some(serial) = self.0.probe.as_ref().and_then(|s| s.serial_number.clone()) {
+            return Some(serial);
+        }
+
         let list = lister.list_all();
-        let selected = match list.len() {
-            0 | 1 => list.first().ok_or(OperationError::NoProbesFound),
-            _ if non_interactive => Err(OperationError::MultipleProbesFound { list }),
-            _ => Self::interactive_probe_select(&list),
+        let probe = match (&self.0.probe, self.0.probe_index) {
+            (Some(selector), _) => list.iter().find(|probe| {
+                probe.vendor_id == selector.vendor_id && probe.product_id == selector.product_id
+            }),
+            (None, Some(index)) => list.get(index),
+            (None, None) if list.len() == 1 => list.first(),
+            (None, None) => None,
         };
 
-        selected.and_then(|probe_info| Ok(lister.open(probe_info)?))
+        probe.and_then(|probe| probe.serial_number.clone())
+    }
+
+    /// Selects the probe at `index` in the list of all probes, instead of the probe given on the
+    /// command line.
+    pub fn set_probe_index(&mut self, index: usize) {
+        self.0.probe = None;
+        self.0.probe_index = Some(index);
+    }
+
+    /// Selects the probe matching `selector`, instead of the probe given on the command line.
+    pub fn set_probe(&mut self, selector: DebugProbeSelector) {
+        self.0.probe = Some(selector);
+        self.0.probe_index = None;
     }
 
     /// Attaches to specified probe and configures it.
@@ -243,10 +648,19 @@ impl LoadedProbeOptions {
             // matching the selector if possible.
             match &self.0.probe {
                 Some(selector) => lister.open(selector)?,
-                None => Self::select_probe(lister, self.0.non_interactive)?,
+                None => Self::select_probe(lister, !self.is_interactive(), self.0.probe_index)?,
             }
         };
 
+        if self.0.power_target && !self.0.dry_run {
+            probe
+                .set_target_power(true)
+                .map_err(OperationError::FailedToSetTargetPower)?;
+
+            // Give the target some time to power up before connecting.
+            std::thread::sleep(TARGET_POWER_SETTLE_TIME);
+        }
+
         if let Some(protocol) = self.0.protocol {
             // Select protocol and speed
             probe.select_protocol(protocol).map_err(|error| {
@@ -285,7 +699,8 @@ impl LoadedProbeOptions {
     }
 
     /// Attaches to target device session. Attaches under reset if
-    /// specified by [ProbeOptions::connect_under_reset].
+    /// specified by [ProbeOptions::connect_under_reset] or [ProbeOptions::attach_under_reset].
+    #[tracing::instrument(name = "attach", skip_all)]
     pub fn attach_session(
         &self,
         probe: Probe,
@@ -295,35 +710,139 @@ impl LoadedProbeOptions {
         if self.0.allow_erase_all {
             permissions = permissions.allow_erase_all();
         }
+        if let Some(path) = &self.0.debug_auth_key {
+            let key =
+                std::fs::read(path).map_err(|source| OperationError::DebugAuthKeyNotFound {
+                    source,
+                    path: path.clone(),
+                })?;
+            permissions = permissions.with_debug_auth_key(key);
+        }
 
-        let session = if self.0.connect_under_reset {
+        // Attaching under reset already catches the reset vector, and verifies that it was caught
+        // with `attach_under_reset_halted`.
+        let catch_under_reset = self.0.reset_vector_catch_on_connect && self.connect_under_reset();
+        let mut session = if self.0.attach_under_reset || catch_under_reset {
+            probe.attach_under_reset_halted(target, permissions)
+        } else if self.0.connect_under_reset {
             probe.attach_under_reset(target, permissions)
         } else {
             probe.attach(target, permissions)
         }
         .map_err(|error| OperationError::AttachingFailed {
             source: error,
-            connect_under_reset: self.0.connect_under_reset,
+            connect_under_reset: self.connect_under_reset(),
         })?;
 
+        session.set_reset_delay(self.reset_delay());
+
+        if let Some(delay) = self.0.post_attach_delay {
+            tracing::debug!("Waiting {delay} ms after attaching");
+            std::thread::sleep(Duration::from_millis(delay));
+        }
+
+        if let Some(chip) = self.0.chip.as_deref().filter(|_| !self.0.dry_run) {
+            self.check_chip(&mut session, chip)?;
+        }
+
+        if self.0.reset_vector_catch_on_connect && !catch_under_reset {
+            self.catch_reset(&mut session)?;
+        }
+
+        if self.0.cross_halt {
+            let cores = session
+                .enable_cross_halt()
+                .map_err(OperationError::CrossHaltFailed)?;
+            if cores.len() < 2 {
+                tracing::warn!(
+                    "Only {} core(s) of the target have a CTI, halting a core does not halt any other core",
+                    cores.len()
+                );
+            } else {
+                tracing::info!("Cores {cores:?} are halted together");
+            }
+        }
+
         Ok(session)
     }
 
+    /// Stops all cores at their reset vector.
+    fn catch_reset(&self, session: &mut Session) -> Result<(), OperationError> {
+        let timeout = self.halt_timeout(DEFAULT_HALT_TIMEOUT);
+
+        for (index, caught) in halt::catch_reset(session, timeout)? {
+            if caught {
+                tracing::info!("Core {index} was stopped at the reset vector");
+            } else {
+                tracing::warn!("Core {index} does not support catching the reset vector, it was halted instead");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Warns if the target does not identify as the selected chip, or fails with `--strict-chip`.
+    fn check_chip(&self, session: &mut Session, chip: &str) -> Result<(), OperationError> {
+        match check_chip(session, chip) {
+            ChipCheck::Match | ChipCheck::Unknown => {}
+            ChipCheck::Ambiguous(reason) => {
+                tracing::warn!("The target might not be a {chip}: {reason}.");
+            }
+            ChipCheck::Mismatch(reason) if self.0.strict_chip => {
+                return Err(OperationError::ChipMismatch {
+                    name: chip.to_string(),
+                    reason,
+                });
+            }
+            ChipCheck::Mismatch(reason) => {
+                tracing::warn!("The connected target does not match the chip '{chip}': {reason}.");
+                tracing::warn!("Flashing the wrong chip can make the target unusable. Check the `--chip` option, or use `--strict-chip` to turn this warning into an error.");
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn protocol(&self) -> Option<WireProtocol> {
         self.0.protocol
     }
 
     pub(crate) fn connect_under_reset(&self) -> bool {
-        self.0.connect_under_reset
+        self.0.connect_under_reset || self.0.attach_under_reset
     }
 
     pub(crate) fn dry_run(&self) -> bool {
         self.0.dry_run
     }
 
+    pub(crate) fn no_reset(&self) -> bool {
+        self.0.no_reset
+    }
+
+    /// Fails with `--no-reset`, because `action` resets the target.
+    pub(crate) fn ensure_reset_allowed(&self, action: &'static str) -> Result<(), OperationError> {
+        if self.0.no_reset {
+            return Err(OperationError::ResetNotAllowed { action });
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn chip(&self) -> Option<String> {
         self.0.chip.clone()
     }
+
+    /// The time to wait for a core to halt, `default` if none was given.
+    pub(crate) fn halt_timeout(&self, default: Duration) -> Duration {
+        self.0.halt_timeout.map_or(default, Duration::from_millis)
+    }
+
+    /// The time to wait after resetting the target, see `--reset-delay`.
+    pub(crate) fn reset_delay(&self) -> Duration {
+        self.0
+            .reset_delay
+            .map_or(Duration::ZERO, Duration::from_millis)
+    }
 }
 
 impl AsRef<ProbeOptions> for LoadedProbeOptions {
@@ -465,9 +984,16 @@ pub enum OperationError {
     #[error("Failed to open the debug probe.")]
     FailedToOpenProbe(#[from] DebugProbeError),
 
-    #[error("{} probes were found: {}", .list.len(), print_list(.list))]
+    #[error(
+        "{} probes were found:{}\nSelect one with `--probe-index <index>` or `--probe VID:PID:Serial`.",
+        .list.len(),
+        print_probe_list(.list)
+    )]
     MultipleProbesFound { list: Vec<DebugProbeInfo> },
 
+    #[error("The probe index {index} is out of range, only {count} probes were found.")]
+    ProbeIndexOutOfRange { index: usize, count: usize },
+
     #[error("The flashing procedure failed for '{path}'.")]
     FlashingFailed {
         source: FlashError,
@@ -482,6 +1008,12 @@ pub enum OperationError {
         path: PathBuf,
     },
 
+    #[error("Failed to read the debug authentication key '{path}'.")]
+    DebugAuthKeyNotFound {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
     #[error("Failed to parse the chip description '{path}'.")]
     FailedChipDescriptionParsing {
         source: RegistryError,
@@ -521,30 +1053,100 @@ pub enum OperationError {
         connect_under_reset: bool,
     },
 
+    #[error("The connected target does not match the chip '{name}': {reason}.")]
+    ChipMismatch { name: String, reason: String },
+
+    #[error("The access port {ap} could not be selected: {reason}.")]
+    AccessPortNotAvailable { ap: u8, reason: String },
+
     #[error("Failed to get a handle to the first core.")]
     AttachingToCoreFailed(#[source] probe_rs::Error),
 
+    #[error("Failed to switch the target power supply of the probe.")]
+    FailedToSetTargetPower(#[source] DebugProbeError),
+
     #[error("The reset of the target failed.")]
     TargetResetFailed(#[source] probe_rs::Error),
 
     #[error("The target could not be reset and halted.")]
     TargetResetHaltFailed(#[source] probe_rs::Error),
 
+    #[error("The core could not be halted.")]
+    HaltFailed(#[source] probe_rs::Error),
+
+    #[error("The core could not be stopped at the reset vector.")]
+    ResetVectorCatchFailed(#[source] probe_rs::Error),
+
+    #[error("The core did not halt within {timeout:?} ({status}). {hint}")]
+    HaltTimeout {
+        timeout: Duration,
+        status: String,
+        hint: &'static str,
+    },
+
+    #[error("The cores could not be connected through their cross trigger interfaces.")]
+    CrossHaltFailed(#[source] probe_rs::Error),
+
+    #[error("`--no-reset` was given, but {action} resets the target.")]
+    ResetNotAllowed { action: &'static str },
+
+    #[error("Failed to preserve or set the option bytes.")]
+    OptionBytes(#[from] OptionBytesError),
+
     #[error("Failed to write to file")]
     IOError(#[source] std::io::Error),
 
+    #[error("Failed to write the operation log '{path}'.")]
+    FailedToWriteOperationLog {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
     #[error("Failed to parse CLI arguments.")]
     CliArgument(#[from] clap::Error),
     #[error("Failed to parse interactive probe index selection")]
     ParseProbeIndex(#[source] std::num::ParseIntError),
+    #[error("No chip was selected.")]
+    NoChipSelected,
+    #[error("The cores of {chip} are on different debug ports, so they can not be selected with one TARGETSEL value.")]
+    MultidropTargetForMultipleDebugPorts { chip: String },
 }
 
-/// Used in errors it can print a list of items.
-fn print_list(list: &[impl std::fmt::Display]) -> String {
+/// Connects to the ARM cores of the `target` through the debug port with the TARGETSEL value
+/// `target_sel`, instead of the debug port given in the target description.
+///
+/// This fails if the cores are on different debug ports, like the two cores of the RP2040,
+/// because one TARGETSEL value can only select one of them.
+fn select_multidrop_target(target: &mut Target, target_sel: u32) -> Result<(), OperationError> {
+    let mut psels = target
+        .cores
+        .iter()
+        .filter_map(|core| match &core.core_access_options {
+            CoreAccessOptions::Arm(options) => Some(options.psel),
+            _ => None,
+        });
+    let first = psels.next();
+    if psels.any(|psel| Some(psel) != first) {
+        return Err(OperationError::MultidropTargetForMultipleDebugPorts {
+            chip: target.name.clone(),
+        });
+    }
+
+    for core in &mut target.cores {
+        if let CoreAccessOptions::Arm(options) = &mut core.core_access_options {
+            options.psel = target_sel;
+        }
+    }
+
+    Ok(())
+}
+
+/// Used in errors to print a list of probes, numbered like `--probe-index` expects.
+fn print_probe_list(list: &[DebugProbeInfo]) -> String {
     let mut output = String::new();
 
     for (i, entry) in list.iter().enumerate() {
-        output.push_str(&format!("\n    {}. {}", i + 1, entry));
+        output.push_str(&format!("\n    [{i}]: {entry}"));
     }
 
     output
@@ -560,6 +1162,41 @@ impl From<std::io::Error> for OperationError {
 mod tests {
     use super::*;
 
+    #[test]
+    fn big_endian_values_are_swapped() {
+        assert_eq!(
+            Endianness::Big.convert(0x1122_3344, ReadWriteBitWidth::B32),
+            0x4433_2211
+        );
+        assert_eq!(
+            Endianness::Big.convert(0x1122_3344_5566_7788, ReadWriteBitWidth::B64),
+            0x8877_6655_4433_2211
+        );
+        assert_eq!(Endianness::Big.convert(0x11, ReadWriteBitWidth::B8), 0x11);
+        assert_eq!(
+            Endianness::Target.convert(0x1122_3344, ReadWriteBitWidth::B32),
+            0x1122_3344
+        );
+    }
+
+    #[test]
+    fn multidrop_target_replaces_the_debug_port() {
+        let mut target = probe_rs::config::get_target_by_name("nRF52840_xxAA").unwrap();
+        select_multidrop_target(&mut target, 0x1100_2927).unwrap();
+
+        let CoreAccessOptions::Arm(options) = &target.cores[0].core_access_options else {
+            panic!("The nRF52840 has an ARM core");
+        };
+        assert_eq!(options.psel, 0x1100_2927);
+
+        // The cores of the RP2040 are on two debug ports.
+        let mut target = probe_rs::config::get_target_by_name("RP2040").unwrap();
+        assert!(matches!(
+            select_multidrop_target(&mut target, 0x1100_2927),
+            Err(OperationError::MultidropTargetForMultipleDebugPorts { .. })
+        ));
+    }
+
     #[test]
     fn to_cargo_options() {
         assert_eq!(
@@ -596,4 +1233,49 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn multiple_probes_error_lists_indices() {
+        let probe = |serial: &str| {
+            DebugProbeInfo::new(
+                "J-Link",
+                0x1366,
+                0x0101,
+                Some(serial.to_string()),
+                &probe_rs::probe::jlink::JLinkFactory,
+                None,
+            )
+        };
+        let error = OperationError::MultipleProbesFound {
+            list: vec![probe("123"), probe("456")],
+        };
+
+        let message = error.to_string();
+        assert!(message.contains("[0]: J-Link -- 1366:0101:123"));
+        assert!(message.contains("[1]: J-Link -- 1366:0101:456"));
+        assert!(message.contains("--probe-index <index>"));
+    }
+
+    #[test]
+    fn no_reset_forbids_resets() {
+        use clap::Parser;
+
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[clap(flatten)]
+            probe_options: ProbeOptions,
+        }
+
+        assert!(Cli::try_parse_from(["test", "--no-reset", "--connect-under-reset"]).is_err());
+
+        let options = Cli::try_parse_from(["test", "--no-reset"])
+            .unwrap()
+            .probe_options
+            .load()
+            .unwrap();
+        assert!(matches!(
+            options.ensure_reset_allowed("flashing"),
+            Err(OperationError::ResetNotAllowed { action: "flashing" })
+        ));
+    }
 }
@@ -0,0 +1,275 @@
+//! Reading the ELF files of images: looking up their symbols and sections, and identifying them
+//! by the GNU build ID and the `.comment` section.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use goblin::elf::section_header::{SHF_ALLOC, SHT_NOTE};
+use goblin::elf::sym::{Sym, STT_FUNC};
+use goblin::elf::Elf;
+use probe_rs::MemoryInterface;
+
+/// The type of the note which contains the build ID.
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// The offset of the build ID in its note, after the header and the name `GNU`.
+const BUILD_ID_OFFSET: usize = 16;
+
+/// The contents of an ELF file.
+pub struct ElfFile {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+impl ElfFile {
+    /// Reads the ELF file at `path`.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read the ELF file {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            data,
+        })
+    }
+
+    /// The raw contents of the file.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Parses the file.
+    pub fn parse(&self) -> anyhow::Result<Elf<'_>> {
+        Elf::parse(&self.data).map_err(|e| {
+            anyhow::anyhow!("Failed to parse the ELF file {}: {e}", self.path.display())
+        })
+    }
+
+    /// Returns the address of the symbol `name`, see [`symbol_address`].
+    pub fn symbol_address(&self, name: &str) -> anyhow::Result<Option<u64>> {
+        let elf = self.parse()?;
+
+        Ok(find_symbol(&elf, name).map(|sym| symbol_address(&sym)))
+    }
+}
+
+/// Finds the symbol `name` in the symbol table.
+pub fn find_symbol(elf: &Elf, name: &str) -> Option<Sym> {
+    elf.syms
+        .iter()
+        .find(|sym| elf.strtab.get_at(sym.st_name) == Some(name))
+}
+
+/// Returns the address of a symbol.
+///
+/// The lowest bit of Thumb function addresses is set, and is not part of the address.
+pub fn symbol_address(sym: &Sym) -> u64 {
+    if sym.st_type() == STT_FUNC {
+        sym.st_value & !1
+    } else {
+        sym.st_value
+    }
+}
+
+/// Finds the address of the section `name`.
+pub fn section_address(elf: &Elf, name: &str) -> Option<u64> {
+    elf.section_headers
+        .iter()
+        .find(|section| elf.shdr_strtab.get_at(section.sh_name) == Some(name))
+        .map(|section| section.sh_addr)
+}
+
+/// The identification of an image, read from its ELF file.
+#[derive(Debug, Default)]
+pub struct ElfIdentity {
+    /// The GNU build ID note, if the image has one.
+    pub build_id: Option<BuildIdNote>,
+    /// The strings of the `.comment` section, usually the compiler and linker versions.
+    pub comments: Vec<String>,
+}
+
+/// The GNU build ID note of an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildIdNote {
+    /// The build ID.
+    pub id: Vec<u8>,
+    /// The complete note, including its header, as it is stored in the image.
+    pub note: Vec<u8>,
+    /// The address of the note in the memory of the target, if the note is part of the image.
+    pub address: Option<u64>,
+}
+
+impl BuildIdNote {
+    /// The build ID as a hexadecimal string.
+    pub fn hex(&self) -> String {
+        hex(&self.id)
+    }
+}
+
+impl ElfIdentity {
+    /// Reads the build ID and the comments of the ELF file at `path`.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let file = ElfFile::read(path)?;
+        let elf = file.parse()?;
+        let data = file.data();
+
+        let mut identity = Self::default();
+        for section in &elf.section_headers {
+            let Some(bytes) = data.get(section.file_range().unwrap_or_default()) else {
+                continue;
+            };
+
+            if section.sh_type == SHT_NOTE && identity.build_id.is_none() {
+                identity.build_id =
+                    find_build_id(bytes, elf.little_endian).map(|(offset, id, note)| BuildIdNote {
+                        id,
+                        note,
+                        address: (section.sh_flags & SHF_ALLOC as u64 != 0)
+                            .then_some(section.sh_addr + offset as u64),
+                    });
+            } else if elf.shdr_strtab.get_at(section.sh_name) == Some(".comment") {
+                identity.comments = parse_comments(bytes);
+            }
+        }
+
+        Ok(identity)
+    }
+
+    /// Prints the build ID and the comments.
+    pub fn print(&self) {
+        match &self.build_id {
+            Some(build_id) => println!("Build ID: {}", build_id.hex()),
+            None => println!("Build ID: none, link with `--build-id` to add one"),
+        }
+        for comment in &self.comments {
+            println!("Comment: {comment}");
+        }
+    }
+}
+
+/// Reads the note at `address` from the target, and checks whether it matches `build_id`.
+///
+/// Returns the build ID read from the target if it does not match.
+pub fn check_running_build_id(
+    memory: &mut impl MemoryInterface,
+    build_id: &BuildIdNote,
+    address: u64,
+) -> anyhow::Result<Result<(), String>> {
+    let mut note = vec![0; build_id.note.len()];
+    memory
+        .read(address, &mut note)
+        .with_context(|| format!("Failed to read the build ID at {address:#010x}"))?;
+
+    if note == build_id.note {
+        return Ok(Ok(()));
+    }
+
+    // A note of another build of the same image only differs in the ID, which follows the header
+    // and the name.
+    let running = if note[..BUILD_ID_OFFSET] == build_id.note[..BUILD_ID_OFFSET] {
+        hex(&note[BUILD_ID_OFFSET..BUILD_ID_OFFSET + build_id.id.len()])
+    } else {
+        format!("no build ID note ({})", hex(&note))
+    };
+
+    Ok(Err(running))
+}
+
+/// Finds the GNU build ID note in the contents of a note section, and returns its offset in the
+/// section, the ID and the whole note.
+fn find_build_id(section: &[u8], little_endian: bool) -> Option<(usize, Vec<u8>, Vec<u8>)> {
+    let word = |offset: usize| -> Option<u32> {
+        let bytes = section.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    let align = |size: usize| size.next_multiple_of(4);
+
+    let mut offset = 0;
+    while offset + 12 <= section.len() {
+        let name_size = word(offset)? as usize;
+        let desc_size = word(offset + 4)? as usize;
+        let note_type = word(offset + 8)?;
+
+        let name_start = offset + 12;
+        let desc_start = name_start + align(name_size);
+        let end = desc_start + align(desc_size);
+
+        let name = section.get(name_start..name_start + name_size)?;
+        let desc = section.get(desc_start..desc_start + desc_size)?;
+        if note_type == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            let note_end = end.min(section.len());
+            return Some((offset, desc.to_vec(), section[offset..note_end].to_vec()));
+        }
+
+        offset = end;
+    }
+
+    None
+}
+
+/// Splits the `.comment` section into its null-terminated strings.
+fn parse_comments(section: &[u8]) -> Vec<String> {
+    section
+        .split(|&byte| byte == 0)
+        .filter(|comment| !comment.is_empty())
+        .map(|comment| String::from_utf8_lossy(comment).into_owned())
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_id_note_is_found() {
+        let mut section = vec![];
+        // Another note first, with a name which needs padding.
+        for word in [5u32, 4, 1] {
+            section.extend(word.to_le_bytes());
+        }
+        section.extend(b"Test\0\0\0\0");
+        section.extend([1, 2, 3, 4]);
+        let build_id_start = section.len();
+        for word in [4u32, 3, NT_GNU_BUILD_ID] {
+            section.extend(word.to_le_bytes());
+        }
+        section.extend(b"GNU\0");
+        section.extend([0xab, 0xcd, 0xef, 0x00]);
+
+        let (offset, id, note) = find_build_id(&section, true).unwrap();
+        assert_eq!(offset, build_id_start);
+        assert_eq!(id, [0xab, 0xcd, 0xef]);
+        assert_eq!(note, section[build_id_start..]);
+        assert_eq!(find_build_id(&section[..build_id_start], true), None);
+    }
+
+    #[test]
+    fn comments_are_split() {
+        let elf = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../probe-rs/tests/debug-unwind-tests/RP2040_full_unwind.elf"
+        ));
+
+        let identity = ElfIdentity::read(elf).unwrap();
+        assert_eq!(identity.build_id, None);
+        assert_eq!(
+            identity.comments,
+            [
+                "rustc version 1.79.0-nightly (7f2fc33da 2024-04-22)",
+                "Linker: LLD 18.1.4"
+            ]
+        );
+    }
+}
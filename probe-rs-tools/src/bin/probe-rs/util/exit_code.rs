@@ -0,0 +1,144 @@
+//! Exit codes of the probe-rs CLI.
+//!
+//! Failures are mapped to distinct exit codes, so that scripts can react to the kind of
+//! failure without parsing the error message.
+
+use probe_rs::{
+    flashing::FlashError,
+    probe::{DebugProbeError, ProbeCreationError},
+};
+
+use super::common_options::OperationError;
+
+/// Description of all exit codes, shown in the help output.
+pub const EXIT_CODE_HELP: &str = "\
+EXIT CODES:
+  0  Success
+  1  Unspecified failure
+  2  Invalid command line arguments
+  3  No matching debug probe was found
+  4  The target chip was not found
+  5  Attaching to the target failed
+  6  Verifying the flash contents failed
+  7  The target reported a failure (e.g. a failed test, or a semihosting exit with an error)";
+
+/// The exit code of the probe-rs CLI.
+///
+/// See [`EXIT_CODE_HELP`] for a description of the codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Failure = 1,
+    ProbeNotFound = 3,
+    TargetNotFound = 4,
+    AttachFailed = 5,
+    VerifyMismatch = 6,
+    TargetFailure = 7,
+}
+
+impl ExitCode {
+    /// Determines the exit code for an error, based on the errors in its chain of causes.
+    ///
+    /// The more specific causes take precedence, e.g. an attach which failed because the
+    /// target could not be detected results in [`ExitCode::TargetNotFound`].
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let mut exit_code = ExitCode::Failure;
+
+        for cause in error.chain() {
+            let code = if cause.is::<TargetReportedFailure>() {
+                ExitCode::TargetFailure
+            } else if let Some(error) = cause.downcast_ref::<OperationError>() {
+                match error {
+                    OperationError::NoProbesFound | OperationError::ProbeIndexOutOfRange { .. } => {
+                        ExitCode::ProbeNotFound
+                    }
+                    OperationError::ChipNotFound { .. } => ExitCode::TargetNotFound,
+                    OperationError::AttachingFailed { .. }
+                    | OperationError::AttachingToCoreFailed(_) => ExitCode::AttachFailed,
+                    _ => ExitCode::Failure,
+                }
+            } else if let Some(error) = cause.downcast_ref::<DebugProbeError>() {
+                match error {
+                    DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound) => {
+                        ExitCode::ProbeNotFound
+                    }
+                    _ => ExitCode::Failure,
+                }
+            } else if let Some(error) = cause.downcast_ref::<probe_rs::Error>() {
+                match error {
+                    probe_rs::Error::ChipNotFound(_) => ExitCode::TargetNotFound,
+                    _ => ExitCode::Failure,
+                }
+            } else if let Some(FlashError::Verify) = cause.downcast_ref::<FlashError>() {
+                ExitCode::VerifyMismatch
+            } else {
+                ExitCode::Failure
+            };
+
+            if code.precedence() > exit_code.precedence() {
+                exit_code = code;
+            }
+        }
+
+        exit_code
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            ExitCode::Failure => 0,
+            ExitCode::AttachFailed => 1,
+            ExitCode::ProbeNotFound
+            | ExitCode::TargetNotFound
+            | ExitCode::VerifyMismatch
+            | ExitCode::TargetFailure => 2,
+        }
+    }
+}
+
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(code: ExitCode) -> Self {
+        std::process::ExitCode::from(code as u8)
+    }
+}
+
+/// The program running on the target reported a failure.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct TargetReportedFailure(pub String);
+
+#[cfg(test)]
+mod tests {
+    use probe_rs::config::RegistryError;
+
+    use super::*;
+
+    #[test]
+    fn generic_error() {
+        let error = anyhow::anyhow!("Something went wrong");
+        assert_eq!(ExitCode::from_error(&error), ExitCode::Failure);
+    }
+
+    #[test]
+    fn attach_failed_because_chip_not_found() {
+        let error = anyhow::Error::from(OperationError::AttachingFailed {
+            source: probe_rs::Error::ChipNotFound(RegistryError::ChipAutodetectFailed),
+            connect_under_reset: false,
+        });
+        assert_eq!(ExitCode::from_error(&error), ExitCode::TargetNotFound);
+    }
+
+    #[test]
+    fn attach_failed() {
+        let error = anyhow::Error::from(OperationError::AttachingFailed {
+            source: probe_rs::Error::Other(anyhow::anyhow!("Timeout")),
+            connect_under_reset: false,
+        });
+        assert_eq!(ExitCode::from_error(&error), ExitCode::AttachFailed);
+    }
+
+    #[test]
+    fn target_failure_with_context() {
+        let error = anyhow::Error::from(TargetReportedFailure("Some tests failed".to_string()))
+            .context("Running the binary failed");
+        assert_eq!(ExitCode::from_error(&error), ExitCode::TargetFailure);
+    }
+}
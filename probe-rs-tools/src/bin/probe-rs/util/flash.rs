@@ -2,23 +2,120 @@ use crate::FormatOptions;
 
 use super::common_options::{BinaryDownloadOptions, LoadedProbeOptions, OperationError};
 use super::logging;
+use super::operation_log::OperationLog;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fs::File;
+use std::rc::Rc;
 use std::time::Duration;
 use std::{path::Path, time::Instant};
 
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use probe_rs::flashing::FlashLayout;
-use probe_rs::InstructionSet;
+use probe_rs::flashing::{FlashError, FlashLayout};
 use probe_rs::{
     flashing::{DownloadOptions, FileDownloadError, FlashLoader, FlashProgress, ProgressEvent},
     Session,
 };
+use probe_rs::{InstructionSet, Target};
 
 use anyhow::Context;
 
+/// How the flash is erased before it is programmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseMode {
+    /// Erase only the sectors which are programmed.
+    Sector,
+    /// Erase the entire chip, or the programmed sectors if the flash algorithm does not support it.
+    ChipIfSupported,
+    /// Erase the entire chip, and fail if the flash algorithm does not support it.
+    Chip,
+}
+
+impl EraseMode {
+    /// The erase mode for a `--chip-erase` flag.
+    pub fn from_chip_erase_flag(chip_erase: bool) -> Self {
+        if chip_erase {
+            Self::ChipIfSupported
+        } else {
+            Self::Sector
+        }
+    }
+}
+
+/// How the cores of the target are halted while the flash is programmed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FlashHaltMode {
+    /// Reset and halt the core which runs the flash algorithm.
+    #[default]
+    ResetAndHalt,
+    /// Halt the core which runs the flash algorithm without resetting it, to keep the clock and
+    /// power configuration of the firmware.
+    Halt,
+    /// Reset and halt the core which runs the flash algorithm, and halt all other cores, for
+    /// multi-core targets whose other cores access the flash, such as the RP2040.
+    HaltAllCores,
+}
+
+impl From<FlashHaltMode> for probe_rs::flashing::FlashHaltMode {
+    fn from(mode: FlashHaltMode) -> Self {
+        match mode {
+            FlashHaltMode::ResetAndHalt => Self::ResetAndHalt,
+            FlashHaltMode::Halt => Self::Halt,
+            FlashHaltMode::HaltAllCores => Self::HaltAllCores,
+        }
+    }
+}
+
+/// What the erased bytes of the programmed sectors which the image does not cover are set to,
+/// e.g. the gaps between two load segments of an ELF file in the same sector.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GapFill {
+    /// Leave the bytes erased. This is the default.
+    #[default]
+    Erased,
+    /// Read the bytes before the sector is erased, and program them again.
+    Preserve,
+    /// Program the bytes with this value.
+    Value(u8),
+}
+
+impl GapFill {
+    /// Parses `erased`, `preserve` or a byte value, for `--elf-gap-fill`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "erased" => Ok(Self::Erased),
+            "preserve" => Ok(Self::Preserve),
+            value => parse_int::parse(value).map(Self::Value).map_err(|_| {
+                format!("expected `erased`, `preserve` or a byte value, not `{value}`")
+            }),
+        }
+    }
+}
+
+/// Adds the gaps of the programmed sectors to the image of the loader, filled with the value of
+/// `--elf-gap-fill`. Does nothing for the other gap fills.
+pub fn fill_gaps(
+    loader: &mut FlashLoader,
+    target: &Target,
+    gap_fill: GapFill,
+) -> Result<(), FlashError> {
+    let GapFill::Value(value) = gap_fill else {
+        return Ok(());
+    };
+
+    let fills: Vec<_> = loader
+        .flash_layouts(target, true)?
+        .iter()
+        .flat_map(|layout| layout.fills().to_vec())
+        .collect();
+    for fill in fills {
+        loader.add_data(fill.address(), &vec![value; fill.size() as usize])?;
+    }
+
+    Ok(())
+}
+
 /// Performs the flash download with the given loader. Ensure that the loader has the data to load already stored.
 /// This function also manages the update and display of progress bars.
 pub fn run_flash_download(
@@ -26,17 +123,43 @@ pub fn run_flash_download(
     path: impl AsRef<Path>,
     download_options: &BinaryDownloadOptions,
     probe_options: &LoadedProbeOptions,
-    loader: FlashLoader,
-    do_chip_erase: bool,
+    mut loader: FlashLoader,
+    erase: EraseMode,
 ) -> Result<(), OperationError> {
-    let mut options = DownloadOptions::default();
-    options.keep_unwritten_bytes = download_options.restore_unwritten;
-    options.dry_run = probe_options.dry_run();
-    options.do_chip_erase = do_chip_erase;
-    options.disable_double_buffering = download_options.disable_double_buffering;
-    options.verify = download_options.verify;
+    probe_options.ensure_reset_allowed("flashing")?;
+
+    fill_gaps(&mut loader, session.target(), download_options.elf_gap_fill).map_err(|error| {
+        OperationError::FlashingFailed {
+            source: error,
+            target: Box::new(session.target().clone()),
+            target_spec: probe_options.chip(),
+            path: path.as_ref().to_path_buf(),
+        }
+    })?;
+
+    let mut options = loader_options(download_options, probe_options.dry_run(), erase);
+
+    if download_options.restores_unwritten() {
+        tracing::warn!("Unwritten bytes will be preserved. Every affected sector is read before it is erased, which slows down flashing.");
+    }
+
+    let skipped_erases = Rc::new(Cell::new(SkippedErases::default()));
+
+    let operation_log = match &download_options.operation_log {
+        Some(path) => Some(OperationLog::create(path, &session.target().name).map_err(
+            |source| OperationError::FailedToWriteOperationLog {
+                source,
+                path: path.clone(),
+            },
+        )?),
+        None => None,
+    };
+    let operation_log = Rc::new(RefCell::new(operation_log));
 
     if !download_options.disable_progressbars {
+        let skipped_erases = skipped_erases.clone();
+        let operation_log = operation_log.clone();
+
         // Create progress bars.
         let multi_progress = MultiProgress::new();
         logging::set_progress_bar(multi_progress.clone());
@@ -113,6 +236,10 @@ pub fn run_flash_download(
                     progress_bars.program.inc(size as u64);
                 }
                 ProgressEvent::SectorErased { size, .. } => progress_bars.erase.inc(size),
+                ProgressEvent::SectorEraseSkipped { size } => {
+                    progress_bars.erase.inc(size);
+                    skipped_erases.set(skipped_erases.get().add(size));
+                }
                 ProgressEvent::PageFilled { size, .. } => progress_bars.fill.inc(size),
                 ProgressEvent::FailedErasing => {
                     progress_bars.erase.abandon();
@@ -124,12 +251,38 @@ pub fn run_flash_download(
                 ProgressEvent::FailedFilling => progress_bars.fill.abandon(),
                 ProgressEvent::FinishedFilling => progress_bars.fill.finish(),
                 ProgressEvent::DiagnosticMessage { .. } => {}
+                ProgressEvent::Operation { operation } => {
+                    if let Some(log) = operation_log.borrow_mut().as_mut() {
+                        log.record(&operation);
+                    }
+                }
+                _ => {}
             }
         });
 
         options.progress = Some(progress);
+    } else {
+        let skipped_erases = skipped_erases.clone();
+        let operation_log = operation_log.clone();
+        options.progress = Some(FlashProgress::new(move |event| match event {
+            ProgressEvent::SectorEraseSkipped { size } => {
+                skipped_erases.set(skipped_erases.get().add(size));
+            }
+            ProgressEvent::Operation { operation } => {
+                if let Some(log) = operation_log.borrow_mut().as_mut() {
+                    log.record(&operation);
+                }
+            }
+            _ => {}
+        }));
     }
 
+    let option_bytes = if probe_options.dry_run() {
+        None
+    } else {
+        download_options.option_bytes.prepare(session)?
+    };
+
     // Start timer.
     let flash_timer = Instant::now();
 
@@ -145,15 +298,60 @@ pub fn run_flash_download(
     // If we don't do this, the progress bars disappear.
     logging::clear_progress_bar();
 
+    if let (Some(log), Some(path)) = (
+        operation_log.borrow_mut().take(),
+        &download_options.operation_log,
+    ) {
+        log.finish()
+            .map_err(|source| OperationError::FailedToWriteOperationLog {
+                source,
+                path: path.clone(),
+            })?;
+    }
+
+    if let Some(option_bytes) = option_bytes {
+        option_bytes.apply(session)?;
+    }
+
     logging::eprintln(format!(
         "    {} in {}s",
         "Finished".green().bold(),
         flash_timer.elapsed().as_secs_f32(),
     ));
 
+    let skipped_erases = skipped_erases.get();
+    if skipped_erases.sectors > 0 {
+        logging::eprintln(format!(
+            "     {} erasing {} already erased sectors ({} bytes)",
+            "Skipped".green().bold(),
+            skipped_erases.sectors,
+            skipped_erases.bytes,
+        ));
+    }
+
     Ok(())
 }
 
+/// Converts the download options of the command line into the options of the flash loader,
+/// without a progress reporter.
+pub fn loader_options(
+    download_options: &BinaryDownloadOptions,
+    dry_run: bool,
+    erase: EraseMode,
+) -> DownloadOptions {
+    let mut options = DownloadOptions::default();
+    options.keep_unwritten_bytes = download_options.restores_unwritten();
+    options.dry_run = dry_run;
+    options.do_chip_erase = erase != EraseMode::Sector;
+    options.require_chip_erase = erase == EraseMode::Chip;
+    options.disable_double_buffering = download_options.disable_double_buffering;
+    options.verify = download_options.verify;
+    options.force_erase = download_options.force_erase;
+    options.halt_mode = download_options.flash_halt_mode.into();
+
+    options
+}
+
 /// Builds a new flash loader for the given target and path. This
 /// will check the path for validity and check what pages have to be
 /// flashed etc.
@@ -166,6 +364,25 @@ pub fn build_loader(
     // Create the flash loader
     let mut loader = session.target().flash_loader();
 
+    add_image(
+        session,
+        &mut loader,
+        path,
+        format_options,
+        image_instruction_set,
+    )?;
+
+    Ok(loader)
+}
+
+/// Adds the image at `path` to the data of the flash loader.
+pub fn add_image(
+    session: &mut Session,
+    loader: &mut FlashLoader,
+    path: impl AsRef<Path>,
+    format_options: FormatOptions,
+    image_instruction_set: Option<InstructionSet>,
+) -> anyhow::Result<()> {
     // Add data from the BIN.
     let mut file = match File::open(path) {
         Ok(file) => file,
@@ -175,7 +392,23 @@ pub fn build_loader(
     let format = format_options.into_format(session.target())?;
     loader.load_image(session, &mut file, format, image_instruction_set)?;
 
-    Ok(loader)
+    Ok(())
+}
+
+/// The sectors which were not erased, because they were already erased.
+#[derive(Debug, Default, Clone, Copy)]
+struct SkippedErases {
+    sectors: usize,
+    bytes: u64,
+}
+
+impl SkippedErases {
+    fn add(self, size: u64) -> Self {
+        Self {
+            sectors: self.sectors + 1,
+            bytes: self.bytes + size,
+        }
+    }
 }
 
 struct ProgressBars {
@@ -257,3 +490,30 @@ impl ProgressBarGroup {
         self.append_phase = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_are_filled_with_the_value() {
+        assert_eq!(GapFill::parse("erased"), Ok(GapFill::Erased));
+        assert_eq!(GapFill::parse("preserve"), Ok(GapFill::Preserve));
+        assert_eq!(GapFill::parse("0xA5"), Ok(GapFill::Value(0xA5)));
+        assert!(GapFill::parse("0x100").is_err());
+
+        let target = probe_rs::config::get_target_by_name("nRF52840_xxAA").unwrap();
+        let mut loader = target.flash_loader();
+        loader.add_data(0x1000, &[1; 4]).unwrap();
+        loader.add_data(0x1010, &[2; 4]).unwrap();
+
+        fill_gaps(&mut loader, &target, GapFill::Value(0xA5)).unwrap();
+
+        // The whole 4 KiB sector is part of the image now.
+        let layouts = loader.flash_layouts(&target, true).unwrap();
+        assert!(layouts.iter().all(|layout| layout.fills().is_empty()));
+        let blocks: Vec<_> = loader.data().collect();
+        let size: usize = blocks.iter().map(|(_, data)| data.len()).sum();
+        assert_eq!(size, 0x1000);
+    }
+}
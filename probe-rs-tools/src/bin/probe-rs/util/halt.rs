@@ -0,0 +1,242 @@
+//! Halting cores, with an explanation if the core does not halt in time.
+
+use std::time::Duration;
+
+use probe_rs::architecture::arm::armv7m::Dhcsr;
+use probe_rs::architecture::arm::ArmError;
+use probe_rs::architecture::riscv::communication_interface::RiscvError;
+use probe_rs::architecture::xtensa::communication_interface::XtensaError;
+use probe_rs::{
+    Core, CoreInformation, Error, MemoryInterface, MemoryMappedRegister, Session,
+    VectorCatchCondition,
+};
+
+use super::common_options::OperationError;
+
+/// The time to wait for a core to halt, if no `--halt-timeout` is given.
+pub const DEFAULT_HALT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Halts the core, and explains why if it does not halt within `timeout`.
+pub fn halt(core: &mut Core, timeout: Duration) -> Result<CoreInformation, OperationError> {
+    core.halt(timeout).map_err(|error| match error {
+        error if is_timeout(&error) => halt_timeout(core, timeout),
+        error => OperationError::HaltFailed(error),
+    })
+}
+
+/// Halts all cores of a multi-core target, so that their state is consistent.
+///
+/// The cores are connected through their cross trigger interfaces (CTI) where available, so that
+/// halting one core halts the others at the same time. The remaining cores are halted one after
+/// the other. Nothing is done for targets with a single core.
+///
+/// The cores are disconnected again afterwards, unless they were connected before.
+pub fn halt_all_cores(session: &mut Session, timeout: Duration) -> Result<(), OperationError> {
+    let cores = session.list_cores();
+    if cores.len() < 2 {
+        return Ok(());
+    }
+
+    let connected_before = session.cross_halt_enabled();
+    if !connected_before {
+        if let Err(error) = session.enable_cross_halt() {
+            tracing::debug!("The cores are halted one after the other: {error}");
+        }
+    }
+
+    let result = cores.into_iter().try_for_each(|(index, _)| {
+        let mut core = session
+            .core(index)
+            .map_err(OperationError::AttachingToCoreFailed)?;
+        if !core.core_halted().map_err(OperationError::HaltFailed)? {
+            halt(&mut core, timeout)?;
+        }
+        Ok(())
+    });
+
+    if !connected_before {
+        if let Err(error) = session.disable_cross_halt() {
+            tracing::warn!("Failed to disconnect the cross triggers of the cores: {error}");
+        }
+    }
+
+    result
+}
+
+/// Resets and halts the core, and explains why if it does not halt within `timeout`.
+pub fn reset_and_halt(
+    core: &mut Core,
+    timeout: Duration,
+) -> Result<CoreInformation, OperationError> {
+    core.reset_and_halt(timeout).map_err(|error| match error {
+        error if is_timeout(&error) => halt_timeout(core, timeout),
+        error => OperationError::TargetResetHaltFailed(error),
+    })
+}
+
+/// Halts all cores, and resets the target with the reset vector catch enabled, so the cores
+/// stop before they execute the first instruction of the firmware.
+///
+/// All cores are halted before the reset, so none of them runs while the others are caught. The
+/// first core is reset with [`Core::reset_and_halt`], which resets the whole target, and the
+/// other cores are caught by their own vector catch.
+///
+/// Returns for each core whether it was caught, or only halted because it does not support
+/// catching the reset vector.
+pub fn catch_reset(
+    session: &mut Session,
+    timeout: Duration,
+) -> Result<Vec<(usize, bool)>, OperationError> {
+    let cores = session
+        .list_cores()
+        .into_iter()
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    let mut caught = vec![];
+    for &index in &cores {
+        let mut core = session
+            .core(index)
+            .map_err(OperationError::AttachingToCoreFailed)?;
+        if !core.core_halted().map_err(OperationError::HaltFailed)? {
+            halt(&mut core, timeout)?;
+        }
+
+        // The first core enables its vector catch when it is reset.
+        if index == cores[0] {
+            continue;
+        }
+        match core.enable_vector_catch(VectorCatchCondition::CoreReset) {
+            Ok(()) => caught.push(index),
+            Err(Error::NotImplemented(_)) => {}
+            Err(error) => return Err(OperationError::ResetVectorCatchFailed(error)),
+        }
+    }
+
+    let first_caught = {
+        let mut core = session
+            .core(cores[0])
+            .map_err(OperationError::AttachingToCoreFailed)?;
+        match core.reset_and_halt(timeout) {
+            Ok(_) => true,
+            Err(Error::NotImplemented(_)) => false,
+            Err(error) if is_timeout(&error) => return Err(halt_timeout(&mut core, timeout)),
+            Err(error) => return Err(OperationError::ResetVectorCatchFailed(error)),
+        }
+    };
+
+    let mut result = vec![(cores[0], first_caught)];
+    for &index in &cores[1..] {
+        let mut core = session
+            .core(index)
+            .map_err(OperationError::AttachingToCoreFailed)?;
+        let is_caught = caught.contains(&index);
+        if is_caught {
+            let halted = core.wait_for_core_halted(timeout);
+
+            // Later resets must not be caught, regardless of whether this one was.
+            core.disable_vector_catch(VectorCatchCondition::CoreReset)
+                .map_err(OperationError::ResetVectorCatchFailed)?;
+            halted.map_err(|error| match error {
+                error if is_timeout(&error) => halt_timeout(&mut core, timeout),
+                error => OperationError::ResetVectorCatchFailed(error),
+            })?;
+        }
+        result.push((index, is_caught));
+    }
+
+    Ok(result)
+}
+
+fn is_timeout(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Timeout
+            | Error::Arm(ArmError::Timeout)
+            | Error::Riscv(RiscvError::Timeout)
+            | Error::Xtensa(XtensaError::Timeout)
+    )
+}
+
+fn halt_timeout(core: &mut Core, timeout: Duration) -> OperationError {
+    let (status, hint) = if core.core_type().is_cortex_m() {
+        match core.read_word_32(Dhcsr::get_mmio_address()) {
+            Ok(value) => diagnose_dhcsr(Dhcsr(value)),
+            Err(error) => (format!("reading DHCSR failed: {error}"), None),
+        }
+    } else {
+        match core.status() {
+            Ok(status) => (format!("the core status is {status:?}"), None),
+            Err(error) => (format!("reading the core status failed: {error}"), None),
+        }
+    };
+
+    OperationError::HaltTimeout {
+        timeout,
+        status,
+        hint: hint.unwrap_or(
+            "The core may be in a reset loop, or the debug logic may be powered down. Try `--connect-under-reset`, or a longer `--halt-timeout`.",
+        ),
+    }
+}
+
+/// Describes the state bits of the DHCSR, and what they mean for halting the core.
+fn diagnose_dhcsr(dhcsr: Dhcsr) -> (String, Option<&'static str>) {
+    let flags = [
+        (dhcsr.c_debugen(), "C_DEBUGEN"),
+        (dhcsr.s_halt(), "S_HALT"),
+        (dhcsr.s_sleep(), "S_SLEEP"),
+        (dhcsr.s_lockup(), "S_LOCKUP"),
+        (dhcsr.s_retire_st(), "S_RETIRE_ST"),
+        (dhcsr.s_reset_st(), "S_RESET_ST"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect::<Vec<_>>();
+
+    let flags = if flags.is_empty() {
+        "no flags set".to_string()
+    } else {
+        flags.join(", ")
+    };
+    let status = format!("DHCSR = {:#010x}: {flags}", u32::from(dhcsr));
+
+    let hint = if !dhcsr.c_debugen() {
+        Some("Debugging is not enabled on the core, it may be disabled by a security setting, or the core may be held in reset. Try `--connect-under-reset`.")
+    } else if dhcsr.s_lockup() {
+        Some("The core is in lockup after an unrecoverable exception. Try `--connect-under-reset`, or reset the target.")
+    } else if dhcsr.s_reset_st() {
+        Some("The core was reset while halting, it may be in a reset loop, e.g. because of a watchdog. Try `--connect-under-reset`.")
+    } else if dhcsr.s_sleep() {
+        Some("The core is sleeping in WFI or WFE, which can gate the debug clock on some chips. Try `--connect-under-reset`, or a longer `--halt-timeout`.")
+    } else {
+        None
+    };
+
+    (status, hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleeping_core_is_diagnosed() {
+        // C_DEBUGEN, S_SLEEP and S_RETIRE_ST.
+        let (status, hint) = diagnose_dhcsr(Dhcsr(0x0104_0001));
+
+        assert_eq!(
+            status,
+            "DHCSR = 0x01040001: C_DEBUGEN, S_SLEEP, S_RETIRE_ST"
+        );
+        assert!(hint.unwrap().contains("WFI"));
+    }
+
+    #[test]
+    fn lockup_takes_precedence_over_sleep() {
+        let (_, hint) = diagnose_dhcsr(Dhcsr(0x000c_0001));
+
+        assert!(hint.unwrap().contains("lockup"));
+    }
+}
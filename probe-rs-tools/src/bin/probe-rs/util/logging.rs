@@ -2,7 +2,8 @@ use indicatif::MultiProgress;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, path::Path};
+use std::{fs::OpenOptions, path::Path};
+use time::{error::IndeterminateOffset, UtcOffset};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
     fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
@@ -123,7 +124,11 @@ pub fn setup_logging(
         return Ok(None);
     };
 
-    let log_file = File::create(log_path)?;
+    // Logs of previous runs in an explicitly given file are kept.
+    let log_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
 
     let (file_appender, guard) = tracing_appender::non_blocking::NonBlockingBuilder::default()
         .lossy(false)
@@ -145,6 +150,17 @@ pub fn setup_logging(
     Ok(Some(FileLoggerGuard::new(guard, log_path)))
 }
 
+/// Warns that timestamps are shown in UTC, if the local time offset could not be determined.
+///
+/// This has to be called after logging is set up.
+pub fn warn_on_utc_fallback(local_offset: Result<UtcOffset, IndeterminateOffset>) {
+    if let Err(error) = local_offset {
+        tracing::warn!(
+            "The local time offset could not be determined ({error}), timestamps are shown in UTC."
+        );
+    }
+}
+
 /// Sets the currently displayed progress bar of the CLI.
 pub fn set_progress_bar(progress: MultiProgress) {
     *PROGRESS_BAR.lock() = Some(progress);
@@ -1,11 +1,25 @@
+pub mod access_port;
+pub mod bundle;
 pub mod cargo;
+pub mod chip_check;
 pub mod common_options;
+pub mod elf;
+pub mod exit_code;
 pub mod flash;
+pub mod halt;
 pub mod logging;
 pub mod meta;
+pub mod operation_log;
+pub mod option_bytes;
 pub mod rtt;
+pub mod run_output;
+pub mod sampling;
+pub mod target_description;
 
 use std::num::ParseIntError;
+use std::path::Path;
+
+use anyhow::anyhow;
 
 pub fn parse_u32(input: &str) -> Result<u32, ParseIntError> {
     parse_int::parse(input)
@@ -14,3 +28,18 @@ pub fn parse_u32(input: &str) -> Result<u32, ParseIntError> {
 pub fn parse_u64(input: &str) -> Result<u64, ParseIntError> {
     parse_int::parse(input)
 }
+
+/// Parses `name` as an address, or resolves it as a symbol of the `elf` file.
+pub fn resolve_address(name: &str, elf: Option<&Path>) -> anyhow::Result<u64> {
+    if let Ok(address) = parse_u64(name) {
+        return Ok(address);
+    }
+
+    let elf = elf.ok_or_else(|| {
+        anyhow!("'{name}' is not an address, and symbols can only be resolved with `--elf`.")
+    })?;
+
+    elf::ElfFile::read(elf)?
+        .symbol_address(name)?
+        .ok_or_else(|| anyhow!("The symbol '{name}' was not found in {}.", elf.display()))
+}
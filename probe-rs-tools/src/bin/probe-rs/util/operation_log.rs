@@ -0,0 +1,219 @@
+//! Recording the low-level flash operations to a JSON Lines file, and reading them back for
+//! `download --replay`.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Context;
+use base64::{engine::general_purpose as base64_engine, Engine as _};
+use probe_rs::flashing::{FlashOperation, FlashOperationKind};
+use serde::{Deserialize, Serialize};
+
+/// The version of the log format, which is increased for incompatible changes.
+const OPERATION_LOG_VERSION: u32 = 1;
+
+/// The first line of the log.
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    /// The chip the operations were issued to.
+    chip: String,
+}
+
+/// A line of the log, after the header.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct LoggedOperation {
+    algorithm: String,
+    core: usize,
+    #[serde(flatten)]
+    kind: LoggedKind,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LoggedKind {
+    EraseAll,
+    EraseSector {
+        address: u64,
+    },
+    ProgramPage {
+        address: u64,
+        /// The data, encoded with base64.
+        data: String,
+    },
+}
+
+impl From<&FlashOperation> for LoggedOperation {
+    fn from(operation: &FlashOperation) -> Self {
+        let kind = match &operation.kind {
+            FlashOperationKind::EraseAll => LoggedKind::EraseAll,
+            FlashOperationKind::EraseSector { address } => {
+                LoggedKind::EraseSector { address: *address }
+            }
+            FlashOperationKind::ProgramPage { address, data } => LoggedKind::ProgramPage {
+                address: *address,
+                data: base64_engine::STANDARD.encode(data),
+            },
+        };
+
+        Self {
+            algorithm: operation.algorithm.clone(),
+            core: operation.core,
+            kind,
+        }
+    }
+}
+
+impl TryFrom<LoggedOperation> for FlashOperation {
+    type Error = anyhow::Error;
+
+    fn try_from(operation: LoggedOperation) -> anyhow::Result<Self> {
+        let kind = match operation.kind {
+            LoggedKind::EraseAll => FlashOperationKind::EraseAll,
+            LoggedKind::EraseSector { address } => FlashOperationKind::EraseSector { address },
+            LoggedKind::ProgramPage { address, data } => FlashOperationKind::ProgramPage {
+                address,
+                data: base64_engine::STANDARD
+                    .decode(data)
+                    .with_context(|| format!("Invalid data for the page at {address:#010x}"))?,
+            },
+        };
+
+        Ok(Self {
+            algorithm: operation.algorithm,
+            core: operation.core,
+            kind,
+        })
+    }
+}
+
+/// Writes the flash operations to a file, one JSON object per line.
+pub struct OperationLog {
+    writer: BufWriter<File>,
+    /// The first error while writing, which is reported once flashing is done.
+    error: Option<std::io::Error>,
+}
+
+impl OperationLog {
+    /// Creates the log at `path`, for the operations issued to `chip`.
+    pub fn create(path: &Path, chip: &str) -> std::io::Result<Self> {
+        let mut log = Self {
+            writer: BufWriter::new(File::create(path)?),
+            error: None,
+        };
+
+        let header = Header {
+            version: OPERATION_LOG_VERSION,
+            chip: chip.to_string(),
+        };
+        log.write_line(&header)?;
+
+        Ok(log)
+    }
+
+    /// Appends the operation to the log.
+    pub fn record(&mut self, operation: &FlashOperation) {
+        if self.error.is_none() {
+            if let Err(error) = self.write_line(&LoggedOperation::from(operation)) {
+                self.error = Some(error);
+            }
+        }
+    }
+
+    /// Flushes the log, and returns the first error which occurred while writing it.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+
+    fn write_line(&mut self, line: &impl Serialize) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.writer, line)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+/// Reads the operations of the log at `path`, and checks that they were issued to `chip`.
+pub fn read_operation_log(path: &Path, chip: &str) -> anyhow::Result<Vec<FlashOperation>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open the operation log {}", path.display()))?;
+    parse_operation_log(BufReader::new(file), chip)
+        .with_context(|| format!("Failed to read the operation log {}", path.display()))
+}
+
+fn parse_operation_log(reader: impl BufRead, chip: &str) -> anyhow::Result<Vec<FlashOperation>> {
+    let mut lines = reader.lines();
+
+    let header = lines.next().context("The log is empty.")??;
+    let header: Header = serde_json::from_str(&header).context("Invalid header")?;
+    if header.version != OPERATION_LOG_VERSION {
+        anyhow::bail!(
+            "The log has version {}, but only version {OPERATION_LOG_VERSION} is supported.",
+            header.version
+        );
+    }
+    if !header.chip.eq_ignore_ascii_case(chip) {
+        anyhow::bail!(
+            "The operations were issued to the chip {}, not to {chip}.",
+            header.chip
+        );
+    }
+
+    let mut operations = vec![];
+    for (index, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let operation = serde_json::from_str::<LoggedOperation>(&line)
+            .map_err(anyhow::Error::from)
+            .and_then(FlashOperation::try_from)
+            // The header is line 1.
+            .with_context(|| format!("Invalid operation in line {}", index + 2))?;
+        operations.push(operation);
+    }
+
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_are_read_back() {
+        let operations = [
+            FlashOperationKind::EraseAll,
+            FlashOperationKind::EraseSector { address: 0x1000 },
+            FlashOperationKind::ProgramPage {
+                address: 0x1000,
+                data: vec![1, 2, 3, 4],
+            },
+        ]
+        .map(|kind| FlashOperation {
+            algorithm: "nrf52840".to_string(),
+            core: 0,
+            kind,
+        });
+
+        let mut log = serde_json::to_string(&Header {
+            version: OPERATION_LOG_VERSION,
+            chip: "nRF52840_xxAA".to_string(),
+        })
+        .unwrap();
+        for operation in &operations {
+            log.push('\n');
+            log.push_str(&serde_json::to_string(&LoggedOperation::from(operation)).unwrap());
+        }
+
+        assert!(log.contains(r#""op":"program_page","address":4096,"data":"AQIDBA==""#));
+        assert_eq!(
+            parse_operation_log(log.as_bytes(), "nrf52840_xxaa").unwrap(),
+            operations
+        );
+        assert!(parse_operation_log(log.as_bytes(), "stm32f429zitx").is_err());
+    }
+}
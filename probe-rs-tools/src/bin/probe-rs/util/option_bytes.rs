@@ -0,0 +1,432 @@
+//! Preserving and setting the option bytes of STM32 chips when the flash is erased.
+//!
+//! The option bytes are read through the registers of the flash controller, which differ between
+//! the families. Supported are the STM32F4 (`FLASH_OPTCR`) and the STM32G0, STM32G4 and STM32L4
+//! (`FLASH_OPTR`) families. The read protection level (RDP) is never changed, as changing it
+//! erases the flash or locks the chip permanently.
+
+use std::time::{Duration, Instant};
+
+use probe_rs::{Core, MemoryInterface, Session};
+
+use super::parse_u32;
+
+/// The keys which unlock the flash control register.
+const FLASH_KEYS: [u32; 2] = [0x4567_0123, 0xCDEF_89AB];
+/// The keys which unlock the option bytes.
+const OPTION_KEYS: [u32; 2] = [0x0819_2A3B, 0x4C5D_6E7F];
+
+/// The time to wait for the flash controller to program the option bytes.
+const PROGRAM_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(clap::Parser, Debug, Clone, Default)]
+pub struct OptionBytesOptions {
+    /// Read the option bytes before the flash is erased, and write them again afterwards if they
+    /// changed. Supported for the STM32F4, STM32G0, STM32G4 and STM32L4 families.
+    #[arg(long, help_heading = "DOWNLOAD CONFIGURATION")]
+    pub preserve_option_bytes: bool,
+
+    /// Set option bytes after the flash was erased or programmed, e.g. `bor_lev=2,iwdg_sw=1`.
+    ///
+    /// The fields are named like in the reference manual of the chip, the read protection
+    /// level cannot be set. Supported for the STM32F4, STM32G0, STM32G4 and STM32L4 families.
+    /// The new values take effect after the next power-on reset.
+    #[arg(
+        long,
+        value_name = "FIELD=VALUE,...",
+        value_parser = parse_settings,
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub set_option_bytes: Option<Settings>,
+}
+
+/// The option byte fields to set, with their values.
+pub type Settings = Vec<(String, u32)>;
+
+fn parse_settings(input: &str) -> Result<Settings, String> {
+    input
+        .split(',')
+        .filter(|setting| !setting.trim().is_empty())
+        .map(|setting| {
+            let (field, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("'{setting}' is not of the form `field=value`"))?;
+            let value = parse_u32(value.trim())
+                .map_err(|e| format!("invalid value for '{}': {e}", field.trim()))?;
+            Ok((field.trim().to_ascii_lowercase(), value))
+        })
+        .collect()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OptionBytesError {
+    #[error("The option bytes of {chip} are not supported, only the STM32F4, STM32G0, STM32G4 and STM32L4 families are.")]
+    Unsupported { chip: String },
+    #[error("The option bytes of the {family} family have no field '{field}'. The fields are: {fields}.")]
+    UnknownField {
+        family: &'static str,
+        field: String,
+        fields: String,
+    },
+    #[error(
+        "The value {value:#x} does not fit into the option byte field '{field}' with {width} bits."
+    )]
+    ValueTooLarge {
+        field: String,
+        value: u32,
+        width: u32,
+    },
+    #[error("The flash controller did not finish programming the option bytes in time.")]
+    Timeout,
+    #[error("The option bytes are {actual:#010x} after programming, instead of {expected:#010x}.")]
+    VerifyFailed { expected: u32, actual: u32 },
+    #[error("Failed to access the flash controller.")]
+    Memory(#[from] probe_rs::Error),
+}
+
+/// How the option bytes are accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Controller {
+    /// The option bytes are in `FLASH_OPTCR`, which also contains the lock and start bits.
+    Optcr,
+    /// The option bytes are in `FLASH_OPTR`, which is unlocked and started with `FLASH_CR`.
+    Optr,
+}
+
+/// A field of the option bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field {
+    name: &'static str,
+    offset: u32,
+    width: u32,
+}
+
+impl Field {
+    const fn new(name: &'static str, offset: u32, width: u32) -> Self {
+        Self {
+            name,
+            offset,
+            width,
+        }
+    }
+
+    fn mask(&self) -> u32 {
+        (u32::MAX >> (32 - self.width)) << self.offset
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Layout {
+    family: &'static str,
+    controller: Controller,
+    flash_base: u64,
+    /// The bits of the read protection level, which are never changed.
+    rdp_mask: u32,
+    fields: &'static [Field],
+}
+
+const LAYOUTS: &[Layout] = &[
+    Layout {
+        family: "STM32F4",
+        controller: Controller::Optcr,
+        flash_base: 0x4002_3C00,
+        rdp_mask: 0x0000_FF00,
+        fields: &[
+            Field::new("bor_lev", 2, 2),
+            Field::new("wdg_sw", 5, 1),
+            Field::new("nrst_stop", 6, 1),
+            Field::new("nrst_stdby", 7, 1),
+            Field::new("nwrp", 16, 12),
+        ],
+    },
+    Layout {
+        family: "STM32G0",
+        controller: Controller::Optr,
+        flash_base: 0x4002_2000,
+        rdp_mask: 0x0000_00FF,
+        fields: &[
+            Field::new("bor_en", 8, 1),
+            Field::new("nrst_stop", 13, 1),
+            Field::new("nrst_stdby", 14, 1),
+            Field::new("nrst_shdw", 15, 1),
+            Field::new("iwdg_sw", 16, 1),
+            Field::new("iwdg_stop", 17, 1),
+            Field::new("iwdg_stdby", 18, 1),
+            Field::new("wwdg_sw", 19, 1),
+        ],
+    },
+    Layout {
+        family: "STM32G4",
+        controller: Controller::Optr,
+        flash_base: 0x4002_2000,
+        rdp_mask: 0x0000_00FF,
+        fields: OPTR_FIELDS,
+    },
+    Layout {
+        family: "STM32L4",
+        controller: Controller::Optr,
+        flash_base: 0x4002_2000,
+        rdp_mask: 0x0000_00FF,
+        fields: OPTR_FIELDS,
+    },
+];
+
+/// The fields of `FLASH_OPTR` which the STM32G4 and STM32L4 have in common.
+const OPTR_FIELDS: &[Field] = &[
+    Field::new("bor_lev", 8, 3),
+    Field::new("nrst_stop", 12, 1),
+    Field::new("nrst_stdby", 13, 1),
+    Field::new("nrst_shdw", 14, 1),
+    Field::new("iwdg_sw", 16, 1),
+    Field::new("iwdg_stop", 17, 1),
+    Field::new("iwdg_stdby", 18, 1),
+    Field::new("wwdg_sw", 19, 1),
+];
+
+impl Layout {
+    fn for_chip(chip: &str) -> Option<&'static Self> {
+        let chip = chip.to_ascii_uppercase();
+        LAYOUTS
+            .iter()
+            .find(|layout| chip.starts_with(layout.family))
+    }
+
+    /// The bits of the register which hold option bytes.
+    fn value_mask(&self) -> u32 {
+        match self.controller {
+            // The lowest bits are OPTLOCK and OPTSTRT, bit 4 is reserved.
+            Controller::Optcr => 0x0FFF_FFEC,
+            Controller::Optr => u32::MAX,
+        }
+    }
+
+    fn option_register(&self) -> u64 {
+        match self.controller {
+            Controller::Optcr => self.flash_base + 0x14,
+            Controller::Optr => self.flash_base + 0x20,
+        }
+    }
+
+    fn status_register(&self) -> u64 {
+        match self.controller {
+            Controller::Optcr => self.flash_base + 0x0C,
+            Controller::Optr => self.flash_base + 0x10,
+        }
+    }
+
+    /// Sets the fields of the option bytes `value`.
+    fn apply(&self, mut value: u32, settings: &Settings) -> Result<u32, OptionBytesError> {
+        for (name, field_value) in settings {
+            let field = self
+                .fields
+                .iter()
+                .find(|field| field.name == name)
+                .ok_or_else(|| OptionBytesError::UnknownField {
+                    family: self.family,
+                    field: name.clone(),
+                    fields: self
+                        .fields
+                        .iter()
+                        .map(|field| field.name)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                })?;
+
+            if *field_value > field.mask() >> field.offset {
+                return Err(OptionBytesError::ValueTooLarge {
+                    field: name.clone(),
+                    value: *field_value,
+                    width: field.width,
+                });
+            }
+
+            value = (value & !field.mask()) | (field_value << field.offset);
+        }
+
+        Ok(value)
+    }
+
+    fn read(&self, core: &mut Core) -> Result<u32, OptionBytesError> {
+        Ok(core.read_word_32(self.option_register())? & self.value_mask())
+    }
+
+    fn write(&self, core: &mut Core, value: u32) -> Result<(), OptionBytesError> {
+        let value = value & self.value_mask();
+        self.wait_until_idle(core)?;
+
+        match self.controller {
+            Controller::Optcr => {
+                const OPTLOCK: u32 = 1 << 0;
+                const OPTSTRT: u32 = 1 << 1;
+                let keyr = self.flash_base + 0x08;
+
+                if core.read_word_32(self.option_register())? & OPTLOCK != 0 {
+                    for key in OPTION_KEYS {
+                        core.write_word_32(keyr, key)?;
+                    }
+                }
+                // Bits which are not option bytes on all chips of the family, e.g. DB1M, are kept.
+                let other = core.read_word_32(self.option_register())?
+                    & !self.value_mask()
+                    & !(OPTLOCK | OPTSTRT);
+                let value = value | other;
+                core.write_word_32(self.option_register(), value)?;
+                core.write_word_32(self.option_register(), value | OPTSTRT)?;
+                self.wait_until_idle(core)?;
+                core.write_word_32(self.option_register(), value | OPTLOCK)?;
+            }
+            Controller::Optr => {
+                const OPTSTRT: u32 = 1 << 17;
+                const OPTLOCK: u32 = 1 << 30;
+                const LOCK: u32 = 1 << 31;
+                let keyr = self.flash_base + 0x08;
+                let optkeyr = self.flash_base + 0x0C;
+                let cr = self.flash_base + 0x14;
+
+                if core.read_word_32(cr)? & LOCK != 0 {
+                    for key in FLASH_KEYS {
+                        core.write_word_32(keyr, key)?;
+                    }
+                }
+                if core.read_word_32(cr)? & OPTLOCK != 0 {
+                    for key in OPTION_KEYS {
+                        core.write_word_32(optkeyr, key)?;
+                    }
+                }
+                core.write_word_32(self.option_register(), value)?;
+                let control = core.read_word_32(cr)?;
+                core.write_word_32(cr, control | OPTSTRT)?;
+                self.wait_until_idle(core)?;
+                // Locking the flash also locks the option bytes.
+                let control = core.read_word_32(cr)?;
+                core.write_word_32(cr, control | LOCK)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn wait_until_idle(&self, core: &mut Core) -> Result<(), OptionBytesError> {
+        const BSY: u32 = 1 << 16;
+
+        let start = Instant::now();
+        while core.read_word_32(self.status_register())? & BSY != 0 {
+            if start.elapsed() > PROGRAM_TIMEOUT {
+                return Err(OptionBytesError::Timeout);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        Ok(())
+    }
+}
+
+/// The option bytes to write once the flash is erased or programmed.
+pub struct PendingOptionBytes {
+    layout: &'static Layout,
+    value: u32,
+}
+
+impl OptionBytesOptions {
+    /// Reads the option bytes before the flash is erased, and returns the option bytes which
+    /// have to be written afterwards.
+    ///
+    /// Returns `None` if the option bytes are neither preserved nor set.
+    pub fn prepare(
+        &self,
+        session: &mut Session,
+    ) -> Result<Option<PendingOptionBytes>, OptionBytesError> {
+        if !self.preserve_option_bytes && self.set_option_bytes.is_none() {
+            return Ok(None);
+        }
+
+        let chip = &session.target().name;
+        let layout = Layout::for_chip(chip)
+            .ok_or_else(|| OptionBytesError::Unsupported { chip: chip.clone() })?;
+
+        let current = layout.read(&mut session.core(0)?)?;
+        let value = match &self.set_option_bytes {
+            Some(settings) => layout.apply(current, settings)?,
+            None => current,
+        };
+
+        Ok(Some(PendingOptionBytes { layout, value }))
+    }
+}
+
+impl PendingOptionBytes {
+    /// Writes the option bytes, if they differ from the current ones.
+    pub fn apply(self, session: &mut Session) -> Result<(), OptionBytesError> {
+        let layout = self.layout;
+        let mut core = session.core(0)?;
+
+        let current = layout.read(&mut core)?;
+        // The read protection level is kept, whatever happened to it in between.
+        let value = (self.value & !layout.rdp_mask) | (current & layout.rdp_mask);
+        if value == current {
+            return Ok(());
+        }
+
+        layout.write(&mut core, value)?;
+
+        let actual = layout.read(&mut core)?;
+        if actual != value {
+            return Err(OptionBytesError::VerifyFailed {
+                expected: value,
+                actual,
+            });
+        }
+
+        tracing::info!(
+            "Changed the option bytes from {current:#010x} to {value:#010x}, they take effect after the next power-on reset."
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_parsed() {
+        assert_eq!(
+            parse_settings("BOR_LEV=2, iwdg_sw=0x1").unwrap(),
+            vec![("bor_lev".to_string(), 2), ("iwdg_sw".to_string(), 1)]
+        );
+        assert!(parse_settings("bor_lev").is_err());
+        assert!(parse_settings("bor_lev=high").is_err());
+    }
+
+    #[test]
+    fn fields_are_set() {
+        let layout = Layout::for_chip("STM32L476RGTx").unwrap();
+        assert_eq!(layout.family, "STM32L4");
+
+        let value = layout
+            .apply(0xFFEF_F8AA, &vec![("bor_lev".to_string(), 4)])
+            .unwrap();
+        assert_eq!(value, 0xFFEF_FCAA);
+    }
+
+    #[test]
+    fn read_protection_cannot_be_set() {
+        let layout = Layout::for_chip("STM32F407VGTx").unwrap();
+
+        assert!(matches!(
+            layout.apply(0x0FFF_AAEC, &vec![("rdp".to_string(), 0xCC)]),
+            Err(OptionBytesError::UnknownField { .. })
+        ));
+        assert!(matches!(
+            layout.apply(0x0FFF_AAEC, &vec![("bor_lev".to_string(), 4)]),
+            Err(OptionBytesError::ValueTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn other_chips_are_not_supported() {
+        assert_eq!(Layout::for_chip("STM32H743ZITx"), None);
+        assert_eq!(Layout::for_chip("nRF52840_xxAA"), None);
+    }
+}
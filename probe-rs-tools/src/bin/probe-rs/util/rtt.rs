@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose as base64_engine, Engine as _};
 use defmt_decoder::log::format::{Formatter, FormatterConfig, FormatterFormat};
-use defmt_decoder::DecodeError;
+use defmt_decoder::{DecodeError, Frame, Location};
 pub use probe_rs::rtt::ChannelMode;
 use probe_rs::rtt::{DownChannel, Error, Rtt, ScanRegion, UpChannel};
 use probe_rs::{Core, Session};
@@ -17,7 +18,9 @@ use std::{
     path::Path,
     thread,
 };
-use time::{macros::format_description, OffsetDateTime, UtcOffset};
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, OffsetDateTime, UtcOffset,
+};
 
 /// Infer the target core from the RTT symbol. Useful for multi-core targets.
 pub fn get_target_core_id(session: &mut Session, elf_file: impl AsRef<Path>) -> usize {
@@ -102,6 +105,11 @@ pub struct RttConfig {
     /// Configure data_format and show_timestamps for select channels
     #[serde(default = "Vec::new", rename = "rttChannelFormats")]
     pub channels: Vec<RttChannelConfig>,
+
+    /// Emit each line and each defmt message of all channels as a JSON object, instead of
+    /// rendered text.
+    #[serde(skip)]
+    pub json: bool,
 }
 
 impl RttConfig {
@@ -166,14 +174,31 @@ pub enum ChannelDataFormat {
         // CWD to strip from file paths in defmt output
         cwd: PathBuf,
     },
+    /// Every line is emitted as a JSON object `{"raw": "..."}`.
+    JsonString {
+        /// The start of a line which was not terminated yet.
+        pending: String,
+    },
+    /// Every chunk of binary data is emitted as a JSON object `{"data": "..."}`, with the data
+    /// encoded with base64.
+    JsonBinary,
+    /// Every defmt message is emitted as a JSON object.
+    JsonDefmt {
+        // CWD to strip from file paths in defmt output
+        cwd: PathBuf,
+    },
 }
 
 impl From<&ChannelDataFormat> for DataFormat {
     fn from(config: &ChannelDataFormat) -> Self {
         match config {
-            ChannelDataFormat::String { .. } => DataFormat::String,
-            ChannelDataFormat::BinaryLE => DataFormat::BinaryLE,
-            ChannelDataFormat::Defmt { .. } => DataFormat::Defmt,
+            ChannelDataFormat::String { .. } | ChannelDataFormat::JsonString { .. } => {
+                DataFormat::String
+            }
+            ChannelDataFormat::BinaryLE | ChannelDataFormat::JsonBinary => DataFormat::BinaryLE,
+            ChannelDataFormat::Defmt { .. } | ChannelDataFormat::JsonDefmt { .. } => {
+                DataFormat::Defmt
+            }
         }
     }
 }
@@ -190,7 +215,15 @@ impl std::fmt::Debug for ChannelDataFormat {
                 .field("last_line_done", last_line_done)
                 .finish(),
             ChannelDataFormat::BinaryLE => f.debug_struct("BinaryLE").finish(),
+            ChannelDataFormat::JsonBinary => f.debug_struct("JsonBinary").finish(),
             ChannelDataFormat::Defmt { .. } => f.debug_struct("Defmt").finish_non_exhaustive(),
+            ChannelDataFormat::JsonString { pending } => f
+                .debug_struct("JsonString")
+                .field("pending", pending)
+                .finish(),
+            ChannelDataFormat::JsonDefmt { cwd } => {
+                f.debug_struct("JsonDefmt").field("cwd", cwd).finish()
+            }
         }
     }
 }
@@ -226,7 +259,91 @@ impl ChannelDataFormat {
                 let string = Self::process_defmt(buffer, defmt_state, formatter, cwd)?;
                 collector.on_string_data(number, string)
             }
+            ChannelDataFormat::JsonString { ref mut pending } => {
+                let string = Self::process_json_string(number, buffer, pending);
+                collector.on_string_data(number, string)
+            }
+            ChannelDataFormat::JsonBinary => {
+                let string = Self::process_json_binary(number, buffer);
+                collector.on_string_data(number, string)
+            }
+            ChannelDataFormat::JsonDefmt { ref cwd } => {
+                let string = Self::process_json_defmt(number, buffer, defmt_state, cwd)?;
+                collector.on_string_data(number, string)
+            }
+        }
+    }
+
+    /// Emits `buffer` as a JSON object, with the data encoded with base64.
+    fn process_json_binary(number: usize, buffer: &[u8]) -> String {
+        if buffer.is_empty() {
+            return String::new();
+        }
+
+        let record = serde_json::json!({
+            "channel": number,
+            "data": base64_engine::STANDARD.encode(buffer),
+        });
+        format!("{record}\n")
+    }
+
+    /// Emits all complete lines in `buffer` as JSON objects, and keeps the rest in `pending`.
+    fn process_json_string(number: usize, buffer: &[u8], pending: &mut String) -> String {
+        pending.push_str(&String::from_utf8_lossy(buffer));
+
+        let Some(end) = pending.rfind('\n') else {
+            return String::new();
+        };
+
+        let mut formatted_data = String::new();
+        for line in pending[..end].split('\n') {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let record = serde_json::json!({ "channel": number, "raw": line });
+            writeln!(formatted_data, "{record}").expect("Writing to String cannot fail");
         }
+        pending.drain(..=end);
+
+        formatted_data
+    }
+
+    fn process_json_defmt(
+        number: usize,
+        buffer: &[u8],
+        defmt_state: Option<&DefmtState>,
+        cwd: &Path,
+    ) -> Result<String> {
+        let Some(defmt_state) = defmt_state else {
+            let record = serde_json::json!({
+                "channel": number,
+                "error": "Trying to process defmt data but table or locations could not be loaded.",
+                "data": base64_engine::STANDARD.encode(buffer),
+            });
+            return Ok(format!("{record}\n"));
+        };
+
+        let mut formatted_data = String::new();
+        decode_defmt(buffer, defmt_state, |frame, loc| {
+            let host_timestamp = OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default();
+            let file = loc.map(|loc| {
+                let relpath = loc.file.strip_prefix(cwd).unwrap_or(&loc.file);
+                relpath.display().to_string()
+            });
+            let record = serde_json::json!({
+                "channel": number,
+                "host_timestamp": host_timestamp,
+                "timestamp": frame.display_timestamp().map(|timestamp| timestamp.to_string()),
+                "level": frame.level().map(|level| level.as_str()),
+                "module": loc.map(|loc| loc.module.as_str()),
+                "file": file,
+                "line": loc.map(|loc| loc.line),
+                "message": frame.display_message().to_string(),
+            });
+            writeln!(formatted_data, "{record}").expect("Writing to String cannot fail");
+        })?;
+
+        Ok(formatted_data)
     }
 
     fn process_string(
@@ -263,60 +380,73 @@ impl ChannelDataFormat {
         formatter: &Formatter,
         cwd: &Path,
     ) -> Result<String> {
-        let Some(DefmtState { table, locs }) = defmt_state else {
+        let Some(defmt_state) = defmt_state else {
             return Ok(String::from(
                 "Trying to process defmt data but table or locations could not be loaded.\n",
             ));
         };
 
-        let mut stream_decoder = table.new_stream_decoder();
+        let mut formatted_data = String::new();
+        decode_defmt(buffer, defmt_state, |frame, loc| {
+            let (file, line, module) = if let Some(loc) = loc {
+                let relpath = loc.file.strip_prefix(cwd).unwrap_or(&loc.file);
+                (
+                    relpath.display().to_string(),
+                    Some(loc.line.try_into().unwrap()),
+                    Some(loc.module.as_str()),
+                )
+            } else {
+                (
+                    format!(
+                        "└─ <invalid location: defmt frame-index: {}>",
+                        frame.index()
+                    ),
+                    None,
+                    None,
+                )
+            };
+            let s = formatter.format_frame(frame, Some(&file), line, module);
+            writeln!(formatted_data, "{s}").expect("Writing to String cannot fail");
+        })?;
+
+        Ok(formatted_data)
+    }
+}
 
-        // FIXME: this assumes we read frames atomically which is implementation-defined and we
-        // should be able to handle the case where a frame is split across two reads with a
-        // temporary buffer.
-        stream_decoder.received(buffer);
+/// Decodes the defmt frames in `buffer`, and calls `on_frame` with every frame and its location.
+fn decode_defmt(
+    buffer: &[u8],
+    DefmtState { table, locs }: &DefmtState,
+    mut on_frame: impl FnMut(Frame<'_>, Option<&Location>),
+) -> Result<()> {
+    let mut stream_decoder = table.new_stream_decoder();
 
-        let mut formatted_data = String::new();
-        loop {
-            match stream_decoder.decode() {
-                Ok(frame) => {
-                    let loc = locs.as_ref().and_then(|locs| locs.get(&frame.index()));
-                    let (file, line, module) = if let Some(loc) = loc {
-                        let relpath = loc.file.strip_prefix(cwd).unwrap_or(&loc.file);
-                        (
-                            relpath.display().to_string(),
-                            Some(loc.line.try_into().unwrap()),
-                            Some(loc.module.as_str()),
-                        )
-                    } else {
-                        (
-                            format!(
-                                "└─ <invalid location: defmt frame-index: {}>",
-                                frame.index()
-                            ),
-                            None,
-                            None,
-                        )
-                    };
-                    let s = formatter.format_frame(frame, Some(&file), line, module);
-                    writeln!(formatted_data, "{s}").expect("Writing to String cannot fail");
-                }
-                Err(DecodeError::UnexpectedEof) => break,
-                Err(DecodeError::Malformed) if table.encoding().can_recover() => {
-                    // If recovery is possible, skip the current frame and continue with new data.
-                }
-                Err(DecodeError::Malformed) => {
-                    return Err(anyhow!(
-                        "Unrecoverable error while decoding Defmt \
-                        data and some data may have been lost: {:?}",
-                        DecodeError::Malformed
-                    ));
-                }
+    // FIXME: this assumes we read frames atomically which is implementation-defined and we
+    // should be able to handle the case where a frame is split across two reads with a
+    // temporary buffer.
+    stream_decoder.received(buffer);
+
+    loop {
+        match stream_decoder.decode() {
+            Ok(frame) => {
+                let loc = locs.as_ref().and_then(|locs| locs.get(&frame.index()));
+                on_frame(frame, loc);
+            }
+            Err(DecodeError::UnexpectedEof) => break,
+            Err(DecodeError::Malformed) if table.encoding().can_recover() => {
+                // If recovery is possible, skip the current frame and continue with new data.
+            }
+            Err(DecodeError::Malformed) => {
+                return Err(anyhow!(
+                    "Unrecoverable error while decoding Defmt \
+                    data and some data may have been lost: {:?}",
+                    DecodeError::Malformed
+                ));
             }
         }
-
-        Ok(formatted_data)
     }
+
+    Ok(())
 }
 
 pub trait ChannelDataCallbacks {
@@ -349,19 +479,28 @@ impl RttActiveUpChannel {
         core: &mut Core,
         up_channel: UpChannel,
         channel_config: &RttChannelConfig,
+        json: bool,
         timestamp_offset: UtcOffset,
         defmt_state: Option<&DefmtState>,
     ) -> Result<Self> {
         let is_defmt_channel = up_channel.name() == Some("defmt");
 
         let data_format = match channel_config.data_format {
+            DataFormat::String if !is_defmt_channel && json => ChannelDataFormat::JsonString {
+                pending: String::new(),
+            },
             DataFormat::String if !is_defmt_channel => ChannelDataFormat::String {
                 timestamp_offset: channel_config.show_timestamps.then_some(timestamp_offset),
                 last_line_done: true,
             },
 
+            DataFormat::BinaryLE if !is_defmt_channel && json => ChannelDataFormat::JsonBinary,
             DataFormat::BinaryLE if !is_defmt_channel => ChannelDataFormat::BinaryLE,
 
+            _ if json => ChannelDataFormat::JsonDefmt {
+                cwd: std::env::current_dir().unwrap(),
+            },
+
             // either DataFormat::Defmt is configured, or defmt_enabled is true
             _ => {
                 let has_timestamp = if let Some(defmt) = defmt_state {
@@ -406,7 +545,10 @@ impl RttActiveUpChannel {
         let mut original_mode = None;
         if let Some(mode) = channel_config.mode.or(
             // Try not to corrupt the byte stream if using defmt
-            if matches!(data_format, ChannelDataFormat::Defmt { .. }) {
+            if matches!(
+                data_format,
+                ChannelDataFormat::Defmt { .. } | ChannelDataFormat::JsonDefmt { .. }
+            ) {
                 Some(ChannelMode::BlockIfFull)
             } else {
                 None
@@ -574,6 +716,7 @@ impl RttActiveTarget {
                     core,
                     channel,
                     &channel_config,
+                    rtt_config.json,
                     timestamp_offset,
                     defmt_state.as_ref(),
                 )?,
@@ -638,6 +781,16 @@ impl RttActiveTarget {
         Ok(())
     }
 
+    /// Discards the data which is already in the up channels, and returns how many bytes were
+    /// discarded.
+    pub fn clear_up_channels(&mut self, core: &mut Core) -> Result<usize> {
+        let mut discarded = 0;
+        for channel in self.active_up_channels.values() {
+            discarded += channel.up_channel.clear(core)?;
+        }
+        Ok(discarded)
+    }
+
     /// Clean up temporary changes made to the channels.
     pub fn clean_up(&mut self, core: &mut Core) -> Result<()> {
         for channel in self.active_up_channels.values_mut() {
@@ -716,3 +869,29 @@ pub fn try_attach_to_rtt_shared(
         timeout,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lines_of_string_channel() {
+        let mut pending = String::new();
+
+        let first = ChannelDataFormat::process_json_string(0, b"Hello\r\nWor", &mut pending);
+        assert_eq!(first, "{\"channel\":0,\"raw\":\"Hello\"}\n");
+        assert_eq!(pending, "Wor");
+
+        let second = ChannelDataFormat::process_json_string(0, b"ld \"1\"\n", &mut pending);
+        assert_eq!(second, "{\"channel\":0,\"raw\":\"World \\\"1\\\"\"}\n");
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn json_records_of_binary_channel() {
+        let record = ChannelDataFormat::process_json_binary(1, &[0x00, 0xff, b'\n']);
+        assert_eq!(record, "{\"channel\":1,\"data\":\"AP8K\"}\n");
+
+        assert!(ChannelDataFormat::process_json_binary(1, &[]).is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+//! Output directories for the samples of `profile` and `trace`, with a `meta.json` which
+//! describes the run, so that runs can be told apart and compared later.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use probe_rs::Session;
+use probe_rs_mi::meta::Meta;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+use super::elf::ElfIdentity;
+use super::meta::current_meta;
+use super::sampling::SampleRateReport;
+
+/// The name of the metadata file in the output directory.
+pub const METADATA_NAME: &str = "meta.json";
+
+/// A timestamped directory for the output of a single run.
+pub struct RunOutput {
+    directory: PathBuf,
+    started: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+struct RunMetadata {
+    probe_rs: Meta,
+    command: &'static str,
+    /// The start of the run, in RFC 3339 format.
+    timestamp: String,
+    chip: String,
+    probe_serial: Option<String>,
+    elf: Option<PathBuf>,
+    /// The GNU build ID of the ELF file, as a hex string.
+    elf_build_id: Option<String>,
+    samples: u64,
+    duration_seconds: f64,
+    sample_rate_hz: f64,
+    /// `None` if the samples were taken as fast as possible.
+    requested_sample_rate_hz: Option<f64>,
+}
+
+impl RunOutput {
+    /// Creates the directory `<parent>/<command>-<timestamp>`.
+    pub fn create(parent: &Path, command: &str) -> Result<Self> {
+        let started = OffsetDateTime::now_utc();
+        let directory = parent.join(directory_name(command, started));
+
+        fs::create_dir_all(&directory).with_context(|| {
+            format!(
+                "Failed to create the output directory {}",
+                directory.display()
+            )
+        })?;
+
+        Ok(Self { directory, started })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Creates a file in the output directory.
+    pub fn create_file(&self, name: &str) -> Result<File> {
+        let path = self.directory.join(name);
+        File::create(&path).with_context(|| format!("Failed to create {}", path.display()))
+    }
+
+    /// Writes the `meta.json` of the run.
+    pub fn write_metadata(
+        &self,
+        command: &'static str,
+        session: &Session,
+        probe_serial: Option<String>,
+        elf: Option<&Path>,
+        report: &SampleRateReport,
+    ) -> Result<()> {
+        let elf = elf.map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+
+        let metadata = RunMetadata {
+            probe_rs: current_meta()?,
+            command,
+            timestamp: self.started.format(&Rfc3339)?,
+            chip: session.target().name.clone(),
+            probe_serial,
+            elf_build_id: elf.as_deref().and_then(elf_build_id),
+            elf,
+            samples: report.samples,
+            duration_seconds: report.elapsed.as_secs_f64(),
+            sample_rate_hz: report.rate(),
+            requested_sample_rate_hz: report.requested,
+        };
+
+        let file = self.create_file(METADATA_NAME)?;
+        serde_json::to_writer_pretty(file, &metadata)?;
+
+        Ok(())
+    }
+}
+
+/// The name of the output directory, which sorts by the time the run started.
+fn directory_name(command: &str, started: OffsetDateTime) -> String {
+    let format = format_description!("[year][month][day]T[hour][minute][second]Z");
+    // Formatting a UTC date with a fixed description cannot fail.
+    let timestamp = started.format(&format).unwrap_or_default();
+
+    format!("{command}-{timestamp}")
+}
+
+/// Reads the GNU build ID of an ELF file, if it has one.
+fn elf_build_id(path: &Path) -> Option<String> {
+    ElfIdentity::read(path)
+        .ok()?
+        .build_id
+        .map(|build_id| build_id.hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn directories_are_named_by_start() {
+        assert_eq!(
+            directory_name("profile", datetime!(2024-05-17 08:03:09 UTC)),
+            "profile-20240517T080309Z"
+        );
+    }
+
+    #[test]
+    fn build_id_is_optional() {
+        let elf = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../probe-rs/tests/debug-unwind-tests/RP2040_full_unwind.elf");
+
+        assert_eq!(elf_build_id(&elf), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Pacing of sampling loops, and reporting of the achieved sample rate.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Paces a sampling loop to a fixed rate, and keeps track of the rate which was achieved.
+pub struct SamplePacer {
+    /// The time between two samples, or `None` to sample as fast as possible.
+    interval: Option<Duration>,
+    start: Instant,
+    /// The time at which the next sample is due.
+    next: Instant,
+    samples: u64,
+    /// The number of samples which were taken more than one interval after they were due.
+    late: u64,
+}
+
+impl SamplePacer {
+    /// Creates a pacer for `rate` samples per second, or for unpaced sampling.
+    pub fn new(rate: Option<f64>) -> Self {
+        let start = Instant::now();
+
+        Self {
+            interval: rate.map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            start,
+            next: start,
+            samples: 0,
+            late: 0,
+        }
+    }
+
+    /// Waits until the next sample is due. Call this once before taking each sample.
+    pub fn wait(&mut self) {
+        self.samples += 1;
+
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        let now = Instant::now();
+        if now < self.next {
+            std::thread::sleep(self.next - now);
+            self.next += interval;
+        } else {
+            if now > self.next + interval {
+                self.late += 1;
+            }
+            // Do not try to catch up, which would sample faster than requested.
+            self.next = now.max(self.next) + interval;
+        }
+    }
+
+    /// The time since the pacer was created.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// A summary of the sampling so far.
+    pub fn report(&self) -> SampleRateReport {
+        SampleRateReport {
+            samples: self.samples,
+            elapsed: self.elapsed(),
+            requested: self.interval.map(|interval| 1.0 / interval.as_secs_f64()),
+            late: self.late,
+        }
+    }
+}
+
+/// The number of samples taken, and the achieved rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleRateReport {
+    pub samples: u64,
+    pub elapsed: Duration,
+    /// The requested rate in Hz, if the sampling was paced.
+    pub requested: Option<f64>,
+    pub late: u64,
+}
+
+impl SampleRateReport {
+    /// The achieved sample rate in Hz.
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.samples as f64 / seconds
+    }
+}
+
+impl fmt::Display for SampleRateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Took {} samples in {:.2?} ({:.1} Hz)",
+            self.samples,
+            self.elapsed,
+            self.rate()
+        )?;
+
+        if let Some(requested) = self.requested {
+            write!(
+                f,
+                ", requested {requested:.1} Hz, {} samples were late",
+                self.late
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a sample rate in Hz, which has to be positive.
+pub fn parse_sample_rate(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        Ok(_) => Err("The sample rate must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_without_pacing() {
+        let report = SampleRateReport {
+            samples: 100,
+            elapsed: Duration::from_secs(2),
+            requested: None,
+            late: 0,
+        };
+
+        assert_eq!(report.rate(), 50.0);
+        assert_eq!(report.to_string(), "Took 100 samples in 2.00s (50.0 Hz)");
+    }
+
+    #[test]
+    fn paced_sampling_is_not_faster_than_requested() {
+        let mut pacer = SamplePacer::new(Some(200.0));
+        for _ in 0..5 {
+            pacer.wait();
+        }
+
+        // The first sample is taken immediately, the others 5 ms apart.
+        assert!(pacer.elapsed() >= Duration::from_millis(20));
+        assert_eq!(pacer.report().samples, 5);
+    }
+
+    #[test]
+    fn sample_rate_must_be_positive() {
+        assert_eq!(parse_sample_rate("1000"), Ok(1000.0));
+        assert!(parse_sample_rate("0").is_err());
+        assert!(parse_sample_rate("-5").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Loading directories of target description files into the registry.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// Adds the targets of all `.yaml` and `.yml` files in the given directories and their
+/// subdirectories to the registry.
+///
+/// The directories are loaded in order, and the files of a directory in the order of their paths.
+/// A chip of a later file overrides a chip of the same name of an earlier one.
+pub fn load_target_description_dirs(directories: &[PathBuf]) -> anyhow::Result<()> {
+    for directory in directories {
+        let files = find_target_descriptions(directory).with_context(|| {
+            format!(
+                "Failed to read the target description directory {}",
+                directory.display()
+            )
+        })?;
+
+        if files.is_empty() {
+            tracing::warn!(
+                "The target description directory {} does not contain any YAML files.",
+                directory.display()
+            );
+        }
+
+        for path in files {
+            tracing::debug!("Loading target description {}", path.display());
+
+            let file =
+                File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+            probe_rs::config::add_target_from_yaml(file).with_context(|| {
+                format!("Failed to load the target description {}", path.display())
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the YAML files in `directory` and its subdirectories, sorted by their path.
+fn find_target_descriptions(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut directories = vec![directory.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                directories.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "yaml" || extension == "yml")
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptions_are_found_recursively() {
+        let directory =
+            std::env::temp_dir().join(format!("probe-rs-targets-{}", std::process::id()));
+        fs::create_dir_all(directory.join("vendor")).unwrap();
+        for name in ["b.yaml", "a.yml", "notes.txt", "vendor/c.yaml"] {
+            File::create(directory.join(name)).unwrap();
+        }
+
+        let files = find_target_descriptions(&directory).unwrap();
+        let files = files
+            .iter()
+            .map(|path| path.strip_prefix(&directory).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            files,
+            [
+                PathBuf::from("a.yml"),
+                PathBuf::from("b.yaml"),
+                Path::new("vendor").join("c.yaml")
+            ]
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Module for using the CTI.
+//!
+//! CTI = Cross Trigger Interface
+
+use super::super::memory::{romtable::CoresightComponent, ArmProbe};
+use crate::architecture::arm::{ArmError, ArmProbeInterface};
+
+const REGISTER_OFFSET_CTICONTROL: u32 = 0x000;
+const REGISTER_OFFSET_CTIINTACK: u32 = 0x010;
+const REGISTER_OFFSET_CTIAPPPULSE: u32 = 0x01C;
+const REGISTER_OFFSET_CTIINEN: u32 = 0x020;
+const REGISTER_OFFSET_CTIOUTEN: u32 = 0x0A0;
+const REGISTER_OFFSET_CTITRIGINSTATUS: u32 = 0x130;
+const REGISTER_OFFSET_CTITRIGOUTSTATUS: u32 = 0x134;
+const REGISTER_OFFSET_CTIGATE: u32 = 0x140;
+const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
+const REGISTER_OFFSET_DEVID: u32 = 0xFC8;
+
+/// The trigger input which signals that a Cortex-M core halted.
+pub const TRIGGER_IN_HALTED: usize = 0;
+/// The trigger output which requests a Cortex-M core to halt (EDBGRQ).
+pub const TRIGGER_OUT_DEBUG_REQUEST: usize = 0;
+/// The channel which carries the halt events between the CTIs of the cores.
+pub const CHANNEL_HALT: usize = 0;
+
+/// The number of triggers and channels of a CTI, from its DEVID register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CtiDevId {
+    /// The number of trigger inputs and outputs.
+    pub triggers: usize,
+    /// The number of channels.
+    pub channels: usize,
+}
+
+impl From<u32> for CtiDevId {
+    fn from(value: u32) -> Self {
+        Self {
+            triggers: ((value >> 8) & 0xFF) as usize,
+            channels: ((value >> 16) & 0x3F) as usize,
+        }
+    }
+}
+
+/// An interface to control the CTI (Cross Trigger Interface) of a core.
+///
+/// The CTI connects the trigger inputs and outputs of a core, such as its halted state and its
+/// debug request, to channels which are shared with the CTIs of the other cores.
+pub struct Cti<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+}
+
+impl<'a> Cti<'a> {
+    /// Create a new CTI interface from a probe and a ROM table component.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Cti {
+            interface,
+            component,
+        }
+    }
+
+    /// Unlock the CTI registers for writing.
+    pub fn unlock(&mut self) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ACCESS, 0xC5AC_CE55)
+    }
+
+    /// Read the number of triggers and channels.
+    pub fn devid(&mut self) -> Result<CtiDevId, ArmError> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_DEVID)
+            .map(CtiDevId::from)
+    }
+
+    /// Check whether the mapping of triggers to channels is enabled.
+    pub fn is_enabled(&mut self) -> Result<bool, ArmError> {
+        Ok(self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_CTICONTROL)?
+            & 1
+            != 0)
+    }
+
+    /// Enable or disable the mapping of triggers to channels.
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CTICONTROL, enabled as u32)
+    }
+
+    /// Read the channels which the trigger input `trigger` generates events on, as a bit mask.
+    pub fn trigger_in_channels(&mut self, trigger: usize) -> Result<u32, ArmError> {
+        self.component.read_reg(
+            self.interface,
+            trigger_offset(REGISTER_OFFSET_CTIINEN, trigger),
+        )
+    }
+
+    /// Set the channels which the trigger input `trigger` generates events on, as a bit mask.
+    pub fn set_trigger_in_channels(
+        &mut self,
+        trigger: usize,
+        channels: u32,
+    ) -> Result<(), ArmError> {
+        self.component.write_reg(
+            self.interface,
+            trigger_offset(REGISTER_OFFSET_CTIINEN, trigger),
+            channels,
+        )
+    }
+
+    /// Read the channels which raise the trigger output `trigger`, as a bit mask.
+    pub fn trigger_out_channels(&mut self, trigger: usize) -> Result<u32, ArmError> {
+        self.component.read_reg(
+            self.interface,
+            trigger_offset(REGISTER_OFFSET_CTIOUTEN, trigger),
+        )
+    }
+
+    /// Set the channels which raise the trigger output `trigger`, as a bit mask.
+    pub fn set_trigger_out_channels(
+        &mut self,
+        trigger: usize,
+        channels: u32,
+    ) -> Result<(), ArmError> {
+        self.component.write_reg(
+            self.interface,
+            trigger_offset(REGISTER_OFFSET_CTIOUTEN, trigger),
+            channels,
+        )
+    }
+
+    /// Read the channels which are passed on to the other CTIs, as a bit mask.
+    pub fn gate(&mut self) -> Result<u32, ArmError> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_CTIGATE)
+    }
+
+    /// Set the channels which are passed on to the other CTIs, as a bit mask.
+    pub fn set_gate(&mut self, channels: u32) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CTIGATE, channels)
+    }
+
+    /// Read the active trigger inputs, as a bit mask.
+    pub fn trigger_in_status(&mut self) -> Result<u32, ArmError> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_CTITRIGINSTATUS)
+    }
+
+    /// Read the active trigger outputs, as a bit mask.
+    pub fn trigger_out_status(&mut self) -> Result<u32, ArmError> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_CTITRIGOUTSTATUS)
+    }
+
+    /// Generate an event on the given channels, as a bit mask.
+    pub fn pulse(&mut self, channels: u32) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CTIAPPPULSE, channels)
+    }
+}
+
+/// Configure the CTI at `address` in the memory of a Cortex-M core so that it halts the core when
+/// any other core connected to the halt channel halts, and raises the halt channel when the core
+/// halts.
+pub(crate) fn enable_cross_halt(memory: &mut dyn ArmProbe, address: u64) -> Result<(), ArmError> {
+    let halt_channel = 1 << CHANNEL_HALT;
+
+    memory.write_word_32(address + REGISTER_OFFSET_ACCESS as u64, 0xC5AC_CE55)?;
+
+    let inputs = trigger_offset(REGISTER_OFFSET_CTIINEN, TRIGGER_IN_HALTED);
+    update_register(memory, address + inputs as u64, |value| {
+        value | halt_channel
+    })?;
+    let outputs = trigger_offset(REGISTER_OFFSET_CTIOUTEN, TRIGGER_OUT_DEBUG_REQUEST);
+    update_register(memory, address + outputs as u64, |value| {
+        value | halt_channel
+    })?;
+    update_register(memory, address + REGISTER_OFFSET_CTIGATE as u64, |value| {
+        value | halt_channel
+    })?;
+
+    memory.write_word_32(address + REGISTER_OFFSET_CTICONTROL as u64, 1)
+}
+
+/// Disconnect the core from the halt channel again, see [`enable_cross_halt`].
+pub(crate) fn disable_cross_halt(memory: &mut dyn ArmProbe, address: u64) -> Result<(), ArmError> {
+    let halt_channel = 1 << CHANNEL_HALT;
+
+    let inputs = trigger_offset(REGISTER_OFFSET_CTIINEN, TRIGGER_IN_HALTED);
+    update_register(memory, address + inputs as u64, |value| {
+        value & !halt_channel
+    })?;
+    let outputs = trigger_offset(REGISTER_OFFSET_CTIOUTEN, TRIGGER_OUT_DEBUG_REQUEST);
+    update_register(memory, address + outputs as u64, |value| {
+        value & !halt_channel
+    })?;
+
+    acknowledge_debug_request(memory, address)
+}
+
+fn update_register(
+    memory: &mut dyn ArmProbe,
+    address: u64,
+    update: impl FnOnce(u32) -> u32,
+) -> Result<(), ArmError> {
+    let value = memory.read_word_32(address)?;
+    memory.write_word_32(address, update(value))
+}
+
+/// Acknowledge the debug request of the CTI at `address` in the memory of a core.
+///
+/// The debug request stays raised until it is acknowledged, and would halt the core again right
+/// after it is resumed.
+pub(crate) fn acknowledge_debug_request(
+    memory: &mut dyn ArmProbe,
+    address: u64,
+) -> Result<(), ArmError> {
+    memory.write_word_32(
+        address + REGISTER_OFFSET_CTIINTACK as u64,
+        1 << TRIGGER_OUT_DEBUG_REQUEST,
+    )
+}
+
+/// Checks whether the CTI at `address` in the memory of a core requests the core to halt, i.e.
+/// whether the core was halted by another core and the request was not acknowledged yet.
+pub(crate) fn debug_requested(memory: &mut dyn ArmProbe, address: u64) -> Result<bool, ArmError> {
+    let status = memory.read_word_32(address + REGISTER_OFFSET_CTITRIGOUTSTATUS as u64)?;
+
+    Ok(status & (1 << TRIGGER_OUT_DEBUG_REQUEST) != 0)
+}
+
+/// The offset of the `CTIINEN<n>` or `CTIOUTEN<n>` register of a trigger.
+fn trigger_offset(base: u32, trigger: usize) -> u32 {
+    base + 4 * trigger as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devid_is_decoded() {
+        // 8 triggers, 4 channels.
+        assert_eq!(
+            CtiDevId::from(0x0004_0800),
+            CtiDevId {
+                triggers: 8,
+                channels: 4
+            }
+        );
+    }
+}
@@ -1,5 +1,6 @@
 //! Types and functions for interacting with CoreSight Components
 
+mod cti;
 mod dwt;
 mod itm;
 mod scs;
@@ -18,6 +19,10 @@ use crate::architecture::arm::{ArmProbeInterface, SwoConfig, SwoMode};
 use crate::{Core, Error, MemoryInterface, MemoryMappedRegister};
 
 pub use self::itm::Itm;
+pub(crate) use cti::{
+    acknowledge_debug_request, debug_requested, disable_cross_halt, enable_cross_halt,
+};
+pub use cti::{Cti, CtiDevId, CHANNEL_HALT, TRIGGER_IN_HALTED, TRIGGER_OUT_DEBUG_REQUEST};
 pub use dwt::Dwt;
 pub use scs::Scs;
 pub use swo::Swo;
@@ -1,16 +1,24 @@
 //! Register types and the core interface for armv6-M
 
-use super::{registers::cortex_m::*, CortexMState, Dfsr};
+use super::{
+    cortex_m::{resume_cross_halted, DwtVersion},
+    registers::cortex_m::*,
+    reset_delay::ResetDelay,
+    CortexMState, Dfsr,
+};
 use crate::{
     architecture::arm::{
-        memory::adi_v5_memory_interface::ArmProbe, sequences::ArmDebugSequence, ArmError,
+        component::{acknowledge_debug_request, disable_cross_halt},
+        memory::adi_v5_memory_interface::ArmProbe,
+        sequences::ArmDebugSequence,
+        ArmError,
     },
     core::{CoreRegisters, RegisterId, RegisterValue, VectorCatchCondition},
     error::Error,
     memory::valid_32bit_address,
     probe::DebugProbeError,
     Architecture, BreakpointCause, CoreInformation, CoreInterface, CoreRegister, CoreStatus,
-    CoreType, HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister,
+    CoreType, HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister, Watchpoint,
 };
 use anyhow::Result;
 use bitfield::bitfield;
@@ -487,6 +495,8 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        // Reading the DHCSR clears the reset status, so it is kept for `was_reset`.
+        self.state.record_reset(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             tracing::warn!(
@@ -588,6 +598,11 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     }
 
     fn run(&mut self) -> Result<(), Error> {
+        // The debug request of the other cores would halt the core again right away.
+        if let Some(cti) = self.state.cross_halt_cti {
+            acknowledge_debug_request(&mut *self.memory, cti)?;
+        }
+
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
 
@@ -601,6 +616,8 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         self.memory.flush()?;
 
         // We assume that the core is running now.
+        resume_cross_halted(&mut *self.memory, &self.state.cross_halt_peers)?;
+
         self.set_core_status(CoreStatus::Running);
         Ok(())
     }
@@ -608,16 +625,22 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     fn reset(&mut self) -> Result<(), Error> {
         self.state.semihosting_command = None;
 
-        self.sequence
-            .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)?;
+        self.sequence.reset_system(
+            &mut ResetDelay::new(&mut *self.memory, self.state.reset_delay),
+            crate::CoreType::Armv6m,
+            None,
+        )?;
         Ok(())
     }
 
     fn reset_and_halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
         self.reset_catch_set()?;
 
-        self.sequence
-            .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)?;
+        self.sequence.reset_system(
+            &mut ResetDelay::new(&mut *self.memory, self.state.reset_delay),
+            crate::CoreType::Armv6m,
+            None,
+        )?;
 
         // Update core status
         let _ = self.status()?;
@@ -793,6 +816,31 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        super::cortex_m::available_watchpoint_units(&mut *self.memory)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        super::cortex_m::hw_watchpoints(&mut *self.memory, DwtVersion::V6V7)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        super::cortex_m::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::V6V7,
+            unit_index,
+            watchpoint,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        super::cortex_m::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
     fn registers(&self) -> &'static CoreRegisters {
         &CORTEX_M_CORE_REGISTERS
     }
@@ -857,6 +905,11 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
     #[tracing::instrument(skip(self))]
     fn debug_core_stop(&mut self) -> Result<(), Error> {
+        if let Some(cti) = self.state.cross_halt_cti.take() {
+            disable_cross_halt(&mut *self.memory, cti)?;
+        }
+        self.state.cross_halt_peers.clear();
+
         self.sequence
             .debug_core_stop(&mut *self.memory, CoreType::Armv6m)?;
         Ok(())
@@ -887,6 +940,13 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         Ok(())
     }
 
+    fn was_reset(&mut self) -> Result<bool, Error> {
+        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        self.state.record_reset(dhcsr.s_reset_st());
+
+        Ok(self.state.take_reset())
+    }
+
     fn disable_vector_catch(&mut self, condition: VectorCatchCondition) -> Result<(), Error> {
         let mut demcr = Demcr(self.memory.read_word_32(Demcr::get_mmio_address())?);
         match condition {
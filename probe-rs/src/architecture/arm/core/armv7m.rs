@@ -1,20 +1,24 @@
 //! Register types and the core interface for armv7-M
 
 use super::{
-    cortex_m::Mvfr0,
+    cortex_m::{resume_cross_halted, DwtVersion, Mvfr0},
     registers::cortex_m::{
         CORTEX_M_CORE_REGISTERS, CORTEX_M_WITH_FP_CORE_REGISTERS, FP, PC, RA, SP,
     },
+    reset_delay::ResetDelay,
     CortexMState, Dfsr,
 };
 use crate::{
     architecture::arm::{
-        core::registers::cortex_m::XPSR, memory::adi_v5_memory_interface::ArmProbe,
-        sequences::ArmDebugSequence, ArmError,
+        component::{acknowledge_debug_request, disable_cross_halt},
+        core::registers::cortex_m::XPSR,
+        memory::adi_v5_memory_interface::ArmProbe,
+        sequences::ArmDebugSequence,
+        ArmError,
     },
     core::{
         Architecture, CoreInformation, CoreInterface, CoreRegisters, CoreStatus, HaltReason,
-        MemoryMappedRegister, RegisterId, RegisterValue, VectorCatchCondition,
+        MemoryMappedRegister, RegisterId, RegisterValue, VectorCatchCondition, Watchpoint,
     },
     error::Error,
     memory::valid_32bit_address,
@@ -666,6 +670,8 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn status(&mut self) -> Result<CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        // Reading the DHCSR clears the reset status, so it is kept for `was_reset`.
+        self.state.record_reset(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             tracing::error!(
@@ -767,6 +773,11 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     }
 
     fn run(&mut self) -> Result<(), Error> {
+        // The debug request of the other cores would halt the core again right away.
+        if let Some(cti) = self.state.cross_halt_cti {
+            acknowledge_debug_request(&mut *self.memory, cti)?;
+        }
+
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
 
@@ -790,6 +801,8 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         self.memory.flush()?;
 
         // We assume that the core is running now
+        resume_cross_halted(&mut *self.memory, &self.state.cross_halt_peers)?;
+
         self.set_core_status(CoreStatus::Running);
 
         Ok(())
@@ -798,8 +811,11 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     fn reset(&mut self) -> Result<(), Error> {
         self.state.semihosting_command = None;
 
-        self.sequence
-            .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)?;
+        self.sequence.reset_system(
+            &mut ResetDelay::new(&mut *self.memory, self.state.reset_delay),
+            crate::CoreType::Armv7m,
+            None,
+        )?;
         Ok(())
     }
 
@@ -808,8 +824,11 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         // This will halt the core after reset.
         self.reset_catch_set()?;
 
-        self.sequence
-            .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)?;
+        self.sequence.reset_system(
+            &mut ResetDelay::new(&mut *self.memory, self.state.reset_delay),
+            crate::CoreType::Armv7m,
+            None,
+        )?;
 
         // Update core status
         let _ = self.status()?;
@@ -1019,6 +1038,31 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        super::cortex_m::available_watchpoint_units(&mut *self.memory)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        super::cortex_m::hw_watchpoints(&mut *self.memory, DwtVersion::V6V7)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        super::cortex_m::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::V6V7,
+            unit_index,
+            watchpoint,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        super::cortex_m::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
     fn registers(&self) -> &'static CoreRegisters {
         if self.state.fp_present {
             &CORTEX_M_WITH_FP_CORE_REGISTERS
@@ -1085,6 +1129,11 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     #[tracing::instrument(skip(self))]
     fn debug_core_stop(&mut self) -> Result<(), Error> {
+        if let Some(cti) = self.state.cross_halt_cti.take() {
+            disable_cross_halt(&mut *self.memory, cti)?;
+        }
+        self.state.cross_halt_peers.clear();
+
         self.sequence
             .debug_core_stop(&mut *self.memory, CoreType::Armv7m)?;
         Ok(())
@@ -1115,6 +1164,13 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         Ok(())
     }
 
+    fn was_reset(&mut self) -> Result<bool, Error> {
+        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        self.state.record_reset(dhcsr.s_reset_st());
+
+        Ok(self.state.take_reset())
+    }
+
     fn disable_vector_catch(&mut self, condition: VectorCatchCondition) -> Result<(), Error> {
         let mut demcr = Demcr(self.memory.read_word_32(Demcr::get_mmio_address())?);
         match condition {
@@ -1,22 +1,26 @@
 //! Register types and the core interface for armv8-M
 
 use super::{
-    cortex_m::{IdPfr1, Mvfr0},
+    cortex_m::{resume_cross_halted, DwtVersion, IdPfr1, Mvfr0},
     registers::cortex_m::{
         CORTEX_M_CORE_REGISTERS, CORTEX_M_WITH_FP_CORE_REGISTERS, FP, PC, RA, SP,
     },
+    reset_delay::ResetDelay,
     CortexMState, Dfsr,
 };
 use crate::{
     architecture::arm::{
-        core::registers::cortex_m::XPSR, memory::adi_v5_memory_interface::ArmProbe,
-        sequences::ArmDebugSequence, ArmError,
+        component::{acknowledge_debug_request, disable_cross_halt},
+        core::registers::cortex_m::XPSR,
+        memory::adi_v5_memory_interface::ArmProbe,
+        sequences::ArmDebugSequence,
+        ArmError,
     },
     core::{CoreRegisters, RegisterId, RegisterValue, VectorCatchCondition},
     error::Error,
     memory::valid_32bit_address,
     Architecture, BreakpointCause, CoreInformation, CoreInterface, CoreRegister, CoreStatus,
-    CoreType, HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister,
+    CoreType, HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister, Watchpoint,
 };
 use anyhow::Result;
 use bitfield::bitfield;
@@ -107,6 +111,8 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        // Reading the DHCSR clears the reset status, so it is kept for `was_reset`.
+        self.state.record_reset(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             tracing::warn!(
@@ -210,6 +216,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         })
     }
     fn run(&mut self) -> Result<(), Error> {
+        // The debug request of the other cores would halt the core again right away.
+        if let Some(cti) = self.state.cross_halt_cti {
+            acknowledge_debug_request(&mut *self.memory, cti)?;
+        }
+
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
 
@@ -223,6 +234,8 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         self.memory.flush()?;
 
         // We assume that the core is running now
+        resume_cross_halted(&mut *self.memory, &self.state.cross_halt_peers)?;
+
         self.set_core_status(CoreStatus::Running);
 
         Ok(())
@@ -231,8 +244,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     fn reset(&mut self) -> Result<(), Error> {
         self.state.semihosting_command = None;
 
-        self.sequence
-            .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)?;
+        self.sequence.reset_system(
+            &mut ResetDelay::new(&mut *self.memory, self.state.reset_delay),
+            crate::CoreType::Armv8m,
+            None,
+        )?;
         Ok(())
     }
 
@@ -241,8 +257,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         // This will halt the core after reset.
         self.reset_catch_set()?;
 
-        self.sequence
-            .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)?;
+        self.sequence.reset_system(
+            &mut ResetDelay::new(&mut *self.memory, self.state.reset_delay),
+            crate::CoreType::Armv8m,
+            None,
+        )?;
 
         // Update core status
         let _ = self.status()?;
@@ -407,6 +426,31 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        super::cortex_m::available_watchpoint_units(&mut *self.memory)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        super::cortex_m::hw_watchpoints(&mut *self.memory, DwtVersion::V8)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        super::cortex_m::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::V8,
+            unit_index,
+            watchpoint,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        super::cortex_m::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
     fn registers(&self) -> &'static CoreRegisters {
         if self.state.fp_present {
             &CORTEX_M_WITH_FP_CORE_REGISTERS
@@ -473,6 +517,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     #[tracing::instrument(skip(self))]
     fn debug_core_stop(&mut self) -> Result<(), Error> {
+        if let Some(cti) = self.state.cross_halt_cti.take() {
+            disable_cross_halt(&mut *self.memory, cti)?;
+        }
+        self.state.cross_halt_peers.clear();
+
         self.sequence
             .debug_core_stop(&mut *self.memory, CoreType::Armv8m)?;
 
@@ -511,6 +560,13 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         Ok(())
     }
 
+    fn was_reset(&mut self) -> Result<bool, Error> {
+        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        self.state.record_reset(dhcsr.s_reset_st());
+
+        Ok(self.state.take_reset())
+    }
+
     fn disable_vector_catch(&mut self, condition: VectorCatchCondition) -> Result<(), Error> {
         let mut demcr = Demcr(self.memory.read_word_32(Demcr::get_mmio_address())?);
         let idpfr1 = IdPfr1(self.memory.read_word_32(IdPfr1::get_mmio_address())?);
@@ -1,11 +1,16 @@
 //! Common functions and data types for Cortex-M core variants
 
 use crate::{
-    architecture::arm::{memory::adi_v5_memory_interface::ArmProbe, ArmError},
+    architecture::arm::{
+        ap::MemoryAp,
+        component::{acknowledge_debug_request, debug_requested},
+        memory::adi_v5_memory_interface::ArmProbe,
+        ArmError,
+    },
     core::RegisterId,
     memory_mapped_bitfield_register,
     semihosting::decode_semihosting_syscall,
-    CoreInterface, Error, MemoryMappedRegister, SemihostingCommand,
+    CoreInterface, Error, MemoryMappedRegister, SemihostingCommand, Watchpoint, WatchpointKind,
 };
 use std::time::{Duration, Instant};
 
@@ -143,6 +148,48 @@ impl IdPfr1 {
     }
 }
 
+/// Resumes the other cores which were halted together with a core by its CTI, after the core
+/// was resumed, see [`crate::Session::enable_cross_halt`].
+///
+/// The `peers` are the memory APs of the other cores and the addresses of their CTIs. Only the
+/// cores which were halted by a debug request of their CTI are resumed, cores which halted on
+/// their own, e.g. at a breakpoint, stay halted.
+pub(crate) fn resume_cross_halted(
+    memory: &mut dyn ArmProbe,
+    peers: &[(MemoryAp, u64)],
+) -> Result<(), ArmError> {
+    if peers.is_empty() {
+        return Ok(());
+    }
+
+    let interface = match memory.get_arm_communication_interface() {
+        Ok(interface) => interface,
+        Err(error) => {
+            tracing::warn!("The other cores are not resumed, they can not be accessed: {error}");
+            return Ok(());
+        }
+    };
+
+    for &(ap, cti) in peers {
+        let mut memory = interface.memory_interface(ap)?;
+        if !debug_requested(&mut *memory, cti)? {
+            continue;
+        }
+
+        // The debug request would halt the core again right away.
+        acknowledge_debug_request(&mut *memory, cti)?;
+
+        let mut dhcsr = Dhcsr(0);
+        dhcsr.set_c_halt(false);
+        dhcsr.set_c_debugen(true);
+        dhcsr.enable_write();
+        memory.write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
+        memory.flush()?;
+    }
+
+    Ok(())
+}
+
 pub(crate) fn read_core_reg(memory: &mut dyn ArmProbe, addr: RegisterId) -> Result<u32, Error> {
     // Write the DCRSR value to select the register we want to read.
     let mut dcrsr_val = Dcrsr(0);
@@ -177,6 +224,194 @@ pub(crate) fn write_core_reg(
     Ok(())
 }
 
+/// DWT Control Register
+const DWT_CTRL: u64 = 0xE000_1000;
+/// First DWT comparator, the registers of the following ones are 16 bytes apart
+const DWT_COMP0: u64 = 0xE000_1020;
+/// Offset of the DWT_MASK register of a comparator. Not present on ARMv8-M.
+const DWT_MASK_OFFSET: u64 = 0x4;
+/// Offset of the DWT_FUNCTION register of a comparator
+const DWT_FUNCTION_OFFSET: u64 = 0x8;
+/// Debug Exception and Monitor Control Register
+const DEMCR: u64 = 0xE000_EDFC;
+/// Global enable for the DWT unit in the DEMCR register
+const DEMCR_TRCENA: u32 = 1 << 24;
+
+/// The layout of the DWT_FUNCTION register, which differs between ARMv6-M/ARMv7-M and ARMv8-M.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DwtVersion {
+    /// ARMv6-M and ARMv7-M, where the watched range is configured in DWT_MASK
+    V6V7,
+    /// ARMv8-M, where the access size is part of DWT_FUNCTION
+    V8,
+}
+
+impl DwtVersion {
+    /// The DWT_FUNCTION value for a watchpoint, or `None` if the length is not supported.
+    fn function(self, watchpoint: &Watchpoint) -> Option<u32> {
+        match self {
+            DwtVersion::V6V7 => Some(match watchpoint.kind {
+                WatchpointKind::Read => 0b0101,
+                WatchpointKind::Write => 0b0110,
+                WatchpointKind::ReadWrite => 0b0111,
+            }),
+            DwtVersion::V8 => {
+                let match_field = match watchpoint.kind {
+                    WatchpointKind::ReadWrite => 0b0100,
+                    WatchpointKind::Write => 0b0101,
+                    WatchpointKind::Read => 0b0110,
+                };
+                let data_size = match watchpoint.length {
+                    1 => 0b00,
+                    2 => 0b01,
+                    4 => 0b10,
+                    _ => return None,
+                };
+                // ACTION = 0b01 generates a debug event, which halts the core.
+                Some(match_field | (0b01 << 4) | (data_size << 10))
+            }
+        }
+    }
+
+    /// Decodes a DWT_FUNCTION value. Returns `None` if the comparator is not used as a watchpoint.
+    fn decode(self, function: u32, mask: u32) -> Option<(WatchpointKind, u64)> {
+        match self {
+            DwtVersion::V6V7 => {
+                let kind = match function & 0xf {
+                    0b0101 => WatchpointKind::Read,
+                    0b0110 => WatchpointKind::Write,
+                    0b0111 => WatchpointKind::ReadWrite,
+                    _ => return None,
+                };
+                Some((kind, 1 << (mask & 0x1f)))
+            }
+            DwtVersion::V8 => {
+                // Watchpoints generate debug events, comparators for trace do not.
+                if (function >> 4) & 0b11 != 0b01 {
+                    return None;
+                }
+                let kind = match function & 0xf {
+                    0b0100 => WatchpointKind::ReadWrite,
+                    0b0101 => WatchpointKind::Write,
+                    0b0110 => WatchpointKind::Read,
+                    _ => return None,
+                };
+                Some((kind, 1 << ((function >> 10) & 0b11)))
+            }
+        }
+    }
+}
+
+fn dwt_comparator_address(unit_index: usize) -> u64 {
+    DWT_COMP0 + 16 * unit_index as u64
+}
+
+/// Returns the number of DWT comparators, which can be used as watchpoints.
+pub(crate) fn available_watchpoint_units(memory: &mut dyn ArmProbe) -> Result<u32, Error> {
+    let ctrl = memory.read_word_32(DWT_CTRL)?;
+
+    Ok(ctrl >> 28)
+}
+
+/// Reads the watchpoints configured in the DWT comparators.
+pub(crate) fn hw_watchpoints(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+) -> Result<Vec<Option<Watchpoint>>, Error> {
+    let units = available_watchpoint_units(memory)? as usize;
+    let mut watchpoints = Vec::with_capacity(units);
+
+    for unit_index in 0..units {
+        let comparator = dwt_comparator_address(unit_index);
+        let function = memory.read_word_32(comparator + DWT_FUNCTION_OFFSET)?;
+        let mask = match version {
+            DwtVersion::V6V7 => memory.read_word_32(comparator + DWT_MASK_OFFSET)?,
+            DwtVersion::V8 => 0,
+        };
+
+        let watchpoint = match version.decode(function, mask) {
+            Some((kind, length)) => Some(Watchpoint {
+                address: memory.read_word_32(comparator)? as u64,
+                length,
+                kind,
+            }),
+            None => None,
+        };
+        watchpoints.push(watchpoint);
+    }
+
+    Ok(watchpoints)
+}
+
+/// Configures DWT comparator `unit_index` as a watchpoint.
+pub(crate) fn set_hw_watchpoint(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+    unit_index: usize,
+    watchpoint: Watchpoint,
+) -> Result<(), Error> {
+    let address = u32::try_from(watchpoint.address).map_err(|_| {
+        Error::Other(anyhow::anyhow!(
+            "Watchpoint address {:#x} is not a valid 32-bit address",
+            watchpoint.address
+        ))
+    })?;
+
+    // The comparators match naturally aligned ranges with a power of two size.
+    let length = watchpoint.length;
+    if !length.is_power_of_two() || watchpoint.address % length != 0 {
+        return Err(Error::Other(anyhow::anyhow!(
+            "Watchpoints must cover a power of two number of bytes at an aligned address, got {length} bytes at {address:#010x}"
+        )));
+    }
+
+    let Some(function) = version.function(&watchpoint) else {
+        return Err(Error::Other(anyhow::anyhow!(
+            "Watchpoints can only cover 1, 2 or 4 bytes on this core, got {length}"
+        )));
+    };
+
+    let demcr = memory.read_word_32(DEMCR)?;
+    memory.write_word_32(DEMCR, demcr | DEMCR_TRCENA)?;
+
+    let comparator = dwt_comparator_address(unit_index);
+
+    // Disable the comparator while it is reconfigured.
+    memory.write_word_32(comparator + DWT_FUNCTION_OFFSET, 0)?;
+    memory.write_word_32(comparator, address)?;
+
+    if version == DwtVersion::V6V7 {
+        let mask = length.trailing_zeros();
+        memory.write_word_32(comparator + DWT_MASK_OFFSET, mask)?;
+
+        // The number of implemented mask bits is implementation defined.
+        if memory.read_word_32(comparator + DWT_MASK_OFFSET)? != mask {
+            memory.write_word_32(comparator + DWT_MASK_OFFSET, 0)?;
+            return Err(Error::Other(anyhow::anyhow!(
+                "The core does not support watchpoints covering {length} bytes"
+            )));
+        }
+    }
+
+    memory.write_word_32(comparator + DWT_FUNCTION_OFFSET, function)?;
+    memory.flush()?;
+
+    Ok(())
+}
+
+/// Disables DWT comparator `unit_index`.
+pub(crate) fn clear_hw_watchpoint(
+    memory: &mut dyn ArmProbe,
+    unit_index: usize,
+) -> Result<(), Error> {
+    let comparator = dwt_comparator_address(unit_index);
+
+    memory.write_word_32(comparator + DWT_FUNCTION_OFFSET, 0)?;
+    memory.flush()?;
+
+    Ok(())
+}
+
 /// Check if the current breakpoint is a semihosting call.
 ///
 /// Call this if you get some kind of breakpoint. Works on ARMv6-M, ARMv7-M and ARMv8-M.
@@ -239,3 +474,47 @@ fn wait_for_core_register_transfer(
     }
     Err(ArmError::Timeout)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dwt_function_round_trip() {
+        for version in [DwtVersion::V6V7, DwtVersion::V8] {
+            for kind in [
+                WatchpointKind::Read,
+                WatchpointKind::Write,
+                WatchpointKind::ReadWrite,
+            ] {
+                let watchpoint = Watchpoint {
+                    address: 0x2000_0000,
+                    length: 4,
+                    kind,
+                };
+                let function = version.function(&watchpoint).unwrap();
+
+                assert_eq!(version.decode(function, 2), Some((kind, 4)));
+            }
+        }
+    }
+
+    #[test]
+    fn dwt_v8_rejects_long_watchpoints() {
+        let watchpoint = Watchpoint {
+            address: 0x2000_0000,
+            length: 8,
+            kind: WatchpointKind::Write,
+        };
+
+        assert_eq!(DwtVersion::V8.function(&watchpoint), None);
+        assert!(DwtVersion::V6V7.function(&watchpoint).is_some());
+    }
+
+    #[test]
+    fn dwt_trace_comparators_are_not_watchpoints() {
+        // Data address sampling on ARMv7-M, and a trace match on ARMv8-M
+        assert_eq!(DwtVersion::V6V7.decode(0b0001, 0), None);
+        assert_eq!(DwtVersion::V8.decode(0b0100 | (0b10 << 4), 0), None);
+    }
+}
@@ -1,6 +1,7 @@
 //! The different ARM core implementations with all constants and custom handling.
 
 use crate::{
+    architecture::arm::ap::MemoryAp,
     core::{BreakpointCause, RegisterValue},
     memory_mapped_bitfield_register, CoreStatus, HaltReason, SemihostingCommand,
 };
@@ -17,6 +18,7 @@ pub(crate) mod cortex_m;
 pub(crate) mod exception_handling;
 pub(crate) mod instructions;
 pub(crate) mod registers;
+pub(crate) mod reset_delay;
 
 /// Core information data which is downloaded from the target, represents its state and can be used for debugging.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,6 +140,18 @@ pub struct CortexMState {
 
     /// The semihosting command that was decoded at the current program counter
     semihosting_command: Option<SemihostingCommand>,
+
+    /// The address of the CTI which halts the core together with the other cores.
+    cross_halt_cti: Option<u64>,
+
+    /// The memory APs and the CTI addresses of the other cores, which are resumed with the core.
+    cross_halt_peers: Vec<(MemoryAp, u64)>,
+
+    /// The time to wait after a reset before the core is accessed again.
+    reset_delay: std::time::Duration,
+
+    /// Whether the DHCSR reported a reset which was not returned by `was_reset` yet.
+    reset_seen: bool,
 }
 
 impl CortexMState {
@@ -148,9 +162,37 @@ impl CortexMState {
             current_state: CoreStatus::Unknown,
             fp_present: false,
             semihosting_command: None,
+            cross_halt_cti: None,
+            cross_halt_peers: Vec::new(),
+            reset_delay: std::time::Duration::ZERO,
+            reset_seen: false,
         }
     }
 
+    pub(crate) fn record_reset(&mut self, s_reset_st: bool) {
+        self.reset_seen |= s_reset_st;
+    }
+
+    pub(crate) fn take_reset(&mut self) -> bool {
+        std::mem::take(&mut self.reset_seen)
+    }
+
+    pub(crate) fn set_cross_halt_cti(&mut self, address: Option<u64>) {
+        self.cross_halt_cti = address;
+    }
+
+    pub(crate) fn cross_halt_cti(&self) -> Option<u64> {
+        self.cross_halt_cti
+    }
+
+    pub(crate) fn set_cross_halt_peers(&mut self, peers: Vec<(MemoryAp, u64)>) {
+        self.cross_halt_peers = peers;
+    }
+
+    pub(crate) fn set_reset_delay(&mut self, delay: std::time::Duration) {
+        self.reset_delay = delay;
+    }
+
     fn initialize(&mut self) {
         self.initialized = true;
     }
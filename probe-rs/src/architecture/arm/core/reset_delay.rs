@@ -0,0 +1,130 @@
+//! Waiting for the target to settle after a reset, see [`crate::Session::set_reset_delay`].
+
+use std::time::Duration;
+
+use crate::architecture::arm::ap::MemoryAp;
+use crate::architecture::arm::communication_interface::{Initialized, SwdSequence};
+use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
+use crate::architecture::arm::{ArmCommunicationInterface, ArmError};
+use crate::probe::DebugProbeError;
+use crate::CoreStatus;
+
+/// The memory of a core, which waits for `delay` before the first read after a write.
+///
+/// The reset sequences assert the reset with a write, and then read the state of the core, so
+/// the target is not accessed until the delay elapsed after the reset, regardless of how the
+/// sequence resets it. Only the first read after the reset waits.
+pub(crate) struct ResetDelay<'a> {
+    memory: &'a mut dyn ArmProbe,
+    delay: Duration,
+    written: bool,
+    waited: bool,
+}
+
+impl<'a> ResetDelay<'a> {
+    pub(crate) fn new(memory: &'a mut dyn ArmProbe, delay: Duration) -> Self {
+        Self {
+            memory,
+            delay,
+            written: false,
+            waited: delay.is_zero(),
+        }
+    }
+
+    fn before_write(&mut self) {
+        self.written = true;
+    }
+
+    fn before_read(&mut self) {
+        if self.written && !self.waited {
+            tracing::debug!("Waiting {:?} after the reset", self.delay);
+            std::thread::sleep(self.delay);
+            self.waited = true;
+        }
+    }
+}
+
+impl SwdSequence for ResetDelay<'_> {
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.before_write();
+        self.memory.swj_sequence(bit_len, bits)
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        self.before_write();
+        self.memory.swj_pins(pin_out, pin_select, pin_wait)
+    }
+}
+
+impl ArmProbe for ResetDelay<'_> {
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), ArmError> {
+        self.before_read();
+        self.memory.read_8(address, data)
+    }
+
+    fn read_16(&mut self, address: u64, data: &mut [u16]) -> Result<(), ArmError> {
+        self.before_read();
+        self.memory.read_16(address, data)
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        self.before_read();
+        self.memory.read_32(address, data)
+    }
+
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), ArmError> {
+        self.before_read();
+        self.memory.read_64(address, data)
+    }
+
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
+        self.before_write();
+        self.memory.write_8(address, data)
+    }
+
+    fn write_16(&mut self, address: u64, data: &[u16]) -> Result<(), ArmError> {
+        self.before_write();
+        self.memory.write_16(address, data)
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
+        self.before_write();
+        self.memory.write_32(address, data)
+    }
+
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), ArmError> {
+        self.before_write();
+        self.memory.write_64(address, data)
+    }
+
+    fn flush(&mut self) -> Result<(), ArmError> {
+        self.memory.flush()
+    }
+
+    fn supports_native_64bit_access(&mut self) -> bool {
+        self.memory.supports_native_64bit_access()
+    }
+
+    fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
+        self.memory.supports_8bit_transfers()
+    }
+
+    fn ap(&mut self) -> MemoryAp {
+        self.memory.ap()
+    }
+
+    fn get_arm_communication_interface(
+        &mut self,
+    ) -> Result<&mut ArmCommunicationInterface<Initialized>, DebugProbeError> {
+        self.memory.get_arm_communication_interface()
+    }
+
+    fn update_core_status(&mut self, state: CoreStatus) {
+        self.memory.update_core_status(state)
+    }
+}
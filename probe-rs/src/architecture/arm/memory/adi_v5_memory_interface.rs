@@ -9,13 +9,18 @@ use crate::architecture::arm::{
 use crate::architecture::arm::{ArmCommunicationInterface, ArmError};
 use crate::{probe::DebugProbeError, CoreStatus};
 
+/// Access to the memory behind a memory access port of an ARM target.
 pub trait ArmProbe: SwdSequence {
+    /// Reads a block of 8 bit words from `address`.
     fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), ArmError>;
 
+    /// Reads a block of 16 bit words from `address`.
     fn read_16(&mut self, address: u64, data: &mut [u16]) -> Result<(), ArmError>;
 
+    /// Reads a block of 32 bit words from `address`.
     fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError>;
 
+    /// Reads a block of 64 bit words from `address`.
     fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), ArmError>;
 
     /// Reads a 64 bit word from `address`.
@@ -76,12 +81,16 @@ pub trait ArmProbe: SwdSequence {
         Ok(())
     }
 
+    /// Writes a block of 8 bit words to `address`.
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError>;
 
+    /// Writes a block of 16 bit words to `address`.
     fn write_16(&mut self, address: u64, data: &[u16]) -> Result<(), ArmError>;
 
+    /// Writes a block of 32 bit words to `address`.
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError>;
 
+    /// Writes a block of 64 bit words to `address`.
     fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), ArmError>;
 
     /// Writes a 64 bit word to `address`.
@@ -157,15 +166,19 @@ pub trait ArmProbe: SwdSequence {
         Ok(())
     }
 
+    /// Flushes any outstanding writes.
     fn flush(&mut self) -> Result<(), ArmError>;
 
+    /// Returns whether the access port supports 64 bit accesses.
     fn supports_native_64bit_access(&mut self) -> bool;
 
+    /// Returns whether the access port supports 8 bit accesses.
     fn supports_8bit_transfers(&self) -> Result<bool, ArmError>;
 
     /// Returns the underlying [`ApAddress`].
     fn ap(&mut self) -> MemoryAp;
 
+    /// Returns the communication interface this memory is accessed through.
     fn get_arm_communication_interface(
         &mut self,
     ) -> Result<&mut ArmCommunicationInterface<Initialized>, DebugProbeError>;
@@ -4,4 +4,5 @@ pub(crate) mod adi_v5_memory_interface;
 pub mod romtable;
 
 use super::ap::AccessPortError;
+pub use adi_v5_memory_interface::ArmProbe;
 pub use romtable::{Component, ComponentId, CoresightComponent, PeripheralType};
@@ -111,6 +111,8 @@ pub struct RomTable {
     /// ALL the entries in the romtable in flattened fashion.
     /// This contains all nested romtable entries.
     entries: Vec<RomTableEntry>,
+    /// Entries which point to a component that could not be identified.
+    unidentified: Vec<UnidentifiedComponent>,
 }
 
 impl RomTable {
@@ -121,6 +123,7 @@ impl RomTable {
     fn try_parse(memory: &mut dyn ArmProbe, base_address: u64) -> Result<RomTable, RomTableError> {
         // This is required for the collect down below.
         let mut entries = vec![];
+        let mut unidentified = vec![];
 
         tracing::debug!("Parsing romtable at base_address {:#010x}", base_address);
 
@@ -139,7 +142,27 @@ impl RomTable {
             tracing::debug!("Parsing entry at {:#010x}", entry_base_addr);
 
             if raw_entry.entry_present {
-                let component = Component::try_parse(memory, u64::from(entry_base_addr))?;
+                let component = match Component::try_parse(memory, u64::from(entry_base_addr)) {
+                    Ok(component) => component,
+                    Err(RomTableError::CSComponentIdentification) => {
+                        tracing::warn!(
+                            "Component at {:#010x} could not be identified, skipping it.",
+                            entry_base_addr
+                        );
+
+                        let (cidr, pidr) =
+                            ComponentInformationReader::new(u64::from(entry_base_addr), memory)
+                                .read_raw_ids()?;
+
+                        unidentified.push(UnidentifiedComponent {
+                            component_address: u64::from(entry_base_addr),
+                            cidr,
+                            pidr,
+                        });
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
 
                 // Finally remember the entry.
                 entries.push(RomTableEntry {
@@ -151,13 +174,47 @@ impl RomTable {
             }
         }
 
-        Ok(RomTable { entries })
+        Ok(RomTable {
+            entries,
+            unidentified,
+        })
     }
 
     /// Returns an iterator over all entries in the ROM table.
     pub fn entries(&self) -> impl Iterator<Item = &RomTableEntry> {
         self.entries.iter()
     }
+
+    /// Returns an iterator over all entries in the ROM table which point to a component
+    /// with an unknown component class.
+    pub fn unidentified_entries(&self) -> impl Iterator<Item = &UnidentifiedComponent> {
+        self.unidentified.iter()
+    }
+}
+
+/// A component referenced by a ROM table, which could not be identified.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnidentifiedComponent {
+    component_address: u64,
+    cidr: u32,
+    pidr: u64,
+}
+
+impl UnidentifiedComponent {
+    /// Retrieve the address of the component.
+    pub fn component_address(&self) -> u64 {
+        self.component_address
+    }
+
+    /// The raw value of the component ID registers (CIDR0 - CIDR3).
+    pub fn cidr(&self) -> u32 {
+        self.cidr
+    }
+
+    /// The raw value of the peripheral ID registers (PIDR0 - PIDR7).
+    pub fn pidr(&self) -> u64 {
+        self.pidr
+    }
 }
 
 /// A ROM table entry with raw information parsed.
@@ -246,6 +303,8 @@ pub struct ComponentId {
     component_address: u64,
     class: RawComponent,
     peripheral_id: PeripheralID,
+    cidr: u32,
+    pidr: u64,
 }
 
 impl ComponentId {
@@ -258,6 +317,16 @@ impl ComponentId {
     pub fn peripheral_id(&self) -> &PeripheralID {
         &self.peripheral_id
     }
+
+    /// The raw value of the component ID registers (CIDR0 - CIDR3).
+    pub fn cidr(&self) -> u32 {
+        self.cidr
+    }
+
+    /// The raw value of the peripheral ID registers (PIDR0 - PIDR7).
+    pub fn pidr(&self) -> u64 {
+        self.pidr
+    }
 }
 
 /// A reader to extract information from a CoreSight component table.
@@ -277,11 +346,10 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
         }
     }
 
-    /// Reads the component class from a component information table.
+    /// Reads the raw component ID registers from a component information table.
     ///
     /// This function does a direct memory access and is meant for internal use only.
-    fn component_class(&mut self) -> Result<RawComponent, RomTableError> {
-        #![allow(clippy::verbose_bit_mask)]
+    fn read_cidr(&mut self) -> Result<[u32; 4], RomTableError> {
         let mut cidr = [0u32; 4];
 
         self.memory
@@ -290,6 +358,52 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
 
         tracing::debug!("CIDR: {:x?}", cidr);
 
+        Ok(cidr)
+    }
+
+    /// Reads the raw peripheral ID registers from a component information table.
+    ///
+    /// The registers are returned in order, PIDR0 first.
+    ///
+    /// This function does a direct memory access and is meant for internal use only.
+    fn read_pidr(&mut self) -> Result<[u32; 8], RomTableError> {
+        let mut data = [0u32; 8];
+
+        let peripheral_id_address = self.base_address + 0xFD0;
+
+        tracing::debug!(
+            "Reading debug id from address: {:#010x}",
+            peripheral_id_address
+        );
+
+        self.memory
+            .read_32(self.base_address + 0xFD0, &mut data[4..])
+            .map_err(RomTableError::memory)?;
+        self.memory
+            .read_32(self.base_address + 0xFE0, &mut data[..4])
+            .map_err(RomTableError::memory)?;
+
+        tracing::debug!("Raw peripheral id: {:x?}", data);
+
+        Ok(data)
+    }
+
+    /// Reads the CIDR and PIDR registers, and combines them into a single value each.
+    ///
+    /// This works for any component, even if its class cannot be identified.
+    pub(crate) fn read_raw_ids(&mut self) -> Result<(u32, u64), RomTableError> {
+        let cidr = self.read_cidr()?;
+        let pidr = self.read_pidr()?;
+
+        Ok((
+            combine_id_registers(&cidr) as u32,
+            combine_id_registers(&pidr),
+        ))
+    }
+
+    /// Determines the component class from the component ID registers.
+    fn component_class(&self, cidr: &[u32; 4]) -> Result<RawComponent, RomTableError> {
+        #![allow(clippy::verbose_bit_mask)]
         let preambles = [
             cidr[0] & 0xff,
             cidr[1] & 0x0f,
@@ -317,25 +431,7 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
     /// Reads the peripheral ID from a component information table.
     ///
     /// This function does a direct memory access and is meant for internal use only.
-    fn peripheral_id(&mut self) -> Result<PeripheralID, RomTableError> {
-        let mut data = [0u32; 8];
-
-        let peripheral_id_address = self.base_address + 0xFD0;
-
-        tracing::debug!(
-            "Reading debug id from address: {:#010x}",
-            peripheral_id_address
-        );
-
-        self.memory
-            .read_32(self.base_address + 0xFD0, &mut data[4..])
-            .map_err(RomTableError::memory)?;
-        self.memory
-            .read_32(self.base_address + 0xFE0, &mut data[..4])
-            .map_err(RomTableError::memory)?;
-
-        tracing::debug!("Raw peripheral id: {:x?}", data);
-
+    fn peripheral_id(&mut self, data: &[u32; 8]) -> Result<PeripheralID, RomTableError> {
         const DEV_TYPE_OFFSET: u64 = 0xFCC;
         const DEV_TYPE_MASK: u32 = 0xFF;
 
@@ -363,21 +459,38 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
 
         tracing::debug!("Dev type: {:x}, arch id: {:x}", dev_type, arch_id);
 
-        Ok(PeripheralID::from_raw(&data, dev_type, arch_id))
+        Ok(PeripheralID::from_raw(data, dev_type, arch_id))
     }
 
     /// Reads all component properties from a component info table
     ///
     /// This function does a direct memory access and is meant for internal use only.
     fn read_all(&mut self) -> Result<ComponentId, RomTableError> {
+        let cidr = self.read_cidr()?;
+        let class = self.component_class(&cidr)?;
+
+        let pidr = self.read_pidr()?;
+        let peripheral_id = self.peripheral_id(&pidr)?;
+
         Ok(ComponentId {
             component_address: self.base_address,
-            class: self.component_class()?,
-            peripheral_id: self.peripheral_id()?,
+            class,
+            peripheral_id,
+            cidr: combine_id_registers(&cidr) as u32,
+            pidr: combine_id_registers(&pidr),
         })
     }
 }
 
+/// Combines ID registers, which only contain a single byte of the ID each, into a single value.
+///
+/// The first register contains the least significant byte.
+fn combine_id_registers(registers: &[u32]) -> u64 {
+    registers.iter().enumerate().fold(0, |id, (i, register)| {
+        id | (u64::from(*register & 0xff) << (8 * i))
+    })
+}
+
 /// This enum describes the class of a CoreSight component.
 ///
 /// This does not describe the exact component type which is determined via the `PeripheralID`.
@@ -755,6 +868,8 @@ impl PeripheralID {
             ("ARM Ltd", 0xD21, 0x11, 0x0000) => Some(PartInfo::new("Cortex-M33 TPIU", PeripheralType::Tpiu)),
             ("ARM Ltd", 0xD21, 0x14, 0x1A14) => Some(PartInfo::new("Cortex-M33 CTI", PeripheralType::Cti)),
             ("ARM Ltd", 0x9A3, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M0 MTB", PeripheralType::Mtb)),
+            ("ARM Ltd", 0x906, 0x14, 0x0000) => Some(PartInfo::new("CoreSight CTI", PeripheralType::Cti)),
+            ("ARM Ltd", _, 0x14, 0x1A14) => Some(PartInfo::new("CoreSight CTI", PeripheralType::Cti)),
             ("Atmel", 0xCD0, 1, 0) => Some(PartInfo::new("Atmel DSU", PeripheralType::Custom)),
             _ => None,
         }
@@ -859,3 +974,13 @@ impl std::fmt::Display for PeripheralType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::combine_id_registers;
+
+    #[test]
+    fn combine_cidr_registers() {
+        assert_eq!(combine_id_registers(&[0x0D, 0x10, 0x05, 0xB1]), 0xB105_100D);
+    }
+}
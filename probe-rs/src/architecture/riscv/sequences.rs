@@ -2,6 +2,7 @@
 
 use crate::architecture::riscv::communication_interface::RiscvError;
 use crate::architecture::riscv::Dmcontrol;
+use crate::flashing::JedecId;
 use crate::Session;
 
 use super::communication_interface::RiscvCommunicationInterface;
@@ -23,6 +24,11 @@ pub trait RiscvDebugSequence: Send + Sync + Debug {
         Ok(None)
     }
 
+    /// Reads the JEDEC ID of the SPI flash of the target.
+    fn read_flash_id(&self, _session: &mut Session) -> Result<Option<JedecId>, crate::Error> {
+        Ok(None)
+    }
+
     /// Configure the target to stop code execution after a reset. After this, the core will halt when it comes
     /// out of reset.
     fn reset_catch_set(
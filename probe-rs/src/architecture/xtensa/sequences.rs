@@ -3,6 +3,7 @@ use std::{fmt::Debug, sync::Arc, time::Duration};
 use crate::architecture::xtensa::communication_interface::{
     ProgramStatus, XtensaCommunicationInterface, XtensaError,
 };
+use crate::flashing::JedecId;
 use crate::Session;
 
 /// A interface to operate debug sequences for Xtensa targets.
@@ -22,6 +23,11 @@ pub trait XtensaDebugSequence: Send + Sync + Debug {
         Ok(None)
     }
 
+    /// Reads the JEDEC ID of the SPI flash of the target.
+    fn read_flash_id(&self, _session: &mut Session) -> Result<Option<JedecId>, crate::Error> {
+        Ok(None)
+    }
+
     /// Executes a system-wide reset without debug domain (or warm-reset that preserves debug connection) via software mechanisms.
     fn reset_system_and_halt(
         &self,
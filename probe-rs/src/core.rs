@@ -100,6 +100,31 @@ pub trait CoreInterface: MemoryInterface {
     /// Clears the breakpoint configured in unit `unit_index`.
     fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), Error>;
 
+    /// Returns the number of hardware watchpoint units of the core.
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        Err(Error::NotImplemented("watchpoints"))
+    }
+
+    /// Read the configured watchpoints. A value of None in any position of the Vector
+    /// indicates that the unit is not used as a watchpoint.
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        Err(Error::NotImplemented("watchpoints"))
+    }
+
+    /// Configures unit `unit_index` as `watchpoint`.
+    fn set_hw_watchpoint(
+        &mut self,
+        _unit_index: usize,
+        _watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented("watchpoints"))
+    }
+
+    /// Clears the watchpoint configured in unit `unit_index`.
+    fn clear_hw_watchpoint(&mut self, _unit_index: usize) -> Result<(), Error> {
+        Err(Error::NotImplemented("watchpoints"))
+    }
+
     /// Returns a list of all the registers of this core.
     fn registers(&self) -> &'static registers::CoreRegisters;
 
@@ -175,6 +200,14 @@ pub trait CoreInterface: MemoryInterface {
         Err(Error::NotImplemented("vector catch"))
     }
 
+    /// Checks whether the core was reset since the last call, e.g. by a watchdog or by the
+    /// firmware itself.
+    ///
+    /// The first call also reports the resets since attaching to the core.
+    fn was_reset(&mut self) -> Result<bool, Error> {
+        Err(Error::NotImplemented("reset detection"))
+    }
+
     /// Check if the integer size is 64-bit
     fn is_64_bit(&self) -> bool {
         false
@@ -674,6 +707,103 @@ impl<'probe> Core<'probe> {
         Ok(())
     }
 
+    /// Returns the number of hardware watchpoint units of the core.
+    pub fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        self.inner.available_watchpoint_units()
+    }
+
+    /// Returns the watchpoints configured in the hardware watchpoint units.
+    ///
+    /// A value of None in any position indicates that the unit is available.
+    pub fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        self.inner.hw_watchpoints()
+    }
+
+    /// Set a hardware watchpoint
+    ///
+    /// This function will try to set a watchpoint for the `length` bytes at `address`,
+    /// which halts the core on the accesses given by `kind`.
+    ///
+    /// The amount of hardware watchpoints which are supported is chip specific,
+    /// and can be queried using the `available_watchpoint_units` function.
+    #[tracing::instrument(skip(self))]
+    pub fn set_hw_watchpoint(
+        &mut self,
+        address: u64,
+        length: u64,
+        kind: WatchpointKind,
+    ) -> Result<(), Error> {
+        let watchpoints = self.inner.hw_watchpoints()?;
+
+        // Reuse the unit if there is a watchpoint at the address already.
+        let unit_index = watchpoints
+            .iter()
+            .position(|wp| wp.is_some_and(|wp| wp.address == address))
+            .or_else(|| watchpoints.iter().position(Option::is_none))
+            .ok_or_else(|| {
+                Error::Other(anyhow!(
+                    "All {} hardware watchpoints are in use",
+                    watchpoints.len()
+                ))
+            })?;
+
+        tracing::debug!(
+            "Trying to set HW watchpoint #{} for {} bytes at {:#010x}",
+            unit_index,
+            length,
+            address
+        );
+
+        self.inner.set_hw_watchpoint(
+            unit_index,
+            Watchpoint {
+                address,
+                length,
+                kind,
+            },
+        )
+    }
+
+    /// Clear a hardware watchpoint
+    ///
+    /// This function will try to clear the watchpoint at `address` if there exists one.
+    #[tracing::instrument(skip(self))]
+    pub fn clear_hw_watchpoint(&mut self, address: u64) -> Result<(), Error> {
+        let unit_index = self
+            .inner
+            .hw_watchpoints()?
+            .iter()
+            .position(|wp| wp.is_some_and(|wp| wp.address == address));
+
+        match unit_index {
+            Some(unit_index) => self.inner.clear_hw_watchpoint(unit_index),
+            None => Err(Error::Other(anyhow!(
+                "No watchpoint found at address {:#010x}",
+                address
+            ))),
+        }
+    }
+
+    /// Clear all hardware watchpoints
+    ///
+    /// Cores which do not support watchpoints have none, so this succeeds for them.
+    #[tracing::instrument(skip(self))]
+    pub fn clear_all_hw_watchpoints(&mut self) -> Result<(), Error> {
+        let watchpoints = match self.inner.hw_watchpoints() {
+            Ok(watchpoints) => watchpoints,
+            Err(Error::NotImplemented(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for (unit_index, watchpoint) in watchpoints.into_iter().enumerate() {
+            if watchpoint.is_some() {
+                self.inner.clear_hw_watchpoint(unit_index)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Returns the architecture of the core.
     pub fn architecture(&self) -> Architecture {
         self.inner.architecture()
@@ -726,6 +856,15 @@ impl<'probe> Core<'probe> {
         self.inner.disable_vector_catch(condition)
     }
 
+    /// Checks whether the core was reset since the last call, e.g. by a watchdog or by the
+    /// firmware itself.
+    ///
+    /// The first call also reports the resets since attaching to the core. This is only
+    /// supported for Cortex-M cores, and returns [`Error::NotImplemented`] otherwise.
+    pub fn was_reset(&mut self) -> Result<bool, Error> {
+        self.inner.was_reset()
+    }
+
     /// Dumps core info with the current state.
     ///
     /// # Arguments
@@ -752,6 +891,7 @@ impl<'probe> Core<'probe> {
         }
 
         Ok(CoreDump {
+            version: CoreDump::VERSION,
             registers,
             data,
             instruction_set,
@@ -836,6 +976,26 @@ impl<'probe> CoreInterface for Core<'probe> {
         self.clear_all_hw_breakpoints()
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        self.available_watchpoint_units()
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        self.hw_watchpoints()
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        self.inner.set_hw_watchpoint(unit_index, watchpoint)
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        self.inner.clear_hw_watchpoint(unit_index)
+    }
+
     fn registers(&self) -> &'static registers::CoreRegisters {
         self.registers()
     }
@@ -86,3 +86,25 @@ pub enum VectorCatchCondition {
     /// We encountered any exception.
     All,
 }
+
+/// The memory accesses which trigger a [`Watchpoint`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WatchpointKind {
+    /// Halt when the memory is read.
+    Read,
+    /// Halt when the memory is written.
+    Write,
+    /// Halt when the memory is read or written.
+    ReadWrite,
+}
+
+/// A data watchpoint, which halts the core when the watched memory is accessed.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Watchpoint {
+    /// The start address of the watched memory.
+    pub address: u64,
+    /// The number of watched bytes.
+    pub length: u64,
+    /// The accesses which trigger the watchpoint.
+    pub kind: WatchpointKind,
+}
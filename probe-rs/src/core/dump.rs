@@ -29,8 +29,20 @@ use std::{
 use super::RegisterDataType;
 
 /// A snapshot representation of a core state.
+///
+/// A core dump file is a single MessagePack map, with the field names of this struct as keys:
+///
+/// - `version`: the version of the format, see [`CoreDump::VERSION`]. Dumps written before the
+///   version was added don't contain it, and are read as version 1.
+/// - `registers`: a map from the register ID to the register value.
+/// - `data`: a list of `(range, bytes)` pairs, with the memory read from each address range.
+/// - `instruction_set`, `supports_native_64bit_access`, `core_type`, `fpu_support` and
+///   `floating_point_register_count`: the properties of the dumped core.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CoreDump {
+    /// The version of the core dump format.
+    #[serde(default = "CoreDump::legacy_version")]
+    pub(crate) version: u32,
     /// The registers we dumped from the core.
     pub registers: HashMap<RegisterId, RegisterValue>,
     /// The memory we dumped from the core.
@@ -48,6 +60,33 @@ pub struct CoreDump {
 }
 
 impl CoreDump {
+    /// The version of the core dump format which is written, and the only one which can be read.
+    ///
+    /// This must be incremented whenever the layout changes in a way older versions can't read.
+    pub const VERSION: u32 = 1;
+
+    /// The version of the dumps written before the version was added to the format.
+    fn legacy_version() -> u32 {
+        1
+    }
+
+    /// Returns the version of the core dump format this dump was read from.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Checks that a loaded dump has a version of the format which can be read.
+    fn check_version(self) -> Result<Self, CoreDumpError> {
+        if self.version == Self::VERSION {
+            Ok(self)
+        } else {
+            Err(CoreDumpError::UnsupportedVersion {
+                found: self.version,
+                supported: Self::VERSION,
+            })
+        }
+    }
+
     /// Store the dumped core to a file.
     pub fn store(&self, path: &Path) -> Result<(), CoreDumpError> {
         let mut file = OpenOptions::new()
@@ -67,12 +106,16 @@ impl CoreDump {
         let file = OpenOptions::new().read(true).open(path).map_err(|e| {
             CoreDumpError::CoreDumpFileRead(e, dunce::canonicalize(path).unwrap_or_default())
         })?;
-        rmp_serde::from_read(&file).map_err(CoreDumpError::DecodingCoreDump)
+        rmp_serde::from_read::<_, Self>(&file)
+            .map_err(CoreDumpError::DecodingCoreDump)?
+            .check_version()
     }
 
     /// Load the dumped core from a file.
     pub fn load_raw(data: &[u8]) -> Result<Self, CoreDumpError> {
-        rmp_serde::from_slice(data).map_err(CoreDumpError::DecodingCoreDump)
+        rmp_serde::from_slice::<Self>(data)
+            .map_err(CoreDumpError::DecodingCoreDump)?
+            .check_version()
     }
 
     /// Read all registers defined in [`crate::core::CoreRegisters`] from the given core.
@@ -284,6 +327,7 @@ impl MemoryInterface for CoreDump {
 
 /// The overarching error type which contains all possible errors as variants.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum CoreDumpError {
     /// Opening the file for writing the core dump failed.
     #[error("Opening {1} for writing the core dump failed.")]
@@ -297,4 +341,58 @@ pub enum CoreDumpError {
     /// Decoding the coredump MessagePack failed.
     #[error("Decoding the coredump MessagePack failed.")]
     DecodingCoreDump(rmp_serde::decode::Error),
+    /// The core dump was written in a version of the format which is not supported.
+    #[error("The core dump has version {found} of the format, but only version {supported} is supported.")]
+    UnsupportedVersion {
+        /// The version of the format of the core dump.
+        found: u32,
+        /// The version of the format which is supported.
+        supported: u32,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(version: u32) -> CoreDump {
+        CoreDump {
+            version,
+            registers: HashMap::new(),
+            data: vec![(0x2000_0000..0x2000_0004, vec![1, 2, 3, 4])],
+            instruction_set: InstructionSet::Thumb2,
+            supports_native_64bit_access: false,
+            core_type: CoreType::Armv7m,
+            fpu_support: false,
+            floating_point_register_count: None,
+        }
+    }
+
+    #[test]
+    fn current_version_is_loaded() {
+        let data = rmp_serde::encode::to_vec_named(&dump(CoreDump::VERSION)).unwrap();
+
+        let loaded = CoreDump::load_raw(&data).unwrap();
+
+        assert_eq!(loaded.version(), CoreDump::VERSION);
+        assert_eq!(loaded.data, dump(CoreDump::VERSION).data);
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let data = rmp_serde::encode::to_vec_named(&dump(CoreDump::VERSION + 1)).unwrap();
+
+        assert!(matches!(
+            CoreDump::load_raw(&data),
+            Err(CoreDumpError::UnsupportedVersion { found, supported })
+                if found == CoreDump::VERSION + 1 && supported == CoreDump::VERSION
+        ));
+    }
+
+    #[test]
+    fn dump_without_version_is_loaded() {
+        let data = std::fs::read("tests/debug-unwind-tests/RP2040_systick.coredump").unwrap();
+
+        assert_eq!(CoreDump::load_raw(&data).unwrap().version(), 1);
+    }
 }
@@ -124,6 +124,27 @@ fn print_instr_sets(instr_sets: &[InstructionSet]) -> String {
         .join(", ")
 }
 
+/// How the cores of the target are halted while the flash is programmed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlashHaltMode {
+    /// Reset and halt the core which runs the flash algorithm, before the algorithm is loaded.
+    #[default]
+    ResetAndHalt,
+    /// Halt the core which runs the flash algorithm without resetting it, so that the clock and
+    /// power configuration of the running firmware is kept.
+    ///
+    /// This helps on targets whose flash algorithm relies on a configuration which is lost
+    /// in a reset, for example a PLL, or a flash which is only powered up by the firmware.
+    Halt,
+    /// Reset and halt the core which runs the flash algorithm, and halt all other cores of the
+    /// target for the whole flashing procedure.
+    ///
+    /// This helps on multi-core targets where another core executes from, or writes to, the
+    /// flash which is programmed, such as the RP2040, or the dual-core STM32H7 and STM32WL
+    /// parts.
+    HaltAllCores,
+}
+
 /// Options for downloading a file onto a target chip.
 ///
 /// This struct should be created using the [`DownloadOptions::default()`] function, and can be configured by setting
@@ -154,13 +175,24 @@ pub struct DownloadOptions {
     /// This is often faster than erasing a lot of single sectors.
     /// So if you do not need the old contents of the flash, this is a good option.
     pub do_chip_erase: bool,
+    /// If this flag is set together with `do_chip_erase`, flashing fails with [`FlashError::ChipEraseNotSupported`]
+    /// instead of falling back to erasing single sectors if a flash algorithm does not support chip erases.
+    /// This is also checked in a dry run. Flashing fails with [`FlashError::ChipEraseIneffective`] if the
+    /// chip erase did not erase the flash.
+    pub require_chip_erase: bool,
     /// If the chip was pre-erased with external erasers, this flag can set to true to skip erasing
     /// It may be useful for mass production.
     pub skip_erase: bool,
+    /// Erase all sectors before programming them, even if they are already erased.
+    ///
+    /// By default, sectors which already contain only the erased byte value are not erased again.
+    pub force_erase: bool,
     /// After flashing, read back all the flashed data to verify it has been written correctly.
     pub verify: bool,
     /// Disable double buffering when loading flash.
     pub disable_double_buffering: bool,
+    /// How the cores are halted while the flash is programmed.
+    pub halt_mode: FlashHaltMode,
 }
 
 impl DownloadOptions {
@@ -209,6 +241,7 @@ pub fn download_file_with_options<P: AsRef<Path>>(
 pub(super) struct ExtractedFlashData<'data> {
     pub(super) section_names: Vec<String>,
     pub(super) address: u32,
+    pub(super) file_offset: u64,
     pub(super) data: &'data [u8],
 }
 
@@ -218,7 +251,8 @@ impl std::fmt::Debug for ExtractedFlashData<'_> {
 
         helper
             .field("name", &self.section_names)
-            .field("address", &self.address);
+            .field("address", &self.address)
+            .field("file_offset", &self.file_offset);
 
         if self.data.len() > 10 {
             helper
@@ -299,6 +333,7 @@ fn extract_from_elf_inner<'data, T: FileHeader>(
                 extracted_data.push(ExtractedFlashData {
                     section_names: elf_section,
                     address: p_paddr as u32,
+                    file_offset: segment_offset,
                     data: section_data,
                 });
             }
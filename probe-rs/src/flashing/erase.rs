@@ -1,9 +1,10 @@
 use std::collections::HashMap;
+use std::ops::Range;
 
 use probe_rs_target::{MemoryRange, MemoryRegion, NvmRegion};
 
-use crate::flashing::{flasher::Flasher, FlashError, FlashLoader};
-use crate::Session;
+use crate::flashing::{flasher::Flasher, FlashError, FlashHaltMode, FlashLoader};
+use crate::{MemoryInterface, Session};
 
 use super::FlashProgress;
 
@@ -14,39 +15,7 @@ use super::FlashProgress;
 pub fn erase_all(session: &mut Session, progress: FlashProgress) -> Result<(), FlashError> {
     tracing::debug!("Erasing all...");
 
-    let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
-    tracing::debug!("Regions:");
-    for region in session
-        .target()
-        .memory_map
-        .iter()
-        .filter_map(MemoryRegion::as_nvm_region)
-    {
-        if region.is_alias {
-            tracing::debug!("Skipping alias memory region {:#010x?}", region.range);
-            continue;
-        }
-        tracing::debug!(
-            "    region: {:#010x?} ({} bytes)",
-            region.range,
-            region.range.end - region.range.start
-        );
-
-        let algo = FlashLoader::get_flash_algorithm_for_region(region, session.target())?;
-
-        // Get the first core that can access the region
-        let core_name = region
-            .cores
-            .first()
-            .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?;
-
-        let entry = algos
-            .entry((algo.name.clone(), core_name.clone()))
-            .or_default();
-        entry.push(region.clone());
-
-        tracing::debug!("     -- using algorithm: {}", algo.name);
-    }
+    let algos = regions_by_algorithm(session)?;
 
     for ((algo_name, core_name), regions) in algos {
         tracing::debug!("Erasing with algorithm: {}", algo_name);
@@ -56,14 +25,28 @@ pub fn erase_all(session: &mut Session, progress: FlashProgress) -> Result<(), F
         let algo = algo.unwrap().clone();
 
         let core_index = session.target().core_index_by_name(&core_name).unwrap();
-        let mut flasher = Flasher::new(session, core_index, &algo, progress.clone())?;
+        let mut flasher = Flasher::new(
+            session,
+            core_index,
+            &algo,
+            progress.clone(),
+            FlashHaltMode::default(),
+        )?;
 
+        let mut chip_erased = false;
         if flasher.is_chip_erase_supported() {
             tracing::debug!("     -- chip erase supported, doing it.");
             flasher.run_erase_all()?;
+
+            chip_erased = flasher.is_chip_erased(&regions);
+            if !chip_erased {
+                tracing::warn!("The chip erase with algorithm {algo_name} did not erase the flash. Erasing it sector by sector instead.");
+            }
         } else {
             tracing::debug!("     -- chip erase not supported, erasing by sector.");
+        }
 
+        if !chip_erased {
             // loop over all sectors erasing them individually instead.
 
             let sectors = flasher
@@ -107,6 +90,169 @@ pub fn erase_sectors(
         start_sector + sectors
     );
 
+    let algos = regions_by_algorithm(session)?;
+
+    for ((algo_name, core_name), regions) in algos {
+        tracing::debug!("Erasing with algorithm: {}", algo_name);
+
+        // This can't fail, algo_name comes from the target.
+        let algo = session.target().flash_algorithm_by_name(&algo_name);
+        let algo = algo.unwrap().clone();
+
+        let core_index = session.target().core_index_by_name(&core_name).unwrap();
+        let mut flasher = Flasher::new(
+            session,
+            core_index,
+            &algo,
+            progress.clone(),
+            FlashHaltMode::default(),
+        )?;
+
+        let sectors = flasher
+            .flash_algorithm()
+            .iter_sectors()
+            .skip(start_sector)
+            .take(sectors)
+            .filter(|info| {
+                let range = info.base_address..info.base_address + info.size;
+                regions.iter().any(|r| r.range.contains_range(&range))
+            })
+            .collect::<Vec<_>>();
+
+        flasher.run_erase(|active| {
+            for info in sectors {
+                tracing::debug!(
+                    "    sector: {:08x}-{:08x} ({} bytes)",
+                    info.base_address,
+                    info.base_address + info.size,
+                    info.size
+                );
+
+                active.erase_sector(info.base_address)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Erases all sectors of the nonvolatile memory which are not part of the `keep` ranges.
+///
+/// The ranges must start and end on sector boundaries, and be contained in a nonvolatile memory
+/// region. Otherwise, an error is returned before any sector is erased.
+pub fn erase_except(
+    session: &mut Session,
+    progress: FlashProgress,
+    keep: &[Range<u64>],
+) -> Result<(), FlashError> {
+    tracing::debug!("Erasing all sectors except {keep:#010x?}");
+
+    let algos = regions_by_algorithm(session)?;
+
+    // A range which is not part of the flash, e.g. because of a typo, would keep nothing.
+    let nvm = algos
+        .values()
+        .flatten()
+        .map(|region| region.range.clone())
+        .collect::<Vec<_>>();
+    if let Some(range) = range_outside(keep, &nvm) {
+        return Err(FlashError::KeepRangeOutsideNvm {
+            range: range.clone(),
+        });
+    }
+
+    // Select the sectors of all algorithms first, so that a misaligned range does not leave the
+    // flash half erased.
+    let mut erases = vec![];
+    for ((algo_name, core_name), regions) in algos {
+        // This can't fail, algo_name comes from the target.
+        let algo = session.target().flash_algorithm_by_name(&algo_name);
+        let algo = algo.unwrap().clone();
+
+        let core_index = session.target().core_index_by_name(&core_name).unwrap();
+        let flash_algorithm =
+            Flasher::assemble_flash_algorithm(session.target(), core_index, &algo)?;
+
+        let mut sectors = vec![];
+        for info in flash_algorithm.iter_sectors() {
+            let range = info.base_address..info.base_address + info.size;
+            if !regions.iter().any(|r| r.range.contains_range(&range)) {
+                continue;
+            }
+
+            match is_kept(&range, keep) {
+                Ok(true) => {}
+                Ok(false) => sectors.push(info),
+                Err(kept) => {
+                    return Err(FlashError::KeepRangeNotAligned {
+                        range: kept.clone(),
+                        sector: range,
+                    })
+                }
+            }
+        }
+
+        erases.push((algo, core_index, sectors));
+    }
+
+    for (algo, core_index, sectors) in erases {
+        if sectors.is_empty() {
+            continue;
+        }
+        tracing::debug!("Erasing with algorithm: {}", algo.name);
+
+        let mut flasher = Flasher::new(
+            session,
+            core_index,
+            &algo,
+            progress.clone(),
+            FlashHaltMode::default(),
+        )?;
+
+        flasher.run_erase(|active| {
+            for info in sectors {
+                tracing::debug!(
+                    "    sector: {:08x}-{:08x} ({} bytes)",
+                    info.base_address,
+                    info.base_address + info.size,
+                    info.size
+                );
+
+                active.erase_sector(info.base_address)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Checks whether the `sector` is contained in one of the `keep` ranges.
+///
+/// Returns the range which covers the sector only partially as the error.
+fn is_kept<'a>(sector: &Range<u64>, keep: &'a [Range<u64>]) -> Result<bool, &'a Range<u64>> {
+    if keep.iter().any(|range| range.contains_range(sector)) {
+        return Ok(true);
+    }
+
+    match keep.iter().find(|range| range.intersects_range(sector)) {
+        Some(range) => Err(range),
+        None => Ok(false),
+    }
+}
+
+/// Returns the first of the `keep` ranges which is not contained in one of the `regions`.
+fn range_outside<'a>(keep: &'a [Range<u64>], regions: &[Range<u64>]) -> Option<&'a Range<u64>> {
+    keep.iter()
+        .find(|range| !regions.iter().any(|region| region.contains_range(range)))
+}
+
+/// Groups the nonvolatile memory regions of the target, except the aliases, by the flash
+/// algorithm and the core used to erase them.
+fn regions_by_algorithm(
+    session: &Session,
+) -> Result<HashMap<(String, String), Vec<NvmRegion>>, FlashError> {
     let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
     tracing::debug!("Regions:");
     for region in session
@@ -141,41 +287,85 @@ pub fn erase_sectors(
         tracing::debug!("     -- using algorithm: {}", algo.name);
     }
 
-    for ((algo_name, core_name), regions) in algos {
-        tracing::debug!("Erasing with algorithm: {}", algo_name);
+    Ok(algos)
+}
 
-        // This can't fail, algo_name comes from the target.
-        let algo = session.target().flash_algorithm_by_name(&algo_name);
-        let algo = algo.unwrap().clone();
+/// Checks that the nonvolatile memory in `range` is erased.
+///
+/// The memory is read back and compared against the erased byte value of the flash
+/// algorithm of the respective region. Alias regions are skipped.
+///
+/// Returns the address of the first byte which is not erased, or `None` if the whole range is blank.
+pub fn blank_check(session: &mut Session, range: Range<u64>) -> Result<Option<u64>, FlashError> {
+    const CHUNK_SIZE: u64 = 4096;
 
-        let core_index = session.target().core_index_by_name(&core_name).unwrap();
-        let mut flasher = Flasher::new(session, core_index, &algo, progress.clone())?;
+    let mut regions = session
+        .target()
+        .memory_map
+        .iter()
+        .filter_map(MemoryRegion::as_nvm_region)
+        .filter(|region| !region.is_alias && region.range.intersects_range(&range))
+        .cloned()
+        .collect::<Vec<_>>();
+    regions.sort_by_key(|region| region.range.start);
 
-        let sectors = flasher
-            .flash_algorithm()
-            .iter_sectors()
-            .skip(start_sector)
-            .take(sectors)
-            .filter(|info| {
-                let range = info.base_address..info.base_address + info.size;
-                regions.iter().any(|r| r.range.contains_range(&range))
-            })
-            .collect::<Vec<_>>();
+    for region in regions {
+        let algo = FlashLoader::get_flash_algorithm_for_region(&region, session.target())?;
+        let erased_byte_value = algo.flash_properties.erased_byte_value;
 
-        flasher.run_erase(|active| {
-            for info in sectors {
-                tracing::debug!(
-                    "    sector: {:08x}-{:08x} ({} bytes)",
-                    info.base_address,
-                    info.base_address + info.size,
-                    info.size
-                );
+        let core_name = region
+            .cores
+            .first()
+            .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?;
+        let core_index = session.target().core_index_by_name(core_name).unwrap();
+        let mut core = session.core(core_index)?;
 
-                active.erase_sector(info.base_address)?;
+        let start = region.range.start.max(range.start);
+        let end = region.range.end.min(range.end);
+
+        tracing::debug!("Blank checking {:#010x}..{:#010x}", start, end);
+
+        let mut buffer = vec![0; CHUNK_SIZE as usize];
+        for address in (start..end).step_by(CHUNK_SIZE as usize) {
+            let chunk = &mut buffer[..(end - address).min(CHUNK_SIZE) as usize];
+            core.read(address, chunk)?;
+
+            if let Some(offset) = chunk.iter().position(|b| *b != erased_byte_value) {
+                return Ok(Some(address + offset as u64));
             }
-            Ok(())
-        })?;
+        }
     }
 
-    Ok(())
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sectors_are_kept_by_aligned_ranges() {
+        let keep = [0x0..0x4000, 0x1_0000..0x1_1000];
+
+        assert_eq!(is_kept(&(0x0..0x1000), &keep), Ok(true));
+        assert_eq!(is_kept(&(0x1_0000..0x1_1000), &keep), Ok(true));
+        assert_eq!(is_kept(&(0x4000..0x5000), &keep), Ok(false));
+        assert_eq!(is_kept(&(0x1_0800..0x1_1800), &keep), Err(&keep[1]));
+    }
+
+    #[test]
+    fn ranges_outside_the_nvm_are_found() {
+        let regions = [0x0..0x8_0000, 0x1000_0000..0x1000_1000];
+
+        assert_eq!(
+            range_outside(&[0x0..0x4000, 0x1000_0000..0x1000_1000], &regions),
+            None
+        );
+        // A typo in the address.
+        let keep = [0x0..0x4000, 0x800_0000..0x800_4000];
+        assert_eq!(range_outside(&keep, &regions), Some(&keep[1]));
+        // Extending past the end of a region.
+        let keep = [0x0..0x4000, 0x7_c000..0x8_4000];
+        assert_eq!(range_outside(&keep, &regions), Some(&keep[1]));
+    }
 }
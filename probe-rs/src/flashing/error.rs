@@ -28,6 +28,20 @@ pub enum FlashError {
         #[source]
         source: Box<dyn std::error::Error + 'static + Send + Sync>,
     },
+    /// A range to keep when erasing covers a sector only partially.
+    #[error("The range {range:#010X?} to keep does not start and end on sector boundaries, it covers the sector {sector:#010X?} only partially.")]
+    KeepRangeNotAligned {
+        /// The range to keep.
+        range: Range<u64>,
+        /// The sector which is covered partially.
+        sector: Range<u64>,
+    },
+    /// A range to keep when erasing is not part of the nonvolatile memory.
+    #[error("The range {range:#010X?} to keep is not contained in the nonvolatile memory of the target.")]
+    KeepRangeOutsideNvm {
+        /// The range to keep.
+        range: Range<u64>,
+    },
     /// Writing the given page failed.
     #[error("The page write of the page at address {page_address:#010x} failed.")]
     PageWrite {
@@ -46,6 +60,12 @@ pub enum FlashError {
     /// This target does not support full chip flash erases.
     #[error("The chip erase routine is not supported with the given flash algorithm.")]
     ChipEraseNotSupported,
+    /// The chip erase reported success, but did not erase the flash.
+    #[error("The chip erase with the flash algorithm {algorithm} did not erase the flash.")]
+    ChipEraseIneffective {
+        /// The name of the flash algorithm which did the chip erase.
+        algorithm: String,
+    },
     /// Calling the given routine returned the given error code.
     #[error("The execution of '{name}' failed with code {error_code}. This might indicate a problem with the flash algorithm.")]
     RoutineCallFailed {
@@ -106,6 +126,19 @@ pub enum FlashError {
         /// The memory region that was tried to be written.
         range: Range<u64>,
     },
+    /// No flash algorithm covers the data that is to be written to the given flash region.
+    ///
+    /// This typically happens for external memory-mapped flash (e.g. QSPI) if the target
+    /// description lacks an algorithm for the attached flash device.
+    #[error("Trying to write {range:#010X?} to flash region '{region_name}', but none of the flash loader algorithms of {name} covers this address range.")]
+    NoFlashLoaderAlgorithmForData {
+        /// The name of the chip.
+        name: String,
+        /// The name of the flash region that was tried to be written.
+        region_name: String,
+        /// The address range of the data that was tried to be written.
+        range: Range<u64>,
+    },
     /// More than one matching flash algorithm was found for the given memory range and all of them is marked as default.
     #[error("Trying to write flash, but found more than one suitable flash loader algorithim marked as default for {region:?}.")]
     MultipleDefaultFlashLoaderAlgorithms {
@@ -155,6 +188,14 @@ pub enum FlashError {
     /// No core can access this RAM region.
     #[error("No core can access the ram region {0:?}.")]
     NoRamCoreAccess(RamRegion),
+    /// The flash algorithm of a [`FlashOperation`](super::FlashOperation) is not part of the target.
+    #[error("The flash algorithm '{algorithm}' is not part of the target {chip}.")]
+    UnknownFlashAlgorithm {
+        /// The name of the flash algorithm.
+        algorithm: String,
+        /// The name of the chip.
+        chip: String,
+    },
     /// The register value supplied for this flash algorithm is out of the supported range.
     #[error("The register value {0:08X?} is out of the supported range.")]
     RegisterValueNotSupported(u64),
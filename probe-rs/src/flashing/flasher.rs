@@ -1,17 +1,26 @@
-use probe_rs_target::{MemoryRegion, RawFlashAlgorithm};
+use probe_rs_target::{MemoryRange, MemoryRegion, RawFlashAlgorithm};
 use tracing::Level;
 
-use super::{FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashPage, FlashProgress};
+use super::{
+    FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashHaltMode, FlashPage, FlashProgress,
+};
 use crate::config::NvmRegion;
 use crate::flashing::encoder::FlashEncoder;
-use crate::flashing::FlashLayout;
+use crate::flashing::{FlashLayout, FlashOperation, FlashOperationKind};
 use crate::memory::MemoryInterface;
-use crate::{core::CoreRegisters, session::Session, Core, InstructionSet};
+use crate::{core::CoreRegisters, session::Session, Core, InstructionSet, Target};
 use std::{
+    collections::HashSet,
     fmt::Debug,
     time::{Duration, Instant},
 };
 
+/// The flash is read in chunks of this size to check whether a sector is erased.
+const ERASE_CHECK_CHUNK_SIZE: usize = 1024;
+
+/// The number of sectors which are checked after a chip erase.
+const CHIP_ERASE_CHECK_SAMPLES: usize = 8;
+
 pub(super) trait Operation {
     fn operation() -> u32;
     fn operation_name() -> &'static str {
@@ -56,6 +65,7 @@ pub(super) struct Flasher<'session> {
     core_index: usize,
     flash_algorithm: FlashAlgorithm,
     progress: FlashProgress,
+    halt_mode: FlashHaltMode,
 }
 
 impl<'session> Flasher<'session> {
@@ -64,9 +74,30 @@ impl<'session> Flasher<'session> {
         core_index: usize,
         raw_flash_algorithm: &RawFlashAlgorithm,
         progress: FlashProgress,
+        halt_mode: FlashHaltMode,
     ) -> Result<Self, FlashError> {
-        let target = session.target();
+        let flash_algorithm =
+            Self::assemble_flash_algorithm(session.target(), core_index, raw_flash_algorithm)?;
 
+        let mut this = Self {
+            session,
+            core_index,
+            flash_algorithm,
+            progress,
+            halt_mode,
+        };
+
+        this.load()?;
+
+        Ok(this)
+    }
+
+    /// Assembles the flash algorithm for the given core, without loading it into the target.
+    pub(super) fn assemble_flash_algorithm(
+        target: &Target,
+        core_index: usize,
+        raw_flash_algorithm: &RawFlashAlgorithm,
+    ) -> Result<FlashAlgorithm, FlashError> {
         // Find a RAM region from which we can run the algo.
         let mm = &target.memory_map;
         let core_name = &target.cores[core_index].name;
@@ -92,7 +123,7 @@ impl<'session> Flasher<'session> {
                 }
             })
             .ok_or(FlashError::NoRamDefined {
-                name: session.target().name.clone(),
+                name: target.name.clone(),
             })?;
         tracing::info!("Chosen RAM to run the algo: {:x?}", ram);
 
@@ -106,30 +137,14 @@ impl<'session> Flasher<'session> {
                     ram.range.contains(&data_load_address) && ram.cores.contains(core_name)
                 })
                 .ok_or(FlashError::NoRamDefined {
-                    name: session.target().name.clone(),
+                    name: target.name.clone(),
                 })?
         } else {
             ram
         };
         tracing::info!("Data will be loaded to: {:x?}", data_ram);
 
-        let flash_algorithm = FlashAlgorithm::assemble_from_raw_with_data(
-            raw_flash_algorithm,
-            ram,
-            data_ram,
-            target,
-        )?;
-
-        let mut this = Self {
-            session,
-            core_index,
-            flash_algorithm,
-            progress,
-        };
-
-        this.load()?;
-
-        Ok(this)
+        FlashAlgorithm::assemble_from_raw_with_data(raw_flash_algorithm, ram, data_ram, target)
     }
 
     pub(super) fn flash_algorithm(&self) -> &FlashAlgorithm {
@@ -156,9 +171,13 @@ impl<'session> Flasher<'session> {
             .halt(Duration::from_millis(100))
             .map_err(FlashError::Core)?;
         tracing::debug!("PC = {:010x}", cpu_info.pc);
-        tracing::debug!("Reset and halt");
-        core.reset_and_halt(Duration::from_millis(500))
-            .map_err(FlashError::Core)?;
+        if self.halt_mode == FlashHaltMode::Halt {
+            tracing::debug!("Not resetting the core, as requested by the halt mode");
+        } else {
+            tracing::debug!("Reset and halt");
+            core.reset_and_halt(Duration::from_millis(500))
+                .map_err(FlashError::Core)?;
+        }
 
         // TODO: Possible special preparation of the target such as enabling faster clocks for the flash e.g.
 
@@ -192,10 +211,37 @@ impl<'session> Flasher<'session> {
         }
 
         tracing::debug!("RAM contents match flashing algo blob.");
+        drop(core);
+
+        if self.halt_mode == FlashHaltMode::HaltAllCores {
+            self.halt_other_cores();
+        }
 
         Ok(())
     }
 
+    /// Halt all cores except the one which runs the flash algorithm.
+    ///
+    /// This is done after the reset, which restarts the other cores on most targets. Cores which
+    /// cannot be halted, for example because they are powered down, cannot access the flash
+    /// either, so failures are only logged.
+    fn halt_other_cores(&mut self) {
+        for core_index in 0..self.session.target().cores.len() {
+            if core_index == self.core_index {
+                continue;
+            }
+
+            tracing::debug!("Halting core {core_index} for flashing");
+            let result = self
+                .session
+                .core(core_index)
+                .and_then(|mut core| core.halt(Duration::from_millis(100)));
+            if let Err(error) = result {
+                tracing::warn!("Failed to halt core {core_index} for flashing: {error}");
+            }
+        }
+    }
+
     pub(super) fn init<O: Operation>(
         &mut self,
         clock: Option<u32>,
@@ -222,6 +268,7 @@ impl<'session> Flasher<'session> {
         Ok(flasher)
     }
 
+    #[tracing::instrument(name = "erase", skip_all, fields(erase_all = true))]
     pub(super) fn run_erase_all(&mut self) -> Result<(), FlashError> {
         self.progress.started_erasing();
         let result = if self.session.has_sequence_erase_all() {
@@ -243,6 +290,11 @@ impl<'session> Flasher<'session> {
         };
 
         if result.is_ok() {
+            self.progress.operation(FlashOperation {
+                algorithm: self.flash_algorithm.name.clone(),
+                core: self.core_index,
+                kind: FlashOperationKind::EraseAll,
+            });
             self.progress.finished_erasing();
         } else {
             self.progress.failed_erasing();
@@ -293,6 +345,8 @@ impl<'session> Flasher<'session> {
     /// If `restore_unwritten_bytes` is `true`, all bytes of a sector,
     /// that are not to be written during flashing will be read from the flash first
     /// and written again once the sector is erased.
+    ///
+    /// If `skip_erased_sectors` is `true`, sectors which are already erased are not erased again.
     pub(super) fn program(
         &mut self,
         region: &NvmRegion,
@@ -300,6 +354,7 @@ impl<'session> Flasher<'session> {
         restore_unwritten_bytes: bool,
         enable_double_buffering: bool,
         skip_erasing: bool,
+        skip_erased_sectors: bool,
     ) -> Result<(), FlashError> {
         tracing::debug!("Starting program procedure.");
         // Convert the list of flash operations into flash sectors and pages.
@@ -339,7 +394,7 @@ impl<'session> Flasher<'session> {
         // Skip erase if necessary (i.e. chip erase was done before)
         if !skip_erasing {
             // Erase all necessary sectors
-            self.sector_erase(&flash_encoder)?;
+            self.sector_erase(&flash_encoder, skip_erased_sectors)?;
         }
 
         // Flash all necessary pages.
@@ -373,6 +428,7 @@ impl<'session> Flasher<'session> {
     }
 
     /// Programs the pages given in `flash_layout` into the flash.
+    #[tracing::instrument(name = "program", skip_all, fields(bytes = flash_encoder.program_size()))]
     fn program_simple(&mut self, flash_encoder: &FlashEncoder) -> Result<(), FlashError> {
         self.progress
             .started_programming(flash_encoder.program_size());
@@ -403,12 +459,35 @@ impl<'session> Flasher<'session> {
     }
 
     /// Perform an erase of all sectors given in `flash_layout`.
-    fn sector_erase(&mut self, flash_encoder: &FlashEncoder) -> Result<(), FlashError> {
+    ///
+    /// If `skip_erased_sectors` is `true`, sectors which are already erased are skipped.
+    #[tracing::instrument(name = "erase", skip_all, fields(erase_all = false))]
+    fn sector_erase(
+        &mut self,
+        flash_encoder: &FlashEncoder,
+        skip_erased_sectors: bool,
+    ) -> Result<(), FlashError> {
+        let erased_sectors = if skip_erased_sectors {
+            self.erased_sectors(flash_encoder)
+        } else {
+            HashSet::new()
+        };
+
         self.progress.started_erasing();
 
         let mut t = Instant::now();
         let result = self.run_erase(|active| {
             for sector in flash_encoder.sectors() {
+                if erased_sectors.contains(&sector.address()) {
+                    tracing::debug!(
+                        "Sector {:#010x} is already erased, skipping it.",
+                        sector.address()
+                    );
+                    active.progress.sector_erase_skipped(sector.size());
+                    t = Instant::now();
+                    continue;
+                }
+
                 active
                     .erase_sector(sector.address())
                     .map_err(|e| FlashError::EraseFailed {
@@ -431,6 +510,69 @@ impl<'session> Flasher<'session> {
         result
     }
 
+    /// Find the sectors given in `flash_encoder` which are already erased, by their address.
+    ///
+    /// Reading erased flash faults on some chips, for example with ECC or write-once flash. If
+    /// the flash cannot be read, no sector is considered to be erased, and all of them are erased.
+    fn erased_sectors(&mut self, flash_encoder: &FlashEncoder) -> HashSet<u64> {
+        let result = self.run_verify(|active| {
+            let mut erased_sectors = HashSet::new();
+
+            for sector in flash_encoder.sectors() {
+                if active.is_erased(sector.address(), sector.size())? {
+                    erased_sectors.insert(sector.address());
+                }
+            }
+
+            Ok(erased_sectors)
+        });
+
+        result.unwrap_or_else(|error| {
+            tracing::debug!(
+                "Failed to check whether the sectors are already erased, erasing all of them: {error}"
+            );
+            HashSet::new()
+        })
+    }
+
+    /// Check whether a chip erase erased the flash of `regions`.
+    ///
+    /// Some flash algorithms report success for a chip erase which the hardware does not
+    /// support. To keep this cheap, only the start of a sample of the sectors is checked. If the
+    /// flash cannot be read, the chip erase is assumed to have worked.
+    pub(super) fn is_chip_erased(&mut self, regions: &[NvmRegion]) -> bool {
+        let sectors = self
+            .flash_algorithm
+            .iter_sectors()
+            .filter(|info| {
+                let range = info.base_address..info.base_address + info.size;
+                regions.iter().any(|r| r.range.contains_range(&range))
+            })
+            .collect::<Vec<_>>();
+
+        let result = self.run_verify(|active| {
+            for index in sample_indices(sectors.len(), CHIP_ERASE_CHECK_SAMPLES) {
+                let sector = &sectors[index];
+                let size = sector.size.min(ERASE_CHECK_CHUNK_SIZE as u64);
+
+                if !active.is_erased(sector.base_address, size)? {
+                    tracing::debug!(
+                        "Sector {:#010x} is not erased after the chip erase",
+                        sector.base_address
+                    );
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        });
+
+        result.unwrap_or_else(|error| {
+            tracing::debug!("Failed to check whether the chip erase erased the flash: {error}");
+            true
+        })
+    }
+
     /// Flash a program using double buffering.
     ///
     /// This uses two buffers to increase the flash speed.
@@ -440,6 +582,7 @@ impl<'session> Flasher<'session> {
     ///
     /// This is only possible if the RAM is large enough to
     /// fit at least two page buffers. See [Flasher::double_buffering_supported].
+    #[tracing::instrument(name = "program", skip_all, fields(bytes = flash_encoder.program_size()))]
     fn program_double_buffer(&mut self, flash_encoder: &FlashEncoder) -> Result<(), FlashError> {
         let mut current_buf = 0;
         self.progress
@@ -448,6 +591,8 @@ impl<'session> Flasher<'session> {
         let mut t = Instant::now();
         let result = self.run_program(|active| {
             let mut last_page_address = 0;
+            // The page which is being programmed, reported once its programming completed.
+            let mut programming = None;
             for page in flash_encoder.pages() {
                 // At the start of each loop cycle load the next page buffer into RAM.
                 active.load_page_buffer(page.address(), page.data(), current_buf)?;
@@ -472,9 +617,16 @@ impl<'session> Flasher<'session> {
                         error_code: result,
                     });
                 }
+                if let Some(kind) = programming.take() {
+                    active.report_operation(kind);
+                }
 
                 // Start the next copy process.
                 active.start_program_page_with_buffer(page.address(), current_buf)?;
+                programming = Some(FlashOperationKind::ProgramPage {
+                    address: page.address(),
+                    data: page.data().to_vec(),
+                });
 
                 // Swap the buffers
                 if current_buf == 1 {
@@ -497,6 +649,9 @@ impl<'session> Flasher<'session> {
                     error_code: result,
                 })
             } else {
+                if let Some(kind) = programming {
+                    active.report_operation(kind);
+                }
                 Ok(0)
             }
         });
@@ -544,6 +699,17 @@ impl Debug for Registers {
     }
 }
 
+/// Select up to `samples` indices of `len` items, spread evenly and including the first and the
+/// last item.
+fn sample_indices(len: usize, samples: usize) -> impl Iterator<Item = usize> {
+    let samples = samples.min(len);
+
+    (0..samples).map(move |i| match samples {
+        1 => 0,
+        _ => i * (len - 1) / (samples - 1),
+    })
+}
+
 fn into_reg(val: u64) -> Result<u32, FlashError> {
     let reg_value: u32 = val
         .try_into()
@@ -794,6 +960,38 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         }
         Ok(())
     }
+
+    /// Reports a successfully completed operation to the progress handler.
+    fn report_operation(&self, kind: FlashOperationKind) {
+        self.progress.operation(FlashOperation {
+            algorithm: self.flash_algorithm.name.clone(),
+            core: self.core.id(),
+            kind,
+        });
+    }
+
+    /// Check whether the `size` bytes of flash at `address` are erased.
+    ///
+    /// The flash is read in chunks, so that the check stops early at the first chunk which is
+    /// not erased.
+    pub(super) fn is_erased(&mut self, address: u64, size: u64) -> Result<bool, FlashError> {
+        let erased_byte_value = self.flash_algorithm.flash_properties.erased_byte_value;
+        let mut buffer = [0; ERASE_CHECK_CHUNK_SIZE];
+        let end = address + size;
+        let mut address = address;
+
+        while address < end {
+            let chunk = &mut buffer[..ERASE_CHECK_CHUNK_SIZE.min((end - address) as usize)];
+            self.core.read(address, chunk).map_err(FlashError::Core)?;
+
+            if chunk.iter().any(|&byte| byte != erased_byte_value) {
+                return Ok(false);
+            }
+            address += chunk.len() as u64;
+        }
+
+        Ok(true)
+    }
 }
 
 impl<'probe> ActiveFlasher<'probe, Erase> {
@@ -863,6 +1061,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
                 error_code: result,
             })
         } else {
+            self.report_operation(FlashOperationKind::EraseSector { address });
             Ok(())
         }
     }
@@ -950,6 +1149,10 @@ impl<'p> ActiveFlasher<'p, Program> {
                 }),
             })
         } else {
+            self.report_operation(FlashOperationKind::ProgramPage {
+                address,
+                data: bytes.to_vec(),
+            });
             Ok(())
         }
     }
@@ -1003,3 +1206,19 @@ impl<'p> ActiveFlasher<'p, Program> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::sample_indices;
+
+    #[test]
+    fn samples_are_spread_evenly() {
+        assert_eq!(sample_indices(0, 8).collect::<Vec<_>>(), []);
+        assert_eq!(sample_indices(1, 8).collect::<Vec<_>>(), [0]);
+        assert_eq!(sample_indices(3, 8).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(
+            sample_indices(100, 5).collect::<Vec<_>>(),
+            [0, 24, 49, 74, 99]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Identification of external SPI flash by its JEDEC ID.
+
+use crate::config::DebugSequence;
+use crate::Session;
+
+/// The JEDEC ID of an SPI flash, as returned by the `RDID` (`0x9F`) command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JedecId {
+    /// The JEP106 manufacturer ID.
+    pub manufacturer: u8,
+    /// The memory type, which is specific to the manufacturer.
+    pub memory_type: u8,
+    /// The capacity, usually as the base 2 logarithm of the size in bytes.
+    pub capacity: u8,
+}
+
+impl JedecId {
+    /// Creates the ID from the first three bytes received after the `RDID` command.
+    pub fn from_bytes([manufacturer, memory_type, capacity]: [u8; 3]) -> Self {
+        Self {
+            manufacturer,
+            memory_type,
+            capacity,
+        }
+    }
+
+    /// The name of the manufacturer, if it is one of the common SPI flash manufacturers.
+    pub fn manufacturer_name(&self) -> Option<&'static str> {
+        let name = match self.manufacturer {
+            0x01 => "Spansion / Infineon",
+            0x1C => "EON",
+            0x1F => "Adesto / Renesas",
+            0x20 => "Micron / XMC",
+            0x5E => "Zbit",
+            0x68 => "Boya",
+            0x85 => "Puya",
+            0x9D => "ISSI",
+            0xA1 => "Fudan",
+            0xBF => "SST / Microchip",
+            0xC2 => "Macronix",
+            0xC8 => "GigaDevice",
+            0xEF => "Winbond",
+            _ => return None,
+        };
+
+        Some(name)
+    }
+
+    /// The size of the flash in bytes, if the capacity byte follows the common encoding of the
+    /// base 2 logarithm of the size.
+    ///
+    /// Some manufacturers use a different encoding for large or uncommon sizes, which is not
+    /// decoded.
+    pub fn size(&self) -> Option<u64> {
+        match self.capacity {
+            // 64 kB to 2 GB
+            0x10..=0x1F => Some(1 << self.capacity),
+            // 64 MB to 256 MB, used by Winbond and others instead of 0x1A to 0x1C.
+            0x20..=0x22 => Some(1 << (self.capacity - 6)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for JedecId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02X} {:02X} {:02X}",
+            self.manufacturer, self.memory_type, self.capacity
+        )
+    }
+}
+
+/// Reads the JEDEC ID of the SPI flash of the target.
+///
+/// Returns `None` if the debug sequence of the target cannot access the SPI flash controller,
+/// which at the moment is only implemented for the Espressif chips.
+pub fn read_jedec_id(session: &mut Session) -> Result<Option<JedecId>, crate::Error> {
+    session.halted_access(|session| match session.target().debug_sequence.clone() {
+        DebugSequence::Riscv(sequence) => sequence.read_flash_id(session),
+        DebugSequence::Xtensa(sequence) => sequence.read_flash_id(session),
+        DebugSequence::Arm(_) => Ok(None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_ids_are_decoded() {
+        // Winbond W25Q128JV
+        let id = JedecId::from_bytes([0xEF, 0x40, 0x18]);
+        assert_eq!(id.manufacturer_name(), Some("Winbond"));
+        assert_eq!(id.size(), Some(16 * 1024 * 1024));
+        assert_eq!(id.to_string(), "EF 40 18");
+
+        // Macronix MX25L51245G and Winbond W25Q512JV encode 64 MB differently.
+        let id = JedecId::from_bytes([0xC2, 0x20, 0x1A]);
+        assert_eq!(id.size(), Some(64 * 1024 * 1024));
+        let id = JedecId::from_bytes([0xEF, 0x40, 0x20]);
+        assert_eq!(id.size(), Some(64 * 1024 * 1024));
+
+        let id = JedecId::from_bytes([0x12, 0x34, 0x05]);
+        assert_eq!(id.manufacturer_name(), None);
+        assert_eq!(id.size(), None);
+    }
+}
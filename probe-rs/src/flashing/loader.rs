@@ -31,6 +31,9 @@ pub struct FlashLoader {
     memory_map: Vec<MemoryRegion>,
     builder: FlashBuilder,
 
+    /// Where the staged data chunks came from.
+    origins: Vec<DataOrigin>,
+
     /// Source of the flash description,
     /// used for diagnostics.
     source: TargetDescriptionSource,
@@ -42,6 +45,7 @@ impl FlashLoader {
         Self {
             memory_map,
             builder: FlashBuilder::new(),
+            origins: Vec::new(),
             source,
         }
     }
@@ -69,6 +73,17 @@ impl FlashLoader {
     ///
     /// The chunk can cross flash boundaries as long as one flash region connects to another flash region.
     pub fn add_data(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        self.add_data_with_origin(address, data, None, None)
+    }
+
+    /// Stages a chunk of data to be programmed, and records where it was read from.
+    fn add_data_with_origin(
+        &mut self,
+        address: u64,
+        data: &[u8],
+        file_offset: Option<u64>,
+        name: Option<String>,
+    ) -> Result<(), FlashError> {
         tracing::trace!(
             "Adding data at address {:#010x} with size {} bytes",
             address,
@@ -76,7 +91,16 @@ impl FlashLoader {
         );
 
         self.check_data_in_memory_map(address..address + data.len() as u64)?;
-        self.builder.add_data(address, data)
+        self.builder.add_data(address, data)?;
+
+        self.origins.push(DataOrigin {
+            address,
+            size: data.len() as u64,
+            file_offset,
+            name,
+        });
+
+        Ok(())
     }
 
     pub(super) fn get_region_for_address(
@@ -138,7 +162,7 @@ impl FlashLoader {
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)?;
 
-        self.add_data(
+        self.add_data_with_origin(
             if let Some(address) = options.base_address {
                 address
             } else {
@@ -147,6 +171,8 @@ impl FlashLoader {
                 0
             },
             &buf,
+            Some(u64::from(options.skip)),
+            None,
         )?;
 
         Ok(())
@@ -294,7 +320,13 @@ impl FlashLoader {
         }
 
         for data in extracted_data {
-            self.add_data(data.address.into(), data.data)?;
+            let name = data.section_names.join(", ");
+            self.add_data_with_origin(
+                data.address.into(),
+                data.data,
+                Some(data.file_offset),
+                Some(name),
+            )?;
         }
 
         Ok(())
@@ -327,6 +359,7 @@ impl FlashLoader {
     /// Writes all the stored data chunks to flash.
     ///
     /// Requires a session with an attached target that has a known flash algorithm.
+    #[tracing::instrument(name = "flash", skip_all)]
     pub fn commit(
         &self,
         session: &mut Session,
@@ -363,60 +396,21 @@ impl FlashLoader {
             tracing::warn!("Memory map of flash loader does not match memory map of target!");
         }
 
-        let mut algos: HashMap<(String, usize), Vec<NvmRegion>> = HashMap::new();
-
         let progress = options.progress.unwrap_or(FlashProgress::new(|_| {}));
 
         // Commit NVM first
-
-        // Iterate all NvmRegions and group them by flash algorithm.
-        // This avoids loading the same algorithm twice if it's used for two regions.
-        //
-        // This also ensures correct operation when chip erase is used. We assume doing a chip erase
-        // using a given algorithm erases all regions controlled by it. Therefore, we must do
-        // chip erase once per algorithm, not once per region. Otherwise subsequent chip erases will
-        // erase previous regions' flashed contents.
-        tracing::debug!("Regions:");
-        for region in self
-            .memory_map
-            .iter()
-            .filter_map(MemoryRegion::as_nvm_region)
-        {
-            if region.is_alias {
-                tracing::debug!("Skipping alias memory region {:#010X?}", region.range);
-                continue;
-            }
-            tracing::debug!(
-                "    region: {:#010X?} ({} bytes)",
-                region.range,
-                region.range.end - region.range.start
-            );
-
-            // If we have no data in this region, ignore it.
-            // This avoids uselessly initializing and deinitializing its flash algorithm.
-            if !self.builder.has_data_in_range(&region.range) {
-                tracing::debug!("     -- empty, ignoring!");
-                continue;
+        let algos = self.regions_by_algorithm(session.target())?;
+
+        // Checked before anything is done, so that a dry run fails in the same way.
+        if options.do_chip_erase && options.require_chip_erase {
+            let has_sequence_erase_all = session.has_sequence_erase_all();
+            for (algo_name, _) in algos.keys() {
+                // This can't fail, algo_name comes from the target.
+                let algo = session.target().flash_algorithm_by_name(algo_name).unwrap();
+                if !has_sequence_erase_all && algo.pc_erase_all.is_none() {
+                    return Err(FlashError::ChipEraseNotSupported);
+                }
             }
-
-            let target = session.target();
-            let algo = Self::get_flash_algorithm_for_region(region, target)?;
-            let core_name = region
-                .cores
-                .first()
-                .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?
-                .clone();
-
-            let core = target
-                .cores
-                .iter()
-                .position(|c| c.name == core_name)
-                .unwrap();
-
-            let entry = algos.entry((algo.name.clone(), core)).or_default();
-            entry.push(region.clone());
-
-            tracing::debug!("     -- using algorithm: {}", algo.name);
         }
 
         if options.dry_run {
@@ -432,9 +426,6 @@ impl FlashLoader {
         let mut do_chip_erase = options.do_chip_erase;
         let mut did_chip_erase = false;
 
-        // No longer needs to be mutable.
-        let algos = algos;
-
         let mut phases = vec![];
 
         // Iterate all flash algorithms to initialize a few things.
@@ -443,7 +434,7 @@ impl FlashLoader {
             let algo = session.target().flash_algorithm_by_name(algo_name);
             let algo = algo.unwrap().clone();
 
-            let flasher = Flasher::new(session, *core, &algo, progress.clone())?;
+            let flasher = Flasher::new(session, *core, &algo, progress.clone(), options.halt_mode)?;
             // If the first flash algo doesn't support erase all, disable chip erase.
             // TODO: we could sort by support but it's unlikely to make a difference.
             if do_chip_erase && !flasher.is_chip_erase_supported() {
@@ -472,13 +463,23 @@ impl FlashLoader {
             let algo = session.target().flash_algorithm_by_name(&algo_name);
             let algo = algo.unwrap().clone();
 
-            let mut flasher = Flasher::new(session, core, &algo, progress.clone())?;
+            let mut flasher =
+                Flasher::new(session, core, &algo, progress.clone(), options.halt_mode)?;
 
             if do_chip_erase {
                 tracing::debug!("    Doing chip erase...");
                 flasher.run_erase_all()?;
                 do_chip_erase = false;
-                did_chip_erase = true;
+
+                did_chip_erase = flasher.is_chip_erased(&regions);
+                if !did_chip_erase && options.require_chip_erase {
+                    return Err(FlashError::ChipEraseIneffective {
+                        algorithm: algo_name,
+                    });
+                }
+                if !did_chip_erase {
+                    tracing::warn!("The chip erase with algorithm {algo_name} did not erase the flash. Erasing the sectors one by one instead.");
+                }
             }
 
             let mut do_use_double_buffering = flasher.double_buffering_supported();
@@ -501,6 +502,7 @@ impl FlashLoader {
                     options.keep_unwritten_bytes,
                     do_use_double_buffering,
                     options.skip_erase || did_chip_erase,
+                    !options.force_erase,
                 )?;
             }
         }
@@ -550,6 +552,7 @@ impl FlashLoader {
         }
 
         if options.verify {
+            let _span = tracing::info_span!("verify").entered();
             tracing::debug!("Verifying!");
             for (&address, data) in &self.builder.data {
                 tracing::debug!(
@@ -580,6 +583,101 @@ impl FlashLoader {
         Ok(())
     }
 
+    /// Iterates all NvmRegions which contain staged data and groups them by flash algorithm and core.
+    ///
+    /// This avoids loading the same algorithm twice if it's used for two regions.
+    ///
+    /// This also ensures correct operation when chip erase is used. We assume doing a chip erase
+    /// using a given algorithm erases all regions controlled by it. Therefore, we must do
+    /// chip erase once per algorithm, not once per region. Otherwise subsequent chip erases will
+    /// erase previous regions' flashed contents.
+    fn regions_by_algorithm(
+        &self,
+        target: &Target,
+    ) -> Result<HashMap<(String, usize), Vec<NvmRegion>>, FlashError> {
+        let mut algos: HashMap<(String, usize), Vec<NvmRegion>> = HashMap::new();
+
+        tracing::debug!("Regions:");
+        for region in self
+            .memory_map
+            .iter()
+            .filter_map(MemoryRegion::as_nvm_region)
+        {
+            if region.is_alias {
+                tracing::debug!("Skipping alias memory region {:#010X?}", region.range);
+                continue;
+            }
+            tracing::debug!(
+                "    region: {:#010X?} ({} bytes)",
+                region.range,
+                region.range.end - region.range.start
+            );
+
+            // If we have no data in this region, ignore it.
+            // This avoids uselessly initializing and deinitializing its flash algorithm.
+            if !self.builder.has_data_in_range(&region.range) {
+                tracing::debug!("     -- empty, ignoring!");
+                continue;
+            }
+
+            let core_name = region
+                .cores
+                .first()
+                .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?
+                .clone();
+
+            let core = target
+                .cores
+                .iter()
+                .position(|c| c.name == core_name)
+                .unwrap();
+
+            for (algo, part) in self.flash_algorithms_for_staged_data(region, target)? {
+                tracing::debug!(
+                    "     -- using algorithm: {} for {:#010X?}",
+                    algo.name,
+                    part.range
+                );
+
+                let entry = algos.entry((algo.name.clone(), core)).or_default();
+                entry.push(part);
+            }
+        }
+
+        Ok(algos)
+    }
+
+    /// Computes the flash layout which [`commit()`](FlashLoader::commit) would program, without
+    /// accessing the target.
+    ///
+    /// One layout is returned for every flash algorithm which is used.
+    pub fn flash_layouts(
+        &self,
+        target: &Target,
+        restore_unwritten_bytes: bool,
+    ) -> Result<Vec<FlashLayout>, FlashError> {
+        let algos = self.regions_by_algorithm(target)?;
+
+        let mut layouts = vec![];
+        for ((algo_name, core), regions) in algos.iter() {
+            // This can't fail, algo_name comes from the target.
+            let algo = target.flash_algorithm_by_name(algo_name).unwrap();
+            let flash_algorithm = Flasher::assemble_flash_algorithm(target, *core, algo)?;
+
+            let mut layout = FlashLayout::default();
+            for region in regions {
+                layout.merge_from(self.builder.build_sectors_and_pages(
+                    region,
+                    &flash_algorithm,
+                    restore_unwritten_bytes,
+                )?);
+            }
+            layouts.push(layout);
+        }
+
+        Ok(layouts)
+    }
+
     /// Try to find a flash algorithm for the given NvmRegion.
     /// Errors when:
     /// - there's no algo for the region.
@@ -589,22 +687,154 @@ impl FlashLoader {
         region: &NvmRegion,
         target: &'a Target,
     ) -> Result<&'a RawFlashAlgorithm, FlashError> {
-        let algorithms = target
+        let algorithms = Self::flash_algorithms_for_range(&region.range, target);
+
+        if algorithms.is_empty() {
+            return Err(FlashError::NoFlashLoaderAlgorithmAttached {
+                range: region.range.clone(),
+                name: target.name.clone(),
+            });
+        }
+
+        Self::select_default_flash_algorithm(algorithms, region)
+    }
+
+    /// Try to find a flash algorithm for the data staged in the given NvmRegion.
+    ///
+    /// Algorithms covering the whole region are preferred. If there are none, an algorithm
+    /// covering all of the staged data is accepted instead. This is common for external
+    /// memory-mapped flash (e.g. QSPI), where the region describes the whole XIP window
+    /// but the algorithm only covers the attached flash device.
+    fn get_flash_algorithm_for_staged_data<'a>(
+        &self,
+        region: &NvmRegion,
+        target: &'a Target,
+    ) -> Result<&'a RawFlashAlgorithm, FlashError> {
+        if !Self::flash_algorithms_for_range(&region.range, target).is_empty() {
+            return Self::get_flash_algorithm_for_region(region, target);
+        }
+
+        // Staged data is returned in ascending address order.
+        let staged_range = self
+            .builder
+            .data_in_range(&region.range)
+            .map(|(address, data)| address..address + data.len() as u64)
+            .reduce(|first, last| first.start..last.end);
+
+        let Some(staged_range) = staged_range else {
+            return Self::get_flash_algorithm_for_region(region, target);
+        };
+
+        let algorithms = Self::flash_algorithms_for_range(&staged_range, target);
+
+        if algorithms.is_empty() {
+            return Err(FlashError::NoFlashLoaderAlgorithmForData {
+                name: target.name.clone(),
+                region_name: region
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "<unnamed>".to_string()),
+                range: staged_range,
+            });
+        }
+
+        tracing::debug!(
+            "No flash algorithm covers the whole region {:#010X?}, using one that covers the staged data {:#010X?}",
+            region.range,
+            staged_range
+        );
+
+        Self::select_default_flash_algorithm(algorithms, region)
+    }
+
+    /// Selects the flash algorithms for the data staged in the given NvmRegion, and the part of
+    /// the region each of them programs.
+    ///
+    /// Usually one algorithm covers the region or all of its data. Otherwise, e.g. if the region
+    /// spans two flash banks with their own algorithms, the region is split at the bounds of the
+    /// algorithms, and each part is programmed with the algorithm covering it.
+    fn flash_algorithms_for_staged_data<'a>(
+        &self,
+        region: &NvmRegion,
+        target: &'a Target,
+    ) -> Result<Vec<(&'a RawFlashAlgorithm, NvmRegion)>, FlashError> {
+        match self.get_flash_algorithm_for_staged_data(region, target) {
+            Ok(algo) => return Ok(vec![(algo, region.clone())]),
+            Err(FlashError::NoFlashLoaderAlgorithmForData { .. }) => {}
+            Err(error) => return Err(error),
+        }
+
+        let mut parts: Vec<(&RawFlashAlgorithm, NvmRegion)> = vec![];
+        for (address, data) in self.builder.data_in_range(&region.range) {
+            let end = address + data.len() as u64;
+            let mut start = address;
+
+            while start < end {
+                let algorithms = target
+                    .flash_algorithms
+                    .iter()
+                    .filter(|fa| fa.flash_properties.address_range.contains(&start))
+                    .collect::<Vec<_>>();
+
+                if algorithms.is_empty() {
+                    // The uncovered data ends where the next algorithm starts.
+                    let uncovered_end = target
+                        .flash_algorithms
+                        .iter()
+                        .map(|fa| fa.flash_properties.address_range.start)
+                        .filter(|&algo_start| algo_start > start && algo_start < end)
+                        .min()
+                        .unwrap_or(end);
+
+                    return Err(FlashError::NoFlashLoaderAlgorithmForData {
+                        name: target.name.clone(),
+                        region_name: region
+                            .name
+                            .clone()
+                            .unwrap_or_else(|| "<unnamed>".to_string()),
+                        range: start..uncovered_end,
+                    });
+                }
+
+                let algo = Self::select_default_flash_algorithm(algorithms, region)?;
+                let algo_range = &algo.flash_properties.address_range;
+
+                if !parts
+                    .iter()
+                    .any(|(part_algo, _)| part_algo.name == algo.name)
+                {
+                    let mut part = region.clone();
+                    part.range = region.range.start.max(algo_range.start)
+                        ..region.range.end.min(algo_range.end);
+                    parts.push((algo, part));
+                }
+
+                start = algo_range.end.min(end);
+            }
+        }
+
+        Ok(parts)
+    }
+
+    /// Returns all flash algorithms of the target that fully contain the given address range.
+    fn flash_algorithms_for_range<'a>(
+        range: &Range<u64>,
+        target: &'a Target,
+    ) -> Vec<&'a RawFlashAlgorithm> {
+        target
             .flash_algorithms
             .iter()
             // filter for algorithims that contiain adress range
-            .filter(|&fa| {
-                fa.flash_properties
-                    .address_range
-                    .contains_range(&region.range)
-            })
-            .collect::<Vec<_>>();
+            .filter(|&fa| fa.flash_properties.address_range.contains_range(range))
+            .collect::<Vec<_>>()
+    }
 
+    /// Selects the algorithm to use from a non-empty list of matching algorithms.
+    fn select_default_flash_algorithm<'a>(
+        algorithms: Vec<&'a RawFlashAlgorithm>,
+        region: &NvmRegion,
+    ) -> Result<&'a RawFlashAlgorithm, FlashError> {
         match algorithms.len() {
-            0 => Err(FlashError::NoFlashLoaderAlgorithmAttached {
-                range: region.range.clone(),
-                name: target.name.clone(),
-            }),
             1 => Ok(algorithms[0]),
             _ => {
                 // filter for defaults
@@ -633,4 +863,156 @@ impl FlashLoader {
             .iter()
             .map(|(address, data)| (*address, data.as_slice()))
     }
+
+    /// Returns where the staged data chunks were read from, in the order they were added.
+    pub fn data_origins(&self) -> &[DataOrigin] {
+        &self.origins
+    }
+}
+
+/// Describes where a chunk of data staged in a [`FlashLoader`] was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataOrigin {
+    address: u64,
+    size: u64,
+    file_offset: Option<u64>,
+    name: Option<String>,
+}
+
+impl DataOrigin {
+    /// Returns the address the data is programmed to.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Returns the size of the data in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the offset of the data in the input file, if it is known.
+    ///
+    /// The offset is unknown for formats which are converted before loading, e.g. Intel HEX or UF2.
+    pub fn file_offset(&self) -> Option<u64> {
+        self.file_offset
+    }
+
+    /// Returns the name of the data, e.g. the ELF sections it contains.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use probe_rs_target::MemoryRegion;
+
+    use super::FlashLoader;
+    use crate::flashing::{BinOptions, FlashError};
+
+    /// Returns a target whose external flash window is larger than its flash algorithm.
+    fn target_with_large_xip_window() -> crate::Target {
+        let mut target = crate::config::get_target_by_name("mimxrt1010").unwrap();
+        for region in target.memory_map.iter_mut() {
+            if let MemoryRegion::Nvm(region) = region {
+                region.range = 0x6000_0000..0x7000_0000;
+            }
+        }
+        target
+    }
+
+    #[test]
+    fn algorithm_for_data_in_external_flash() {
+        let target = target_with_large_xip_window();
+        let mut loader = FlashLoader::new(target.memory_map.clone(), target.source().clone());
+        loader.add_data(0x6000_1000, &[0x1, 0x2, 0x3, 0x4]).unwrap();
+
+        let region = target.memory_map[0].as_nvm_region().unwrap();
+        let algo = loader
+            .get_flash_algorithm_for_staged_data(region, &target)
+            .unwrap();
+
+        assert_eq!(algo.name, "mimxrt1011_quadspi_4kb_sec");
+    }
+
+    #[test]
+    fn no_algorithm_for_data_in_external_flash() {
+        let target = target_with_large_xip_window();
+        let mut loader = FlashLoader::new(target.memory_map.clone(), target.source().clone());
+        loader.add_data(0x6800_0000, &[0x1, 0x2, 0x3, 0x4]).unwrap();
+
+        let region = target.memory_map[0].as_nvm_region().unwrap();
+        let error = loader
+            .get_flash_algorithm_for_staged_data(region, &target)
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            FlashError::NoFlashLoaderAlgorithmForData { range, .. } if range == (0x6800_0000..0x6800_0004)
+        ));
+    }
+
+    #[test]
+    fn region_is_split_between_the_algorithms_of_two_banks() {
+        let mut target = target_with_large_xip_window();
+        let mut second_bank = target.flash_algorithms[0].clone();
+        second_bank.name = "second_bank".to_string();
+        second_bank.default = false;
+        second_bank.flash_properties.address_range = 0x6100_0000..0x6200_0000;
+        target.flash_algorithms.push(second_bank);
+
+        let mut loader = FlashLoader::new(target.memory_map.clone(), target.source().clone());
+        loader.add_data(0x60FF_FFFC, &[0x1; 8]).unwrap();
+
+        let region = target.memory_map[0].as_nvm_region().unwrap();
+        let parts = loader
+            .flash_algorithms_for_staged_data(region, &target)
+            .unwrap();
+        let parts: Vec<_> = parts
+            .iter()
+            .map(|(algo, part)| (algo.name.as_str(), part.range.clone()))
+            .collect();
+
+        assert_eq!(
+            parts,
+            [
+                ("mimxrt1011_quadspi_4kb_sec", 0x6000_0000..0x6100_0000),
+                ("second_bank", 0x6100_0000..0x6200_0000),
+            ]
+        );
+
+        // Data which no algorithm covers is reported.
+        loader.add_data(0x6800_0000, &[0x1; 4]).unwrap();
+        let error = loader
+            .flash_algorithms_for_staged_data(region, &target)
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            FlashError::NoFlashLoaderAlgorithmForData { range, .. } if range == (0x6800_0000..0x6800_0004)
+        ));
+    }
+
+    #[test]
+    fn bin_data_origin_includes_skipped_bytes() {
+        let target = target_with_large_xip_window();
+        let mut loader = FlashLoader::new(target.memory_map.clone(), target.source().clone());
+
+        let mut file = std::io::Cursor::new(vec![0xAA; 0x100]);
+        loader
+            .load_bin_data(
+                &mut file,
+                BinOptions {
+                    base_address: Some(0x6000_0000),
+                    skip: 0x10,
+                },
+            )
+            .unwrap();
+
+        let origins = loader.data_origins();
+        assert_eq!(origins.len(), 1);
+        assert_eq!(origins[0].address(), 0x6000_0000);
+        assert_eq!(origins[0].size(), 0xF0);
+        assert_eq!(origins[0].file_offset(), Some(0x10));
+    }
 }
@@ -50,7 +50,9 @@ mod erase;
 mod error;
 mod flash_algorithm;
 mod flasher;
+mod jedec;
 mod loader;
+mod operation;
 mod progress;
 mod visualizer;
 
@@ -62,6 +64,8 @@ pub use download::*;
 pub use erase::*;
 pub use error::*;
 pub use flash_algorithm::*;
+pub use jedec::*;
 pub use loader::*;
+pub use operation::*;
 pub use progress::*;
 pub use visualizer::*;
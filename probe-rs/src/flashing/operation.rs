@@ -0,0 +1,144 @@
+//! Recording and replaying the low-level operations of the flash algorithms.
+
+use crate::flashing::{flasher::Flasher, FlashError, FlashHaltMode, FlashProgress};
+use crate::Session;
+
+/// A low-level operation which was issued to a flash algorithm.
+///
+/// The operations are reported with [`ProgressEvent::Operation`](super::ProgressEvent::Operation)
+/// while flashing, and can be issued again with [`run_flash_operations`], e.g. to reproduce a
+/// failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashOperation {
+    /// The name of the flash algorithm.
+    pub algorithm: String,
+    /// The index of the core which ran the flash algorithm.
+    pub core: usize,
+    /// What the flash algorithm did.
+    pub kind: FlashOperationKind,
+}
+
+/// The kind of a [`FlashOperation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlashOperationKind {
+    /// The entire chip was erased.
+    EraseAll,
+    /// The sector at `address` was erased.
+    EraseSector {
+        /// The address of the sector.
+        address: u64,
+    },
+    /// The page at `address` was programmed.
+    ProgramPage {
+        /// The address of the page.
+        address: u64,
+        /// The data as it was transferred to the flash algorithm, after the transfer encoding of
+        /// the algorithm was applied.
+        data: Vec<u8>,
+    },
+}
+
+impl FlashOperationKind {
+    /// Whether the operation runs in the same flash algorithm session as `other`.
+    fn same_phase(&self, other: &FlashOperationKind) -> bool {
+        matches!(
+            (self, other),
+            (
+                FlashOperationKind::EraseSector { .. },
+                FlashOperationKind::EraseSector { .. }
+            ) | (
+                FlashOperationKind::ProgramPage { .. },
+                FlashOperationKind::ProgramPage { .. }
+            )
+        )
+    }
+}
+
+/// Issues the `operations` to the flash algorithms of the target, in order.
+///
+/// Consecutive erases or programs with the same algorithm are run without initializing the
+/// algorithm again, like the flash loader does.
+pub fn run_flash_operations(
+    session: &mut Session,
+    progress: FlashProgress,
+    operations: &[FlashOperation],
+) -> Result<(), FlashError> {
+    let mut remaining = operations;
+
+    while let Some(first) = remaining.first() {
+        let phase_len = remaining
+            .iter()
+            .take_while(|operation| {
+                operation.algorithm == first.algorithm
+                    && operation.core == first.core
+                    && operation.kind.same_phase(&first.kind)
+            })
+            .count()
+            .max(1);
+        let (phase, rest) = remaining.split_at(phase_len);
+        remaining = rest;
+
+        let algorithm = session
+            .target()
+            .flash_algorithm_by_name(&first.algorithm)
+            .ok_or_else(|| FlashError::UnknownFlashAlgorithm {
+                algorithm: first.algorithm.clone(),
+                chip: session.target().name.clone(),
+            })?
+            .clone();
+        tracing::debug!(
+            "Replaying {} operations with algorithm {}",
+            phase.len(),
+            algorithm.name
+        );
+
+        let mut flasher = Flasher::new(
+            session,
+            first.core,
+            &algorithm,
+            progress.clone(),
+            FlashHaltMode::default(),
+        )?;
+
+        match first.kind {
+            FlashOperationKind::EraseAll => flasher.run_erase_all()?,
+            FlashOperationKind::EraseSector { .. } => flasher.run_erase(|active| {
+                for operation in phase {
+                    if let FlashOperationKind::EraseSector { address } = operation.kind {
+                        active.erase_sector(address)?;
+                    }
+                }
+                Ok(())
+            })?,
+            FlashOperationKind::ProgramPage { .. } => flasher.run_program(|active| {
+                for operation in phase {
+                    if let FlashOperationKind::ProgramPage { address, data } = &operation.kind {
+                        active.program_page(*address, data)?;
+                    }
+                }
+                Ok(())
+            })?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn erases_and_programs_are_separate_phases() {
+        let erase = FlashOperationKind::EraseSector { address: 0 };
+        let program = FlashOperationKind::ProgramPage {
+            address: 0,
+            data: vec![],
+        };
+
+        assert!(erase.same_phase(&FlashOperationKind::EraseSector { address: 4096 }));
+        assert!(program.same_phase(&program));
+        assert!(!erase.same_phase(&program));
+        assert!(!FlashOperationKind::EraseAll.same_phase(&FlashOperationKind::EraseAll));
+    }
+}
@@ -1,4 +1,4 @@
-use super::FlashLayout;
+use super::{FlashLayout, FlashOperation};
 use std::{sync::Arc, time::Duration};
 
 /// A structure to manage the flashing procedure progress reporting.
@@ -78,6 +78,16 @@ impl FlashProgress {
         self.emit(ProgressEvent::SectorErased { size, time });
     }
 
+    /// Signalize that a sector was not erased, because it is already erased.
+    pub(super) fn sector_erase_skipped(&self, size: u64) {
+        self.emit(ProgressEvent::SectorEraseSkipped { size });
+    }
+
+    /// Signalize that a low-level operation of a flash algorithm completed successfully.
+    pub(super) fn operation(&self, operation: FlashOperation) {
+        self.emit(ProgressEvent::Operation { operation });
+    }
+
     /// Signalize that the page filling procedure has made progress.
     pub(super) fn page_filled(&self, size: u64, time: Duration) {
         self.emit(ProgressEvent::PageFilled { size, time });
@@ -128,7 +138,7 @@ impl FlashProgress {
 /// * `PageFilled` for every page
 /// * `FinishedFilling`
 /// * `StartedErasing`
-/// * `SectorErased` for every sector
+/// * `SectorErased` or `SectorEraseSkipped` for every sector
 /// * `FinishedErasing`
 /// * `StartedProgramming`
 /// * `PageProgrammed` for every page
@@ -136,7 +146,10 @@ impl FlashProgress {
 ///
 /// If an error occurs in any stage, one of the `Failed*` event will be returned,
 /// and no further events will be returned.
+///
+/// New events may be added in the future, so matches on this need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ProgressEvent {
     /// The flash layout has been built and the flashing procedure was initialized.
     Initialized {
@@ -177,6 +190,11 @@ pub enum ProgressEvent {
         /// The time it took to erase this sector.
         time: Duration,
     },
+    /// A sector was not erased, because it is already erased.
+    SectorEraseSkipped {
+        /// The size of the sector in bytes.
+        size: u64,
+    },
     /// Erasing of the flash has failed.
     FailedErasing,
     /// Erasing of the flash has finished successfully.
@@ -202,4 +220,9 @@ pub enum ProgressEvent {
         /// The message that was emitted.
         message: String,
     },
+    /// A low-level operation of a flash algorithm completed successfully.
+    Operation {
+        /// The operation which completed.
+        operation: FlashOperation,
+    },
 }
@@ -5,3 +5,4 @@ mod stub;
 mod target;
 
 pub use stub::{run, GdbInstanceConfiguration};
+pub use target::MonitorCommands;
@@ -3,15 +3,18 @@ use anyhow::Result;
 use parking_lot::FairMutex;
 
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 use itertools::Itertools;
 
-use super::target;
+use super::target::{self, MonitorCommands};
 
 const CONNECTION_STRING: &str = "127.0.0.1:1337";
 
 /// Configuration for a single GDB endpoint
+#[non_exhaustive]
 pub struct GdbInstanceConfiguration {
     /// The core type that will be sent to GDB
     pub core_type: CoreType,
@@ -19,9 +22,38 @@ pub struct GdbInstanceConfiguration {
     pub cores: Vec<usize>,
     /// The list of [SocketAddr] addresses to bind to
     pub socket_addrs: Vec<SocketAddr>,
+    /// The ELF file which is advertised to GDB, so that it can load the symbols on its own
+    exec_file: Option<PathBuf>,
+    /// Additional `monitor` commands, which are tried before the built-in ones
+    monitor_commands: Option<Arc<dyn MonitorCommands>>,
 }
 
 impl GdbInstanceConfiguration {
+    /// Create a configuration exposing `cores`, which are all of the type `core_type`, on
+    /// `socket_addrs`.
+    pub fn new(core_type: CoreType, cores: Vec<usize>, socket_addrs: Vec<SocketAddr>) -> Self {
+        Self {
+            core_type,
+            cores,
+            socket_addrs,
+            exec_file: None,
+            monitor_commands: None,
+        }
+    }
+
+    /// Advertise `path` to GDB as the executable, so that it can load the symbols on its own.
+    pub fn with_exec_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.exec_file = Some(path.into());
+        self
+    }
+
+    /// Offer the additional `monitor` commands of `commands`, which are tried before the built-in
+    /// ones.
+    pub fn with_monitor_commands(mut self, commands: Arc<dyn MonitorCommands>) -> Self {
+        self.monitor_commands = Some(commands);
+        self
+    }
+
     /// Build a GDB configuration from a session object.  All cores are included.
     ///
     /// # Arguments
@@ -61,10 +93,8 @@ impl GdbInstanceConfiguration {
         let ret = groups
             .iter()
             .enumerate()
-            .map(|(i, (core_type, cores))| GdbInstanceConfiguration {
-                core_type: *core_type,
-                cores: cores.to_vec(),
-                socket_addrs: adjust_addrs(&addrs, i),
+            .map(|(i, (core_type, cores))| {
+                GdbInstanceConfiguration::new(*core_type, cores.to_vec(), adjust_addrs(&addrs, i))
             })
             .collect();
 
@@ -89,7 +119,13 @@ pub fn run<'a>(
     // Turn our group list into GDB targets
     let mut targets = instances
         .map(|instance| {
-            target::RuntimeTarget::new(session, instance.cores.to_vec(), &instance.socket_addrs[..])
+            target::RuntimeTarget::new(
+                session,
+                instance.cores.to_vec(),
+                &instance.socket_addrs[..],
+                instance.exec_file.as_deref(),
+                instance.monitor_commands.as_deref(),
+            )
         })
         .collect::<Result<Vec<target::RuntimeTarget>, Error>>()?;
 
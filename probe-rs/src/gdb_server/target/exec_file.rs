@@ -0,0 +1,20 @@
+use super::utils::copy_range_to_buf;
+use super::RuntimeTarget;
+
+use gdbstub::common::Pid;
+use gdbstub::target::ext::exec_file::ExecFile;
+use gdbstub::target::TargetResult;
+
+impl ExecFile for RuntimeTarget<'_> {
+    fn get_exec_file(
+        &self,
+        _pid: Option<Pid>,
+        offset: u64,
+        length: usize,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        let path = self.exec_file.as_deref().unwrap_or_default();
+
+        Ok(copy_range_to_buf(path.as_bytes(), offset, length, buf))
+    }
+}
@@ -0,0 +1,250 @@
+//! Flash programming with the `vFlashErase`, `vFlashWrite` and `vFlashDone` packets.
+//!
+//! gdbstub does not support these packets, so they are taken from the connection
+//! before the data is passed on to the GDB stub state machine.
+
+use std::io::Write;
+use std::net::TcpStream;
+use std::time::Duration;
+
+use super::{ProbeRsErrorExt, RuntimeTarget};
+use crate::flashing::DownloadOptions;
+use crate::Error;
+
+use gdbstub::conn::ConnectionExt;
+
+/// Start of all flash packets, including the packet start marker
+const FLASH_PACKET_PREFIX: &[u8] = b"$vFlash";
+
+/// A flash command sent by GDB
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum FlashCommand {
+    /// Erase the given address range
+    Erase { address: u64, length: u64 },
+    /// Write the data to the given address
+    Write { address: u64, data: Vec<u8> },
+    /// All erase and write commands were sent
+    Done,
+}
+
+impl FlashCommand {
+    /// Parse the payload of a packet, without the `$` and the checksum.
+    pub(crate) fn parse(payload: &[u8]) -> Option<Self> {
+        if let Some(args) = payload.strip_prefix(b"vFlashErase:") {
+            let args = std::str::from_utf8(args).ok()?;
+            let (address, length) = args.split_once(',')?;
+
+            Some(Self::Erase {
+                address: u64::from_str_radix(address, 16).ok()?,
+                length: u64::from_str_radix(length, 16).ok()?,
+            })
+        } else if let Some(args) = payload.strip_prefix(b"vFlashWrite:") {
+            let separator = args.iter().position(|&b| b == b':')?;
+            let address = std::str::from_utf8(&args[..separator]).ok()?;
+
+            Some(Self::Write {
+                address: u64::from_str_radix(address, 16).ok()?,
+                data: unescape_binary(&args[separator + 1..]),
+            })
+        } else if payload == b"vFlashDone" {
+            Some(Self::Done)
+        } else {
+            None
+        }
+    }
+}
+
+impl RuntimeTarget<'_> {
+    /// Handle a flash packet, if the next packet on the connection is one.
+    ///
+    /// Returns `true` if a packet was handled, and the connection should be checked again.
+    pub(crate) fn handle_flash_packet(&mut self, conn: &mut TcpStream) -> Result<bool, Error> {
+        if !starts_with_flash_packet(conn)? {
+            return Ok(false);
+        }
+
+        let packet = read_packet(conn)?;
+
+        let Some((payload, checksum)) = packet
+            .strip_prefix(b"$")
+            .and_then(|packet| packet.split_last_chunk::<3>())
+        else {
+            conn.write_all(b"-").into_error()?;
+            return Ok(true);
+        };
+
+        let expected = std::str::from_utf8(&checksum[1..])
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+        if expected != Some(packet_checksum(payload)) {
+            conn.write_all(b"-").into_error()?;
+            return Ok(true);
+        }
+
+        let response = match FlashCommand::parse(payload) {
+            Some(command) => self.execute_flash_command(command),
+            None => "",
+        };
+
+        conn.write_all(b"+").into_error()?;
+        conn.write_all(
+            format!("${response}#{:02x}", packet_checksum(response.as_bytes())).as_bytes(),
+        )
+        .into_error()?;
+        conn.flush().into_error()?;
+
+        Ok(true)
+    }
+
+    /// Executes the command, and returns the response for GDB.
+    fn execute_flash_command(&mut self, command: FlashCommand) -> &'static str {
+        match command {
+            FlashCommand::Erase { address, length } => {
+                // The loader erases all sectors it writes to, so the data is only collected here.
+                tracing::debug!(
+                    "GDB requested erase of {:#010x}..{:#010x}",
+                    address,
+                    address + length
+                );
+
+                if self.flash_loader.is_none() {
+                    self.flash_loader = Some(self.session.lock().target().flash_loader());
+                }
+
+                "OK"
+            }
+            FlashCommand::Write { address, data } => {
+                let loader = self
+                    .flash_loader
+                    .get_or_insert_with(|| self.session.lock().target().flash_loader());
+
+                match loader.add_data(address, &data) {
+                    Ok(()) => "OK",
+                    Err(e) => {
+                        tracing::error!("Failed to add data for flashing: {e}");
+                        "E01"
+                    }
+                }
+            }
+            FlashCommand::Done => {
+                let Some(loader) = self.flash_loader.take() else {
+                    return "OK";
+                };
+
+                let mut session = self.session.lock();
+                if let Err(e) = loader.commit(&mut session, DownloadOptions::default()) {
+                    tracing::error!("Failed to program flash: {e}");
+                    return "E02";
+                }
+
+                // The core is expected to be halted after programming.
+                for core_id in &self.cores {
+                    let result = session
+                        .core(*core_id)
+                        .and_then(|mut core| core.halt(Duration::from_millis(100)));
+                    if let Err(e) = result {
+                        tracing::warn!("Failed to halt core {core_id} after flashing: {e}");
+                    }
+                }
+
+                "OK"
+            }
+        }
+    }
+}
+
+/// Checks if the data available on the connection starts with a flash packet, without consuming it.
+fn starts_with_flash_packet(conn: &mut TcpStream) -> Result<bool, Error> {
+    if conn.peek().into_error()? != Some(b'$') {
+        return Ok(false);
+    }
+
+    // GDB sends complete packets, so the rest of the prefix arrives right away.
+    let mut buf = [0u8; FLASH_PACKET_PREFIX.len()];
+    loop {
+        let available = match TcpStream::peek(conn, &mut buf) {
+            Ok(available) => available,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => 0,
+            Err(e) => return Err(Error::Other(e.into())),
+        };
+
+        if buf[..available] != FLASH_PACKET_PREFIX[..available] {
+            return Ok(false);
+        }
+        if available == FLASH_PACKET_PREFIX.len() {
+            return Ok(true);
+        }
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Reads a packet up to and including its checksum.
+fn read_packet(conn: &mut TcpStream) -> Result<Vec<u8>, Error> {
+    let mut packet = Vec::new();
+
+    loop {
+        let byte = conn.read().into_error()?;
+        packet.push(byte);
+
+        if packet.len() >= 3 && packet[packet.len() - 3] == b'#' {
+            return Ok(packet);
+        }
+    }
+}
+
+fn packet_checksum(payload: &[u8]) -> u8 {
+    payload.iter().fold(0, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Undo the escaping of binary data, where `}` is followed by the byte XOR 0x20.
+fn unescape_binary(data: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+
+    while let Some(&byte) = bytes.next() {
+        if byte == b'}' {
+            if let Some(&escaped) = bytes.next() {
+                unescaped.push(escaped ^ 0x20);
+            }
+        } else {
+            unescaped.push(byte);
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_erase() {
+        assert_eq!(
+            FlashCommand::parse(b"vFlashErase:08000000,400"),
+            Some(FlashCommand::Erase {
+                address: 0x0800_0000,
+                length: 0x400
+            })
+        );
+    }
+
+    #[test]
+    fn parse_write_unescapes_data() {
+        assert_eq!(
+            FlashCommand::parse(b"vFlashWrite:20:\x01}\x5d}\x03:"),
+            Some(FlashCommand::Write {
+                address: 0x20,
+                data: vec![0x01, 0x7d, 0x23, b':'],
+            })
+        );
+    }
+
+    #[test]
+    fn parse_done_and_unknown() {
+        assert_eq!(FlashCommand::parse(b"vFlashDone"), Some(FlashCommand::Done));
+        assert_eq!(FlashCommand::parse(b"vFlashErase:xyz"), None);
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use super::RuntimeTarget;
+use crate::Core;
+
+use gdbstub::target::ext::monitor_cmd::outputln;
+use gdbstub::target::ext::monitor_cmd::MonitorCmd;
+
+const HELP_TEXT: &str = r#"Supported Commands:
+
+    info - print session information
+    reset - reset target
+    reset halt - reset target and halt afterwards
+"#;
+
+/// Additional `monitor` commands of a GDB server, e.g. with knowledge of the peripherals of the
+/// target.
+pub trait MonitorCommands: Send + Sync {
+    /// Handles `command` on the first core of the GDB instance.
+    ///
+    /// Returns the output to print in GDB, or `None` if the command is not one of these commands.
+    fn handle(&self, command: &str, core: &mut Core<'_>) -> Option<String>;
+
+    /// The help text of the commands, printed after the built-in commands.
+    fn help(&self) -> &str;
+}
+
+impl MonitorCmd for RuntimeTarget<'_> {
+    fn handle_monitor_cmd(
+        &mut self,
+        cmd: &[u8],
+        mut out: gdbstub::target::ext::monitor_cmd::ConsoleOutput<'_>,
+    ) -> Result<(), Self::Error> {
+        let cmd = String::from_utf8_lossy(cmd);
+
+        if let Some(commands) = self.monitor_commands {
+            let mut session = self.session.lock();
+            let output = commands.handle(cmd.trim(), &mut session.core(self.cores[0])?);
+            if let Some(output) = output {
+                outputln!(out, "{}", output);
+                return Ok(());
+            }
+        }
+
+        match cmd.as_ref() {
+            "info" => {
+                outputln!(out, "Target info:\n\n{:#?}", self.session.lock().target());
+            }
+            "reset" => {
+                outputln!(out, "Resetting target");
+                match self.session.lock().core(0)?.reset() {
+                    Ok(_) => {
+                        outputln!(out, "Done")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while resetting target:\n\t{}", e)
+                    }
+                }
+            }
+            "reset halt" => {
+                let timeout: Duration = Duration::new(1, 0);
+                outputln!(out, "Resetting and halting target");
+                match self.session.lock().core(0)?.reset_and_halt(timeout) {
+                    Ok(_) => {
+                        outputln!(out, "Target halted")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while halting target:\n\t{}", e)
+                    }
+                }
+            }
+            _ => {
+                outputln!(out, "{}", HELP_TEXT);
+                if let Some(commands) = self.monitor_commands {
+                    outputln!(out, "{}", commands.help());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
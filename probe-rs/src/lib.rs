@@ -95,7 +95,7 @@ pub use crate::core::{
     exception_handler_for_core, Architecture, BreakpointCause, Core, CoreInformation,
     CoreInterface, CoreRegister, CoreRegisters, CoreState, CoreStatus, HaltReason,
     MemoryMappedRegister, RegisterId, RegisterRole, RegisterValue, SpecificCoreState,
-    VectorCatchCondition,
+    VectorCatchCondition, Watchpoint, WatchpointKind,
 };
 pub use crate::error::Error;
 pub use crate::memory::MemoryInterface;
@@ -307,20 +307,27 @@ impl Probe {
         permissions: Permissions,
     ) -> Result<Session, Error> {
         // The session will de-assert reset after connecting to the debug interface.
-        Session::new(self, target.into(), AttachMethod::UnderReset, permissions).map_err(
-            |e| match e {
-                Error::Arm(ArmError::Timeout)
-                | Error::Riscv(RiscvError::Timeout)
-                | Error::Xtensa(XtensaError::Timeout) => Error::Other(anyhow::anyhow!(
-                    "Timeout while attaching to target under reset. \
-                    This can happen if the target is not responding to the reset sequence. \
-                    Ensure the chip's reset pin is connected, or try attaching without reset \
-                    (`connectUnderReset = false` for DAP Clients, or remove `connect-under-reset` \
-                        option from CLI options.)."
-                )),
-                e => e,
-            },
+        Session::new(self, target.into(), AttachMethod::UnderReset, permissions)
+            .map_err(map_under_reset_timeout)
+    }
+
+    /// Attach to the chip under hard-reset, and release the reset into a halted state.
+    ///
+    /// This works like [`Probe::attach_under_reset`], but additionally verifies that all cores
+    /// are halted after the reset has been released. Cores which are not halted are reset and
+    /// halted again. This is useful for targets which gate the debug clock in deep sleep.
+    pub fn attach_under_reset_halted(
+        self,
+        target: impl Into<TargetSelector>,
+        permissions: Permissions,
+    ) -> Result<Session, Error> {
+        Session::new(
+            self,
+            target.into(),
+            AttachMethod::UnderResetHalted,
+            permissions,
         )
+        .map_err(map_under_reset_timeout)
     }
 
     /// Selects the transport protocol to be used by the debug probe.
@@ -508,6 +515,31 @@ impl Probe {
         self.inner.get_target_voltage()
     }
 
+    /// Try reading the current drawn by the target.
+    ///
+    /// This does not work on all probes.
+    pub fn get_target_current(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        self.inner.get_target_current()
+    }
+
+    /// Enable or disable the target power supply of the probe.
+    ///
+    /// This does not work on all probes.
+    pub fn set_target_power(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        self.inner.set_target_power(enabled)
+    }
+
+    /// Returns whether the probe can supply power to the target.
+    pub fn supports_target_power(&self) -> bool {
+        self.inner.supports_target_power()
+    }
+
+    /// Returns the speeds which can be selected for the active protocol, if the probe
+    /// can report them.
+    pub fn supported_speeds(&mut self) -> Result<Option<SupportedSpeeds>, DebugProbeError> {
+        self.inner.supported_speeds()
+    }
+
     /// Try to get a J-Link interface from the debug probe.
     pub fn try_into_jlink(&mut self) -> Result<&mut jlink::JLink, DebugProbeError> {
         self.inner.try_into_jlink()
@@ -700,6 +732,31 @@ pub trait DebugProbe: Send + fmt::Debug {
         Ok(None)
     }
 
+    /// Reads the current drawn by the target in Amperes, if possible. Returns `Ok(None)`
+    /// if the probe doesn’t support measuring the target current.
+    fn get_target_current(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        Ok(None)
+    }
+
+    /// Enables or disables the target power supply of the probe, if possible.
+    fn set_target_power(&mut self, _enabled: bool) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe {
+            command_name: "target power",
+        })
+    }
+
+    /// Returns whether the probe can supply power to the target, see
+    /// [`DebugProbe::set_target_power`].
+    fn supports_target_power(&self) -> bool {
+        false
+    }
+
+    /// Returns the speeds which can be selected for the active protocol. Returns `Ok(None)`
+    /// if the probe can't report its supported speeds.
+    fn supported_speeds(&mut self) -> Result<Option<SupportedSpeeds>, DebugProbeError> {
+        Ok(None)
+    }
+
     /// Try to get a J-Link interface from the debug probe.
     fn try_into_jlink(&mut self) -> Result<&mut jlink::JLink, DebugProbeError> {
         Err(DebugProbeError::Other(anyhow::anyhow!(
@@ -721,6 +778,26 @@ impl PartialEq for dyn ProbeFactory {
     }
 }
 
+/// The protocol speeds supported by a debug probe.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupportedSpeeds {
+    /// Any speed up to the given maximum in kHz can be selected.
+    UpTo(u32),
+    /// Only the listed speeds in kHz can be selected, in descending order.
+    List(Vec<u32>),
+}
+
+impl SupportedSpeeds {
+    /// Returns the highest supported speed in kHz.
+    pub fn max_khz(&self) -> Option<u32> {
+        match self {
+            SupportedSpeeds::UpTo(max) => Some(*max),
+            SupportedSpeeds::List(speeds) => speeds.iter().copied().max(),
+        }
+    }
+}
+
 /// Gathers some information about a debug probe which was found during a scan.
 #[derive(Debug, Clone, PartialEq)]
 pub struct DebugProbeInfo {
@@ -737,6 +814,9 @@ pub struct DebugProbeInfo {
     /// This is necessary for composite HID devices.
     pub hid_interface: Option<u8>,
 
+    /// Where the debug probe is connected to the USB, if the driver reports it.
+    pub usb_location: Option<UsbLocation>,
+
     /// A reference to the [`ProbeFactory`] that created this info object.
     probe_factory: &'static dyn ProbeFactory,
 }
@@ -772,9 +852,20 @@ impl DebugProbeInfo {
             serial_number,
             probe_factory,
             hid_interface,
+            usb_location: None,
         }
     }
 
+    /// Sets the [`UsbLocation`] of the probe to where `device` is connected.
+    pub(crate) fn with_usb_location(mut self, device: &DeviceInfo) -> Self {
+        self.usb_location = Some(UsbLocation {
+            bus_number: device.bus_number(),
+            device_address: device.device_address(),
+            port_path: usb_util::port_path(device),
+        });
+        self
+    }
+
     /// Open the probe described by this `DebugProbeInfo`.
     pub fn open(&self) -> Result<Probe, DebugProbeError> {
         let selector = DebugProbeSelector::from(self);
@@ -783,6 +874,32 @@ impl DebugProbeInfo {
             .map(Probe::from_specific_probe)
     }
 
+    /// Resets the USB device of the probe, which disconnects it and makes it enumerate again.
+    ///
+    /// This can recover a probe which stopped responding, without unplugging it. The probe has to
+    /// be opened again afterwards. USB resets are not supported on Windows.
+    pub fn reset_usb_device(&self) -> Result<(), DebugProbeError> {
+        let selector = DebugProbeSelector::from(self);
+        let device = nusb::list_devices()
+            .map_err(ProbeCreationError::Usb)?
+            .filter(|device| selector.matches(device))
+            .find(|device| {
+                self.usb_location.as_ref().map_or(true, |location| {
+                    location.bus_number == device.bus_number()
+                        && location.device_address == device.device_address()
+                })
+            })
+            .ok_or(ProbeCreationError::NotFound)?;
+
+        let device = device.open().map_err(ProbeCreationError::Usb)?;
+        device.reset().map_err(|error| match error.kind() {
+            std::io::ErrorKind::Unsupported => DebugProbeError::CommandNotSupportedByProbe {
+                command_name: "USB reset",
+            },
+            _ => DebugProbeError::Usb(error),
+        })
+    }
+
     /// Returns whether this info was returned by a particular probe factory.
     pub fn is_probe_type<F: ProbeFactory>(&self) -> bool {
         self.probe_factory.type_id() == std::any::TypeId::of::<F>()
@@ -796,6 +913,31 @@ impl DebugProbeInfo {
     }
 }
 
+/// Where a debug probe is connected to the USB.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct UsbLocation {
+    /// The number of the bus, or host controller.
+    pub bus_number: u8,
+    /// The address of the device on the bus, which changes when the device is reconnected.
+    pub device_address: u8,
+    /// The ports of the hubs between the host controller and the device, separated by dots,
+    /// e.g. `2.3` for port 3 of a hub connected to port 2 of the root hub.
+    ///
+    /// On Windows, this is only the port of the hub the device is connected to. `None` if the
+    /// operating system does not report the ports.
+    pub port_path: Option<String>,
+}
+
+impl fmt::Display for UsbLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bus {}", self.bus_number)?;
+        if let Some(port_path) = &self.port_path {
+            write!(f, ", port {port_path}")?;
+        }
+        write!(f, ", device {}", self.device_address)
+    }
+}
+
 /// An error which can occur while parsing a [`DebugProbeSelector`].
 #[derive(thiserror::Error, Debug, docsplay::Display)]
 pub enum DebugProbeSelectorParseError {
@@ -1253,6 +1395,22 @@ impl std::hash::Hash for DeferredResultIndex {
     }
 }
 
+/// Replaces timeout errors which occur while attaching under reset with a more helpful error.
+fn map_under_reset_timeout(error: Error) -> Error {
+    match error {
+        Error::Arm(ArmError::Timeout)
+        | Error::Riscv(RiscvError::Timeout)
+        | Error::Xtensa(XtensaError::Timeout) => Error::Other(anyhow::anyhow!(
+            "Timeout while attaching to target under reset. \
+            This can happen if the target is not responding to the reset sequence. \
+            Ensure the chip's reset pin is connected, or try attaching without reset \
+            (`connectUnderReset = false` for DAP Clients, or remove `connect-under-reset` \
+                option from CLI options.)."
+        )),
+        e => e,
+    }
+}
+
 /// The method that should be used for attaching.
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum AttachMethod {
@@ -1262,6 +1420,22 @@ pub enum AttachMethod {
     ///
     /// This is required on targets that can remap SWD pins or disable the SWD interface in sleep.
     UnderReset,
+    /// Attach to the target while it is in reset, and make sure all cores are halted
+    /// once the reset is released.
+    ///
+    /// If a core is not halted after the reset has been released, for example because
+    /// it ran away before the debug connection was established, it is reset and halted again.
+    UnderResetHalted,
+}
+
+impl AttachMethod {
+    /// Returns `true` if the target is held in reset while attaching.
+    pub(crate) fn is_under_reset(self) -> bool {
+        matches!(
+            self,
+            AttachMethod::UnderReset | AttachMethod::UnderResetHalted
+        )
+    }
 }
 
 #[cfg(test)]
@@ -1294,4 +1468,14 @@ mod test {
             Some("DC:DA:0C:D3:FE:D8".to_string())
         );
     }
+
+    #[test]
+    fn test_supported_speeds_max() {
+        assert_eq!(SupportedSpeeds::UpTo(12_000).max_khz(), Some(12_000));
+        assert_eq!(
+            SupportedSpeeds::List(vec![4600, 1800, 100]).max_khz(),
+            Some(4600)
+        );
+        assert_eq!(SupportedSpeeds::List(vec![]).max_khz(), None);
+    }
 }
@@ -486,6 +486,7 @@ pub(super) fn list_espjtag_devices() -> Vec<DebugProbeInfo> {
                 &EspUsbJtagFactory,
                 None,
             )
+            .with_usb_location(&device)
         })
         .collect()
 }
@@ -624,13 +624,16 @@ static FTDI_COMPAT_DEVICES: &[FtdiDevice] = &[
 
 fn get_device_info(device: &DeviceInfo) -> Option<DebugProbeInfo> {
     FTDI_COMPAT_DEVICES.iter().find_map(|ftdi| {
-        ftdi.matches(device).then(|| DebugProbeInfo {
-            identifier: device.product_string().unwrap_or("FTDI").to_string(),
-            vendor_id: device.vendor_id(),
-            product_id: device.product_id(),
-            serial_number: device.serial_number().map(|s| s.to_string()),
-            probe_factory: &FtdiProbeFactory,
-            hid_interface: None,
+        ftdi.matches(device).then(|| {
+            DebugProbeInfo::new(
+                device.product_string().unwrap_or("FTDI"),
+                device.vendor_id(),
+                device.product_id(),
+                device.serial_number().map(|s| s.to_string()),
+                &FtdiProbeFactory,
+                None,
+            )
+            .with_usb_location(device)
         })
     })
 }
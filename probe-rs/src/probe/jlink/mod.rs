@@ -46,7 +46,7 @@ use crate::{
     probe::{
         arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
         DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, ProbeFactory,
-        WireProtocol,
+        SupportedSpeeds, WireProtocol,
     },
 };
 
@@ -602,6 +602,26 @@ impl JLink {
         Ok(u16::from_le_bytes(voltage))
     }
 
+    /// Reads the current drawn by the target in mA, if the probe measures it.
+    ///
+    /// This requires the probe to support [`Capability::GetHwInfo`].
+    fn read_target_current(&self) -> Result<Option<u32>, JlinkError> {
+        /// The `HW_INFO_ITARGET` entry of `GET_HW_INFO`.
+        const HW_INFO_ITARGET: u32 = 1 << 2;
+
+        self.require_capability(Capability::GetHwInfo)?;
+
+        let mut cmd = [Command::GetHwInfo as u8, 0, 0, 0, 0];
+        cmd[1..].copy_from_slice(&HW_INFO_ITARGET.to_le_bytes());
+        self.write_cmd(&cmd)?;
+
+        // Probes which don't measure the current report all bits set.
+        match self.read_u32()? {
+            u32::MAX => Ok(None),
+            current => Ok(Some(current)),
+        }
+    }
+
     fn shift_jtag_bit(
         &mut self,
         tms: bool,
@@ -991,6 +1011,46 @@ impl DebugProbe for JLink {
         Ok(Some((self.read_target_voltage()? as f32) / 1000f32))
     }
 
+    fn get_target_current(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        if !self.caps.contains(Capability::GetHwInfo) {
+            return Ok(None);
+        }
+
+        // Convert the integer milliamperes value to amperes as an f32.
+        Ok(self
+            .read_target_current()?
+            .map(|current| current as f32 / 1000f32))
+    }
+
+    fn set_target_power(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        if !self.caps.contains(Capability::SetKsPower) {
+            return Err(DebugProbeError::CommandNotSupportedByProbe {
+                command_name: "target power",
+            });
+        }
+
+        self.set_kickstart_power(enabled)?;
+
+        Ok(())
+    }
+
+    fn supports_target_power(&self) -> bool {
+        self.caps.contains(Capability::SetKsPower)
+    }
+
+    fn supported_speeds(&mut self) -> Result<Option<SupportedSpeeds>, DebugProbeError> {
+        if !self.caps.contains(Capability::SpeedInfo) {
+            return Ok(None);
+        }
+
+        let speeds = self.read_interface_speeds()?;
+
+        // Speeds are configured in kHz, and have to fit into 16 bits.
+        let max_speed_khz = (speeds.max_speed_hz() / 1000).min(0xfffe);
+
+        Ok(Some(SupportedSpeeds::UpTo(max_speed_khz)))
+    }
+
     fn try_get_xtensa_interface<'probe>(
         &'probe mut self,
         state: &'probe mut XtensaDebugInterfaceState,
@@ -1207,6 +1267,7 @@ fn list_jlink_devices() -> Vec<DebugProbeInfo> {
                 &JLinkFactory,
                 None,
             )
+            .with_usb_location(&info)
         })
         .collect()
 }
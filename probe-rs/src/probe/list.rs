@@ -88,29 +88,69 @@ impl AllProbesLister {
         Self
     }
 
-    fn open(selector: impl Into<DebugProbeSelector>) -> Result<Probe, DebugProbeError> {
-        let selector = selector.into();
-
-        for probe_ctor in Self::DRIVERS {
-            match probe_ctor.open(&selector) {
-                Ok(link) => return Ok(Probe::from_specific_probe(link)),
-                Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
-                Err(e) => return Err(e),
-            };
-        }
+    /// Returns all built-in probe drivers.
+    pub fn drivers() -> &'static [&'static dyn ProbeFactory] {
+        Self::DRIVERS
+    }
 
-        Err(DebugProbeError::ProbeCouldNotBeCreated(
-            ProbeCreationError::NotFound,
-        ))
+    fn open(selector: impl Into<DebugProbeSelector>) -> Result<Probe, DebugProbeError> {
+        open_with_drivers(Self::DRIVERS, selector.into())
     }
 
     fn list_all() -> Vec<DebugProbeInfo> {
-        let mut list = vec![];
+        list_with_drivers(Self::DRIVERS)
+    }
+}
 
-        for driver in Self::DRIVERS {
-            list.extend(driver.list_probes());
-        }
+/// Lister implementation that only uses a subset of the probe drivers.
+///
+/// This can be used to force a probe to be handled by a specific driver,
+/// or to exclude a misbehaving driver.
+#[derive(Debug)]
+pub struct DriverSubsetLister {
+    drivers: Vec<&'static dyn ProbeFactory>,
+}
+
+impl DriverSubsetLister {
+    /// Create a new lister which only uses the given probe drivers.
+    pub fn new(drivers: Vec<&'static dyn ProbeFactory>) -> Self {
+        Self { drivers }
+    }
+}
 
-        list
+impl ProbeLister for DriverSubsetLister {
+    fn open(&self, selector: &DebugProbeSelector) -> Result<Probe, DebugProbeError> {
+        open_with_drivers(&self.drivers, selector.clone())
+    }
+
+    fn list_all(&self) -> Vec<DebugProbeInfo> {
+        list_with_drivers(&self.drivers)
+    }
+}
+
+fn open_with_drivers(
+    drivers: &[&'static dyn ProbeFactory],
+    selector: DebugProbeSelector,
+) -> Result<Probe, DebugProbeError> {
+    for probe_ctor in drivers {
+        match probe_ctor.open(&selector) {
+            Ok(link) => return Ok(Probe::from_specific_probe(link)),
+            Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
+            Err(e) => return Err(e),
+        };
     }
+
+    Err(DebugProbeError::ProbeCouldNotBeCreated(
+        ProbeCreationError::NotFound,
+    ))
+}
+
+fn list_with_drivers(drivers: &[&'static dyn ProbeFactory]) -> Vec<DebugProbeInfo> {
+    let mut list = vec![];
+
+    for driver in drivers {
+        list.extend(driver.list_probes());
+    }
+
+    list
 }
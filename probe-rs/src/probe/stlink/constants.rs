@@ -157,6 +157,22 @@ pub enum SwdFrequencyToDelayCount {
 }
 
 impl SwdFrequencyToDelayCount {
+    /// All settings, ordered from the highest to the lowest frequency.
+    pub(crate) const ALL: [Self; 12] = [
+        Self::Hz4600000,
+        Self::Hz1800000,
+        Self::Hz1200000,
+        Self::Hz950000,
+        Self::Hz650000,
+        Self::Hz480000,
+        Self::Hz400000,
+        Self::Hz360000,
+        Self::Hz240000,
+        Self::Hz150000,
+        Self::Hz125000,
+        Self::Hz100000,
+    ];
+
     /// Try to find an appropriate setting for the given frequency in kHz.
     ///
     /// If a direct match is not found, return the setting for a lower frequency
@@ -218,6 +234,18 @@ pub enum JTagFrequencyToDivider {
 }
 
 impl JTagFrequencyToDivider {
+    /// All settings, ordered from the highest to the lowest frequency.
+    pub(crate) const ALL: [Self; 8] = [
+        Self::Hz18000000,
+        Self::Hz9000000,
+        Self::Hz4500000,
+        Self::Hz2250000,
+        Self::Hz1120000,
+        Self::Hz560000,
+        Self::Hz280000,
+        Self::Hz140000,
+    ];
+
     /// Try to find an appropriate setting for the given frequency in kHz.
     ///
     /// If a direct match is not found, return the setting for a higher frequency
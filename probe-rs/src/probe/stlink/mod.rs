@@ -5,7 +5,7 @@ mod tools;
 mod usb_interface;
 
 use self::usb_interface::{StLinkUsb, StLinkUsbDevice};
-use super::{DebugProbe, DebugProbeError, ProbeCreationError, WireProtocol};
+use super::{DebugProbe, DebugProbeError, ProbeCreationError, SupportedSpeeds, WireProtocol};
 use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
 use crate::architecture::arm::{valid_32bit_arm_address, ArmError};
 use crate::{
@@ -159,6 +159,27 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         }
     }
 
+    fn supported_speeds(&mut self) -> Result<Option<SupportedSpeeds>, DebugProbeError> {
+        let mut speeds = match self.hw_version.cmp(&3) {
+            Ordering::Less => match self.protocol {
+                WireProtocol::Swd => SwdFrequencyToDelayCount::ALL
+                    .iter()
+                    .map(|setting| setting.to_khz())
+                    .collect(),
+                WireProtocol::Jtag => JTagFrequencyToDivider::ALL
+                    .iter()
+                    .map(|setting| setting.to_khz())
+                    .collect(),
+            },
+            Ordering::Equal => self.get_communication_frequencies(self.protocol)?.0,
+            Ordering::Greater => return Ok(None),
+        };
+
+        speeds.sort_unstable_by(|a, b| b.cmp(a));
+
+        Ok(Some(SupportedSpeeds::List(speeds)))
+    }
+
     fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
         tracing::info!("Setting scan chain to {:?}", scan_chain);
         self.scan_chain = Some(scan_chain);
@@ -40,3 +40,58 @@ impl InterfaceExt for Interface {
         }))
     }
 }
+
+/// Returns the ports of the hubs between the host controller and `device`, separated by dots.
+pub(crate) fn port_path(device: &nusb::DeviceInfo) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    return port_path_from_sysfs_name(device.sysfs_path().file_name()?.to_str()?);
+
+    #[cfg(target_os = "macos")]
+    return port_path_from_location_id(device.location_id());
+
+    #[cfg(target_os = "windows")]
+    return Some(device.port_number().to_string());
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        let _ = device;
+        None
+    }
+}
+
+/// Parses the port path from the name of a Linux sysfs USB device directory, e.g. `1-2.3`.
+#[cfg(any(target_os = "linux", test))]
+fn port_path_from_sysfs_name(name: &str) -> Option<String> {
+    let (_bus, ports) = name.split_once('-')?;
+    Some(ports.to_string())
+}
+
+/// Decodes the port path from a macOS location ID, which contains the bus in the highest byte,
+/// followed by one port per hub in each 4 bits, until the first zero.
+#[cfg(any(target_os = "macos", test))]
+fn port_path_from_location_id(location_id: u32) -> Option<String> {
+    let ports = (0..6)
+        .map(|hub| (location_id >> (20 - 4 * hub)) & 0xF)
+        .take_while(|&port| port != 0)
+        .map(|port| port.to_string())
+        .collect::<Vec<_>>();
+
+    (!ports.is_empty()).then(|| ports.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_paths_are_decoded() {
+        assert_eq!(port_path_from_sysfs_name("1-2.3").as_deref(), Some("2.3"));
+        assert_eq!(port_path_from_sysfs_name("usb1"), None);
+
+        assert_eq!(
+            port_path_from_location_id(0x1432_0000).as_deref(),
+            Some("3.2")
+        );
+        assert_eq!(port_path_from_location_id(0x1400_0000), None);
+    }
+}
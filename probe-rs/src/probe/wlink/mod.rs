@@ -510,14 +510,17 @@ impl JTAGAccess for WchLink {
 
 fn get_wlink_info(device: &DeviceInfo) -> Option<DebugProbeInfo> {
     if matches!(device.product_string(), Some("WCH-Link") | Some("WCH_Link")) {
-        Some(DebugProbeInfo::new(
-            "WCH-Link",
-            VENDOR_ID,
-            PRODUCT_ID,
-            device.serial_number().map(|s| s.to_string()),
-            &WchLinkFactory,
-            None,
-        ))
+        Some(
+            DebugProbeInfo::new(
+                "WCH-Link",
+                VENDOR_ID,
+                PRODUCT_ID,
+                device.serial_number().map(|s| s.to_string()),
+                &WchLinkFactory,
+                None,
+            )
+            .with_usb_location(device),
+        )
     } else {
         None
     }
@@ -392,6 +392,24 @@ impl UpChannel {
         Ok(self.read_core(core, buf)?.1)
     }
 
+    /// Discards the data which is currently in the channel buffer, without reading it, and
+    /// returns how many bytes were discarded.
+    pub fn clear(&self, core: &mut Core) -> Result<usize, Error> {
+        let (write, read) = self.0.read_pointers(core, "up ")?;
+
+        if write != read {
+            self.0.info.write_read_buffer_ptr(core, self.0.ptr, write)?;
+        }
+
+        let discarded = if write >= read {
+            write - read
+        } else {
+            self.0.size - read + write
+        };
+
+        Ok(discarded as usize)
+    }
+
     /// Calculates amount of contiguous data available for reading
     fn readable_contiguous(&self, write: u64, read: u64) -> usize {
         let end = if read > write { self.0.size } else { write };
@@ -1,5 +1,8 @@
 use crate::architecture::arm::ap::AccessPort;
-use crate::architecture::arm::component::get_arm_components;
+use crate::architecture::arm::component::{
+    disable_cross_halt, enable_cross_halt, get_arm_components,
+};
+use crate::architecture::arm::memory::PeripheralType;
 use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence};
 use crate::architecture::arm::{ArmError, DpAddress};
 use crate::architecture::riscv::communication_interface::{RiscvDebugInterfaceState, RiscvError};
@@ -7,7 +10,7 @@ use crate::architecture::xtensa::communication_interface::{
     XtensaCommunicationInterface, XtensaDebugInterfaceState, XtensaError,
 };
 use crate::config::{ChipInfo, CoreExt, RegistryError, Target, TargetSelector};
-use crate::core::{Architecture, CombinedCoreState};
+use crate::core::{Architecture, CombinedCoreState, SpecificCoreState};
 use crate::probe::fake_probe::FakeProbe;
 use crate::probe::ProbeCreationError;
 use crate::{
@@ -25,6 +28,7 @@ use crate::{
     Core, CoreType, Error,
 };
 use anyhow::anyhow;
+use std::collections::hash_map::{Entry, HashMap};
 use std::ops::DerefMut;
 use std::{fmt, sync::Arc, time::Duration};
 
@@ -189,7 +193,7 @@ impl Session {
             _ => unreachable!("Mismatch between architecture and sequence type!"),
         };
 
-        if AttachMethod::UnderReset == attach_method {
+        if attach_method.is_under_reset() {
             let _span = tracing::debug_span!("Asserting hardware reset").entered();
 
             if let Some(dap_probe) = probe.try_as_dap_probe() {
@@ -237,7 +241,7 @@ impl Session {
             core.enable_arm_debug(&mut *interface)?;
         }
 
-        if attach_method == AttachMethod::UnderReset {
+        if attach_method.is_under_reset() {
             {
                 for core in &cores {
                     core.arm_reset_catch_set(&mut *interface)?;
@@ -275,7 +279,11 @@ impl Session {
                 for core_id in 0..session.cores.len() {
                     let mut core = session.core(core_id)?;
 
-                    core.wait_for_core_halted(Duration::from_millis(100))?;
+                    if attach_method == AttachMethod::UnderResetHalted {
+                        ensure_halted_after_reset(&mut core)?;
+                    } else {
+                        core.wait_for_core_halted(Duration::from_millis(100))?;
+                    }
 
                     core.reset_catch_clear()?;
                 }
@@ -681,6 +689,136 @@ impl Session {
         get_arm_components(interface, dp)
     }
 
+    /// Connects the Cortex-M cores through their CoreSight cross trigger interfaces (CTI), so
+    /// that when one core halts, the other cores are halted as well.
+    ///
+    /// The CTI of each core is looked up in the ROM table of the access port of the core. Cores
+    /// without a CTI are skipped. Resuming a core resumes the other cores which were halted with
+    /// it, but not the cores which halted on their own, e.g. at a breakpoint. The cores are
+    /// disconnected again when the session ends.
+    ///
+    /// Returns the IDs of the connected cores.
+    pub fn enable_cross_halt(&mut self) -> Result<Vec<usize>, Error> {
+        let ArchitectureInterface::Arm(interface) = &mut self.interfaces else {
+            return Err(ArmError::NoArmTarget.into());
+        };
+
+        let mut components = HashMap::new();
+        let mut connected = vec![];
+        for core in &mut self.cores {
+            let state = match &mut core.specific_state {
+                SpecificCoreState::Armv6m(state)
+                | SpecificCoreState::Armv7m(state)
+                | SpecificCoreState::Armv7em(state)
+                | SpecificCoreState::Armv8m(state) => state,
+                _ => continue,
+            };
+
+            let ap = core.core_state.memory_ap();
+            let dp = ap.ap_address().dp;
+            let dp_components = match components.entry(dp) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(get_arm_components(interface.deref_mut(), dp)?)
+                }
+            };
+            let Some(cti) = dp_components
+                .iter()
+                .filter(|component| component.ap.ap_address() == ap.ap_address())
+                .find_map(|component| component.find_component(PeripheralType::Cti))
+            else {
+                tracing::warn!(
+                    "Core {} has no CTI, it is not halted with the other cores",
+                    core.id
+                );
+                continue;
+            };
+
+            let address = cti.component.id().component_address();
+            enable_cross_halt(&mut *interface.memory_interface(ap)?, address)?;
+            state.set_cross_halt_cti(Some(address));
+            connected.push((core.id, ap, address));
+        }
+
+        for core in &mut self.cores {
+            let (SpecificCoreState::Armv6m(state)
+            | SpecificCoreState::Armv7m(state)
+            | SpecificCoreState::Armv7em(state)
+            | SpecificCoreState::Armv8m(state)) = &mut core.specific_state
+            else {
+                continue;
+            };
+            if state.cross_halt_cti().is_none() {
+                continue;
+            }
+
+            let peers = connected
+                .iter()
+                .filter(|(id, _, _)| *id != core.id)
+                .map(|&(_, ap, address)| (ap, address))
+                .collect();
+            state.set_cross_halt_peers(peers);
+        }
+
+        Ok(connected.into_iter().map(|(id, _, _)| id).collect())
+    }
+
+    /// Waits for `delay` after the Cortex-M cores are reset by [`Core::reset`] and
+    /// [`Core::reset_and_halt`], before they are accessed again.
+    ///
+    /// Some targets need time to settle after a reset before the debug port responds reliably.
+    /// The delay starts when the debug sequence of the target asserted the reset.
+    pub fn set_reset_delay(&mut self, delay: Duration) {
+        for core in &mut self.cores {
+            match &mut core.specific_state {
+                SpecificCoreState::Armv6m(state)
+                | SpecificCoreState::Armv7m(state)
+                | SpecificCoreState::Armv7em(state)
+                | SpecificCoreState::Armv8m(state) => state.set_reset_delay(delay),
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether any core is connected to the other cores by [`Session::enable_cross_halt`].
+    pub fn cross_halt_enabled(&self) -> bool {
+        self.cores.iter().any(|core| match &core.specific_state {
+            SpecificCoreState::Armv6m(state)
+            | SpecificCoreState::Armv7m(state)
+            | SpecificCoreState::Armv7em(state)
+            | SpecificCoreState::Armv8m(state) => state.cross_halt_cti().is_some(),
+            _ => false,
+        })
+    }
+
+    /// Disconnects the cores connected by [`Session::enable_cross_halt`] again, so that halting
+    /// one core no longer halts the others.
+    pub fn disable_cross_halt(&mut self) -> Result<(), Error> {
+        let ArchitectureInterface::Arm(interface) = &mut self.interfaces else {
+            return Ok(());
+        };
+
+        for core in &mut self.cores {
+            let state = match &mut core.specific_state {
+                SpecificCoreState::Armv6m(state)
+                | SpecificCoreState::Armv7m(state)
+                | SpecificCoreState::Armv7em(state)
+                | SpecificCoreState::Armv8m(state) => state,
+                _ => continue,
+            };
+            let Some(address) = state.cross_halt_cti() else {
+                continue;
+            };
+
+            let ap = core.core_state.memory_ap();
+            disable_cross_halt(&mut *interface.memory_interface(ap)?, address)?;
+            state.set_cross_halt_cti(None);
+            state.set_cross_halt_peers(Vec::new());
+        }
+
+        Ok(())
+    }
+
     /// Get the target description of the connected target.
     pub fn target(&self) -> &Target {
         &self.target
@@ -795,6 +933,16 @@ impl Drop for Session {
             );
         }
 
+        if let Err(err) = { 0..self.cores.len() }.try_for_each(|i| {
+            self.core(i)
+                .and_then(|mut core| core.clear_all_hw_watchpoints())
+        }) {
+            tracing::warn!(
+                "Could not clear all hardware watchpoints: {:?}",
+                anyhow!(err)
+            );
+        }
+
         // Call any necessary deconfiguration/shutdown hooks.
         if let Err(err) = { 0..self.cores.len() }
             .try_for_each(|i| self.core(i).and_then(|mut core| core.debug_core_stop()))
@@ -807,6 +955,39 @@ impl Drop for Session {
     }
 }
 
+/// How often a core is reset again if it is not halted after attaching under reset.
+const ATTACH_HALT_RETRIES: usize = 3;
+
+/// Verifies that the core is halted after the reset has been released, and resets
+/// and halts the core again if it ran away.
+fn ensure_halted_after_reset(core: &mut Core) -> Result<(), Error> {
+    let timeout = Duration::from_millis(100);
+
+    if core.wait_for_core_halted(timeout).is_ok() && core.core_halted()? {
+        return Ok(());
+    }
+
+    for attempt in 1..=ATTACH_HALT_RETRIES {
+        tracing::warn!(
+            "Core {} is not halted after releasing reset, retrying ({}/{})",
+            core.id(),
+            attempt,
+            ATTACH_HALT_RETRIES
+        );
+
+        match core.reset_and_halt(timeout) {
+            Ok(_) if core.core_halted()? => return Ok(()),
+            Ok(_) => {}
+            Err(e) => tracing::debug!("Failed to reset and halt core {}: {}", core.id(), e),
+        }
+    }
+
+    Err(Error::Other(anyhow::anyhow!(
+        "Core {} could not be halted after releasing reset",
+        core.id()
+    )))
+}
+
 /// Determine the [Target] from a [TargetSelector].
 ///
 /// If the selector is [TargetSelector::Unspecified], the target will be looked up in the registry.
@@ -832,7 +1013,7 @@ fn get_target_from_selector(
             // At this point we do not know what the target is, so we cannot use the chip specific reset sequence.
             // Thus, we try just using a normal reset for target detection if we want to do so under reset.
             // This can of course fail, but target detection is a best effort, not a guarantee!
-            if AttachMethod::UnderReset == attach_method {
+            if attach_method.is_under_reset() {
                 probe.target_reset_assert()?;
             }
             probe.attach_to_unspecified()?;
@@ -913,6 +1094,8 @@ fn get_target_from_selector(
 pub struct Permissions {
     /// When set to true, all memory of the chip may be erased or reset to factory default
     erase_all: bool,
+    /// The key which unlocks the debug access of a locked chip
+    debug_auth_key: Option<Vec<u8>>,
 }
 
 impl Permissions {
@@ -934,6 +1117,24 @@ impl Permissions {
         }
     }
 
+    /// Unlock the debug access of a locked chip with this key, instead of erasing it.
+    ///
+    /// The nRF5340 and nRF9160 are unlocked with the 4 byte key which the firmware wrote to the
+    /// `APPROTECT.DISABLE` and `SECUREAPPROTECT.DISABLE` registers of the CTRL-AP, in little-endian
+    /// byte order. If the key does not unlock the chip, it is erased if [`Self::allow_erase_all`]
+    /// is given.
+    #[must_use]
+    pub fn with_debug_auth_key(self, key: Vec<u8>) -> Self {
+        Self {
+            debug_auth_key: Some(key),
+            ..self
+        }
+    }
+
+    pub(crate) fn debug_auth_key(&self) -> Option<&[u8]> {
+        self.debug_auth_key.as_deref()
+    }
+
     pub(crate) fn erase_all(&self) -> Result<(), MissingPermissions> {
         if self.erase_all {
             Ok(())